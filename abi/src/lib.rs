@@ -1,9 +1,16 @@
 #![no_std]
 
 /// the static version of the ABI
-pub const ABI_VERSION: u32 = 1;
+pub const ABI_VERSION: u32 = 7;
+/// Memory type the loader gives every page it hands to the kernel: the
+/// `BootAbi`, the memory map, the status page, and the microcode and memory
+/// attributes copies. It sits in the range UEFI leaves to the OS loader
+/// (`0x8000_0000` and up); the kernel keeps it reserved.
+pub const OXIDE_HANDOFF_MEMORY: u32 = 0x8000_0000;
 /// Maximum number of bytes in the firmware vendor string.
 pub const ABI_VENDOR_CAP: usize = 32;
+/// Maximum number of bytes in the raw boot command line.
+pub const ABI_CMDLINE_CAP: usize = 256;
 
 /// Shared ABI between the UEFI loader and Oxide kernel.
 #[repr(C)]
@@ -16,16 +23,139 @@ pub struct BootAbi {
     /// Measured processor TSC frequency in hertz (0 when unavailable).
     pub tsc_frequency_hz: u64,
     pub memory_map: MemoryMap,
+    /// Physical address of the loader-allocated [`StatusPage`] (0 when absent).
+    pub status_page_phys: u64,
+    /// CPU microcode update blob loaded from the ESP (empty when absent).
+    pub microcode: PhysRange,
+    /// Copy of the firmware's `EFI_MEMORY_ATTRIBUTES_TABLE` (empty when the
+    /// firmware provides none).
+    pub memory_attributes: PhysRange,
+    /// The loader's PE/COFF image, which contains the kernel (empty when the
+    /// loader could not determine it).
+    pub loader_image: PhysRange,
+    /// XXH32 (seed 0) of the `microcode` blob, as the loader verified it
+    /// against the file's checksum (0 when the blob is empty).
+    pub microcode_xxh32: u32,
+}
+
+/// A physical memory range handed over by the loader.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PhysRange {
+    /// Physical start address (0 when the range is empty).
+    pub phys: u64,
+    /// Length in bytes.
+    pub len: u64,
+}
+
+/// Identifies an initialized [`StatusPage`] ("OXST").
+pub const STATUS_MAGIC: u32 = u32::from_le_bytes(*b"OXST");
+
+/// Page shared by the loader and kernel recording how far a boot got.
+///
+/// The loader allocates it below 1 GiB, remembers its address in a UEFI
+/// variable, and on the next boot reads whatever the previous kernel left
+/// there before reinitializing it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct StatusPage {
+    /// [`STATUS_MAGIC`] once the loader has initialized the page.
+    pub magic: u32,
+    /// Last [`BootStage`] reached, as its raw value.
+    pub stage: u32,
+    /// Current [`BootStatus`], as its raw value.
+    pub status: u32,
+    /// Fatal paths the kernel entered while another was still reporting.
+    pub nested_faults: u32,
+    /// Status-specific detail, such as the exception vector.
+    pub detail: u64,
+}
+
+/// Boot milestones written to [`StatusPage::stage`], in order.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BootStage {
+    /// The loader initialized the page; the kernel has not run yet.
+    Loader = 1,
+    /// The kernel validated the handoff.
+    KernelEntry = 2,
+    /// The console is up.
+    Console = 3,
+    /// Memory bring-up finished.
+    Memory = 4,
+    /// The IDT is installed.
+    Interrupts = 5,
+    /// The kernel entered epoch 2.
+    Epoch2 = 6,
+}
+
+impl BootStage {
+    pub const fn from_raw(raw: u32) -> Option<Self> {
+        Some(match raw {
+            1 => BootStage::Loader,
+            2 => BootStage::KernelEntry,
+            3 => BootStage::Console,
+            4 => BootStage::Memory,
+            5 => BootStage::Interrupts,
+            6 => BootStage::Epoch2,
+            _ => return None,
+        })
+    }
+}
+
+/// Outcome written to [`StatusPage::status`].
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BootStatus {
+    /// Still booting or running; a reset in this state was unexpected.
+    Running = 0,
+    /// The kernel halted deliberately.
+    Halted = 1,
+    /// `kernel_run` returned an error.
+    Fatal = 2,
+    /// The kernel panicked.
+    Panic = 3,
+    /// A fatal CPU exception; `detail` holds the vector.
+    Exception = 4,
+}
+
+impl BootStatus {
+    pub const fn from_raw(raw: u32) -> Option<Self> {
+        Some(match raw {
+            0 => BootStatus::Running,
+            1 => BootStatus::Halted,
+            2 => BootStatus::Fatal,
+            3 => BootStatus::Panic,
+            4 => BootStatus::Exception,
+            _ => return None,
+        })
+    }
 }
 
 /// Boot options from the loader to kernel.
 #[repr(C)]
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug)]
 pub struct Options {
     /// Debug flag (1 = enabled, 0 = disabled).
     pub debug: u8,
     /// Quiet flag (1 = enabled, 0 = disabled).
     pub quiet: u8,
+    /// Number of valid bytes in `cmdline`.
+    pub cmdline_len: u16,
+    /// Raw UTF-8 command line as received by the loader (`key=value` tokens
+    /// are interpreted by the kernel).
+    pub cmdline: [u8; ABI_CMDLINE_CAP],
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            debug: 0,
+            quiet: 0,
+            cmdline_len: 0,
+            cmdline: [0; ABI_CMDLINE_CAP],
+        }
+    }
 }
 
 /// Numeric identifiers for UEFI memory types.
@@ -96,7 +226,8 @@ pub struct Framebuffer {
     pub width: u32,
     /// Height in pixels.
     pub height: u32,
-    /// Number of pixels per scanline.
+    /// Distance from one row to the next, in pixels (not bytes), as UEFI's
+    /// `PixelsPerScanLine`. At least `width`; larger when rows are padded.
     pub pixels_per_scanline: u32,
     /// Pixel format.
     pub pixel_format: PixelFormat,
@@ -145,3 +276,35 @@ pub enum PixelFormat {
     /// PixelBlueGreenRedReserved8BitPerColor.
     Bgr = 1,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_codes_round_trip() {
+        for stage in [
+            BootStage::Loader,
+            BootStage::KernelEntry,
+            BootStage::Console,
+            BootStage::Memory,
+            BootStage::Interrupts,
+            BootStage::Epoch2,
+        ] {
+            assert_eq!(BootStage::from_raw(stage as u32), Some(stage));
+        }
+        assert_eq!(BootStage::from_raw(0), None);
+
+        for status in [
+            BootStatus::Running,
+            BootStatus::Halted,
+            BootStatus::Fatal,
+            BootStatus::Panic,
+            BootStatus::Exception,
+        ] {
+            assert_eq!(BootStatus::from_raw(status as u32), Some(status));
+        }
+        assert_eq!(BootStatus::from_raw(5), None);
+        assert_eq!(core::mem::size_of::<StatusPage>(), 24);
+    }
+}
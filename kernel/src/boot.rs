@@ -2,7 +2,9 @@
 
 use core::mem::{align_of, size_of};
 
-use oxide_abi::{ABI_VERSION, BootAbi, Framebuffer, MemoryDescriptor, MemoryMap, PixelFormat};
+use oxide_abi::{
+    ABI_VERSION, BootAbi, Framebuffer, MemoryDescriptor, MemoryMap, PhysRange, PixelFormat,
+};
 
 /// Errors that can occur while validating loader-provided boot data.
 #[derive(Debug)]
@@ -10,8 +12,15 @@ pub enum BootValidationError {
     VersionMismatch { expected: u32, found: u32 },
     FramebufferInvalid(&'static str),
     MemoryMapInvalid(&'static str),
+    StatusPageInvalid(&'static str),
+    MicrocodeInvalid(&'static str),
+    MemoryAttributesInvalid(&'static str),
+    LoaderImageInvalid(&'static str),
+    MachineStateInvalid(&'static str),
 }
 
+const STATUS_PAGE_ALIGN: u64 = 4096;
+
 /// Validate the loader handoff structure before the kernel touches its fields.
 ///
 /// Ensures the ABI version matches, framebuffer geometry is sane, and the
@@ -27,6 +36,27 @@ pub fn validate_boot_abi(abi: &BootAbi) -> Result<(), BootValidationError> {
     validate_framebuffer(&abi.framebuffer)?;
     validate_memory_map(&abi.memory_map)?;
 
+    if !abi.status_page_phys.is_multiple_of(STATUS_PAGE_ALIGN) {
+        return Err(BootValidationError::StatusPageInvalid(
+            "status page address not page-aligned",
+        ));
+    }
+
+    check_range(abi.microcode).map_err(BootValidationError::MicrocodeInvalid)?;
+    check_range(abi.memory_attributes).map_err(BootValidationError::MemoryAttributesInvalid)?;
+    check_range(abi.loader_image).map_err(BootValidationError::LoaderImageInvalid)?;
+
+    Ok(())
+}
+
+/// Reject a non-empty range at address zero or one that wraps.
+fn check_range(range: PhysRange) -> Result<(), &'static str> {
+    if range.len != 0 && range.phys == 0 {
+        return Err("address is null");
+    }
+    if range.phys.checked_add(range.len).is_none() {
+        return Err("range overflows");
+    }
     Ok(())
 }
 
@@ -116,20 +146,199 @@ fn validate_memory_map(map: &MemoryMap) -> Result<(), BootValidationError> {
         ));
     }
 
-    let entry_size = map.entry_size as u64;
-    if !map.map_size.is_multiple_of(entry_size) {
+    // Disagreeing size fields are tolerated; `reconcile_memory_map` trims
+    // them. A buffer too small for even one descriptor is not.
+    if map.map_size < map.entry_size as u64 {
         return Err(BootValidationError::MemoryMapInvalid(
-            "map size not divisible by entry size",
+            "map size smaller than one entry",
         ));
     }
 
-    let max_entries = map.map_size / entry_size;
-    if map.entry_count as u64 > max_entries {
-        return Err(BootValidationError::MemoryMapInvalid(
-            "entry count exceeds buffer capacity",
-        ));
+    Ok(())
+}
+
+/// A memory map whose `map_size` and `entry_count * entry_size` disagreed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MapFixup {
+    pub reported_entries: u32,
+    pub reported_size: u64,
+    pub entries: u32,
+}
+
+impl MapFixup {
+    /// Warn about the inconsistency once the console is up.
+    pub fn log(&self) {
+        crate::println!(
+            "WARNING: memory map reports {} entries in {} bytes; using {}",
+            self.reported_entries,
+            self.reported_size,
+            self.entries
+        );
+    }
+}
+
+/// Make a validated map self-consistent.
+///
+/// Some firmware reports a `map_size` with trailing slack, or one that does
+/// not match `entry_count`. Only descriptors that both fields agree exist
+/// are trusted: the smaller of `entry_count` and the whole entries in
+/// `map_size`, with `map_size` trimmed to match.
+pub fn reconcile_memory_map(map: &mut MemoryMap) -> Option<MapFixup> {
+    let entry_size = map.entry_size as u64;
+    let whole_entries = (map.map_size / entry_size).min(u32::MAX as u64) as u32;
+    let entries = map.entry_count.min(whole_entries);
+    let map_size = entries as u64 * entry_size;
+    if entries == map.entry_count && map_size == map.map_size {
+        return None;
+    }
+
+    let fixup = MapFixup {
+        reported_entries: map.entry_count,
+        reported_size: map.map_size,
+        entries,
+    };
+    map.entry_count = entries;
+    map.map_size = map_size;
+    Some(fixup)
+}
+
+const RFLAGS_IF: u64 = 1 << 9;
+const RFLAGS_DF: u64 = 1 << 10;
+const CR0_PE: u64 = 1 << 0;
+const CR0_WP: u64 = 1 << 16;
+const CR0_PG: u64 = 1 << 31;
+const CR4_PAE: u64 = 1 << 5;
+const CR4_LA57: u64 = 1 << 12;
+const CR4_SMEP: u64 = 1 << 20;
+const CR4_SMAP: u64 = 1 << 21;
+const EFER_LME: u64 = 1 << 8;
+const EFER_LMA: u64 = 1 << 10;
+const EFER_NXE: u64 = 1 << 11;
+const IA32_EFER: u32 = 0xC000_0080;
+
+/// Processor state as the loader left it, captured at kernel entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MachineState {
+    pub rflags: u64,
+    pub cr0: u64,
+    pub cr3: u64,
+    pub cr4: u64,
+    pub efer: u64,
+    pub cs: u16,
+    pub ss: u16,
+    pub rsp: u64,
+}
+
+impl MachineState {
+    /// Read the state; must run before `kernel_main` changes any of it.
+    #[inline(always)]
+    pub fn capture() -> Self {
+        let (rflags, cr0, cr3, cr4, cs, ss, rsp): (u64, u64, u64, u64, u16, u16, u64);
+        // SAFETY: reading control and segment registers has no side effects
+        // at CPL 0. No `nostack`, so `rsp` is ABI-aligned at the asm block
+        // if the loader called us with an aligned stack.
+        unsafe {
+            core::arch::asm!(
+                "pushfq",
+                "pop {rflags}",
+                "mov {cr0}, cr0",
+                "mov {cr3}, cr3",
+                "mov {cr4}, cr4",
+                "mov {cs:x}, cs",
+                "mov {ss:x}, ss",
+                "mov {rsp}, rsp",
+                rflags = out(reg) rflags,
+                cr0 = out(reg) cr0,
+                cr3 = out(reg) cr3,
+                cr4 = out(reg) cr4,
+                cs = out(reg) cs,
+                ss = out(reg) ss,
+                rsp = out(reg) rsp,
+            );
+        }
+        // SAFETY: EFER exists on every x86_64 CPU.
+        let efer = unsafe { crate::cpu::rdmsr(IA32_EFER) };
+        Self {
+            rflags,
+            cr0,
+            cr3,
+            cr4,
+            efer,
+            cs,
+            ss,
+            rsp,
+        }
     }
 
+    /// Print the captured state under `debug`, and note the optional
+    /// protections the firmware left off.
+    pub fn log(&self) {
+        crate::debug_structured!(
+            "Entry machine state:",
+            [
+                ("rflags", format_args!("{:#x}", self.rflags)),
+                ("cr0", format_args!("{:#x}", self.cr0)),
+                ("cr3", format_args!("{:#x}", self.cr3)),
+                ("cr4", format_args!("{:#x}", self.cr4)),
+                ("efer", format_args!("{:#x}", self.efer)),
+                ("cs", format_args!("{:#x}", self.cs)),
+                ("ss", format_args!("{:#x}", self.ss)),
+                ("rsp", format_args!("{:#x}", self.rsp)),
+            ]
+        );
+        crate::diagln!(
+            "entry: interrupts {}, WP {}, NXE {}, SMEP {}, SMAP {}",
+            on_off(self.rflags & RFLAGS_IF),
+            on_off(self.cr0 & CR0_WP),
+            on_off(self.efer & EFER_NXE),
+            on_off(self.cr4 & CR4_SMEP),
+            on_off(self.cr4 & CR4_SMAP)
+        );
+        crate::debugln!(
+            "entry: 4-level paging; CPU {} 5-level paging",
+            if crate::cpu::supports_la57() {
+                "also supports"
+            } else {
+                "does not support"
+            }
+        );
+    }
+}
+
+fn on_off(bit: u64) -> &'static str {
+    if bit != 0 { "on" } else { "off" }
+}
+
+/// Check the entry state against what `kernel_main` assumes: 64-bit long
+/// mode with 4-level paging, ring 0, a 16-byte-aligned stack, and the
+/// direction flag clear. Interrupts may be on; `kernel_main` disables them.
+pub fn verify_machine_state(state: &MachineState) -> Result<(), BootValidationError> {
+    let fail = |reason| Err(BootValidationError::MachineStateInvalid(reason));
+
+    if state.cr0 & (CR0_PE | CR0_PG) != CR0_PE | CR0_PG {
+        return fail("protected mode or paging is off (CR0.PE/PG)");
+    }
+    if state.cr4 & CR4_PAE == 0 {
+        return fail("PAE paging is off (CR4.PAE)");
+    }
+    if state.cr4 & CR4_LA57 != 0 {
+        return fail("5-level paging is on (CR4.LA57); the kernel builds 4-level tables");
+    }
+    if state.efer & (EFER_LME | EFER_LMA) != EFER_LME | EFER_LMA {
+        return fail("long mode is not active (EFER.LME/LMA)");
+    }
+    if state.cs & 3 != 0 || state.ss & 3 != 0 {
+        return fail("not running in ring 0 (CS/SS RPL)");
+    }
+    if state.cr3 & !0xFFF == 0 {
+        return fail("no page-table root (CR3)");
+    }
+    if !state.rsp.is_multiple_of(16) {
+        return fail("stack is not 16-byte aligned");
+    }
+    if state.rflags & RFLAGS_DF != 0 {
+        return fail("direction flag is set (RFLAGS.DF)");
+    }
     Ok(())
 }
 
@@ -139,7 +348,7 @@ extern crate std;
 #[cfg(test)]
 mod tests {
     use super::*;
-    use oxide_abi::{BootAbi, Firmware, Options, PixelFormat};
+    use oxide_abi::{BootAbi, Firmware, Options, PhysRange, PixelFormat};
 
     fn valid_framebuffer() -> Framebuffer {
         Framebuffer {
@@ -180,6 +389,11 @@ mod tests {
             framebuffer: valid_framebuffer(),
             tsc_frequency_hz: 0,
             memory_map: valid_memory_map(),
+            status_page_phys: 0,
+            microcode: PhysRange::default(),
+            memory_attributes: PhysRange::default(),
+            loader_image: PhysRange::default(),
+            microcode_xxh32: 0,
         }
     }
 
@@ -200,6 +414,39 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn validate_boot_abi_rejects_misaligned_status_page() {
+        let mut abi = valid_boot_abi();
+        abi.status_page_phys = 0x1008;
+        assert!(matches!(
+            validate_boot_abi(&abi),
+            Err(BootValidationError::StatusPageInvalid(_))
+        ));
+        abi.status_page_phys = 0x1000;
+        assert!(validate_boot_abi(&abi).is_ok());
+    }
+
+    #[test]
+    fn validate_boot_abi_rejects_null_microcode_range() {
+        let mut abi = valid_boot_abi();
+        abi.microcode = PhysRange { phys: 0, len: 2048 };
+        assert!(matches!(
+            validate_boot_abi(&abi),
+            Err(BootValidationError::MicrocodeInvalid(_))
+        ));
+        abi.microcode.phys = 0x10_0000;
+        assert!(validate_boot_abi(&abi).is_ok());
+
+        abi.memory_attributes = PhysRange {
+            phys: u64::MAX,
+            len: 16,
+        };
+        assert!(matches!(
+            validate_boot_abi(&abi),
+            Err(BootValidationError::MemoryAttributesInvalid(_))
+        ));
+    }
+
     #[test]
     fn validate_framebuffer_rejects_null_base() {
         let mut fb = valid_framebuffer();
@@ -266,6 +513,24 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn validate_framebuffer_sizes_rows_by_scanline_not_width() {
+        // 1366 is not a multiple of 8, so GOP pads each row to 1376 pixels.
+        let mut fb = valid_framebuffer();
+        fb.width = 1366;
+        fb.height = 768;
+        fb.pixels_per_scanline = 1376;
+        fb.buffer_size = 1376 * 4 * 768;
+        assert!(validate_framebuffer(&fb).is_ok());
+
+        fb.buffer_size = 1366 * 4 * 768;
+        assert!(matches!(
+            validate_framebuffer(&fb),
+            Err(BootValidationError::FramebufferInvalid(reason))
+                if reason.contains("buffer smaller")
+        ));
+    }
+
     #[test]
     fn validate_framebuffer_allows_bgr_pixel_format() {
         let mut fb = valid_framebuffer();
@@ -285,14 +550,22 @@ mod tests {
     }
 
     #[test]
-    fn validate_memory_map_rejects_excess_entries() {
+    fn reconcile_memory_map_uses_the_smaller_count() {
         let mut map = valid_memory_map();
         map.entry_count = 10;
-        assert!(matches!(
-            validate_memory_map(&map),
-            Err(BootValidationError::MemoryMapInvalid(reason))
-                if reason.contains("count exceeds")
-        ));
+        assert!(validate_memory_map(&map).is_ok());
+
+        let fixup = reconcile_memory_map(&mut map);
+        assert_eq!(
+            fixup,
+            Some(MapFixup {
+                reported_entries: 10,
+                reported_size: map.entry_size as u64 * 4,
+                entries: 4,
+            })
+        );
+        assert_eq!(map.entry_count, 4);
+        assert_eq!(reconcile_memory_map(&mut map), None);
     }
 
     #[test]
@@ -340,13 +613,22 @@ mod tests {
     }
 
     #[test]
-    fn validate_memory_map_requires_map_size_multiple_of_entry_size() {
+    fn reconcile_memory_map_trims_trailing_slack() {
         let mut map = valid_memory_map();
-        map.map_size = map.entry_size as u64 * map.entry_count as u64 + 1;
+        map.entry_count = 3;
+        map.map_size = map.entry_size as u64 * 4 + 7;
+        assert!(validate_memory_map(&map).is_ok());
+
+        let fixup = reconcile_memory_map(&mut map).unwrap();
+        assert_eq!(fixup.entries, 3);
+        assert_eq!(map.entry_count, 3);
+        assert_eq!(map.map_size, map.entry_size as u64 * 3);
+
+        map.map_size = map.entry_size as u64 - 1;
         assert!(matches!(
             validate_memory_map(&map),
             Err(BootValidationError::MemoryMapInvalid(reason))
-                if reason.contains("not divisible")
+                if reason.contains("smaller than one entry")
         ));
     }
 
@@ -360,4 +642,41 @@ mod tests {
                 if reason.contains("address is null")
         ));
     }
+
+    #[test]
+    fn verify_machine_state_names_the_broken_assumption() {
+        let good = MachineState {
+            rflags: RFLAGS_IF | 0x2,
+            cr0: CR0_PE | CR0_PG | CR0_WP,
+            cr3: 0x10_0000,
+            cr4: CR4_PAE,
+            efer: EFER_LME | EFER_LMA | EFER_NXE,
+            cs: 0x38,
+            ss: 0x30,
+            rsp: 0x7F_F000,
+        };
+        assert!(verify_machine_state(&good).is_ok());
+
+        let broken = [
+            MachineState {
+                cr4: CR4_PAE | CR4_LA57,
+                ..good
+            },
+            MachineState { cs: 0x3B, ..good },
+            MachineState {
+                rsp: good.rsp - 8,
+                ..good
+            },
+            MachineState {
+                rflags: good.rflags | RFLAGS_DF,
+                ..good
+            },
+        ];
+        for state in broken {
+            assert!(matches!(
+                verify_machine_state(&state),
+                Err(BootValidationError::MachineStateInvalid(_))
+            ));
+        }
+    }
 }
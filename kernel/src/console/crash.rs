@@ -0,0 +1,196 @@
+//! The fatal-error screen.
+//!
+//! A fatal path clears the console and lays its report out in fixed sections
+//! (banner, error, trap, backtrace, recent log lines, build) so nothing the
+//! reader needs has scrolled off the top. Every line is also written to COM1,
+//! as plain text or as a `fatal` record under `logfmt=json`, and recorded in
+//! history.
+
+use core::fmt::{self, Write};
+
+use oxide_abi::ABI_VERSION;
+use oxide_util::FixedBufWriter;
+
+use super::{
+    CONSOLE_STATE, ConsoleState, LineSlot, MAX_LINE_CHARS, TIMESTAMP_PREFIX_MAX,
+    format_timestamp_prefix, timestamp_now,
+};
+use crate::{
+    console::{
+        Level,
+        format::{fmt_bin, fmt_hex},
+        serial_line,
+    },
+    emergency::EMERGENCY_COLOR,
+    framebuffer,
+    interrupts::{FaultFrame, PageFaultCode},
+};
+
+/// History lines shown under "recent log", when the screen has room.
+const RECENT_LINES: usize = 10;
+
+/// Rows used by everything except the recent log lines, counting the page
+/// fault's CR2, RIP, and error code lines and the empty row the cursor ends
+/// on.
+const FIXED_ROWS: usize = 14;
+
+/// CPU state captured by an exception handler.
+#[derive(Clone, Copy, Debug)]
+pub struct Trap {
+    pub vector: u8,
+    /// Faulting address, for page faults.
+    pub cr2: Option<u64>,
+    /// The exception frame, for vectors whose stub captures it.
+    pub frame: Option<FaultFrame>,
+}
+
+/// Replace the screen with the crash report for `error`.
+///
+/// If the fault hit while the console was being written, the crash screen
+/// takes the console from the interrupted writer. Falls back to the
+/// emergency writer when the console is not installed yet.
+pub fn show_crash(error: fmt::Arguments<'_>, trap: Option<Trap>) {
+    crate::serial::init();
+    let mut slot = CONSOLE_STATE
+        .try_lock()
+        .unwrap_or_else(|| CONSOLE_STATE.seize());
+    match slot.as_mut() {
+        Some(state) => state.draw_crash(error, trap),
+        None => fallback(error),
+    }
+}
+
+fn fallback(error: fmt::Arguments<'_>) {
+    crate::emergency::write(error);
+    mirror(error);
+}
+
+fn mirror(line: fmt::Arguments<'_>) {
+    let mut buf = [0u8; MAX_LINE_CHARS];
+    let mut writer = FixedBufWriter::new(&mut buf);
+    let _ = writer.write_fmt(line);
+    serial_line(Level::Fatal, module_path!(), &[writer.as_bytes()]);
+}
+
+impl ConsoleState {
+    pub(super) fn draw_crash(&mut self, error: fmt::Arguments<'_>, trap: Option<Trap>) {
+        self.replay_deferred();
+        if self.line.len() > 0 {
+            let _ = self.fb.write_bytes(b"\n");
+            self.finish_line();
+        }
+
+        // Copy the lines before the report adds its own to history.
+        let shown = RECENT_LINES
+            .min(self.fb.rows().saturating_sub(FIXED_ROWS))
+            .min(self.history.len);
+        let mut recent = [LineSlot::EMPTY; RECENT_LINES];
+        let skip = self.history.len - shown;
+        for (slot, (timestamp, line)) in recent.iter_mut().zip(self.history.lines().skip(skip)) {
+            slot.write(timestamp, line);
+        }
+
+        let theme = self.fb.theme();
+        let _ = self.fb.clear();
+        self.fb.set_color(EMERGENCY_COLOR);
+        self.crash_line(format_args!("*** OXIDE HAS STOPPED ***"));
+        self.fb.set_color(theme.foreground);
+        self.crash_line(error);
+
+        self.section("trap");
+        match trap {
+            Some(trap) => {
+                self.crash_line(format_args!(
+                    "  vector {}",
+                    fmt_hex(u64::from(trap.vector), 2)
+                ));
+                if let Some(cr2) = trap.cr2 {
+                    self.crash_line(format_args!("  cr2    {}", fmt_hex(cr2, 16)));
+                }
+                match trap.frame {
+                    Some(frame) => {
+                        self.crash_line(format_args!("  rip    {}", fmt_hex(frame.rip, 16)));
+                        if trap.vector == 0x0E {
+                            self.crash_line(format_args!(
+                                "  error  {} ({})",
+                                fmt_bin(frame.error_code, 7),
+                                PageFaultCode(frame.error_code)
+                            ));
+                        } else {
+                            self.crash_line(format_args!(
+                                "  error  {}",
+                                fmt_hex(frame.error_code, 4)
+                            ));
+                        }
+                        self.crash_line(format_args!(
+                            "  general registers not captured by the stub"
+                        ));
+                    }
+                    None => self.crash_line(format_args!(
+                        "  registers not captured: handler stubs save no frame"
+                    )),
+                }
+            }
+            None => self.crash_line(format_args!("  none: not a CPU exception")),
+        }
+
+        self.section("backtrace");
+        self.crash_line(format_args!(
+            "  unavailable: built without frame pointers or symbols"
+        ));
+
+        self.section("recent log");
+        for slot in &recent[..shown] {
+            self.crash_bytes(b"  ", &slot.data[..usize::from(slot.len)]);
+        }
+
+        self.section("build");
+        self.crash_line(format_args!(
+            "  oxide-kernel {} ({}), BootAbi v{}, {} nested faults",
+            env!("CARGO_PKG_VERSION"),
+            if cfg!(debug_assertions) {
+                "debug"
+            } else {
+                "release"
+            },
+            ABI_VERSION,
+            crate::emergency::nested_faults()
+        ));
+    }
+
+    fn section(&mut self, name: &str) {
+        let theme = self.fb.theme();
+        self.fb.set_color(theme.diagnostic);
+        self.crash_line(format_args!("-- {} --", name));
+        self.fb.set_color(theme.foreground);
+    }
+
+    fn crash_line(&mut self, line: fmt::Arguments<'_>) {
+        let mut buf = [0u8; MAX_LINE_CHARS];
+        let mut writer = FixedBufWriter::new(&mut buf);
+        let _ = writer.write_fmt(line);
+        let len = writer.len();
+        self.crash_bytes(b"", &buf[..len]);
+    }
+
+    /// Draw `indent` and `text` on one row, cut at the screen edge, and
+    /// mirror them to serial in full. No timestamp is drawn; history gets one.
+    fn crash_bytes(&mut self, indent: &[u8], text: &[u8]) {
+        let timestamp = timestamp_now();
+        let mut prefix = [0u8; TIMESTAMP_PREFIX_MAX];
+        let prefix_len = format_timestamp_prefix(&mut prefix, timestamp);
+        self.line.extend_from_slice(&prefix[..prefix_len]);
+
+        let visible = self.fb.cols().saturating_sub(indent.len()).min(text.len());
+        for &byte in indent.iter().chain(&text[..visible]) {
+            let shown = framebuffer::text::sanitize_byte(byte);
+            let _ = self.fb.write_bytes(&[shown]);
+            self.line.push(shown);
+        }
+        let _ = self.fb.write_bytes(b"\n");
+        serial_line(Level::Fatal, module_path!(), &[indent, text]);
+
+        self.history.push(timestamp, self.line.as_slice());
+        self.line.clear();
+    }
+}
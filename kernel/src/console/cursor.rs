@@ -0,0 +1,78 @@
+//! The blinking block cursor at the console's write position.
+//!
+//! The block is drawn solid after every write, so a stalled boot shows where
+//! output stopped, and [`Blink::tick`] flips it each half period from the
+//! timer interrupt once ticks arrive. Nothing blinks until then; the cursor
+//! just stays lit.
+
+use crate::time::Instant;
+
+/// Time the cursor spends lit, and then dark, in each blink.
+const HALF_PERIOD_NANOS: u64 = 500_000_000;
+
+/// Blink phase and the `set_cursor_visible` setting.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Blink {
+    visible: bool,
+    lit: bool,
+    since: Instant,
+}
+
+impl Blink {
+    pub(super) const fn new() -> Self {
+        Self {
+            visible: true,
+            lit: true,
+            since: Instant::ZERO,
+        }
+    }
+
+    /// Whether the block should be on screen right now.
+    pub(super) fn shown(&self) -> bool {
+        self.visible && self.lit
+    }
+
+    pub(super) fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Light the cursor and restart the blink, as after output.
+    pub(super) fn restart(&mut self, now: Instant) {
+        self.lit = true;
+        self.since = now;
+    }
+
+    /// Advance the blink to `now`; returns whether the phase flipped.
+    pub(super) fn tick(&mut self, now: Instant) -> bool {
+        if now.as_nanos().saturating_sub(self.since.as_nanos()) < HALF_PERIOD_NANOS {
+            return false;
+        }
+        self.lit = !self.lit;
+        self.since = now;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blink_flips_each_half_period_and_restarts_lit() {
+        let at = |millis: u64| Instant::from_nanos(millis * 1_000_000);
+        let mut blink = Blink::new();
+        blink.restart(at(1_000));
+        assert!(!blink.tick(at(1_499)));
+        assert!(blink.tick(at(1_500)));
+        assert!(!blink.shown());
+        assert!(blink.tick(at(2_000)));
+        assert!(blink.shown());
+
+        blink.tick(at(2_500));
+        blink.restart(at(2_600));
+        assert!(blink.shown());
+
+        blink.set_visible(false);
+        assert!(!blink.shown());
+    }
+}
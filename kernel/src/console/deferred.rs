@@ -0,0 +1,159 @@
+//! Bounded staging buffer for console output emitted while the console lock is held.
+//!
+//! Interrupt and exception handlers may log while another context is part-way
+//! through rendering a line. Rather than re-entering the console state, their
+//! formatted bytes are parked here and replayed by the lock holder once it
+//! finishes its own write.
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+const DEFERRED_CAPACITY: usize = 2048;
+
+/// Fixed-capacity FIFO of raw console bytes.
+pub(super) struct DeferredRing {
+    data: [u8; DEFERRED_CAPACITY],
+    head: usize,
+    len: usize,
+}
+
+impl DeferredRing {
+    pub(super) const fn new() -> Self {
+        Self {
+            data: [0; DEFERRED_CAPACITY],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Append as many bytes as fit, returning the number that were dropped.
+    pub(super) fn push(&mut self, bytes: &[u8]) -> usize {
+        let available = DEFERRED_CAPACITY - self.len;
+        let accepted = bytes.len().min(available);
+
+        for &byte in &bytes[..accepted] {
+            let tail = (self.head + self.len) % DEFERRED_CAPACITY;
+            self.data[tail] = byte;
+            self.len += 1;
+        }
+
+        bytes.len() - accepted
+    }
+
+    /// Remove the oldest byte from the ring.
+    pub(super) fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let byte = self.data[self.head];
+        self.head = (self.head + 1) % DEFERRED_CAPACITY;
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+struct DeferredCell {
+    busy: AtomicBool,
+    ring: UnsafeCell<DeferredRing>,
+}
+
+unsafe impl Sync for DeferredCell {}
+
+static DEFERRED: DeferredCell = DeferredCell {
+    busy: AtomicBool::new(false),
+    ring: UnsafeCell::new(DeferredRing::new()),
+};
+
+static DROPPED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Run `f` with exclusive access to the deferred ring.
+///
+/// Returns `None` when the ring is already being accessed (for example by a
+/// handler that interrupted a drain), in which case the caller must not block.
+fn with_ring<R>(f: impl FnOnce(&mut DeferredRing) -> R) -> Option<R> {
+    if DEFERRED
+        .busy
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        return None;
+    }
+
+    // SAFETY: the busy flag grants exclusive access until it is released below.
+    let result = f(unsafe { &mut *DEFERRED.ring.get() });
+    DEFERRED.busy.store(false, Ordering::Release);
+    Some(result)
+}
+
+/// Format `args` into the deferred ring, counting anything that does not fit.
+pub(super) fn push_fmt(args: fmt::Arguments<'_>) {
+    let mut writer = DeferredWriter;
+    let _ = fmt::write(&mut writer, args);
+}
+
+/// Pop the next deferred byte, if any.
+pub(super) fn pop() -> Option<u8> {
+    with_ring(|ring| ring.pop()).flatten()
+}
+
+/// Take and reset the count of bytes dropped since the previous call.
+pub(super) fn take_dropped() -> usize {
+    DROPPED_BYTES.swap(0, Ordering::Relaxed)
+}
+
+struct DeferredWriter;
+
+impl fmt::Write for DeferredWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let bytes = s.as_bytes();
+        let dropped = with_ring(|ring| ring.push(bytes)).unwrap_or(bytes.len());
+        if dropped > 0 {
+            DROPPED_BYTES.fetch_add(dropped, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deferred_ring_preserves_fifo_order() {
+        let mut ring = DeferredRing::new();
+        assert_eq!(ring.push(b"abc"), 0);
+        assert_eq!(ring.pop(), Some(b'a'));
+        assert_eq!(ring.push(b"d"), 0);
+        assert_eq!(ring.pop(), Some(b'b'));
+        assert_eq!(ring.pop(), Some(b'c'));
+        assert_eq!(ring.pop(), Some(b'd'));
+        assert_eq!(ring.pop(), None);
+        assert_eq!(ring.len, 0);
+    }
+
+    #[test]
+    fn deferred_ring_reports_dropped_bytes_when_full() {
+        let mut ring = DeferredRing::new();
+        let fill = [b'x'; DEFERRED_CAPACITY - 2];
+        assert_eq!(ring.push(&fill), 0);
+        assert_eq!(ring.push(b"12345"), 3);
+        assert_eq!(ring.len, DEFERRED_CAPACITY);
+    }
+
+    #[test]
+    fn deferred_ring_wraps_around_capacity() {
+        let mut ring = DeferredRing::new();
+        let fill = [b'x'; DEFERRED_CAPACITY - 1];
+        ring.push(&fill);
+        for _ in 0..fill.len() {
+            ring.pop();
+        }
+        assert_eq!(ring.push(b"yz"), 0);
+        assert_eq!(ring.pop(), Some(b'y'));
+        assert_eq!(ring.pop(), Some(b'z'));
+    }
+}
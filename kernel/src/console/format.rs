@@ -0,0 +1,217 @@
+//! Number formatting for console output without allocation.
+//!
+//! Each helper returns a small value implementing `Display`, so it drops
+//! straight into `println!` and honours width, fill, and alignment:
+//!
+//! ```text
+//! println!("{:>9}", fmt_size(1_610_612_736));  // "  1.5 GiB"
+//! println!("{}", fmt_hex(0xFEE0_0000, 16));    // "0x0000_0000_fee0_0000"
+//! ```
+
+use core::fmt::{self, Write};
+
+/// Longest rendering: 64 binary digits, 15 group separators, and `0b`.
+const BUF_CAP: usize = 81;
+
+const SIZE_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+const HZ_UNITS: [&str; 4] = ["Hz", "kHz", "MHz", "GHz"];
+
+/// A byte count in binary units with one decimal, e.g. `1.5 GiB`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Size(pub u64);
+
+/// A frequency in decimal units with two decimals, e.g. `3.20 GHz`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hz(pub u64);
+
+/// A value in `0x`-prefixed hex, zero-padded to `digits` and grouped by four.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hex {
+    value: u64,
+    digits: usize,
+}
+
+/// A value in `0b`-prefixed binary, zero-padded to `bits` and grouped by four.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bin {
+    value: u64,
+    bits: usize,
+}
+
+pub fn fmt_size(bytes: u64) -> Size {
+    Size(bytes)
+}
+
+pub fn fmt_hz(hz: u64) -> Hz {
+    Hz(hz)
+}
+
+/// Hex with at least `digits` digits (at most 16); more if the value needs them.
+pub fn fmt_hex(value: u64, digits: usize) -> Hex {
+    Hex {
+        value,
+        digits: digits.min(16),
+    }
+}
+
+/// Binary with at least `bits` digits (at most 64); more if the value needs them.
+pub fn fmt_bin(value: u64, bits: usize) -> Bin {
+    Bin {
+        value,
+        bits: bits.min(64),
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut buf = Buf::new();
+        let (unit, scale) = pick_unit(self.0, 1024, SIZE_UNITS.len());
+        if unit == 0 {
+            let _ = write!(buf, "{} B", self.0);
+        } else {
+            let tenths = (self.0 as u128 * 10 / scale as u128) as u64;
+            let _ = match tenths % 10 {
+                0 => write!(buf, "{} {}", tenths / 10, SIZE_UNITS[unit]),
+                frac => write!(buf, "{}.{} {}", tenths / 10, frac, SIZE_UNITS[unit]),
+            };
+        }
+        f.pad(buf.as_str())
+    }
+}
+
+impl fmt::Display for Hz {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut buf = Buf::new();
+        let (unit, scale) = pick_unit(self.0, 1000, HZ_UNITS.len());
+        let hundredths = (self.0 as u128 * 100 / scale as u128) as u64;
+        let _ = write!(
+            buf,
+            "{}.{:02} {}",
+            hundredths / 100,
+            hundredths % 100,
+            HZ_UNITS[unit]
+        );
+        f.pad(buf.as_str())
+    }
+}
+
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let used = (64 - self.value.leading_zeros() as usize).div_ceil(4);
+        let mut buf = Buf::new();
+        let _ = buf.write_str("0x");
+        write_grouped(&mut buf, self.value, 4, used.max(self.digits).max(1));
+        f.pad(buf.as_str())
+    }
+}
+
+impl fmt::Display for Bin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let used = 64 - self.value.leading_zeros() as usize;
+        let mut buf = Buf::new();
+        let _ = buf.write_str("0b");
+        write_grouped(&mut buf, self.value, 1, used.max(self.bits).max(1));
+        f.pad(buf.as_str())
+    }
+}
+
+/// Largest unit index (below `units`) whose scale does not exceed `value`.
+fn pick_unit(value: u64, step: u64, units: usize) -> (usize, u64) {
+    let mut unit = 0;
+    let mut scale = 1u64;
+    while unit + 1 < units {
+        match scale.checked_mul(step) {
+            Some(next) if next <= value => {
+                scale = next;
+                unit += 1;
+            }
+            _ => break,
+        }
+    }
+    (unit, scale)
+}
+
+/// Write the low `digits` digits of `value` (each `bits_per_digit` wide),
+/// most significant first, with `_` between groups of four.
+fn write_grouped(buf: &mut Buf, value: u64, bits_per_digit: usize, digits: usize) {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mask = (1u64 << bits_per_digit) - 1;
+    for index in (0..digits).rev() {
+        let digit = (value >> (index * bits_per_digit)) & mask;
+        buf.push(DIGITS[digit as usize]);
+        if index != 0 && index % 4 == 0 {
+            buf.push(b'_');
+        }
+    }
+}
+
+/// Fixed stack buffer the helpers render into before padding.
+struct Buf {
+    bytes: [u8; BUF_CAP],
+    len: usize,
+}
+
+impl Buf {
+    const fn new() -> Self {
+        Self {
+            bytes: [0; BUF_CAP],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        if let Some(slot) = self.bytes.get_mut(self.len) {
+            *slot = byte;
+            self.len += 1;
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // Only ASCII is ever written.
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
+
+impl Write for Buf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.push(byte);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn sizes_and_frequencies_pick_the_largest_unit() {
+        assert_eq!(format!("{}", fmt_size(512)), "512 B");
+        assert_eq!(format!("{}", fmt_size(4096)), "4 KiB");
+        assert_eq!(format!("{}", fmt_size(1_610_612_736)), "1.5 GiB");
+        assert_eq!(format!("{}", fmt_size(u64::MAX)), "15.9 EiB");
+        assert_eq!(format!("{:>9}", fmt_size(3 << 20)), "    3 MiB");
+
+        assert_eq!(format!("{}", fmt_hz(3_200_000_000)), "3.20 GHz");
+        assert_eq!(format!("{}", fmt_hz(1_500_000)), "1.50 MHz");
+        assert_eq!(format!("{}", fmt_hz(750_000)), "750.00 kHz");
+        assert_eq!(format!("{}", fmt_hz(500)), "500.00 Hz");
+    }
+
+    #[test]
+    fn hex_and_binary_pad_and_group() {
+        assert_eq!(
+            format!("{}", fmt_hex(0xFEE0_0000, 16)),
+            "0x0000_0000_fee0_0000"
+        );
+        assert_eq!(format!("{}", fmt_hex(0x1F, 2)), "0x1f");
+        assert_eq!(format!("{}", fmt_hex(0x12345, 2)), "0x1_2345");
+        assert_eq!(format!("{}", fmt_hex(0, 0)), "0x0");
+        assert_eq!(format!("{}", fmt_bin(0b101, 8)), "0b0000_0101");
+        assert_eq!(format!("{}", fmt_bin(u64::MAX, 0)).len(), 2 + 64 + 15);
+    }
+}
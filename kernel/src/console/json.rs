@@ -0,0 +1,182 @@
+//! Log records as JSON lines on the serial port, with `logfmt=json`.
+//!
+//! Each console line, shown or captured, becomes one object:
+//! `{"ts":1.250000,"level":"diag","module":"oxide_kernel::memory","msg":"..."}`.
+//! The message is the text as written, before the console's uppercasing and
+//! without the timestamp prefix, so harnesses can match it exactly.
+
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::{MAX_LINE_CHARS, Timestamp};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Which macro family produced a record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    /// `print!`/`println!`.
+    Info,
+    /// `diag!`/`diagln!`.
+    Diag,
+    /// `debug!`/`debugln!`.
+    Debug,
+    /// A row of the crash report.
+    Fatal,
+}
+
+impl Level {
+    const fn name(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Diag => "diag",
+            Level::Debug => "debug",
+            Level::Fatal => "fatal",
+        }
+    }
+}
+
+/// Turn the sink on if `logfmt=json` was given and COM1 exists.
+pub(super) fn init() {
+    if crate::options::value("logfmt") == Some("json") && crate::serial::init() {
+        ENABLED.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Whether records are being sent.
+pub(super) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Write one line to COM1 that does not go through the console's line
+/// assembly, such as a crash-report row or a screen-dump line. With
+/// `logfmt=json` it becomes a record, so the port only ever carries JSON;
+/// otherwise `parts` go out as plain text followed by a newline.
+pub fn serial_line(level: Level, module: &'static str, parts: &[&[u8]]) {
+    if !enabled() {
+        for part in parts {
+            crate::serial::write_bytes(part);
+        }
+        crate::serial::write_bytes(b"\n");
+        return;
+    }
+    let mut line = JsonLine::new(level, module);
+    for &byte in parts.iter().copied().flatten() {
+        line.push(byte);
+    }
+    line.emit(super::timestamp_now());
+}
+
+/// The record being assembled for the current console line.
+pub(super) struct JsonLine {
+    level: Level,
+    module: &'static str,
+    msg: [u8; MAX_LINE_CHARS],
+    len: usize,
+}
+
+impl JsonLine {
+    pub(super) const fn new(level: Level, module: &'static str) -> Self {
+        Self {
+            level,
+            module,
+            msg: [0; MAX_LINE_CHARS],
+            len: 0,
+        }
+    }
+
+    /// Attribute the line to `level` and `module` unless it already has text.
+    pub(super) fn set_source(&mut self, level: Level, module: &'static str) {
+        if self.len == 0 {
+            self.level = level;
+            self.module = module;
+        }
+    }
+
+    pub(super) fn push(&mut self, byte: u8) {
+        if self.len < MAX_LINE_CHARS {
+            self.msg[self.len] = byte;
+            self.len += 1;
+        }
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Send the record to the serial port, if enabled, and start a new one.
+    pub(super) fn emit(&mut self, timestamp: Timestamp) {
+        if ENABLED.load(Ordering::Relaxed) {
+            let _ = self.encode(&mut SerialWriter, timestamp);
+        }
+        self.clear();
+    }
+
+    fn encode(&self, out: &mut impl Write, timestamp: Timestamp) -> fmt::Result {
+        let nanos = timestamp.as_nanos();
+        write!(
+            out,
+            "{{\"ts\":{}.{:06},\"level\":\"{}\",\"module\":",
+            nanos / 1_000_000_000,
+            (nanos % 1_000_000_000) / 1_000,
+            self.level.name(),
+        )?;
+        write_string(out, self.module.as_bytes())?;
+        out.write_str(",\"msg\":")?;
+        write_string(out, &self.msg[..self.len])?;
+        out.write_str("}\n")
+    }
+}
+
+/// `bytes` as a quoted JSON string; bytes outside printable ASCII are
+/// escaped as `\u00XX`.
+fn write_string(out: &mut impl Write, bytes: &[u8]) -> fmt::Result {
+    out.write_char('"')?;
+    for &byte in bytes {
+        match byte {
+            b'"' => out.write_str("\\\"")?,
+            b'\\' => out.write_str("\\\\")?,
+            0x20..=0x7E => out.write_char(byte as char)?,
+            _ => write!(out, "\\u{:04x}", byte)?,
+        }
+    }
+    out.write_char('"')
+}
+
+struct SerialWriter;
+
+impl Write for SerialWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        crate::serial::write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::string::String;
+
+    #[test]
+    fn records_encode_as_escaped_json_objects() {
+        let mut line = JsonLine::new(Level::Info, "");
+        line.set_source(Level::Diag, "oxide_kernel::memory");
+        for &byte in b"free \"low\"\t\\ok" {
+            line.push(byte);
+        }
+        line.set_source(Level::Debug, "ignored");
+
+        let mut out = String::new();
+        line.encode(&mut out, Timestamp::from_nanos(1_250_000_999))
+            .unwrap();
+        assert_eq!(
+            out,
+            "{\"ts\":1.250000,\"level\":\"diag\",\"module\":\"oxide_kernel::memory\",\
+             \"msg\":\"free \\\"low\\\"\\u0009\\\\ok\"}\n"
+        );
+    }
+}
@@ -0,0 +1,26 @@
+//! A plain-text copy of the console on the serial port, with `serial`.
+//!
+//! Each finished line goes to COM1 exactly as history records it, timestamp
+//! prefix included, so a headless run logs what the screen would have shown.
+//! Lines captured silently by `quiet` are not sent. `logfmt=json` owns the
+//! port when given, and the mirror stays off.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn the mirror on if `serial` was given, JSON output is off, and COM1
+/// exists.
+pub(super) fn init() {
+    if crate::options::flag("serial") && !super::json::enabled() && crate::serial::init() {
+        ENABLED.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Send one finished console line, if the mirror is on.
+pub(super) fn line(bytes: &[u8]) {
+    if ENABLED.load(Ordering::Relaxed) {
+        crate::serial::write_bytes(bytes);
+        crate::serial::write_bytes(b"\n");
+    }
+}
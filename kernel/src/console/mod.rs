@@ -1,14 +1,38 @@
 //! Framebuffer-backed kernel console with timestamped history.
 
-use core::{cell::UnsafeCell, cmp::min, fmt, mem};
+use core::{
+    cell::UnsafeCell,
+    cmp::min,
+    fmt::{self, Write},
+    mem,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
 
 use oxide_abi::Framebuffer;
+use oxide_util::FixedBufWriter;
 
 use crate::{
-    framebuffer::{self, FramebufferColor},
+    framebuffer::{self, FramebufferColor, FramebufferSurface, Theme},
+    interrupts::IrqGuard,
     time,
 };
 
+mod crash;
+mod cursor;
+mod deferred;
+pub mod format;
+mod json;
+mod mirror;
+mod summary;
+mod wrap;
+
+pub use crash::{Trap, show_crash};
+use json::JsonLine;
+pub use json::{Level, serial_line};
+use summary::CaptureSummary;
+use wrap::{LineLayout, WrapPolicy};
+
 const MAX_LINE_CHARS: usize = 160;
 const HISTORY_CAPACITY: usize = 128;
 const TIMESTAMP_PREFIX_MAX: usize = 32;
@@ -54,8 +78,22 @@ impl ConsoleStorage {
     /// The caller must guarantee the region is appropriately sized and mapped
     /// for exclusive console use.
     pub unsafe fn from_physical(start: u64) -> Self {
+        unsafe { Self::with_capacity(start, HISTORY_CAPACITY) }
+    }
+
+    /// Interpret `bytes` of physical memory at `start` as console storage,
+    /// holding as many lines as fit.
+    ///
+    /// # Safety
+    /// As for [`ConsoleStorage::from_physical`].
+    pub unsafe fn from_physical_region(start: u64, bytes: u64) -> Self {
+        let lines = bytes as usize / mem::size_of::<LineSlot>();
+        unsafe { Self::with_capacity(start, lines) }
+    }
+
+    unsafe fn with_capacity(start: u64, lines: usize) -> Self {
         let ptr = start as *mut LineSlot;
-        let slots = unsafe { core::slice::from_raw_parts_mut(ptr, HISTORY_CAPACITY) };
+        let slots = unsafe { core::slice::from_raw_parts_mut(ptr, lines) };
         for slot in slots.iter_mut() {
             *slot = LineSlot::EMPTY;
         }
@@ -72,84 +110,404 @@ impl ConsoleStorage {
 pub enum ConsoleInitError {
     AlreadyInitialized,
     FramebufferUnavailable,
+    Busy,
 }
 
-struct ConsoleCell(UnsafeCell<Option<ConsoleState>>);
+/// Global console state guarded by an interrupt-masking spin flag.
+///
+/// The flag is only ever tried, never spun on: a context that finds the console
+/// busy (typically an interrupt or exception handler that fired mid-write)
+/// parks its output in the deferred ring instead of re-entering the state.
+struct ConsoleCell {
+    locked: AtomicBool,
+    state: UnsafeCell<Option<ConsoleState>>,
+}
 
 unsafe impl Sync for ConsoleCell {}
 
-static CONSOLE_STATE: ConsoleCell = ConsoleCell(UnsafeCell::new(None));
+impl ConsoleCell {
+    const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            state: UnsafeCell::new(None),
+        }
+    }
+
+    /// Attempt to take exclusive ownership of the console with interrupts masked.
+    fn try_lock(&self) -> Option<ConsoleGuard<'_>> {
+        let irq = IrqGuard::save();
+
+        if self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return None;
+        }
+
+        Some(ConsoleGuard {
+            cell: self,
+            _irq: irq,
+        })
+    }
+
+    /// Take the console whether or not its lock is held, for the fatal path.
+    ///
+    /// Only sound when the holder never runs again. With one CPU, a fatal
+    /// path that finds the lock held has interrupted the holder, and fatal
+    /// paths never return to it. A fault while drawing over the holder's
+    /// half-finished line is caught by `emergency::enter`.
+    fn seize(&self) -> ConsoleGuard<'_> {
+        let irq = IrqGuard::save();
+        self.locked.swap(true, Ordering::Acquire);
+        ConsoleGuard {
+            cell: self,
+            _irq: irq,
+        }
+    }
+}
+
+/// Exclusive handle on the console state; releases the lock and restores the
+/// caller's interrupt flag on drop.
+struct ConsoleGuard<'a> {
+    cell: &'a ConsoleCell,
+    /// Dropped after the lock is released, restoring the interrupt flag.
+    _irq: IrqGuard,
+}
+
+impl Deref for ConsoleGuard<'_> {
+    type Target = Option<ConsoleState>;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the guard holds the console lock.
+        unsafe { &*self.cell.state.get() }
+    }
+}
+
+impl DerefMut for ConsoleGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: the guard holds the console lock.
+        unsafe { &mut *self.cell.state.get() }
+    }
+}
+
+impl Drop for ConsoleGuard<'_> {
+    fn drop(&mut self) {
+        self.cell.locked.store(false, Ordering::Release);
+    }
+}
+
+static CONSOLE_STATE: ConsoleCell = ConsoleCell::new();
+/// Set once [`init`] installs the console, so [`capture`] can skip the lock
+/// (and its interrupt masking) for the diagnostics emitted before that.
+static CONSOLE_READY: AtomicBool = AtomicBool::new(false);
 
-/// Install the framebuffer console using the provided storage and colour.
+/// Install the framebuffer console using the provided storage and theme.
 pub fn init(
     framebuffer: Framebuffer,
-    color: FramebufferColor,
+    theme: Theme,
     storage: ConsoleStorage,
 ) -> Result<(), ConsoleInitError> {
-    unsafe {
-        let slot = &mut *CONSOLE_STATE.0.get();
-        if slot.is_some() {
-            return Err(ConsoleInitError::AlreadyInitialized);
-        }
+    let mut slot = CONSOLE_STATE.try_lock().ok_or(ConsoleInitError::Busy)?;
+    if slot.is_some() {
+        return Err(ConsoleInitError::AlreadyInitialized);
+    }
 
-        let mut console = framebuffer::text::FramebufferConsole::new(
-            framebuffer,
-            0,
-            framebuffer::FONT_HEIGHT,
-            color,
-        );
+    // Leave one glyph row above the viewport for emergency output.
+    let font = framebuffer::font();
+    let scale = framebuffer::font_scale();
+    let mut console = framebuffer::text::FramebufferConsole::new(
+        framebuffer,
+        0,
+        font.height * scale,
+        theme,
+        font,
+        scale,
+    );
+
+    if !console.is_usable() {
+        return Err(ConsoleInitError::FramebufferUnavailable);
+    }
+
+    console
+        .clear()
+        .map_err(|_| ConsoleInitError::FramebufferUnavailable)?;
+
+    let state = ConsoleState::new(console, storage.into_slots(), wrap::policy());
+    *slot = Some(state);
+    json::init();
+    mirror::init();
+    CONSOLE_READY.store(true, Ordering::Release);
+    let _ = crate::power::register("console", flush);
+
+    Ok(())
+}
 
-        if !console.is_usable() {
-            return Err(ConsoleInitError::FramebufferUnavailable);
+/// Move the history into `storage`, keeping every line it holds; returns the
+/// new capacity in lines.
+///
+/// Meant for a larger buffer once the runtime allocator is up. `storage` is
+/// handed back when it is no larger than the current buffer, or when the
+/// console is busy or not installed, so the caller can release it.
+pub fn grow_history(storage: ConsoleStorage) -> Result<usize, ConsoleStorage> {
+    let Some(mut slot) = CONSOLE_STATE.try_lock() else {
+        return Err(storage);
+    };
+    match slot.as_mut() {
+        Some(state) if storage.slots.len() > state.history.slots.len() => {
+            state.history.migrate(storage.into_slots());
+            Ok(state.history.slots.len())
         }
+        _ => Err(storage),
+    }
+}
 
-        console
-            .clear()
-            .map_err(|_| ConsoleInitError::FramebufferUnavailable)?;
+/// Render any deferred output and commit a partial line to history.
+///
+/// Skipped when the console is busy, which on a fatal path means the
+/// faulting code held it.
+fn flush(_: crate::power::Teardown) {
+    let Some(mut slot) = CONSOLE_STATE.try_lock() else {
+        return;
+    };
+    if let Some(state) = slot.as_mut() {
+        state.replay_deferred();
+        if state.line.len() > 0 {
+            state.finish_line();
+        }
+    }
+}
+
+/// Forward formatted output from `module_path` into the global console, if
+/// initialised.
+///
+/// Safe to call from interrupt context: if the console is already being
+/// written, the output is deferred and replayed by the current holder (or the
+/// next writer) instead of corrupting the in-progress line.
+pub fn write(module_path: &'static str, args: fmt::Arguments<'_>) -> fmt::Result {
+    write_in(Level::Info, module_path, |theme| theme.foreground, args)
+}
+
+/// Like [`write`], but rendered in the theme's diagnostic colour.
+///
+/// Used by `diag!`/`debug!`. Output deferred because the console was busy is
+/// replayed in the normal foreground colour.
+pub fn write_diag(
+    level: Level,
+    module_path: &'static str,
+    args: fmt::Arguments<'_>,
+) -> fmt::Result {
+    write_in(level, module_path, |theme| theme.diagnostic, args)
+}
+
+/// Shared body of [`write`] and [`write_diag`]: render `args` in the colour
+/// `color` picks from the theme, then restore the foreground colour.
+fn write_in(
+    level: Level,
+    module_path: &'static str,
+    color: fn(&Theme) -> FramebufferColor,
+    args: fmt::Arguments<'_>,
+) -> fmt::Result {
+    let Some(mut slot) = CONSOLE_STATE.try_lock() else {
+        deferred::push_fmt(args);
+        return Ok(());
+    };
+
+    let state = slot.as_mut().ok_or(fmt::Error)?;
+    state.replay_deferred();
+    state.json.set_source(level, module_path);
+    let theme = state.fb.theme();
+    state.fb.set_color(color(&theme));
+    let result = state.write_fmt(args);
+    state.fb.set_color(theme.foreground);
+    state.replay_deferred();
+    state.relight_cursor();
+    result
+}
 
-        let state = ConsoleState::new(console, storage.into_slots());
-        *slot = Some(state);
+/// A `debug_structured!` record: the headline, then one indented
+/// `key=value` line per pair.
+pub struct Structured<'a> {
+    pub headline: fmt::Arguments<'a>,
+    pub pairs: &'a [(&'a dyn fmt::Display, &'a dyn fmt::Display)],
+}
 
+impl fmt::Display for Structured<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.headline)?;
+        for (key, value) in self.pairs {
+            writeln!(f, "  {}={}", key, value)?;
+        }
         Ok(())
     }
 }
 
-/// Forward formatted output into the global console, if initialised.
-pub fn write(args: fmt::Arguments<'_>) -> fmt::Result {
-    unsafe {
-        let state_slot = &mut *CONSOLE_STATE.0.get();
-        let state = state_slot.as_mut().ok_or(fmt::Error)?;
-        state.write_fmt(args)
+/// Record a diagnostic in the console history without rendering it.
+///
+/// Used by `diag!`/`debug!` and their line variants whenever their output is
+/// suppressed, so the history holds the details of a quiet boot. In
+/// `quiet=summary` mode `module_path` also attributes the line to a subsystem
+/// for [`log_capture_summary`]. Output from a context that finds the console
+/// busy is dropped rather than deferred, since it would never be shown anyway.
+pub fn capture(level: Level, module_path: &'static str, args: fmt::Arguments<'_>) {
+    if !CONSOLE_READY.load(Ordering::Acquire) {
+        return;
+    }
+    let Some(mut slot) = CONSOLE_STATE.try_lock() else {
+        return;
+    };
+
+    if let Some(state) = slot.as_mut() {
+        if crate::options::diagnostics_captured() {
+            state.captured.record(module_path);
+        }
+        let timestamp = timestamp_now();
+        let mut writer = CaptureWriter {
+            history: &mut state.history,
+            line: LineBuffer::new(),
+            json: JsonLine::new(level, module_path),
+            timestamp,
+        };
+        let _ = fmt::write(&mut writer, args);
+        writer.finish();
+    }
+}
+
+/// Show or hide the block cursor at the console's write position.
+///
+/// Visible by default. Does nothing when the console is busy or not
+/// installed.
+pub fn set_cursor_visible(visible: bool) {
+    let Some(mut slot) = CONSOLE_STATE.try_lock() else {
+        return;
+    };
+    if let Some(state) = slot.as_mut() {
+        state.cursor.set_visible(visible);
+        state.fb.show_cursor(state.cursor.shown());
+    }
+}
+
+/// Advance the cursor blink; called from the timer interrupt.
+///
+/// A tick that finds the console busy is skipped, since the writer relights
+/// the cursor when it finishes.
+pub fn cursor_tick() {
+    let Some(mut slot) = CONSOLE_STATE.try_lock() else {
+        return;
+    };
+    let Some(state) = slot.as_mut() else {
+        return;
+    };
+    if state.cursor.tick(time::now()) {
+        state.fb.show_cursor(state.cursor.shown());
     }
 }
 
+/// Print one line summarising the diagnostics captured by [`capture`].
+///
+/// Does nothing when no diagnostics were captured.
+pub fn log_capture_summary() {
+    let summary = {
+        let Some(slot) = CONSOLE_STATE.try_lock() else {
+            return;
+        };
+        match slot.as_ref() {
+            Some(state) => state.captured.clone(),
+            None => return,
+        }
+    };
+
+    if summary.total() == 0 {
+        return;
+    }
+
+    crate::print!(
+        "quiet: {} diagnostics kept in console history (",
+        summary.total()
+    );
+    for (index, (name, count)) in summary.iter().enumerate() {
+        let separator = if index == 0 { "" } else { ", " };
+        crate::print!("{}{} {}", separator, name, count);
+    }
+    crate::println!(")");
+}
+
+/// Redraw the console history into `surface`, oldest line first, with the
+/// console's theme, font, and scale; returns the number of lines drawn.
+///
+/// The surface may be ordinary memory, so tests can read back exactly what a
+/// screen would show. Fails when the console is busy or not installed.
+#[allow(dead_code)]
+pub fn render_history_to(surface: FramebufferSurface) -> Result<usize, ()> {
+    let slot = CONSOLE_STATE.try_lock().ok_or(())?;
+    let state = slot.as_ref().ok_or(())?;
+    let mut target = framebuffer::text::FramebufferConsole::with_surface(
+        surface,
+        0,
+        0,
+        state.fb.theme(),
+        framebuffer::font(),
+        framebuffer::font_scale(),
+    );
+    state.history.render(&mut target, state.layout.policy())
+}
+
 struct ConsoleState {
     fb: framebuffer::text::FramebufferConsole,
     history: History,
     line: LineBuffer,
-    current_column: usize,
-    columns: usize,
+    json: JsonLine,
+    layout: LineLayout,
     current_timestamp: Option<Timestamp>,
+    captured: CaptureSummary,
+    cursor: cursor::Blink,
 }
 
 impl ConsoleState {
-    fn new(fb: framebuffer::text::FramebufferConsole, slots: &'static mut [LineSlot]) -> Self {
-        let columns = fb.cols();
+    fn new(
+        fb: framebuffer::text::FramebufferConsole,
+        slots: &'static mut [LineSlot],
+        policy: WrapPolicy,
+    ) -> Self {
+        let layout = LineLayout::new(policy, fb.cols());
         Self {
             fb,
             history: History::new(slots),
             line: LineBuffer::new(),
-            current_column: 0,
-            columns,
+            json: JsonLine::new(Level::Info, module_path!()),
+            layout,
             current_timestamp: None,
+            captured: CaptureSummary::new(),
+            cursor: cursor::Blink::new(),
         }
     }
 
+    /// Draw the cursor solid after output, restarting its blink.
+    fn relight_cursor(&mut self) {
+        self.cursor.restart(time::now());
+        self.fb.show_cursor(self.cursor.shown());
+    }
+
     fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
         let mut writer = ConsoleWriter { state: self };
         fmt::write(&mut writer, args)
     }
 
+    /// Render output that was parked while the console lock was held elsewhere.
+    fn replay_deferred(&mut self) {
+        while let Some(byte) = deferred::pop() {
+            let _ = self.handle_byte(byte);
+        }
+
+        let dropped = deferred::take_dropped();
+        if dropped > 0 {
+            let _ = self.write_fmt(format_args!(
+                "[console: {} deferred bytes dropped]\n",
+                dropped
+            ));
+        }
+    }
+
     fn handle_str(&mut self, s: &str) -> Result<(), ()> {
         for byte in s.bytes() {
             self.handle_byte(byte)?;
@@ -168,25 +526,20 @@ impl ConsoleState {
             b'\r' => {
                 self.fb.write_bytes(&[sanitized])?;
                 self.line.clear();
-                self.current_column = 0;
+                self.json.clear();
+                self.layout.end();
                 self.current_timestamp = None;
             }
             _ => {
                 self.ensure_line_prefix()?;
+                self.json.push(byte);
 
-                if self.columns > 0 && self.current_column >= self.columns {
-                    self.finish_line();
-                    self.ensure_line_prefix()?;
-                }
-
+                // A line wider than the screen continues on the next row as
+                // the same history entry; whatever history cannot hold is not
+                // drawn either.
                 if self.line.len() < MAX_LINE_CHARS {
                     self.line.push(sanitized);
-                }
-
-                self.fb.write_bytes(&[sanitized])?;
-
-                if self.columns > 0 {
-                    self.current_column = self.current_column.saturating_add(1);
+                    self.layout.put(&mut self.fb, sanitized)?;
                 }
             }
         }
@@ -195,65 +548,31 @@ impl ConsoleState {
     }
 
     fn finish_line(&mut self) {
-        let timestamp = self
-            .current_timestamp
-            .unwrap_or_else(|| self.capture_timestamp());
+        let timestamp = self.current_timestamp.unwrap_or_else(timestamp_now);
 
         let line = self.line.as_slice();
         self.history.push(timestamp, line);
+        mirror::line(line);
+        self.json.emit(timestamp);
         self.line.clear();
-        self.current_column = 0;
+        self.layout.end();
         self.current_timestamp = None;
     }
 
     fn ensure_line_prefix(&mut self) -> Result<(), ()> {
         if self.line.len() == 0 {
-            let timestamp = self
-                .current_timestamp
-                .unwrap_or_else(|| self.capture_timestamp());
+            let timestamp = self.current_timestamp.unwrap_or_else(timestamp_now);
             self.current_timestamp = Some(timestamp);
 
             let mut prefix_buf = [0u8; TIMESTAMP_PREFIX_MAX];
             let prefix_len = format_timestamp_prefix(&mut prefix_buf, timestamp);
 
-            if self.line.len() < MAX_LINE_CHARS {
-                let available = MAX_LINE_CHARS - self.line.len();
-                let copy_len = prefix_len.min(available);
-                self.line.extend_from_slice(&prefix_buf[..copy_len]);
-            }
-
-            self.fb.write_bytes(&prefix_buf[..prefix_len])?;
-
-            if self.columns > 0 {
-                self.current_column = self
-                    .current_column
-                    .saturating_add(prefix_len)
-                    .min(self.columns);
-
-                if self.current_column >= self.columns {
-                    self.finish_line();
-                    return Ok(());
-                }
-            }
+            self.line.extend_from_slice(&prefix_buf[..prefix_len]);
+            self.layout.start(&mut self.fb, &prefix_buf[..prefix_len])?;
         }
 
         Ok(())
     }
-
-    fn capture_timestamp(&self) -> Timestamp {
-        if let Some(nanos) = time::monotonic_nanos() {
-            Timestamp {
-                value: nanos,
-                is_nanos: true,
-            }
-        } else {
-            let ticks = time::monotonic_ticks().unwrap_or(0);
-            Timestamp {
-                value: ticks,
-                is_nanos: false,
-            }
-        }
-    }
 }
 
 struct ConsoleWriter<'a> {
@@ -266,10 +585,55 @@ impl fmt::Write for ConsoleWriter<'_> {
     }
 }
 
+/// Formats captured diagnostics straight into history, one slot per line.
+struct CaptureWriter<'a> {
+    history: &'a mut History,
+    line: LineBuffer,
+    json: JsonLine,
+    timestamp: Timestamp,
+}
+
+impl CaptureWriter<'_> {
+    fn start_line(&mut self) {
+        let mut prefix_buf = [0u8; TIMESTAMP_PREFIX_MAX];
+        let prefix_len = format_timestamp_prefix(&mut prefix_buf, self.timestamp);
+        self.line.extend_from_slice(&prefix_buf[..prefix_len]);
+    }
+
+    fn finish(&mut self) {
+        if self.line.len() > 0 {
+            self.history.push(self.timestamp, self.line.as_slice());
+            self.json.emit(self.timestamp);
+            self.line.clear();
+        }
+    }
+}
+
+impl fmt::Write for CaptureWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            let sanitized = framebuffer::text::sanitize_byte(byte);
+            if self.line.len() == 0 {
+                self.start_line();
+            }
+
+            if sanitized == b'\n' {
+                self.finish();
+            } else {
+                self.line.push(sanitized);
+                self.json.push(byte);
+            }
+        }
+        Ok(())
+    }
+}
+
 struct History {
     slots: &'static mut [LineSlot],
     start: usize,
     len: usize,
+    /// Lines overwritten by newer ones since boot.
+    dropped: u64,
 }
 
 impl History {
@@ -278,9 +642,71 @@ impl History {
             slots,
             start: 0,
             len: 0,
+            dropped: 0,
         }
     }
 
+    /// Copy every stored line into `slots`, oldest first, and keep recording
+    /// there. The old buffer is left as it was.
+    fn migrate(&mut self, slots: &'static mut [LineSlot]) {
+        let mut moved = History::new(slots);
+        moved.dropped = self.dropped;
+        for (timestamp, line) in self.lines() {
+            moved.push(timestamp, line);
+        }
+        *self = moved;
+    }
+
+    /// Stored lines with their timestamps, oldest first.
+    fn lines(&self) -> impl Iterator<Item = (Timestamp, &[u8])> + '_ {
+        let capacity = self.slots.len();
+        (0..self.len).map(move |offset| {
+            let slot = &self.slots[(self.start + offset) % capacity];
+            (slot.timestamp, &slot.data[..usize::from(slot.len)])
+        })
+    }
+
+    /// Clear `target` and write every stored line to it, wrapping long lines
+    /// the way the live console does under `policy`.
+    fn render(
+        &self,
+        target: &mut framebuffer::text::FramebufferConsole,
+        policy: WrapPolicy,
+    ) -> Result<usize, ()> {
+        target.clear()?;
+        let mut layout = LineLayout::new(policy, target.cols());
+        let mut drawn = 0;
+        if self.dropped > 0 {
+            let mut buf = [0u8; MAX_LINE_CHARS];
+            let mut notice = FixedBufWriter::new(&mut buf);
+            let _ = writeln!(
+                notice,
+                "[console: {} older lines rotated out]",
+                self.dropped
+            );
+            target.write_bytes(notice.as_bytes())?;
+            drawn += 1;
+        }
+        for (timestamp, line) in self.lines() {
+            let mut prefix_buf = [0u8; TIMESTAMP_PREFIX_MAX];
+            let prefix_len = format_timestamp_prefix(&mut prefix_buf, timestamp);
+            let prefix_len = if line.starts_with(&prefix_buf[..prefix_len]) {
+                prefix_len
+            } else {
+                0
+            };
+
+            layout.start(target, &line[..prefix_len])?;
+            for &byte in &line[prefix_len..] {
+                layout.put(target, byte)?;
+            }
+            target.write_bytes(b"\n")?;
+            layout.end();
+            drawn += 1;
+        }
+        Ok(drawn)
+    }
+
     fn push(&mut self, timestamp: Timestamp, line: &[u8]) {
         if self.slots.is_empty() {
             return;
@@ -299,6 +725,7 @@ impl History {
             self.len += 1;
         } else {
             self.start = (self.start + 1) % capacity;
+            self.dropped += 1;
         }
     }
 }
@@ -352,17 +779,20 @@ impl LineBuffer {
 #[macro_export]
 macro_rules! print {
     ($($arg:tt)*) => {{
-        let _ = $crate::console::write(core::format_args!($($arg)*));
+        let _ = $crate::console::write(module_path!(), core::format_args!($($arg)*));
     }};
 }
 
 #[macro_export]
 macro_rules! println {
     () => {{
-        let _ = $crate::console::write(core::format_args!("\n"));
+        let _ = $crate::console::write(module_path!(), core::format_args!("\n"));
     }};
     ($fmt:expr $(, $arg:expr)* $(,)?) => {{
-        let _ = $crate::console::write(core::format_args!(concat!($fmt, "\n") $(, $arg)*));
+        let _ = $crate::console::write(
+            module_path!(),
+            core::format_args!(concat!($fmt, "\n") $(, $arg)*),
+        );
     }};
 }
 
@@ -370,7 +800,17 @@ macro_rules! println {
 macro_rules! diag {
     ($($arg:tt)*) => {{
         if $crate::options::diagnostics_enabled() {
-            let _ = $crate::console::write(core::format_args!($($arg)*));
+            let _ = $crate::console::write_diag(
+                $crate::console::Level::Diag,
+                module_path!(),
+                core::format_args!($($arg)*),
+            );
+        } else {
+            $crate::console::capture(
+                $crate::console::Level::Diag,
+                module_path!(),
+                core::format_args!($($arg)*),
+            );
         }
     }};
 }
@@ -379,12 +819,32 @@ macro_rules! diag {
 macro_rules! diagln {
     () => {{
         if $crate::options::diagnostics_enabled() {
-            let _ = $crate::console::write(core::format_args!("\n"));
+            let _ = $crate::console::write_diag(
+                $crate::console::Level::Diag,
+                module_path!(),
+                core::format_args!("\n"),
+            );
+        } else {
+            $crate::console::capture(
+                $crate::console::Level::Diag,
+                module_path!(),
+                core::format_args!("\n"),
+            );
         }
     }};
     ($fmt:expr $(, $arg:expr)* $(,)?) => {{
         if $crate::options::diagnostics_enabled() {
-            let _ = $crate::console::write(core::format_args!(concat!($fmt, "\n") $(, $arg)*));
+            let _ = $crate::console::write_diag(
+                $crate::console::Level::Diag,
+                module_path!(),
+                core::format_args!(concat!($fmt, "\n") $(, $arg)*),
+            );
+        } else {
+            $crate::console::capture(
+                $crate::console::Level::Diag,
+                module_path!(),
+                core::format_args!(concat!($fmt, "\n") $(, $arg)*),
+            );
         }
     }};
 }
@@ -393,7 +853,17 @@ macro_rules! diagln {
 macro_rules! debug {
     ($($arg:tt)*) => {{
         if $crate::options::debug_enabled() {
-            let _ = $crate::console::write(core::format_args!($($arg)*));
+            let _ = $crate::console::write_diag(
+                $crate::console::Level::Debug,
+                module_path!(),
+                core::format_args!($($arg)*),
+            );
+        } else {
+            $crate::console::capture(
+                $crate::console::Level::Debug,
+                module_path!(),
+                core::format_args!($($arg)*),
+            );
         }
     }};
 }
@@ -402,44 +872,90 @@ macro_rules! debug {
 macro_rules! debugln {
     () => {{
         if $crate::options::debug_enabled() {
-            let _ = $crate::console::write(core::format_args!("\n"));
+            let _ = $crate::console::write_diag(
+                $crate::console::Level::Debug,
+                module_path!(),
+                core::format_args!("\n"),
+            );
+        } else {
+            $crate::console::capture(
+                $crate::console::Level::Debug,
+                module_path!(),
+                core::format_args!("\n"),
+            );
         }
     }};
     ($fmt:expr $(, $arg:expr)* $(,)?) => {{
         if $crate::options::debug_enabled() {
-            let _ = $crate::console::write(core::format_args!(concat!($fmt, "\n") $(, $arg)*));
+            let _ = $crate::console::write_diag(
+                $crate::console::Level::Debug,
+                module_path!(),
+                core::format_args!(concat!($fmt, "\n") $(, $arg)*),
+            );
+        } else {
+            $crate::console::capture(
+                $crate::console::Level::Debug,
+                module_path!(),
+                core::format_args!(concat!($fmt, "\n") $(, $arg)*),
+            );
         }
     }};
 }
 
+/// Like `debug!`, but never recorded in history when suppressed.
+///
+/// For traces on interrupt paths (a tick, a spurious IRQ), which would
+/// otherwise fill the history with one line per interrupt on a quiet boot.
 #[macro_export]
-macro_rules! debug_structured {
-    ($fmt:expr, [$(( $key:expr, $value:expr )),* $(,)?] $(, $arg:expr)*) => {{
+macro_rules! debug_irq {
+    ($($arg:tt)*) => {{
         if $crate::options::debug_enabled() {
-            $crate::println!($fmt $(, $arg)*);
-            $(
-                $crate::println!("  {}={}", $key, $value);
-            )*
+            let _ = $crate::console::write_diag(
+                $crate::console::Level::Debug,
+                module_path!(),
+                core::format_args!($($arg)*),
+            );
         }
     }};
 }
 
+#[macro_export]
+macro_rules! debug_structured {
+    ($fmt:expr, [$(( $key:expr, $value:expr )),* $(,)?] $(, $arg:expr)*) => {{
+        $crate::debug!(
+            "{}",
+            $crate::console::Structured {
+                headline: core::format_args!($fmt $(, $arg)*),
+                pairs: &[$((&$key, &$value)),*],
+            }
+        );
+    }};
+}
+
+/// Latest timestamp handed out, in nanoseconds.
+static LAST_TIMESTAMP: AtomicU64 = AtomicU64::new(0);
+
+/// Current time from `time::now()`, never earlier than a timestamp already
+/// handed out, so history stays ordered whichever writer stamps first.
+fn timestamp_now() -> Timestamp {
+    let now = time::now().as_nanos();
+    let latest = LAST_TIMESTAMP.fetch_max(now, Ordering::Relaxed).max(now);
+    Timestamp::from_nanos(latest)
+}
+
 fn format_timestamp_prefix(buf: &mut [u8; TIMESTAMP_PREFIX_MAX], timestamp: Timestamp) -> usize {
     let mut index = 0;
     buf[index] = b'[';
     index += 1;
 
-    if timestamp.is_nanos {
-        let seconds = timestamp.value / 1_000_000_000;
-        let micros = ((timestamp.value % 1_000_000_000) / 1_000) as u32;
+    let nanos = timestamp.as_nanos();
+    let seconds = nanos / 1_000_000_000;
+    let micros = ((nanos % 1_000_000_000) / 1_000) as u32;
 
-        index += write_decimal(&mut buf[index..], seconds);
-        buf[index] = b'.';
-        index += 1;
-        index += write_fixed_width_decimal(&mut buf[index..], micros, 6);
-    } else {
-        index += write_decimal(&mut buf[index..], timestamp.value);
-    }
+    index += write_decimal(&mut buf[index..], seconds);
+    buf[index] = b'.';
+    index += 1;
+    index += write_fixed_width_decimal(&mut buf[index..], micros, 6);
 
     buf[index] = b']';
     index += 1;
@@ -480,56 +996,51 @@ fn write_fixed_width_decimal(out: &mut [u8], mut value: u32, width: usize) -> us
     width
 }
 
-#[derive(Clone, Copy)]
-struct Timestamp {
-    value: u64,
-    is_nanos: bool,
-}
-
-impl Timestamp {
-    const ZERO: Self = Self {
-        value: 0,
-        is_nanos: false,
-    };
-}
+/// Line timestamps are points on the kernel's monotonic clock.
+type Timestamp = time::Instant;
 
 #[cfg(test)]
 mod tests {
     extern crate alloc;
 
     use super::*;
-    use alloc::boxed::Box;
+    use alloc::{boxed::Box, format, vec, vec::Vec};
+    use oxide_abi::PixelFormat;
+
+    #[test]
+    fn structured_records_put_each_pair_on_its_own_line() {
+        let pages = 512u64;
+        let record = Structured {
+            headline: format_args!("plan for {}", "ram"),
+            pairs: &[(&"pages", &pages), (&"order", &3)],
+        };
+        assert_eq!(
+            format!("{}", record),
+            "plan for ram\n  pages=512\n  order=3\n"
+        );
+
+        let empty = Structured {
+            headline: format_args!("nothing"),
+            pairs: &[],
+        };
+        assert_eq!(format!("{}", empty), "nothing\n");
+    }
 
     #[test]
     fn timestamp_zero_formatting() {
         let mut buf = [0u8; TIMESTAMP_PREFIX_MAX];
         let len = format_timestamp_prefix(&mut buf, Timestamp::ZERO);
-        assert_eq!(len, 4);
-        assert_eq!(&buf[..len], b"[0] ");
+        assert_eq!(&buf[..len], b"[0.000000] ");
     }
 
     #[test]
     fn timestamp_nanos_formatting() {
         let mut buf = [0u8; TIMESTAMP_PREFIX_MAX];
-        let ts = Timestamp {
-            value: 12_345_678_901,
-            is_nanos: true,
-        };
+        let ts = Timestamp::from_nanos(12_345_678_901);
         let len = format_timestamp_prefix(&mut buf, ts);
         assert_eq!(&buf[..len], b"[12.345678] ");
     }
 
-    #[test]
-    fn timestamp_ticks_formatting() {
-        let mut buf = [0u8; TIMESTAMP_PREFIX_MAX];
-        let ts = Timestamp {
-            value: 987_654,
-            is_nanos: false,
-        };
-        let len = format_timestamp_prefix(&mut buf, ts);
-        assert_eq!(&buf[..len], b"[987654] ");
-    }
-
     #[test]
     fn line_buffer_push_and_clear() {
         let mut buffer = LineBuffer::new();
@@ -558,13 +1069,7 @@ mod tests {
         let mut history = History::new(slots);
 
         for i in 0..6u8 {
-            history.push(
-                Timestamp {
-                    value: i as u64,
-                    is_nanos: false,
-                },
-                &[i],
-            );
+            history.push(Timestamp::from_nanos(i as u64), &[i]);
             let expected_len = (usize::from(i) + 1).min(4);
             assert_eq!(history.len, expected_len);
         }
@@ -572,10 +1077,172 @@ mod tests {
         assert_eq!(history.start, 2);
         let capacity = history.slots.len();
         let mut collected = [0u8; 4];
-        for idx in 0..history.len {
+        for (idx, byte) in collected.iter_mut().enumerate().take(history.len) {
             let slot_index = (history.start + idx) % capacity;
-            collected[idx] = history.slots[slot_index].data[0];
+            *byte = history.slots[slot_index].data[0];
         }
         assert_eq!(&collected, b"");
     }
+
+    /// A console over a zeroed in-memory surface of `cols` × `rows` 8×8 cells.
+    fn memory_console(cols: usize, rows: usize) -> framebuffer::text::FramebufferConsole {
+        let width = cols * framebuffer::FONT_8X8.width;
+        let height = rows * (framebuffer::FONT_8X8.height + framebuffer::FONT_8X8.line_spacing);
+        let pixels: &'static mut [u32] = Box::leak(vec![0u32; width * height].into_boxed_slice());
+        let surface = FramebufferSurface {
+            base_ptr: pixels.as_mut_ptr(),
+            pixels_per_scanline: width,
+            width,
+            height,
+            pixel_format: PixelFormat::Rgb,
+            rotation: framebuffer::Rotation::Deg0,
+        };
+        let mut console = framebuffer::text::FramebufferConsole::with_surface(
+            surface,
+            0,
+            0,
+            framebuffer::theme(),
+            framebuffer::FONT_8X8,
+            1,
+        );
+        console.clear().unwrap();
+        console
+    }
+
+    #[test]
+    fn history_renders_what_the_screen_scrolled_to() {
+        let slots: &'static mut [LineSlot; 8] = Box::leak(Box::new([LineSlot::EMPTY; 8]));
+        let mut state = ConsoleState::new(memory_console(20, 4), slots, WrapPolicy::Indent);
+        for line in 1..=5 {
+            state.write_fmt(format_args!("line {}\n", line)).unwrap();
+        }
+
+        let expected = [
+            "[0.000000] LINE 3",
+            "[0.000000] LINE 4",
+            "[0.000000] LINE 5",
+            "",
+        ];
+        assert_eq!(state.fb.screen_text(), expected);
+
+        let mut replay = memory_console(20, 4);
+        assert_eq!(state.history.render(&mut replay, WrapPolicy::Indent), Ok(5));
+        assert_eq!(replay.screen_text(), expected);
+    }
+
+    #[test]
+    fn long_lines_continue_under_the_text_as_one_history_entry() {
+        let wrapped = |policy| {
+            let slots: &'static mut [LineSlot; 4] = Box::leak(Box::new([LineSlot::EMPTY; 4]));
+            let mut state = ConsoleState::new(memory_console(24, 3), slots, policy);
+            state
+                .write_fmt(format_args!("0123456789ABCDEFGHIJ\n"))
+                .unwrap();
+            assert_eq!(state.history.len, 1);
+            state.fb.screen_text()
+        };
+
+        assert_eq!(
+            wrapped(WrapPolicy::Indent),
+            ["[0.000000] 0123456789ABC", "           DEFGHIJ", ""]
+        );
+        assert_eq!(
+            wrapped(WrapPolicy::Marker),
+            ["[0.000000] 0123456789ABC", "          \u{21aa}DEFGHIJ", ""]
+        );
+        assert_eq!(
+            wrapped(WrapPolicy::Hard),
+            ["[0.000000] 0123456789ABC", "DEFGHIJ", ""]
+        );
+
+        // Past MAX_LINE_CHARS, the screen and history drop the same bytes.
+        let slots: &'static mut [LineSlot; 4] = Box::leak(Box::new([LineSlot::EMPTY; 4]));
+        let mut state = ConsoleState::new(memory_console(24, 16), slots, WrapPolicy::Marker);
+        for _ in 0..200 {
+            state.handle_byte(b'X').unwrap();
+        }
+        state.handle_byte(b'\n').unwrap();
+        let mut replay = memory_console(24, 16);
+        state
+            .history
+            .render(&mut replay, WrapPolicy::Marker)
+            .unwrap();
+        assert_eq!(usize::from(state.history.slots[0].len), MAX_LINE_CHARS);
+        assert_eq!(replay.screen_text(), state.fb.screen_text());
+    }
+
+    #[test]
+    fn migrated_history_keeps_lines_and_counts_rotation() {
+        let small: &'static mut [LineSlot; 2] = Box::leak(Box::new([LineSlot::EMPTY; 2]));
+        let mut history = History::new(small);
+        for line in [b"one".as_slice(), b"two", b"three"] {
+            history.push(Timestamp::ZERO, line);
+        }
+        assert_eq!(history.dropped, 1);
+
+        let large: &'static mut [LineSlot; 4] = Box::leak(Box::new([LineSlot::EMPTY; 4]));
+        history.migrate(large);
+        history.push(Timestamp::ZERO, b"four");
+        let lines: Vec<&[u8]> = history.lines().map(|(_, line)| line).collect();
+        assert_eq!(lines, [b"two".as_slice(), b"three", b"four"]);
+        assert_eq!(history.dropped, 1);
+
+        let mut replay = memory_console(40, 5);
+        assert_eq!(history.render(&mut replay, WrapPolicy::Hard), Ok(4));
+        // The 8x8 font draws O and 0 alike, so the read-back shows zeros.
+        assert_eq!(
+            replay.screen_text()[0],
+            "[C0NS0LE: 1 0LDER LINES R0TATED 0UT]"
+        );
+    }
+
+    #[test]
+    fn crash_screen_keeps_the_recent_log_that_fits() {
+        let slots: &'static mut [LineSlot; 32] = Box::leak(Box::new([LineSlot::EMPTY; 32]));
+        let mut state = ConsoleState::new(memory_console(40, 16), slots, WrapPolicy::Indent);
+        for line in 1..=6 {
+            state.write_fmt(format_args!("line {}\n", line)).unwrap();
+        }
+        state.write_fmt(format_args!("partial")).unwrap();
+
+        let trap = Trap {
+            vector: 0x0E,
+            cr2: Some(0xdead_b000),
+            frame: Some(crate::interrupts::FaultFrame {
+                error_code: 0b10,
+                rip: 0x10_2040,
+                cs: 0x38,
+                rflags: 0x2,
+                rsp: 0x7F_F000,
+                ss: 0x30,
+            }),
+        };
+        state.draw_crash(format_args!("exception: page fault"), Some(trap));
+
+        // The report fits: the banner is still on the top row and the
+        // cursor ends on the last one.
+        let screen = state.fb.screen_text();
+        assert!(screen[0].starts_with("*** "));
+        assert_eq!(screen[15], "");
+
+        // History holds the same rows, each behind a timestamp.
+        let report: Vec<&[u8]> = state
+            .history
+            .lines()
+            .skip(7)
+            .map(|(_, line)| &line[b"[0.000000] ".len()..])
+            .collect();
+        assert_eq!(report.len(), 15);
+        assert_eq!(report[1], b"EXCEPTION: PAGE FAULT");
+        assert_eq!(report[4], b"  CR2    0X0000_0000_DEAD_B000");
+        assert_eq!(report[5], b"  RIP    0X0000_0000_0010_2040");
+        // Cut at the 40-column screen edge; serial gets the whole row.
+        assert_eq!(report[6], b"  ERROR  0B000_0010 (NOT-PRESENT WRITE I");
+        assert_eq!(report[10], b"-- RECENT LOG --");
+        assert_eq!(
+            report[11..13],
+            [b"  [0.000000] LINE 6".as_slice(), b"  [0.000000] PARTIAL",]
+        );
+        assert_eq!(report[13], b"-- BUILD --");
+    }
 }
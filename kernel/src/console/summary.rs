@@ -0,0 +1,96 @@
+//! Per-subsystem counts of diagnostics captured silently in `quiet=summary` mode.
+
+const MAX_SUBSYSTEMS: usize = 12;
+
+/// Diagnostic counts keyed by the subsystem that emitted them.
+#[derive(Clone)]
+pub(super) struct CaptureSummary {
+    entries: [(&'static str, u32); MAX_SUBSYSTEMS],
+    len: usize,
+    /// Diagnostics from subsystems beyond the table's capacity.
+    other: u32,
+}
+
+impl CaptureSummary {
+    pub(super) const fn new() -> Self {
+        Self {
+            entries: [("", 0); MAX_SUBSYSTEMS],
+            len: 0,
+            other: 0,
+        }
+    }
+
+    /// Count one diagnostic emitted from `module_path`.
+    pub(super) fn record(&mut self, module_path: &'static str) {
+        let name = subsystem(module_path);
+        if let Some(entry) = self.entries[..self.len]
+            .iter_mut()
+            .find(|(existing, _)| *existing == name)
+        {
+            entry.1 += 1;
+        } else if self.len < MAX_SUBSYSTEMS {
+            self.entries[self.len] = (name, 1);
+            self.len += 1;
+        } else {
+            self.other += 1;
+        }
+    }
+
+    pub(super) fn total(&self) -> u32 {
+        self.entries[..self.len]
+            .iter()
+            .map(|(_, count)| count)
+            .sum::<u32>()
+            + self.other
+    }
+
+    /// Counts in first-seen order, with overflow reported as `other`.
+    pub(super) fn iter(&self) -> impl Iterator<Item = (&'static str, u32)> + '_ {
+        self.entries[..self.len]
+            .iter()
+            .copied()
+            .chain((self.other > 0).then_some(("other", self.other)))
+    }
+}
+
+/// Top-level kernel module of `module_path`, e.g. `memory` for
+/// `oxide_kernel::memory::init`; the crate root counts as `kernel`.
+fn subsystem(module_path: &'static str) -> &'static str {
+    module_path.split("::").nth(1).unwrap_or("kernel")
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn counts_group_by_top_level_module() {
+        let mut summary = CaptureSummary::new();
+        summary.record("oxide_kernel::memory::init");
+        summary.record("oxide_kernel::time");
+        summary.record("oxide_kernel::memory::fixmap");
+        summary.record("oxide_kernel");
+
+        let counts: Vec<_> = summary.iter().collect();
+        assert_eq!(counts, [("memory", 2), ("time", 1), ("kernel", 1)]);
+        assert_eq!(summary.total(), 4);
+    }
+
+    #[test]
+    fn overflow_is_reported_as_other() {
+        const MODULES: [&str; MAX_SUBSYSTEMS + 2] = [
+            "k::a", "k::b", "k::c", "k::d", "k::e", "k::f", "k::g", "k::h", "k::i", "k::j", "k::k",
+            "k::l", "k::m", "k::n",
+        ];
+        let mut summary = CaptureSummary::new();
+        for module in MODULES {
+            summary.record(module);
+        }
+
+        assert_eq!(summary.iter().last(), Some(("other", 2)));
+        assert_eq!(summary.total(), MODULES.len() as u32);
+    }
+}
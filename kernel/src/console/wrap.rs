@@ -0,0 +1,105 @@
+//! How a line wider than the screen continues on the rows below.
+
+use crate::framebuffer::{WRAP_MARKER, text::FramebufferConsole};
+
+/// Continuation-row style requested with `console_wrap=`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrapPolicy {
+    /// Continue at the left edge.
+    Hard,
+    /// Continue under the text, past the timestamp prefix.
+    Indent,
+    /// Like `Indent`, with `↪` just before the continued text.
+    Marker,
+}
+
+impl WrapPolicy {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "hard" => Some(Self::Hard),
+            "indent" => Some(Self::Indent),
+            "marker" => Some(Self::Marker),
+            _ => None,
+        }
+    }
+
+    /// Columns a continuation row skips: the prefix width, but never more
+    /// than half the screen so narrow screens still make progress.
+    fn indent(self, prefix_len: usize, columns: usize) -> usize {
+        match self {
+            Self::Hard => 0,
+            Self::Indent | Self::Marker => prefix_len.min(columns / 2),
+        }
+    }
+}
+
+/// Policy requested with `console_wrap=<hard|indent|marker>`, or indent when
+/// absent or unknown.
+pub fn policy() -> WrapPolicy {
+    crate::options::value("console_wrap")
+        .and_then(WrapPolicy::parse)
+        .unwrap_or(WrapPolicy::Indent)
+}
+
+/// Column bookkeeping for the line being drawn, so that the live console and
+/// a history replay break lines at the same places.
+pub struct LineLayout {
+    policy: WrapPolicy,
+    columns: usize,
+    column: usize,
+    indent: usize,
+}
+
+impl LineLayout {
+    pub fn new(policy: WrapPolicy, columns: usize) -> Self {
+        Self {
+            policy,
+            columns,
+            column: 0,
+            indent: 0,
+        }
+    }
+
+    pub fn policy(&self) -> WrapPolicy {
+        self.policy
+    }
+
+    /// Draw the timestamp prefix that opens a line.
+    pub fn start(&mut self, fb: &mut FramebufferConsole, prefix: &[u8]) -> Result<(), ()> {
+        fb.write_bytes(prefix)?;
+        self.column = prefix.len();
+        self.indent = self.policy.indent(prefix.len(), self.columns);
+        Ok(())
+    }
+
+    /// Draw one byte of the line's text, first breaking to a continuation
+    /// row when the current one is full.
+    pub fn put(&mut self, fb: &mut FramebufferConsole, byte: u8) -> Result<(), ()> {
+        if self.columns > 0 && self.column >= self.columns {
+            fb.write_bytes(b"\n")?;
+            self.continue_line(fb)?;
+        }
+        fb.write_bytes(&[byte])?;
+        self.column += 1;
+        Ok(())
+    }
+
+    /// Forget the line; the next one starts at the left edge.
+    pub fn end(&mut self) {
+        self.column = 0;
+        self.indent = 0;
+    }
+
+    fn continue_line(&mut self, fb: &mut FramebufferConsole) -> Result<(), ()> {
+        let marked = self.policy == WrapPolicy::Marker && self.indent > 0;
+        let padding = if marked { self.indent - 1 } else { self.indent };
+        for _ in 0..padding {
+            fb.write_bytes(b" ")?;
+        }
+        if marked {
+            fb.write_bytes(&[WRAP_MARKER])?;
+        }
+        self.column = self.indent;
+        Ok(())
+    }
+}
@@ -0,0 +1,292 @@
+//! Early microcode updates from a loader-provided blob.
+//!
+//! `microcode=<path>` makes the loader read an update file from the ESP into
+//! `OXIDE_HANDOFF_MEMORY` pages below 1 GiB and pass its range as `BootAbi::microcode`.
+//! [`apply`] picks the update matching this CPU from the blob, verifies it,
+//! and hands it to the processor through `IA32_BIOS_UPDT_TRIG` before anything
+//! depends on the fixed behavior. Only Intel's format (one or more
+//! concatenated 48-byte-header updates, as shipped in `intel-ucode/`) is
+//! understood; extended signature tables are not consulted.
+
+use oxide_abi::PhysRange;
+use oxide_util::xxh32::xxh32;
+
+use super::{is_intel, rdmsr, wrmsr};
+
+const IA32_PLATFORM_ID: u32 = 0x17;
+const IA32_BIOS_UPDT_TRIG: u32 = 0x79;
+const IA32_BIOS_SIGN_ID: u32 = 0x8B;
+
+const HEADER_LEN: usize = 48;
+/// Sizes implied by a zero `data_size`/`total_size` in old-style headers.
+const DEFAULT_DATA_SIZE: usize = 2000;
+const DEFAULT_TOTAL_SIZE: usize = 2048;
+
+/// Why a microcode blob was not applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MicrocodeError {
+    /// The blob's XXH32 no longer matches the digest the loader verified.
+    Digest { expected: u32, found: u32 },
+    /// The processor is not an Intel CPU.
+    UnsupportedVendor,
+    /// An update header or its declared size runs past the blob.
+    Truncated,
+    /// Header or loader version is not 1, or the sizes are inconsistent.
+    BadHeader,
+    /// The update's dwords do not sum to zero.
+    Checksum,
+    /// No update in the blob matches this processor and platform.
+    NoMatch { signature: u32 },
+    /// The matching update is not newer than what is loaded.
+    NotNewer { loaded: u32, offered: u32 },
+    /// The processor kept its old revision after the update was triggered.
+    Rejected { revision: u32 },
+}
+
+/// The fields of an Intel update header the kernel checks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Update {
+    /// Offset of the header within the blob.
+    offset: usize,
+    revision: u32,
+    signature: u32,
+    platform_flags: u32,
+    total_size: usize,
+}
+
+/// Currently loaded microcode revision.
+pub fn microcode_revision() -> u32 {
+    // SAFETY: MSR 0x8B is architectural on Intel and AMD x86_64 CPUs. Intel
+    // only latches the revision (high half) after the MSR is cleared and
+    // CPUID(1) runs; AMD's read-only PATCH_LEVEL keeps it in the low half.
+    unsafe {
+        if is_intel() {
+            wrmsr(IA32_BIOS_SIGN_ID, 0);
+            core::arch::x86_64::__cpuid(1);
+            (rdmsr(IA32_BIOS_SIGN_ID) >> 32) as u32
+        } else {
+            rdmsr(IA32_BIOS_SIGN_ID) as u32
+        }
+    }
+}
+
+/// Apply the first update in `blob` that matches this processor, returning
+/// the old and new revisions.
+///
+/// # Safety
+/// `blob` must be readable and identity-mapped for its whole length.
+pub unsafe fn apply(blob: PhysRange) -> Result<(u32, u32), MicrocodeError> {
+    if !is_intel() {
+        return Err(MicrocodeError::UnsupportedVendor);
+    }
+
+    let bytes = unsafe { core::slice::from_raw_parts(blob.phys as *const u8, blob.len as usize) };
+    let signature = core::arch::x86_64::__cpuid(1).eax;
+    // SAFETY: IA32_PLATFORM_ID is architectural on Intel CPUs.
+    let platform = ((unsafe { rdmsr(IA32_PLATFORM_ID) } >> 50) & 0x7) as u32;
+    let update = find_update(bytes, signature, platform)?;
+
+    let loaded = microcode_revision();
+    if update.revision as i32 <= loaded as i32 {
+        return Err(MicrocodeError::NotNewer {
+            loaded,
+            offered: update.revision,
+        });
+    }
+
+    let data = blob.phys + (update.offset + HEADER_LEN) as u64;
+    // SAFETY: the update was validated for this processor; the trigger MSR
+    // takes the linear address of its data, which is identity-mapped.
+    unsafe { wrmsr(IA32_BIOS_UPDT_TRIG, data) };
+
+    let revision = microcode_revision();
+    if revision != update.revision {
+        return Err(MicrocodeError::Rejected { revision });
+    }
+    Ok((loaded, revision))
+}
+
+/// Apply the loader's blob, if any, and log the outcome. The blob is only
+/// applied if it still hashes to `digest`, the XXH32 the loader checked it
+/// against, so pages overwritten between the loader and here are caught.
+pub fn apply_boot_blob(blob: PhysRange, digest: u32) {
+    if blob.len == 0 {
+        return;
+    }
+    // SAFETY: handoff validation checked the range; the loader placed it
+    // below the identity-mapped low gigabyte.
+    let bytes = unsafe { core::slice::from_raw_parts(blob.phys as *const u8, blob.len as usize) };
+    let found = xxh32(bytes, 0);
+    let outcome = if found == digest {
+        // SAFETY: as above.
+        unsafe { apply(blob) }
+    } else {
+        Err(MicrocodeError::Digest {
+            expected: digest,
+            found,
+        })
+    };
+    match outcome {
+        Ok((old, new)) => crate::println!("cpu: microcode updated {:#x} -> {:#x}", old, new),
+        Err(err) => crate::println!("cpu: microcode update not applied: {:?}", err),
+    }
+}
+
+/// Walk the concatenated updates in `blob` and return the first valid one
+/// for `signature` and `platform` (0–7).
+///
+/// A corrupt entry is skipped when its header still gives a usable size, so
+/// one damaged update does not hide a good one after it. If nothing matches,
+/// the first corruption found is reported in preference to `NoMatch`.
+fn find_update(blob: &[u8], signature: u32, platform: u32) -> Result<Update, MicrocodeError> {
+    let mut offset = 0;
+    let mut corrupt = None;
+    while offset < blob.len() {
+        match parse_update(blob, offset) {
+            Ok(update) => {
+                if update.signature == signature && update.platform_flags & (1 << platform) != 0 {
+                    return Ok(update);
+                }
+                offset += update.total_size;
+            }
+            Err(err) => {
+                corrupt.get_or_insert(err);
+                match declared_size(blob, offset) {
+                    Some(size) => offset += size,
+                    None => break,
+                }
+            }
+        }
+    }
+    Err(corrupt.unwrap_or(MicrocodeError::NoMatch { signature }))
+}
+
+/// Total size the header at `offset` declares, if it is plausible enough to
+/// step over the entry and lies within `blob`.
+fn declared_size(blob: &[u8], offset: usize) -> Option<usize> {
+    let bytes = blob.get(offset + 32..offset + 36)?;
+    let size = match u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize {
+        0 => DEFAULT_TOTAL_SIZE,
+        size => size,
+    };
+    (size > HEADER_LEN && size.is_multiple_of(4) && offset + size <= blob.len()).then_some(size)
+}
+
+fn parse_update(blob: &[u8], offset: usize) -> Result<Update, MicrocodeError> {
+    let word = |index: usize| -> Result<u32, MicrocodeError> {
+        let start = offset + index * 4;
+        blob.get(start..start + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .ok_or(MicrocodeError::Truncated)
+    };
+
+    if word(0)? != 1 || word(5)? != 1 {
+        return Err(MicrocodeError::BadHeader);
+    }
+    let data_size = match word(7)? as usize {
+        0 => DEFAULT_DATA_SIZE,
+        size => size,
+    };
+    let total_size = match word(8)? as usize {
+        0 => DEFAULT_TOTAL_SIZE,
+        size => size,
+    };
+    if data_size + HEADER_LEN > total_size || !total_size.is_multiple_of(4) {
+        return Err(MicrocodeError::BadHeader);
+    }
+
+    let update = blob
+        .get(offset..offset + total_size)
+        .ok_or(MicrocodeError::Truncated)?;
+    let sum = update
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .fold(0u32, u32::wrapping_add);
+    if sum != 0 {
+        return Err(MicrocodeError::Checksum);
+    }
+
+    Ok(Update {
+        offset,
+        revision: word(1)?,
+        signature: word(3)?,
+        platform_flags: word(6)?,
+        total_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// Build a minimal update of `total` bytes with a correct checksum.
+    fn update(revision: u32, signature: u32, platforms: u32, total: usize) -> Vec<u8> {
+        let mut words = alloc::vec![0u32; total / 4];
+        words[0] = 1;
+        words[1] = revision;
+        words[3] = signature;
+        words[5] = 1;
+        words[6] = platforms;
+        words[7] = (total - HEADER_LEN) as u32;
+        words[8] = total as u32;
+        words[12] = 0xDEAD_BEEF;
+        let sum = words.iter().fold(0u32, |acc, &w| acc.wrapping_add(w));
+        words[4] = 0u32.wrapping_sub(sum);
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn find_update_selects_matching_signature_and_platform() {
+        let mut blob = update(0x10, 0x906EA, 0b0000_0010, 128);
+        blob.extend(update(0x20, 0x806EC, 0b0000_0100, 96));
+
+        let found = find_update(&blob, 0x806EC, 2).unwrap();
+        assert_eq!(found.offset, 128);
+        assert_eq!(found.revision, 0x20);
+
+        assert_eq!(
+            find_update(&blob, 0x806EC, 1),
+            Err(MicrocodeError::NoMatch { signature: 0x806EC })
+        );
+    }
+
+    #[test]
+    fn find_update_skips_corrupt_entries() {
+        let mut blob = update(0x10, 0x806EC, 0b0000_0100, 128);
+        blob[HEADER_LEN] ^= 1;
+        blob.extend(update(0x20, 0x806EC, 0b0000_0100, 96));
+
+        let found = find_update(&blob, 0x806EC, 2).unwrap();
+        assert_eq!(found.offset, 128);
+        assert_eq!(found.revision, 0x20);
+
+        assert_eq!(
+            find_update(&blob, 0x906EA, 2),
+            Err(MicrocodeError::Checksum)
+        );
+    }
+
+    #[test]
+    fn parse_update_rejects_corrupt_blobs() {
+        let good = update(0x10, 0x906EA, 1, 128);
+
+        let mut corrupt = good.clone();
+        corrupt[HEADER_LEN] ^= 1;
+        assert_eq!(parse_update(&corrupt, 0), Err(MicrocodeError::Checksum));
+
+        assert_eq!(
+            parse_update(&good[..100], 0),
+            Err(MicrocodeError::Truncated)
+        );
+
+        let mut bad_version = good.clone();
+        bad_version[0] = 2;
+        assert_eq!(
+            parse_update(&bad_version, 0),
+            Err(MicrocodeError::BadHeader)
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Microcode revision and speculative-execution mitigation reporting.
+//!
+//! At boot the kernel reads the microcode revision, the features CPUID leaf 7
+//! advertises for `IA32_SPEC_CTRL`, and `IA32_ARCH_CAPABILITIES` where
+//! present, then reports which speculation issues the hardware claims to be
+//! immune to. Unless `mitigations=off` is given it also sets the cheap
+//! `IA32_SPEC_CTRL` defaults: IBRS when the CPU offers the always-on
+//! ("enhanced") form, and SSBD when speculative store bypass is not ruled out.
+
+use super::{max_leaf, microcode::microcode_revision, rdmsr, wrmsr};
+
+const IA32_SPEC_CTRL: u32 = 0x48;
+const IA32_ARCH_CAPABILITIES: u32 = 0x10A;
+
+const SPEC_CTRL_IBRS: u64 = 1 << 0;
+const SPEC_CTRL_SSBD: u64 = 1 << 2;
+
+// CPUID.(EAX=7,ECX=0):EDX
+const CPUID7_EDX_SPEC_CTRL: u32 = 1 << 26;
+const CPUID7_EDX_STIBP: u32 = 1 << 27;
+const CPUID7_EDX_ARCH_CAPABILITIES: u32 = 1 << 29;
+const CPUID7_EDX_SSBD: u32 = 1 << 31;
+
+/// `IA32_ARCH_CAPABILITIES` bits reporting immunity or enhanced controls.
+const ARCH_CAPABILITY_NAMES: [(u64, &str); 6] = [
+    (1 << 0, "RDCL_NO (Meltdown)"),
+    (1 << 1, "IBRS_ALL (enhanced IBRS)"),
+    (1 << 4, "SSB_NO (store bypass)"),
+    (1 << 5, "MDS_NO"),
+    (1 << 6, "PSCHANGE_MC_NO"),
+    (1 << 8, "TAA_NO"),
+];
+const ARCH_CAP_IBRS_ALL: u64 = 1 << 1;
+const ARCH_CAP_SSB_NO: u64 = 1 << 4;
+
+/// Speculation controls the CPU advertises.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpeculationFeatures {
+    /// `IA32_SPEC_CTRL` with IBRS, and IBPB.
+    pub spec_ctrl: bool,
+    pub stibp: bool,
+    pub ssbd: bool,
+    /// `IA32_ARCH_CAPABILITIES`, when the MSR exists.
+    pub arch_capabilities: Option<u64>,
+}
+
+impl SpeculationFeatures {
+    /// `IA32_SPEC_CTRL` value for the boot-time defaults, or `None` when
+    /// nothing should be written.
+    pub fn default_spec_ctrl(&self, mitigations_off: bool) -> Option<u64> {
+        if mitigations_off || !self.spec_ctrl {
+            return None;
+        }
+        let arch = self.arch_capabilities.unwrap_or(0);
+
+        let mut value = 0;
+        if arch & ARCH_CAP_IBRS_ALL != 0 {
+            value |= SPEC_CTRL_IBRS;
+        }
+        if self.ssbd && arch & ARCH_CAP_SSB_NO == 0 {
+            value |= SPEC_CTRL_SSBD;
+        }
+        (value != 0).then_some(value)
+    }
+}
+
+/// Read the speculation controls from CPUID and, if present,
+/// `IA32_ARCH_CAPABILITIES`.
+pub fn features() -> SpeculationFeatures {
+    if max_leaf() < 7 {
+        return SpeculationFeatures::default();
+    }
+    let edx = core::arch::x86_64::__cpuid_count(7, 0).edx;
+    SpeculationFeatures {
+        spec_ctrl: edx & CPUID7_EDX_SPEC_CTRL != 0,
+        stibp: edx & CPUID7_EDX_STIBP != 0,
+        ssbd: edx & CPUID7_EDX_SSBD != 0,
+        // SAFETY: CPUID reports the MSR as implemented.
+        arch_capabilities: (edx & CPUID7_EDX_ARCH_CAPABILITIES != 0)
+            .then(|| unsafe { rdmsr(IA32_ARCH_CAPABILITIES) }),
+    }
+}
+
+/// Report microcode and speculation state and apply the default controls
+/// unless `mitigations=off`.
+pub fn init() {
+    let features = features();
+    crate::diagln!("cpu: microcode revision {:#x}", microcode_revision());
+    crate::diagln!(
+        "cpu: SPEC_CTRL {}, STIBP {}, SSBD {}",
+        yes_no(features.spec_ctrl),
+        yes_no(features.stibp),
+        yes_no(features.ssbd)
+    );
+    match features.arch_capabilities {
+        Some(arch) => {
+            for &(bit, name) in &ARCH_CAPABILITY_NAMES {
+                if arch & bit != 0 {
+                    crate::diagln!("cpu: hardware reports {}", name);
+                }
+            }
+        }
+        None => crate::diagln!("cpu: no ARCH_CAPABILITIES; assuming all issues apply"),
+    }
+
+    let off = crate::options::value("mitigations") == Some("off");
+    match features.default_spec_ctrl(off) {
+        Some(value) => {
+            // SAFETY: CPUID reports SPEC_CTRL, and only advertised bits are set.
+            unsafe { wrmsr(IA32_SPEC_CTRL, value) };
+            crate::diagln!("cpu: SPEC_CTRL set to {:#x}", value);
+        }
+        None if off => crate::println!("cpu: speculation mitigations disabled (mitigations=off)"),
+        None => {}
+    }
+}
+
+fn yes_no(flag: bool) -> &'static str {
+    if flag { "yes" } else { "no" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_spec_ctrl_follows_advertised_features() {
+        let mut features = SpeculationFeatures {
+            spec_ctrl: true,
+            stibp: true,
+            ssbd: true,
+            arch_capabilities: None,
+        };
+        assert_eq!(features.default_spec_ctrl(false), Some(SPEC_CTRL_SSBD));
+        assert_eq!(features.default_spec_ctrl(true), None);
+
+        features.arch_capabilities = Some(ARCH_CAP_IBRS_ALL | ARCH_CAP_SSB_NO);
+        assert_eq!(features.default_spec_ctrl(false), Some(SPEC_CTRL_IBRS));
+
+        features.arch_capabilities = Some(ARCH_CAP_SSB_NO);
+        assert_eq!(features.default_spec_ctrl(false), None);
+
+        features.spec_ctrl = false;
+        features.arch_capabilities = None;
+        assert_eq!(features.default_spec_ctrl(false), None);
+    }
+}
@@ -0,0 +1,104 @@
+//! Processor identification and model-specific registers.
+
+use core::arch::asm;
+
+pub mod microcode;
+pub mod mitigations;
+pub mod pmu;
+pub mod telemetry;
+
+pub use crate::interrupts::rdmsr_safe;
+
+/// Read a model-specific register.
+///
+/// # Safety
+/// `msr` must exist on this CPU; reading an unimplemented MSR raises #GP.
+pub unsafe fn rdmsr(msr: u32) -> u64 {
+    let (high, low): (u32, u32);
+    unsafe {
+        asm!(
+            "rdmsr",
+            in("ecx") msr,
+            out("eax") low,
+            out("edx") high,
+            options(nomem, nostack, preserves_flags),
+        );
+    }
+    ((high as u64) << 32) | low as u64
+}
+
+/// Write a model-specific register.
+///
+/// # Safety
+/// `msr` must exist and accept `value`; the caller owns the side effects.
+pub unsafe fn wrmsr(msr: u32, value: u64) {
+    unsafe {
+        asm!(
+            "wrmsr",
+            in("ecx") msr,
+            in("eax") value as u32,
+            in("edx") (value >> 32) as u32,
+            options(nostack, preserves_flags),
+        );
+    }
+}
+
+/// Highest standard CPUID leaf.
+pub fn max_leaf() -> u32 {
+    core::arch::x86_64::__cpuid(0).eax
+}
+
+/// Whether CPUID leaf 0 reports "GenuineIntel".
+pub fn is_intel() -> bool {
+    let leaf = core::arch::x86_64::__cpuid(0);
+    (leaf.ebx, leaf.edx, leaf.ecx) == (0x756E_6547, 0x4965_6E69, 0x6C65_746E)
+}
+
+/// Whether the CPU can run 5-level paging (CPUID.7.0:ECX.LA57). The kernel
+/// builds 4-level tables either way; boot refuses if firmware turned it on.
+pub fn supports_la57() -> bool {
+    max_leaf() >= 7 && core::arch::x86_64::__cpuid_count(7, 0).ecx & (1 << 16) != 0
+}
+
+/// Physical address width (MAXPHYADDR) from CPUID 0x8000_0008, or 36 bits
+/// when the CPU lacks that leaf.
+pub fn phys_addr_bits() -> u8 {
+    oxide_util::cpuid::phys_addr_bits()
+}
+
+const IA32_EFER: u32 = 0xC000_0080;
+const EFER_NXE: u64 = 1 << 11;
+const CR0_WP: u64 = 1 << 16;
+
+/// Turn on no-execute paging (`EFER.NXE`) if the CPU supports it, and
+/// report whether it is on.
+pub fn enable_no_execute() -> bool {
+    use core::arch::x86_64::__cpuid;
+
+    // CPUID 0x8000_0001 EDX bit 20: XD/NX.
+    if __cpuid(0x8000_0000).eax < 0x8000_0001 || __cpuid(0x8000_0001).edx & (1 << 20) == 0 {
+        return false;
+    }
+    // SAFETY: EFER exists on every x86_64 CPU, and NXE only makes bit 63 of
+    // page-table entries meaningful; none is set before this returns.
+    unsafe {
+        let efer = rdmsr(IA32_EFER);
+        if efer & EFER_NXE == 0 {
+            wrmsr(IA32_EFER, efer | EFER_NXE);
+        }
+    }
+    true
+}
+
+/// Make ring 0 honor read-only pages (`CR0.WP`); firmware may leave it clear.
+pub fn enable_write_protect() {
+    // SAFETY: the kernel never relies on writing through read-only mappings.
+    unsafe {
+        let mut cr0: u64;
+        asm!("mov {}, cr0", out(reg) cr0, options(nomem, nostack, preserves_flags));
+        if cr0 & CR0_WP == 0 {
+            cr0 |= CR0_WP;
+            asm!("mov cr0, {}", in(reg) cr0, options(nostack, preserves_flags));
+        }
+    }
+}
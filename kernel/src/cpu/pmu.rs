@@ -0,0 +1,191 @@
+//! Fixed-function performance counters (instructions retired, core cycles).
+//!
+//! Intel's architectural performance monitoring (CPUID leaf 0xA) provides
+//! fixed counters that need no event programming: counter 0 counts retired
+//! instructions and counter 1 unhalted core cycles. Unlike the TSC, both are
+//! unaffected by frequency scaling, so deltas around a region measure the
+//! work done rather than wall time. [`init`] enables them for ring 0 and
+//! [`measure`] reports the delta for a closure; boot logs one delta per phase.
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+use super::{is_intel, max_leaf, rdmsr, wrmsr};
+
+const IA32_FIXED_CTR0: u32 = 0x309;
+const IA32_FIXED_CTR1: u32 = 0x30A;
+const IA32_FIXED_CTR_CTRL: u32 = 0x38D;
+const IA32_PERF_GLOBAL_CTRL: u32 = 0x38F;
+
+const CPUID_PERFMON: u32 = 0xA;
+/// `IA32_PERF_GLOBAL_CTRL` first appears in version 2.
+const MIN_VERSION: u32 = 2;
+const FIXED_COUNTERS_USED: u32 = 2;
+
+/// Count in ring 0 only (per-counter field in `IA32_FIXED_CTR_CTRL`).
+const FIXED_CTRL_OS: u64 = 0b0001;
+/// Global enable bit of fixed counter 0; counter `i` is this shifted by `i`.
+const GLOBAL_FIXED0: u64 = 1 << 32;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Counter width in bits, for wrap-around arithmetic.
+static WIDTH: AtomicU8 = AtomicU8::new(0);
+
+/// One reading of the fixed counters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CounterSample {
+    pub instructions: u64,
+    pub cycles: u64,
+}
+
+/// Work done between two samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CounterDelta {
+    pub instructions: u64,
+    pub cycles: u64,
+}
+
+impl CounterSample {
+    /// Read the counters, if [`init`] enabled them.
+    pub fn read() -> Option<Self> {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return None;
+        }
+        // SAFETY: `init` verified both fixed counters exist.
+        Some(unsafe {
+            CounterSample {
+                instructions: rdmsr(IA32_FIXED_CTR0),
+                cycles: rdmsr(IA32_FIXED_CTR1),
+            }
+        })
+    }
+
+    /// Counts accumulated since `earlier`, allowing one wrap of the counters.
+    pub fn delta_since(&self, earlier: CounterSample) -> CounterDelta {
+        let width = WIDTH.load(Ordering::Relaxed);
+        CounterDelta {
+            instructions: wrapping_delta(earlier.instructions, self.instructions, width),
+            cycles: wrapping_delta(earlier.cycles, self.cycles, width),
+        }
+    }
+}
+
+impl CounterDelta {
+    /// Instructions per cycle in hundredths, or `None` without cycles.
+    pub fn ipc_hundredths(&self) -> Option<u64> {
+        (self.cycles != 0).then(|| (self.instructions as u128 * 100 / self.cycles as u128) as u64)
+    }
+}
+
+/// Enable fixed counters 0 and 1 for ring 0 if the CPU has them.
+///
+/// Hypervisors often hide the PMU, in which case the counters stay off and
+/// every sample is `None`.
+pub fn init() {
+    if !is_intel() || max_leaf() < CPUID_PERFMON {
+        return;
+    }
+    let leaf = core::arch::x86_64::__cpuid(CPUID_PERFMON);
+    let version = leaf.eax & 0xFF;
+    let fixed = leaf.edx & 0x1F;
+    let width = ((leaf.edx >> 5) & 0xFF) as u8;
+    if version < MIN_VERSION || fixed < FIXED_COUNTERS_USED || width == 0 {
+        return;
+    }
+
+    // SAFETY: CPUID.0AH reports version 2+ with at least two fixed counters,
+    // which guarantees these MSRs. Other counters' bits are preserved.
+    unsafe {
+        let ctrl = rdmsr(IA32_FIXED_CTR_CTRL) & !0xFF;
+        wrmsr(
+            IA32_FIXED_CTR_CTRL,
+            ctrl | FIXED_CTRL_OS | FIXED_CTRL_OS << 4,
+        );
+        let global = rdmsr(IA32_PERF_GLOBAL_CTRL);
+        wrmsr(
+            IA32_PERF_GLOBAL_CTRL,
+            global | GLOBAL_FIXED0 | GLOBAL_FIXED0 << 1,
+        );
+    }
+    WIDTH.store(width, Ordering::Relaxed);
+    ENABLED.store(true, Ordering::Relaxed);
+    crate::diagln!(
+        "cpu: fixed counters enabled (perfmon v{}, {} bits)",
+        version,
+        width
+    );
+}
+
+/// Run `f` and return its result with the counter delta it caused.
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, Option<CounterDelta>) {
+    let start = CounterSample::read();
+    let result = f();
+    let delta = start
+        .zip(CounterSample::read())
+        .map(|(start, end)| end.delta_since(start));
+    (result, delta)
+}
+
+struct PhaseCell(UnsafeCell<Option<CounterSample>>);
+
+unsafe impl Sync for PhaseCell {}
+
+static PHASE_START: PhaseCell = PhaseCell(UnsafeCell::new(None));
+
+/// Log the counts since the previous call as boot phase `name`.
+///
+/// The first call only records the starting point. Called from the
+/// single-threaded boot path.
+pub fn phase_done(name: &str) {
+    let Some(now) = CounterSample::read() else {
+        return;
+    };
+    // SAFETY: only the boot path calls this, one call at a time.
+    let previous = unsafe { (*PHASE_START.0.get()).replace(now) };
+    let Some(previous) = previous else {
+        return;
+    };
+    let delta = now.delta_since(previous);
+    let ipc = delta.ipc_hundredths().unwrap_or(0);
+    crate::diagln!(
+        "cpu: {}: {} instructions, {} cycles, IPC {}.{:02}",
+        name,
+        delta.instructions,
+        delta.cycles,
+        ipc / 100,
+        ipc % 100
+    );
+}
+
+fn wrapping_delta(earlier: u64, later: u64, width: u8) -> u64 {
+    let mask = match width {
+        1..=63 => (1u64 << width) - 1,
+        _ => u64::MAX,
+    };
+    later.wrapping_sub(earlier) & mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deltas_wrap_at_counter_width() {
+        assert_eq!(wrapping_delta(100, 250, 48), 150);
+        assert_eq!(wrapping_delta((1 << 48) - 10, 5, 48), 15);
+        assert_eq!(wrapping_delta(u64::MAX, 4, 0), 5);
+
+        let delta = CounterDelta {
+            instructions: 3_000,
+            cycles: 2_000,
+        };
+        assert_eq!(delta.ipc_hundredths(), Some(150));
+        let idle = CounterDelta {
+            instructions: 0,
+            cycles: 0,
+        };
+        assert_eq!(idle.ipc_hundredths(), None);
+    }
+}
@@ -0,0 +1,145 @@
+//! Read-only thermal and effective-frequency telemetry from MSRs.
+//!
+//! The digital thermal sensor reports degrees below the throttle point
+//! (TjMax), and the APERF/MPERF pair counts actual versus nominal cycles, so
+//! their ratio times the nominal (TSC) frequency approximates the current
+//! clock. Both explain TSC-based timing drifting on throttling laptops. Only
+//! Intel's thermal MSRs are decoded; other vendors report no temperature.
+//!
+//! Every read goes through `rdmsr_safe`, on top of the CPUID checks, because
+//! hypervisors often advertise these MSRs without implementing them. Nothing
+//! here works before `interrupts::init`.
+
+use super::{is_intel, max_leaf, rdmsr_safe};
+
+const IA32_MPERF: u32 = 0xE7;
+const IA32_APERF: u32 = 0xE8;
+const IA32_THERM_STATUS: u32 = 0x19C;
+const MSR_TEMPERATURE_TARGET: u32 = 0x1A2;
+
+// CPUID.06H
+const CPUID6_EAX_DTS: u32 = 1 << 0;
+const CPUID6_ECX_APERF_MPERF: u32 = 1 << 0;
+
+const THERM_STATUS_VALID: u64 = 1 << 31;
+/// TjMax assumed when the CPU does not report one.
+const DEFAULT_TJMAX: u32 = 100;
+
+/// One reading of the APERF/MPERF counters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CycleSample {
+    pub aperf: u64,
+    pub mperf: u64,
+}
+
+impl CycleSample {
+    /// Read both counters, if the CPU has them. Take one sample at the
+    /// start and one at the end of whatever interval is being measured.
+    pub fn read() -> Option<Self> {
+        if !has_leaf6() || core::arch::x86_64::__cpuid(6).ecx & CPUID6_ECX_APERF_MPERF == 0 {
+            return None;
+        }
+        Some(CycleSample {
+            mperf: rdmsr_safe(IA32_MPERF)?,
+            aperf: rdmsr_safe(IA32_APERF)?,
+        })
+    }
+
+    /// Average frequency between `earlier` and `self`, given the nominal
+    /// frequency MPERF counts at.
+    pub fn effective_hz_since(&self, earlier: CycleSample, nominal_hz: u64) -> Option<u64> {
+        let actual = self.aperf.wrapping_sub(earlier.aperf);
+        let nominal = self.mperf.wrapping_sub(earlier.mperf);
+        if nominal == 0 || nominal_hz == 0 {
+            return None;
+        }
+        let hz = nominal_hz as u128 * actual as u128 / nominal as u128;
+        Some(hz.min(u64::MAX as u128) as u64)
+    }
+}
+
+/// Current package-relative core temperature in degrees Celsius.
+pub fn temperature_celsius() -> Option<u32> {
+    if !is_intel() || !has_leaf6() || core::arch::x86_64::__cpuid(6).eax & CPUID6_EAX_DTS == 0 {
+        return None;
+    }
+    let status = rdmsr_safe(IA32_THERM_STATUS)?;
+    // Architecturally undocumented; older parts and many hypervisors lack it.
+    let target = rdmsr_safe(MSR_TEMPERATURE_TARGET).unwrap_or(0);
+    celsius_from_msrs(status, target)
+}
+
+fn celsius_from_msrs(therm_status: u64, temperature_target: u64) -> Option<u32> {
+    if therm_status & THERM_STATUS_VALID == 0 {
+        return None;
+    }
+    let below_tjmax = ((therm_status >> 16) & 0x7F) as u32;
+    let tjmax = match ((temperature_target >> 16) & 0xFF) as u32 {
+        0 => DEFAULT_TJMAX,
+        tjmax => tjmax,
+    };
+    Some(tjmax.saturating_sub(below_tjmax))
+}
+
+fn has_leaf6() -> bool {
+    max_leaf() >= 6
+}
+
+/// Log the average frequency between `start` and now, such as over the rest
+/// of boot. Sampling across work that runs anyway avoids a busy loop.
+pub fn log_frequency_since(start: Option<CycleSample>, nominal_hz: u64) {
+    let Some(hz) = start
+        .zip(CycleSample::read())
+        .and_then(|(start, end)| end.effective_hz_since(start, nominal_hz))
+    else {
+        return;
+    };
+    crate::diagln!("cpu: effective frequency ~{} MHz", hz / 1_000_000);
+}
+
+/// Log the current temperature.
+pub fn log() {
+    if let Some(celsius) = temperature_celsius() {
+        crate::diagln!("cpu: temperature {} C", celsius);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_frequency_scales_nominal_by_cycle_ratio() {
+        let start = CycleSample {
+            aperf: 1_000,
+            mperf: 2_000,
+        };
+        let end = CycleSample {
+            aperf: 1_500,
+            mperf: 3_000,
+        };
+        assert_eq!(
+            end.effective_hz_since(start, 2_000_000_000),
+            Some(1_000_000_000)
+        );
+        assert_eq!(start.effective_hz_since(start, 2_000_000_000), None);
+
+        let wrapped = CycleSample {
+            aperf: 99,
+            mperf: 99,
+        };
+        let before = CycleSample {
+            aperf: u64::MAX - 100,
+            mperf: u64::MAX - 100,
+        };
+        assert_eq!(wrapped.effective_hz_since(before, 1_000), Some(1_000));
+    }
+
+    #[test]
+    fn temperature_is_tjmax_minus_readout() {
+        let status = THERM_STATUS_VALID | (35 << 16);
+        assert_eq!(celsius_from_msrs(status, 100 << 16), Some(65));
+        assert_eq!(celsius_from_msrs(status, 0), Some(DEFAULT_TJMAX - 35));
+        assert_eq!(celsius_from_msrs(35 << 16, 100 << 16), None);
+    }
+}
@@ -0,0 +1,231 @@
+//! The received `BootAbi`, printed field by field.
+//!
+//! With `bootinfo=on` the kernel prints its reading of every handoff field
+//! right after the console comes up, so a disagreement between what the
+//! loader meant and what the kernel parsed shows on target. `bootinfo=raw`
+//! adds a hex dump of the structure itself.
+
+use core::{mem, slice};
+
+use oxide_abi::{ABI_CMDLINE_CAP, BootAbi, MemoryMap, OXIDE_HANDOFF_MEMORY, PhysRange};
+use oxide_util::hexdump::hexdump;
+
+use crate::{
+    console::format::{fmt_hex, fmt_hz, fmt_size},
+    memory::{frame::FRAME_SIZE, map::MemoryMapIter},
+};
+
+/// UEFI memory types with a name, by value.
+const TYPE_NAMES: [&str; 15] = [
+    "reserved",
+    "loader code",
+    "loader data",
+    "boot code",
+    "boot data",
+    "runtime code",
+    "runtime data",
+    "conventional",
+    "unusable",
+    "acpi reclaim",
+    "acpi nvs",
+    "mmio",
+    "mmio port",
+    "pal code",
+    "persistent",
+];
+
+/// Descriptor count and pages per memory type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct TypeTotal {
+    descriptors: u32,
+    pages: u64,
+}
+
+/// Summary rows after the UEFI types: the loader's handoff pages, then
+/// everything else.
+const HANDOFF_ROW: usize = TYPE_NAMES.len();
+const OTHER_ROW: usize = TYPE_NAMES.len() + 1;
+
+fn row_of(typ: u32) -> usize {
+    match typ {
+        OXIDE_HANDOFF_MEMORY => HANDOFF_ROW,
+        _ => (typ as usize).min(OTHER_ROW),
+    }
+}
+
+fn row_name(row: usize) -> &'static str {
+    match row {
+        HANDOFF_ROW => "oxide handoff",
+        _ => TYPE_NAMES.get(row).copied().unwrap_or("other"),
+    }
+}
+
+/// Totals for each named type, the handoff pages, then everything else.
+fn summarize(map: &MemoryMap) -> [TypeTotal; OTHER_ROW + 1] {
+    let mut totals = [TypeTotal::default(); OTHER_ROW + 1];
+    for descriptor in MemoryMapIter::new(map) {
+        let index = row_of(descriptor.typ);
+        totals[index].descriptors += 1;
+        totals[index].pages = totals[index]
+            .pages
+            .saturating_add(descriptor.number_of_pages);
+    }
+    totals
+}
+
+/// Print the handoff if `bootinfo=` asks for it.
+pub fn show_if_requested(abi: &BootAbi) {
+    let raw = match crate::options::value("bootinfo") {
+        Some("on") => false,
+        Some("raw") => true,
+        _ => return,
+    };
+
+    crate::println!(
+        "bootinfo: BootAbi v{} at {} ({} bytes)",
+        abi.version,
+        fmt_hex(abi as *const BootAbi as u64, 16),
+        mem::size_of::<BootAbi>()
+    );
+
+    let options = &abi.options;
+    let cmdline_len = usize::from(options.cmdline_len).min(ABI_CMDLINE_CAP);
+    crate::println!(
+        "  options: debug={} quiet={} cmdline=\"{}\"",
+        options.debug,
+        options.quiet,
+        core::str::from_utf8(&options.cmdline[..cmdline_len]).unwrap_or("<not utf-8>")
+    );
+
+    let firmware = &abi.firmware;
+    let vendor_len = usize::from(firmware.vendor_len).min(firmware.vendor.len());
+    crate::println!(
+        "  firmware: \"{}\"{} revision {}",
+        core::str::from_utf8(&firmware.vendor[..vendor_len]).unwrap_or("<not utf-8>"),
+        if firmware.vendor_truncated != 0 {
+            " (truncated)"
+        } else {
+            ""
+        },
+        fmt_hex(u64::from(firmware.revision), 8)
+    );
+
+    let fb = &abi.framebuffer;
+    crate::println!(
+        "  framebuffer: {}x{} ({} px/scanline) {:?} at {} ({})",
+        fb.width,
+        fb.height,
+        fb.pixels_per_scanline,
+        fb.pixel_format,
+        fmt_hex(fb.base_address, 16),
+        fmt_size(fb.buffer_size)
+    );
+    crate::println!(
+        "  tsc: {} ({} Hz)",
+        fmt_hz(abi.tsc_frequency_hz),
+        abi.tsc_frequency_hz
+    );
+
+    let map = &abi.memory_map;
+    crate::println!(
+        "  memory map: {} descriptors of {} bytes (version {}) at {}, {} bytes",
+        map.entry_count,
+        map.entry_size,
+        map.entry_version,
+        fmt_hex(map.descriptors_phys, 16),
+        map.map_size
+    );
+    for (index, total) in summarize(map).iter().enumerate() {
+        if total.descriptors == 0 {
+            continue;
+        }
+        crate::println!(
+            "    {:<13} {:>4} descriptors {:>10}",
+            row_name(index),
+            total.descriptors,
+            fmt_size(total.pages.saturating_mul(FRAME_SIZE))
+        );
+    }
+
+    crate::println!("  status page: {}", fmt_hex(abi.status_page_phys, 16));
+    show_range("microcode", abi.microcode);
+    if abi.microcode.len != 0 {
+        crate::println!("    xxh32 {}", fmt_hex(abi.microcode_xxh32 as u64, 8));
+    }
+    show_range("memory attributes", abi.memory_attributes);
+    show_range("loader image", abi.loader_image);
+
+    if raw {
+        // SAFETY: `abi` is a live, plain-data `repr(C)` structure.
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                abi as *const BootAbi as *const u8,
+                mem::size_of::<BootAbi>(),
+            )
+        };
+        crate::print!("{}", hexdump(bytes, abi as *const BootAbi as u64));
+    }
+}
+
+fn show_range(name: &str, range: PhysRange) {
+    if range.len == 0 {
+        crate::println!("  {}: none", name);
+    } else {
+        crate::println!(
+            "  {}: {} ({})",
+            name,
+            fmt_hex(range.phys, 16),
+            fmt_size(range.len)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxide_abi::{EfiMemoryType, MemoryDescriptor};
+
+    fn descriptor(typ: u32, pages: u64) -> MemoryDescriptor {
+        MemoryDescriptor {
+            typ,
+            _pad: 0,
+            physical_start: 0,
+            virtual_start: 0,
+            number_of_pages: pages,
+            attribute: 0,
+        }
+    }
+
+    #[test]
+    fn summary_groups_descriptors_by_type() {
+        let descriptors = [
+            descriptor(EfiMemoryType::ConventionalMemory as u32, 16),
+            descriptor(EfiMemoryType::LoaderData as u32, 2),
+            descriptor(EfiMemoryType::ConventionalMemory as u32, 4),
+            descriptor(0x7000_0000, 1),
+            descriptor(OXIDE_HANDOFF_MEMORY, 3),
+        ];
+        let entry_size = mem::size_of::<MemoryDescriptor>();
+        let map = MemoryMap {
+            descriptors_phys: descriptors.as_ptr() as u64,
+            map_size: (entry_size * descriptors.len()) as u64,
+            entry_size: entry_size as u32,
+            entry_version: 1,
+            entry_count: descriptors.len() as u32,
+        };
+
+        let totals = summarize(&map);
+        let conventional = EfiMemoryType::ConventionalMemory as usize;
+        assert_eq!(
+            totals[conventional],
+            TypeTotal {
+                descriptors: 2,
+                pages: 20
+            }
+        );
+        assert_eq!(totals[EfiMemoryType::LoaderData as usize].pages, 2);
+        assert_eq!(totals[HANDOFF_ROW].pages, 3);
+        assert_eq!(totals[OTHER_ROW].descriptors, 1);
+        assert_eq!(totals.iter().map(|total| total.descriptors).sum::<u32>(), 5);
+    }
+}
@@ -0,0 +1,338 @@
+//! Hardware breakpoints and watchpoints through the debug registers.
+//!
+//! DR0–DR3 hold up to four linear addresses; DR7 enables each one locally and
+//! selects its condition (execute, write, read/write) and length. A hit raises
+//! #DB (vector 1), whose handler reads DR6 to report which slot fired.
+//!
+//! The interrupt stubs do not save the interrupted context yet, so the #DB
+//! handler reports the hit and halts instead of resuming.
+
+use core::arch::asm;
+
+use oxide_util::cmdline::parse_u64;
+
+use crate::console::format::fmt_hex;
+
+/// Number of address registers (DR0–DR3).
+pub const SLOTS: usize = 4;
+
+/// What access triggers the breakpoint (DR7 R/W field).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Execute,
+    Write,
+    ReadWrite,
+}
+
+impl Condition {
+    const fn bits(self) -> u64 {
+        match self {
+            Condition::Execute => 0b00,
+            Condition::Write => 0b01,
+            Condition::ReadWrite => 0b11,
+        }
+    }
+
+    const fn from_bits(bits: u64) -> Self {
+        match bits & 0b11 {
+            0b00 => Condition::Execute,
+            0b01 => Condition::Write,
+            _ => Condition::ReadWrite,
+        }
+    }
+}
+
+/// A breakpoint or watchpoint on `len` bytes at `address`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u64,
+    pub condition: Condition,
+    /// 1, 2, 4, or 8 bytes; execute breakpoints must use 1.
+    pub len: u8,
+}
+
+/// Errors raised while programming the debug registers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HwbpError {
+    /// All four address registers are in use.
+    NoFreeSlot,
+    /// Slot index outside DR0–DR3.
+    InvalidSlot(usize),
+    /// Length other than 1, 2, 4, or 8, or not 1 for an execute breakpoint.
+    InvalidLength(u8),
+    /// Address not aligned to the watched length.
+    Misaligned(u64),
+    /// Text did not match `<addr> <x|w|rw> <len>`.
+    Parse,
+}
+
+impl Breakpoint {
+    fn validate(&self) -> Result<(), HwbpError> {
+        let len_ok = match self.condition {
+            Condition::Execute => self.len == 1,
+            _ => matches!(self.len, 1 | 2 | 4 | 8),
+        };
+        if !len_ok {
+            return Err(HwbpError::InvalidLength(self.len));
+        }
+        if !self.address.is_multiple_of(self.len as u64) {
+            return Err(HwbpError::Misaligned(self.address));
+        }
+        Ok(())
+    }
+
+    /// Parse `<addr> <x|w|rw> <len>`, e.g. `0xFFFF8000 w 8`, as a shell
+    /// `watch` command would pass it.
+    pub fn parse(text: &str) -> Result<Self, HwbpError> {
+        let mut parts = text.split_ascii_whitespace();
+        let address = parts.next().ok_or(HwbpError::Parse)?;
+        let address = parse_u64(address).ok_or(HwbpError::Parse)?;
+
+        let condition = match parts.next().ok_or(HwbpError::Parse)? {
+            "x" => Condition::Execute,
+            "w" => Condition::Write,
+            "rw" => Condition::ReadWrite,
+            _ => return Err(HwbpError::Parse),
+        };
+
+        let len = match parts.next() {
+            Some(len) => len.parse().map_err(|_| HwbpError::Parse)?,
+            None => 1,
+        };
+        if parts.next().is_some() {
+            return Err(HwbpError::Parse);
+        }
+
+        let breakpoint = Self {
+            address,
+            condition,
+            len,
+        };
+        breakpoint.validate()?;
+        Ok(breakpoint)
+    }
+}
+
+/// Program the first free slot with `breakpoint`, returning the slot index.
+pub fn set(breakpoint: Breakpoint) -> Result<usize, HwbpError> {
+    breakpoint.validate()?;
+
+    let dr7 = read_dr7();
+    let slot = free_slot(dr7).ok_or(HwbpError::NoFreeSlot)?;
+    unsafe {
+        write_address(slot, breakpoint.address);
+        write_dr7(dr7_with(dr7, slot, &breakpoint));
+    }
+    Ok(slot)
+}
+
+/// Disable the breakpoint in `slot`.
+pub fn clear(slot: usize) -> Result<(), HwbpError> {
+    if slot >= SLOTS {
+        return Err(HwbpError::InvalidSlot(slot));
+    }
+    unsafe { write_dr7(dr7_without(read_dr7(), slot)) };
+    Ok(())
+}
+
+/// The breakpoint programmed in `slot`, if it is enabled.
+pub fn get(slot: usize) -> Option<Breakpoint> {
+    if slot >= SLOTS {
+        return None;
+    }
+    decode(read_dr7(), slot, read_address(slot))
+}
+
+/// Report the breakpoints that caused the current #DB and acknowledge them.
+///
+/// Called from the #DB handler.
+pub fn report_hit() {
+    let dr6 = read_dr6();
+    let dr7 = read_dr7();
+
+    for slot in hit_slots(dr6) {
+        match decode(dr7, slot, read_address(slot)) {
+            Some(bp) => crate::println!(
+                "hw breakpoint {}: {:?} of {} byte(s) at {}",
+                slot,
+                bp.condition,
+                bp.len,
+                fmt_hex(bp.address, 16)
+            ),
+            None => crate::println!("hw breakpoint {}: hit while disabled", slot),
+        }
+    }
+    if dr6 & DR6_SINGLE_STEP != 0 {
+        crate::println!("debug exception: single step");
+    }
+
+    // DR6 status bits are sticky; clear them for the next exception.
+    unsafe { write_dr6(DR6_RESERVED_ONES) };
+}
+
+const DR6_SINGLE_STEP: u64 = 1 << 14;
+const DR6_RESERVED_ONES: u64 = 0xFFFF_0FF0;
+
+fn hit_slots(dr6: u64) -> impl Iterator<Item = usize> {
+    (0..SLOTS).filter(move |slot| dr6 & (1 << slot) != 0)
+}
+
+fn free_slot(dr7: u64) -> Option<usize> {
+    (0..SLOTS).find(|&slot| dr7 & enable_bit(slot) == 0)
+}
+
+const fn enable_bit(slot: usize) -> u64 {
+    1 << (slot * 2)
+}
+
+const fn control_shift(slot: usize) -> usize {
+    16 + slot * 4
+}
+
+const fn len_bits(len: u8) -> u64 {
+    match len {
+        2 => 0b01,
+        8 => 0b10,
+        4 => 0b11,
+        _ => 0b00,
+    }
+}
+
+const fn len_from_bits(bits: u64) -> u8 {
+    match bits & 0b11 {
+        0b01 => 2,
+        0b10 => 8,
+        0b11 => 4,
+        _ => 1,
+    }
+}
+
+fn dr7_with(dr7: u64, slot: usize, breakpoint: &Breakpoint) -> u64 {
+    let shift = control_shift(slot);
+    let control = breakpoint.condition.bits() | len_bits(breakpoint.len) << 2;
+    (dr7 & !(0b1111 << shift)) | control << shift | enable_bit(slot)
+}
+
+fn dr7_without(dr7: u64, slot: usize) -> u64 {
+    dr7 & !enable_bit(slot) & !(0b1111 << control_shift(slot))
+}
+
+fn decode(dr7: u64, slot: usize, address: u64) -> Option<Breakpoint> {
+    if dr7 & enable_bit(slot) == 0 {
+        return None;
+    }
+    let control = dr7 >> control_shift(slot);
+    Some(Breakpoint {
+        address,
+        condition: Condition::from_bits(control),
+        len: len_from_bits(control >> 2),
+    })
+}
+
+fn read_address(slot: usize) -> u64 {
+    let value: u64;
+    unsafe {
+        match slot {
+            0 => asm!("mov {}, dr0", out(reg) value, options(nomem, nostack)),
+            1 => asm!("mov {}, dr1", out(reg) value, options(nomem, nostack)),
+            2 => asm!("mov {}, dr2", out(reg) value, options(nomem, nostack)),
+            _ => asm!("mov {}, dr3", out(reg) value, options(nomem, nostack)),
+        }
+    }
+    value
+}
+
+unsafe fn write_address(slot: usize, address: u64) {
+    unsafe {
+        match slot {
+            0 => asm!("mov dr0, {}", in(reg) address, options(nomem, nostack)),
+            1 => asm!("mov dr1, {}", in(reg) address, options(nomem, nostack)),
+            2 => asm!("mov dr2, {}", in(reg) address, options(nomem, nostack)),
+            _ => asm!("mov dr3, {}", in(reg) address, options(nomem, nostack)),
+        }
+    }
+}
+
+fn read_dr6() -> u64 {
+    let value: u64;
+    unsafe { asm!("mov {}, dr6", out(reg) value, options(nomem, nostack)) };
+    value
+}
+
+unsafe fn write_dr6(value: u64) {
+    unsafe { asm!("mov dr6, {}", in(reg) value, options(nomem, nostack)) };
+}
+
+fn read_dr7() -> u64 {
+    let value: u64;
+    unsafe { asm!("mov {}, dr7", out(reg) value, options(nomem, nostack)) };
+    value
+}
+
+unsafe fn write_dr7(value: u64) {
+    unsafe { asm!("mov dr7, {}", in(reg) value, options(nomem, nostack)) };
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn dr7_encoding_round_trips() {
+        let watch = Breakpoint {
+            address: 0x1000,
+            condition: Condition::Write,
+            len: 8,
+        };
+        let exec = Breakpoint {
+            address: 0x2001,
+            condition: Condition::Execute,
+            len: 1,
+        };
+
+        let dr7 = dr7_with(0, 1, &watch);
+        assert_eq!(dr7, 0b1001 << 20 | 1 << 2);
+        assert_eq!(free_slot(dr7), Some(0));
+
+        let dr7 = dr7_with(dr7, 0, &exec);
+        assert_eq!(decode(dr7, 1, 0x1000), Some(watch));
+        assert_eq!(decode(dr7, 0, 0x2001), Some(exec));
+        assert_eq!(free_slot(dr7), Some(2));
+
+        let dr7 = dr7_without(dr7, 1);
+        assert_eq!(decode(dr7, 1, 0x1000), None);
+        assert_eq!(dr7, enable_bit(0));
+    }
+
+    #[test]
+    fn parse_validates_watch_commands() {
+        assert_eq!(
+            Breakpoint::parse("0xADD0 w 8"),
+            Ok(Breakpoint {
+                address: 0xADD0,
+                condition: Condition::Write,
+                len: 8,
+            })
+        );
+        assert_eq!(Breakpoint::parse("4096 x").map(|bp| bp.len), Ok(1));
+        assert_eq!(
+            Breakpoint::parse("0xADD4 rw 8"),
+            Err(HwbpError::Misaligned(0xADD4))
+        );
+        assert_eq!(
+            Breakpoint::parse("0x1000 x 4"),
+            Err(HwbpError::InvalidLength(4))
+        );
+        assert_eq!(Breakpoint::parse("0x1000 r 4"), Err(HwbpError::Parse));
+        assert_eq!(Breakpoint::parse("0x1000 w 4 extra"), Err(HwbpError::Parse));
+    }
+
+    #[test]
+    fn hit_slots_follow_dr6_status_bits() {
+        let hits: Vec<_> = hit_slots(DR6_RESERVED_ONES | 0b1010).collect();
+        assert_eq!(hits, [1, 3]);
+    }
+}
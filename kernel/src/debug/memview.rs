@@ -0,0 +1,238 @@
+//! Physical memory map drawn as a bar on the framebuffer.
+//!
+//! With `memview=on`, the end of boot paints the runtime allocator's copy of
+//! the firmware map along the bottom of the screen: one colour per
+//! `EfiMemoryType`, with the allocator's reservations as a strip above it.
+//! Each descriptor is as wide as its share of the mapped pages, so holes in
+//! the address space take no room and a 4 KiB descriptor is still visible.
+//! Later console output may scroll over the bar.
+
+use oxide_abi::{EfiMemoryType, Framebuffer, MemoryMap};
+
+use crate::{
+    framebuffer::{self, FramebufferColor},
+    memory::{allocator::with_runtime_allocator, frame::FRAME_SIZE, map::MemoryMapIter},
+};
+
+const BAR_HEIGHT: usize = 24;
+const RESERVED_HEIGHT: usize = 6;
+const MARGIN: usize = 8;
+
+const RESERVED: FramebufferColor = FramebufferColor::new(0xFF, 0xD0, 0x20);
+const OTHER: FramebufferColor = FramebufferColor::new(0x40, 0x40, 0x40);
+
+/// Colours in the order the legend lists them.
+const LEGEND: [(&str, FramebufferColor); 8] = [
+    ("conventional", FramebufferColor::new(0x30, 0xC0, 0x50)),
+    ("loader", FramebufferColor::new(0x40, 0x70, 0xF0)),
+    ("boot services", FramebufferColor::new(0x30, 0xB0, 0xB0)),
+    ("runtime services", FramebufferColor::new(0xF0, 0x90, 0x20)),
+    ("acpi", FramebufferColor::new(0xA0, 0x50, 0xE0)),
+    ("mmio", FramebufferColor::new(0x90, 0x90, 0x90)),
+    ("unusable", FramebufferColor::new(0xE0, 0x30, 0x30)),
+    ("other", OTHER),
+];
+
+fn color_of(typ: u32) -> FramebufferColor {
+    const CONVENTIONAL: u32 = EfiMemoryType::ConventionalMemory as u32;
+    const LOADER_CODE: u32 = EfiMemoryType::LoaderCode as u32;
+    const LOADER_DATA: u32 = EfiMemoryType::LoaderData as u32;
+    const HANDOFF: u32 = oxide_abi::OXIDE_HANDOFF_MEMORY;
+    const BOOT_CODE: u32 = EfiMemoryType::BootServicesCode as u32;
+    const BOOT_DATA: u32 = EfiMemoryType::BootServicesData as u32;
+    const RUNTIME_CODE: u32 = EfiMemoryType::RuntimeServicesCode as u32;
+    const RUNTIME_DATA: u32 = EfiMemoryType::RuntimeServicesData as u32;
+    const ACPI_RECLAIM: u32 = EfiMemoryType::ACPIReclaimMemory as u32;
+    const ACPI_NVS: u32 = EfiMemoryType::ACPIMemoryNVS as u32;
+    const MMIO: u32 = EfiMemoryType::MemoryMappedIO as u32;
+    const MMIO_PORT: u32 = EfiMemoryType::MemoryMappedIOPortSpace as u32;
+    const UNUSABLE: u32 = EfiMemoryType::UnusableMemory as u32;
+
+    let class = match typ {
+        CONVENTIONAL => 0,
+        LOADER_CODE | LOADER_DATA | HANDOFF => 1,
+        BOOT_CODE | BOOT_DATA => 2,
+        RUNTIME_CODE | RUNTIME_DATA => 3,
+        ACPI_RECLAIM | ACPI_NVS => 4,
+        MMIO | MMIO_PORT => 5,
+        UNUSABLE => 6,
+        _ => 7,
+    };
+    LEGEND[class].1
+}
+
+/// Maps physical addresses to bar columns, descriptor by descriptor.
+struct Layout<'a> {
+    map: &'a MemoryMap,
+    total_pages: u64,
+    width: usize,
+}
+
+impl<'a> Layout<'a> {
+    fn new(map: &'a MemoryMap, width: usize) -> Self {
+        let total_pages = MemoryMapIter::new(map)
+            .map(|descriptor| descriptor.number_of_pages)
+            .sum();
+        Self {
+            map,
+            total_pages,
+            width,
+        }
+    }
+
+    /// Column of `addr`: the pages of every descriptor below it, scaled to
+    /// the bar. Independent of the order descriptors appear in.
+    fn column(&self, addr: u64) -> usize {
+        if self.total_pages == 0 {
+            return 0;
+        }
+        let pages_below: u64 = MemoryMapIter::new(self.map)
+            .map(|descriptor| {
+                let below = addr.saturating_sub(descriptor.physical_start) / FRAME_SIZE;
+                below.min(descriptor.number_of_pages)
+            })
+            .sum();
+        (pages_below as u128 * self.width as u128 / self.total_pages as u128) as usize
+    }
+
+    /// Columns `[start, end)` covering `[start, end)` in memory, at least one
+    /// wide so that small ranges stay visible.
+    fn columns(&self, start: u64, end: u64) -> (usize, usize) {
+        let first = self.column(start).min(self.width.saturating_sub(1));
+        let last = self.column(end).max(first + 1);
+        (first, last)
+    }
+
+    /// Each descriptor's columns and colour.
+    fn spans(&self) -> impl Iterator<Item = (usize, usize, FramebufferColor)> + '_ {
+        MemoryMapIter::new(self.map).map(|descriptor| {
+            let end = descriptor
+                .physical_start
+                .saturating_add(descriptor.number_of_pages.saturating_mul(FRAME_SIZE));
+            let (first, last) = self.columns(descriptor.physical_start, end);
+            (first, last, color_of(descriptor.typ))
+        })
+    }
+}
+
+/// Draw the bar if `memview=on` was given and the runtime allocator is up.
+pub fn show_if_requested(fb: &Framebuffer) {
+    if crate::options::value("memview") != Some("on") {
+        return;
+    }
+    let drawn = with_runtime_allocator(|allocator| {
+        let map = allocator.memory_map();
+        draw(
+            fb,
+            &map,
+            allocator.reserved_regions().map(|r| (r.start, r.end)),
+        )
+    });
+    if drawn != Some(Ok(())) {
+        crate::println!("memview: framebuffer too small or allocator unavailable");
+        return;
+    }
+
+    crate::print!("memview:");
+    for (name, color) in LEGEND {
+        let (r, g, b) = color.components();
+        crate::print!(" {}=#{:02x}{:02x}{:02x}", name, r, g, b);
+    }
+    crate::println!(" reserved=#ffd020 (upper strip)");
+}
+
+fn draw(
+    fb: &Framebuffer,
+    map: &MemoryMap,
+    reservations: impl Iterator<Item = (u64, u64)>,
+) -> Result<(), ()> {
+    let screen = framebuffer::surface(*fb)?;
+    let width = screen.width.checked_sub(2 * MARGIN).ok_or(())?;
+    let strip_y = screen
+        .height
+        .checked_sub(MARGIN + BAR_HEIGHT + RESERVED_HEIGHT)
+        .ok_or(())?;
+    let bar_y = strip_y + RESERVED_HEIGHT;
+    if width == 0 {
+        return Err(());
+    }
+
+    let layout = Layout::new(map, width);
+    framebuffer::draw_rect(
+        fb,
+        MARGIN,
+        strip_y,
+        width,
+        RESERVED_HEIGHT,
+        FramebufferColor::BLACK,
+    )?;
+    for (first, last, color) in layout.spans() {
+        framebuffer::draw_rect(fb, MARGIN + first, bar_y, last - first, BAR_HEIGHT, color)?;
+    }
+    for (start, end) in reservations {
+        let (first, last) = layout.columns(start, end);
+        framebuffer::draw_rect(
+            fb,
+            MARGIN + first,
+            strip_y,
+            last - first,
+            RESERVED_HEIGHT,
+            RESERVED,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::vec::Vec;
+    use oxide_abi::MemoryDescriptor;
+
+    fn descriptor(typ: EfiMemoryType, physical_start: u64, pages: u64) -> MemoryDescriptor {
+        MemoryDescriptor {
+            typ: typ as u32,
+            _pad: 0,
+            physical_start,
+            virtual_start: 0,
+            number_of_pages: pages,
+            attribute: 0,
+        }
+    }
+
+    #[test]
+    fn layout_scales_descriptors_by_pages_and_skips_holes() {
+        let descriptors = [
+            descriptor(EfiMemoryType::LoaderData, 0x10_0000, 1),
+            descriptor(EfiMemoryType::ConventionalMemory, 0, 3),
+            // A hole between 0x10_1000 and 4 GiB takes no room.
+            descriptor(EfiMemoryType::MemoryMappedIO, 0x1_0000_0000, 4),
+        ];
+        let entry_size = core::mem::size_of::<MemoryDescriptor>();
+        let map = MemoryMap {
+            descriptors_phys: descriptors.as_ptr() as u64,
+            map_size: (entry_size * descriptors.len()) as u64,
+            entry_size: entry_size as u32,
+            entry_version: 1,
+            entry_count: descriptors.len() as u32,
+        };
+
+        let layout = Layout::new(&map, 80);
+        let spans: Vec<_> = layout
+            .spans()
+            .map(|(first, last, _)| (first, last))
+            .collect();
+        assert_eq!(spans, [(30, 40), (0, 30), (40, 80)]);
+        assert_eq!(layout.column(0x1000), 10);
+        assert_eq!(layout.column(0x8000_0000), 40);
+        assert_eq!(layout.columns(0x1000, 0x1000), (10, 11));
+        assert_eq!(
+            color_of(EfiMemoryType::ConventionalMemory as u32),
+            LEGEND[0].1
+        );
+        assert_eq!(color_of(oxide_abi::OXIDE_HANDOFF_MEMORY), LEGEND[1].1);
+        assert_eq!(color_of(0x8000_0001), OTHER);
+    }
+}
@@ -0,0 +1,7 @@
+//! On-target debugging aids.
+
+pub mod bootinfo;
+pub mod hwbp;
+pub mod memview;
+pub mod probes;
+pub mod screendump;
@@ -0,0 +1,37 @@
+//! Named hook points for external debuggers and tracing harnesses.
+//!
+//! Each probe is an empty, unmangled function called at one boot milestone,
+//! so a GDB script attached to QEMU can `break oxide_probe_memory_init_done`
+//! without knowing kernel addresses. The names are stable: rename or remove
+//! one only alongside the tooling that uses it. The inline `asm!` block keeps
+//! the compiler from discarding calls to an otherwise empty function.
+
+use core::arch::asm;
+
+macro_rules! probe {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[unsafe(no_mangle)]
+        #[inline(never)]
+        pub extern "C" fn $name() {
+            unsafe { asm!("", options(nomem, nostack, preserves_flags)) };
+        }
+    };
+}
+
+probe!(
+    /// The handoff validated; nothing else has run yet.
+    oxide_probe_kernel_entry
+);
+probe!(
+    /// The runtime allocator and identity paging are installed.
+    oxide_probe_memory_init_done
+);
+probe!(
+    /// The IDT is loaded and exception handlers are in place.
+    oxide_probe_interrupts_init_done
+);
+probe!(
+    /// The kernel entered epoch 2.
+    oxide_probe_epoch2
+);
@@ -0,0 +1,279 @@
+//! Framebuffer capture over the serial port.
+//!
+//! With `screendump=fatal` (panics, fatal errors, exceptions) or
+//! `screendump=always` (also reboot and power-off), the kernel streams the
+//! framebuffer to COM1 as a binary PPM (`P6`) image, base64-encoded between
+//! marker lines:
+//!
+//! ```text
+//! OXIDE-SCREENDUMP BEGIN <ppm bytes>
+//! <base64, wrapped at 76 columns>
+//! OXIDE-SCREENDUMP END
+//! ```
+//!
+//! Under `logfmt=json` each of these lines is sent as the `msg` of a `diag`
+//! record instead, so the port keeps carrying only JSON.
+//!
+//! A test harness can cut the block out of the serial log and decode it to
+//! get exactly what was on screen, minus the console cursor, which is hidden
+//! for the dump so the picture does not depend on the blink phase. The dump runs as the last shutdown hook, so
+//! the console has already flushed its deferred output into the picture, and
+//! it waits for the UART to drain before returning. Fatal paths only end a
+//! `qemu-test` session after the hooks (`status::end_session`), so the dump
+//! is complete by the time QEMU exits.
+
+use core::{cell::UnsafeCell, fmt::Write};
+
+use oxide_abi::{Framebuffer, PixelFormat};
+
+use crate::{
+    console::{Level, serial_line},
+    power::{self, Teardown},
+    volatile::VolatileSlice,
+};
+
+/// Base64 characters per output line.
+const LINE_WIDTH: usize = 76;
+/// Capacity of the `P6` header and the BEGIN marker line.
+const HEADER_CAP: usize = 48;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// When the dump runs, from `screendump=`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Trigger {
+    Fatal,
+    Always,
+}
+
+struct DumpCell(UnsafeCell<Option<(Framebuffer, Trigger)>>);
+
+unsafe impl Sync for DumpCell {}
+
+static ARMED: DumpCell = DumpCell(UnsafeCell::new(None));
+
+/// Arm the dump if `screendump=` asks for it.
+///
+/// Call before the console registers its shutdown hook, so the dump runs
+/// after the console has flushed.
+pub fn init(framebuffer: Framebuffer) {
+    let trigger = match crate::options::value("screendump") {
+        Some("fatal") => Trigger::Fatal,
+        Some("always") => Trigger::Always,
+        _ => return,
+    };
+    if !crate::serial::init() {
+        return;
+    }
+
+    // SAFETY: written once during single-threaded bring-up, before the hook
+    // that reads it is registered.
+    unsafe {
+        *ARMED.0.get() = Some((framebuffer, trigger));
+    }
+    let _ = power::register("screendump", on_teardown);
+}
+
+fn on_teardown(mode: Teardown) {
+    // SAFETY: only written by `init`, before this hook was registered.
+    let Some((framebuffer, trigger)) = (unsafe { *ARMED.0.get() }) else {
+        return;
+    };
+    if trigger == Trigger::Fatal && mode == Teardown::Orderly {
+        return;
+    }
+    dump(&framebuffer);
+}
+
+/// Stream `framebuffer` to the serial port as a base64 PPM block.
+pub fn dump(framebuffer: &Framebuffer) {
+    let width = framebuffer.width as usize;
+    let height = framebuffer.height as usize;
+    let scanline = framebuffer.pixels_per_scanline as usize;
+    let fits = scanline
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .is_some_and(|bytes| bytes as u64 <= framebuffer.buffer_size);
+    if framebuffer.base_address == 0 || width == 0 || width > scanline || !fits {
+        line(b"OXIDE-SCREENDUMP UNAVAILABLE");
+        return;
+    }
+
+    let mut header = Header::new();
+    let _ = write!(header, "P6\n{} {}\n255\n", width, height);
+    let ppm_len = header.len + width * height * 3;
+
+    let mut marker = Header::new();
+    let _ = write!(marker, "OXIDE-SCREENDUMP BEGIN {}", ppm_len);
+    line(marker.as_bytes());
+
+    crate::console::set_cursor_visible(false);
+    let mut encoder =
+        Base64Writer::new(|chunk: &[u8]| line(chunk.strip_suffix(b"\n").unwrap_or(chunk)));
+    encoder.push_all(header.as_bytes());
+
+    // SAFETY: `fits` checked that `scanline * height` pixels lie inside the
+    // reported buffer.
+    let pixels =
+        unsafe { VolatileSlice::new(framebuffer.base_address as *mut u32, scanline * height) };
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = pixels.read(y * scanline + x);
+            encoder.push_all(&decode_pixel(framebuffer.pixel_format, pixel));
+        }
+    }
+    encoder.finish();
+    crate::console::set_cursor_visible(true);
+
+    line(b"OXIDE-SCREENDUMP END");
+    crate::serial::flush();
+}
+
+/// Send one line of the dump.
+fn line(text: &[u8]) {
+    serial_line(Level::Diag, module_path!(), &[text]);
+}
+
+/// Inverse of the framebuffer's `encode_pixel`.
+fn decode_pixel(format: PixelFormat, pixel: u32) -> [u8; 3] {
+    let [a, g, c, _] = pixel.to_le_bytes();
+    match format {
+        PixelFormat::Rgb => [a, g, c],
+        PixelFormat::Bgr => [c, g, a],
+    }
+}
+
+/// Fixed-size text buffer for the header lines.
+struct Header {
+    bytes: [u8; HEADER_CAP],
+    len: usize,
+}
+
+impl Header {
+    const fn new() -> Self {
+        Self {
+            bytes: [0; HEADER_CAP],
+            len: 0,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl Write for Header {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+        let dest = self.bytes.get_mut(self.len..end).ok_or(core::fmt::Error)?;
+        dest.copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Streaming base64 encoder that emits newline-terminated lines.
+struct Base64Writer<F: FnMut(&[u8])> {
+    sink: F,
+    pending: [u8; 3],
+    pending_len: usize,
+    line: [u8; LINE_WIDTH + 1],
+    line_len: usize,
+}
+
+impl<F: FnMut(&[u8])> Base64Writer<F> {
+    fn new(sink: F) -> Self {
+        Self {
+            sink,
+            pending: [0; 3],
+            pending_len: 0,
+            line: [0; LINE_WIDTH + 1],
+            line_len: 0,
+        }
+    }
+
+    fn push_all(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.pending[self.pending_len] = byte;
+            self.pending_len += 1;
+            if self.pending_len == 3 {
+                self.encode_pending();
+            }
+        }
+    }
+
+    /// Encode and pad any partial group, then flush the last line.
+    fn finish(mut self) {
+        if self.pending_len > 0 {
+            self.encode_pending();
+        }
+        if self.line_len > 0 {
+            self.flush_line();
+        }
+    }
+
+    fn encode_pending(&mut self) {
+        let [a, b, c] = self.pending;
+        let n = self.pending_len;
+        let group = u32::from_be_bytes([0, a, b, c]);
+        for i in 0..4 {
+            let symbol = if i <= n {
+                ALPHABET[(group >> (18 - 6 * i)) as usize & 0x3F]
+            } else {
+                b'='
+            };
+            self.line[self.line_len] = symbol;
+            self.line_len += 1;
+        }
+        self.pending = [0; 3];
+        self.pending_len = 0;
+        if self.line_len == LINE_WIDTH {
+            self.flush_line();
+        }
+    }
+
+    fn flush_line(&mut self) {
+        self.line[self.line_len] = b'\n';
+        (self.sink)(&self.line[..=self.line_len]);
+        self.line_len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn encode(bytes: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = Base64Writer::new(|chunk: &[u8]| out.extend_from_slice(chunk));
+        encoder.push_all(bytes);
+        encoder.finish();
+        out
+    }
+
+    #[test]
+    fn base64_pads_and_wraps_lines() {
+        assert_eq!(encode(b"P6"), b"UDY=\n");
+        assert_eq!(encode(b"P6\n"), b"UDYK\n");
+        assert_eq!(encode(b"P"), b"UA==\n");
+
+        let wrapped = encode(&[0u8; 60]);
+        assert_eq!(wrapped.len(), 80 + 2);
+        assert_eq!(wrapped[LINE_WIDTH], b'\n');
+    }
+
+    #[test]
+    fn decode_pixel_inverts_both_formats() {
+        assert_eq!(
+            decode_pixel(PixelFormat::Rgb, 0xFF_CC_BB_AA),
+            [0xAA, 0xBB, 0xCC]
+        );
+        assert_eq!(
+            decode_pixel(PixelFormat::Bgr, 0xFF_11_22_33),
+            [0x11, 0x22, 0x33]
+        );
+    }
+}
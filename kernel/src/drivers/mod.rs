@@ -0,0 +1,3 @@
+//! Drivers for platform devices the kernel programs directly.
+
+pub mod pic8259;
@@ -0,0 +1,202 @@
+//! The legacy 8259 programmable interrupt controller pair.
+//!
+//! Firmware may leave the PICs delivering IRQ 0–7 on vectors 0x08–0x0F, where
+//! they collide with CPU exceptions. [`init`] reprograms the master to vectors
+//! 0x20–0x27 and the slave (cascaded on IRQ 2) to 0x28–0x2F, then masks every
+//! line. Drivers unmask their IRQ once a handler is installed. When the local
+//! APIC takes over, the PICs stay remapped and fully masked so a stray legacy
+//! interrupt cannot masquerade as an exception.
+
+#![allow(dead_code)]
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::port::{inb, outb};
+
+/// First vector of the master PIC (IRQ 0).
+pub const MASTER_OFFSET: u8 = 0x20;
+/// First vector of the slave PIC (IRQ 8).
+pub const SLAVE_OFFSET: u8 = 0x28;
+
+const MASTER_COMMAND: u16 = 0x20;
+const MASTER_DATA: u16 = 0x21;
+const SLAVE_COMMAND: u16 = 0xA0;
+const SLAVE_DATA: u16 = 0xA1;
+/// Unused POST-code port; writing it gives the PICs time between commands.
+const WAIT_PORT: u16 = 0x80;
+
+/// ICW1: start initialization, ICW4 follows.
+const ICW1_INIT: u8 = 0x11;
+/// ICW3 (master): a slave is attached to IRQ 2.
+const ICW3_MASTER_SLAVE_ON_IRQ2: u8 = 1 << CASCADE_IRQ;
+/// ICW3 (slave): its cascade identity is 2.
+const ICW3_SLAVE_ID: u8 = CASCADE_IRQ;
+/// ICW4: 8086 mode, normal EOI.
+const ICW4_8086: u8 = 0x01;
+/// OCW3: the next command-port read returns the in-service register.
+const OCW3_READ_ISR: u8 = 0x0B;
+const EOI: u8 = 0x20;
+
+const CASCADE_IRQ: u8 = 2;
+const ALL_MASKED: u8 = 0xFF;
+
+static REMAPPED: AtomicBool = AtomicBool::new(false);
+static SPURIOUS: AtomicU32 = AtomicU32::new(0);
+
+/// Remap both PICs to [`MASTER_OFFSET`]/[`SLAVE_OFFSET`] and mask every IRQ.
+pub fn init() {
+    // SAFETY: standard 8259 initialization sequence on the fixed ISA ports.
+    unsafe {
+        outb(MASTER_DATA, ALL_MASKED);
+        outb(SLAVE_DATA, ALL_MASKED);
+
+        outb(MASTER_COMMAND, ICW1_INIT);
+        io_wait();
+        outb(SLAVE_COMMAND, ICW1_INIT);
+        io_wait();
+        outb(MASTER_DATA, MASTER_OFFSET);
+        io_wait();
+        outb(SLAVE_DATA, SLAVE_OFFSET);
+        io_wait();
+        outb(MASTER_DATA, ICW3_MASTER_SLAVE_ON_IRQ2);
+        io_wait();
+        outb(SLAVE_DATA, ICW3_SLAVE_ID);
+        io_wait();
+        outb(MASTER_DATA, ICW4_8086);
+        io_wait();
+        outb(SLAVE_DATA, ICW4_8086);
+        io_wait();
+    }
+    mask_all();
+    REMAPPED.store(true, Ordering::Release);
+    crate::diagln!(
+        "pic: remapped to {:#04x}-{:#04x}, all IRQs masked",
+        MASTER_OFFSET,
+        SLAVE_OFFSET + 7
+    );
+}
+
+/// Mask every IRQ on both controllers.
+pub fn mask_all() {
+    // SAFETY: writing the interrupt mask registers only gates delivery.
+    unsafe {
+        outb(MASTER_DATA, ALL_MASKED);
+        outb(SLAVE_DATA, ALL_MASKED);
+    }
+}
+
+/// Stop delivery of `irq` (0–15).
+pub fn mask(irq: u8) {
+    let (port, bit) = mask_bit(irq);
+    // SAFETY: read-modify-write of an interrupt mask register.
+    unsafe { outb(port, inb(port) | bit) };
+}
+
+/// Allow delivery of `irq` (0–15). Unmasking a slave IRQ also unmasks the
+/// cascade line on the master.
+pub fn unmask(irq: u8) {
+    let (port, bit) = mask_bit(irq);
+    // SAFETY: read-modify-write of an interrupt mask register.
+    unsafe {
+        outb(port, inb(port) & !bit);
+        if irq >= 8 {
+            outb(MASTER_DATA, inb(MASTER_DATA) & !(1 << CASCADE_IRQ));
+        }
+    }
+}
+
+/// Whether [`init`] has moved the PICs off the exception vectors.
+pub fn is_remapped() -> bool {
+    REMAPPED.load(Ordering::Acquire)
+}
+
+/// Whether `irq` (0–15) is currently masked.
+pub fn is_masked(irq: u8) -> bool {
+    let (port, bit) = mask_bit(irq);
+    // SAFETY: reading an interrupt mask register has no side effects.
+    unsafe { inb(port) & bit != 0 }
+}
+
+/// Signal the end of `irq`'s handler.
+pub fn end_of_interrupt(irq: u8) {
+    // SAFETY: EOI only clears the highest in-service bit.
+    unsafe {
+        if irq >= 8 {
+            outb(SLAVE_COMMAND, EOI);
+        }
+        outb(MASTER_COMMAND, EOI);
+    }
+}
+
+/// Whether `irq` is a spurious IRQ 7 or 15 rather than a real interrupt.
+///
+/// A line that deasserts before the CPU acknowledges it is reported as the
+/// controller's lowest-priority IRQ with no in-service bit set. A spurious
+/// IRQ must not receive an EOI, except that the master did see a real
+/// cascade for a spurious IRQ 15; this function sends that EOI itself.
+pub fn is_spurious(irq: u8) -> bool {
+    let command = match irq {
+        7 => MASTER_COMMAND,
+        15 => SLAVE_COMMAND,
+        _ => return false,
+    };
+    // SAFETY: OCW3 selects the ISR for the next read; no other side effects.
+    let in_service = unsafe {
+        outb(command, OCW3_READ_ISR);
+        inb(command)
+    };
+    if in_service & (1 << 7) != 0 {
+        return false;
+    }
+
+    if irq == 15 {
+        // SAFETY: acknowledges the cascade IRQ on the master.
+        unsafe { outb(MASTER_COMMAND, EOI) };
+    }
+    SPURIOUS.fetch_add(1, Ordering::Relaxed);
+    true
+}
+
+/// Number of spurious IRQs seen since boot.
+pub fn spurious_count() -> u32 {
+    SPURIOUS.load(Ordering::Relaxed)
+}
+
+/// Vector that `irq` arrives on after [`init`].
+pub const fn vector(irq: u8) -> u8 {
+    if irq < 8 {
+        MASTER_OFFSET + irq
+    } else {
+        SLAVE_OFFSET + (irq - 8)
+    }
+}
+
+fn mask_bit(irq: u8) -> (u16, u8) {
+    debug_assert!(irq < 16);
+    if irq < 8 {
+        (MASTER_DATA, 1 << irq)
+    } else {
+        (SLAVE_DATA, 1 << (irq - 8))
+    }
+}
+
+fn io_wait() {
+    // SAFETY: port 0x80 is the POST diagnostic port; writes are ignored.
+    unsafe { outb(WAIT_PORT, 0) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn irqs_map_to_remapped_vectors_and_mask_bits() {
+        assert_eq!(vector(0), 0x20);
+        assert_eq!(vector(7), 0x27);
+        assert_eq!(vector(8), 0x28);
+        assert_eq!(vector(15), 0x2F);
+
+        assert_eq!(mask_bit(1), (MASTER_DATA, 0b0000_0010));
+        assert_eq!(mask_bit(12), (SLAVE_DATA, 0b0001_0000));
+    }
+}
@@ -0,0 +1,152 @@
+//! Recursion guard and last-resort output path for fatal errors.
+//!
+//! Every fatal path (CPU exceptions, panics, `fatal()`) calls [`enter`] before
+//! doing any work. The first entry reports through the regular console. A
+//! fault raised while that report is in progress — for example the console
+//! itself page-faulting — is reported through a raw framebuffer writer that
+//! bypasses the console lock and history, and any deeper nesting halts without
+//! touching memory-backed state at all. Nested entries are counted, and the
+//! count is recorded in the status page so the next boot can report it.
+
+use core::{
+    arch::asm,
+    cell::UnsafeCell,
+    fmt::{self, Write},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use oxide_abi::Framebuffer;
+
+use crate::framebuffer::{FramebufferColor, Theme, text::FramebufferConsole};
+
+pub const EMERGENCY_COLOR: FramebufferColor = FramebufferColor::new(0xFF, 0x40, 0x40);
+
+/// How deeply the current fatal path is nested inside another one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FaultDepth {
+    /// No other fatal path is in progress; the console may be used.
+    First,
+    /// A fatal path was entered while another was still reporting.
+    Nested,
+    /// The emergency path itself faulted; halt without further output.
+    Runaway,
+}
+
+struct FramebufferCell(UnsafeCell<Option<Framebuffer>>);
+
+unsafe impl Sync for FramebufferCell {}
+
+static EMERGENCY_FRAMEBUFFER: FramebufferCell = FramebufferCell(UnsafeCell::new(None));
+static FAULT_DEPTH: AtomicU32 = AtomicU32::new(0);
+static NESTED_FAULTS: AtomicU32 = AtomicU32::new(0);
+
+/// Record the framebuffer used by the emergency writer.
+///
+/// Called once during early boot, before any fatal path can run.
+pub fn init(framebuffer: Framebuffer) {
+    unsafe {
+        *EMERGENCY_FRAMEBUFFER.0.get() = Some(framebuffer);
+    }
+}
+
+/// Register entry into a fatal path described by `what`.
+///
+/// Returns only for the first entry, which may report through the console.
+/// A nested entry is counted, recorded in the status page, and reported
+/// through [`nested`]; deeper nesting halts at once. Fatal paths never
+/// return, so the depth is never decremented.
+pub fn enter(what: fmt::Arguments<'_>) {
+    let depth = FAULT_DEPTH.fetch_add(1, Ordering::SeqCst);
+    match classify(depth) {
+        FaultDepth::First => {}
+        FaultDepth::Nested => {
+            let count = NESTED_FAULTS.fetch_add(1, Ordering::Relaxed) + 1;
+            crate::status::nested_faults(count);
+            nested(what)
+        }
+        FaultDepth::Runaway => {
+            NESTED_FAULTS.fetch_add(1, Ordering::Relaxed);
+            halt()
+        }
+    }
+}
+
+/// Number of fatal paths entered while another was already in progress.
+pub fn nested_faults() -> u32 {
+    NESTED_FAULTS.load(Ordering::Relaxed)
+}
+
+/// Write directly to the framebuffer, bypassing the console lock and history.
+///
+/// Output starts at the top-left corner (the band above the console viewport)
+/// and may overwrite whatever was rendered there.
+pub fn write(args: fmt::Arguments<'_>) {
+    let framebuffer = unsafe { *EMERGENCY_FRAMEBUFFER.0.get() };
+    let Some(framebuffer) = framebuffer else {
+        return;
+    };
+
+    let mut console = FramebufferConsole::new(
+        framebuffer,
+        0,
+        0,
+        Theme {
+            foreground: EMERGENCY_COLOR,
+            ..crate::framebuffer::theme()
+        },
+        crate::framebuffer::font(),
+        crate::framebuffer::font_scale(),
+    );
+    let _ = console.write_fmt(args);
+}
+
+/// Thickness of the [`border`] in pixels.
+const BORDER_WIDTH: usize = 16;
+
+/// Paint a frame around the screen edge, leaving the report inside intact.
+pub fn border(color: FramebufferColor) {
+    let framebuffer = unsafe { *EMERGENCY_FRAMEBUFFER.0.get() };
+    if let Some(framebuffer) = framebuffer {
+        let _ = crate::framebuffer::draw_border(&framebuffer, BORDER_WIDTH, color);
+    }
+}
+
+/// Report a fatal error raised while another was being handled, then halt.
+fn nested(what: fmt::Arguments<'_>) -> ! {
+    write(format_args!(
+        "NESTED FAULT #{}: {}\n",
+        nested_faults(),
+        what
+    ));
+    halt()
+}
+
+/// Stop the current CPU with interrupts masked.
+pub fn halt() -> ! {
+    loop {
+        unsafe {
+            asm!("cli", "hlt", options(nomem, nostack));
+        }
+    }
+}
+
+fn classify(depth: u32) -> FaultDepth {
+    match depth {
+        0 => FaultDepth::First,
+        1 => FaultDepth::Nested,
+        _ => FaultDepth::Runaway,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_maps_depth_to_levels() {
+        assert_eq!(classify(0), FaultDepth::First);
+        assert_eq!(classify(1), FaultDepth::Nested);
+        assert_eq!(classify(2), FaultDepth::Runaway);
+        assert_eq!(classify(u32::MAX), FaultDepth::Runaway);
+    }
+}
@@ -1,7 +1,8 @@
 use core::{cmp::min, ptr};
 use oxide_abi::{Framebuffer, PixelFormat};
 
-use super::{FONT_HEIGHT, FONT_WIDTH, glyph_for};
+use super::{Font, glyph_for};
+use crate::volatile::VolatileSlice;
 
 /// Simple RGB color helper for framebuffer drawing.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -24,52 +25,155 @@ impl FramebufferColor {
     }
 }
 
+/// Clockwise turn between what is drawn and how the panel scans out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    fn is_quarter_turn(self) -> bool {
+        matches!(self, Self::Deg90 | Self::Deg270)
+    }
+}
+
 /// Minimal viewport over the firmware-provided framebuffer.
+///
+/// `width`, `height`, and every coordinate passed to the drawing functions
+/// are logical: they already account for `rotation`, so a portrait-mounted
+/// 1920×1080 panel rotated by 90° is a 1080×1920 surface.
 #[derive(Clone, Copy, Debug)]
 pub struct FramebufferSurface {
     pub base_ptr: *mut u32,
-    pub pitch: usize,
+    /// Row-to-row distance in pixels; `width` plus any padding.
+    pub pixels_per_scanline: usize,
     pub width: usize,
     pub height: usize,
     pub pixel_format: PixelFormat,
+    pub rotation: Rotation,
 }
 
 impl FramebufferSurface {
     pub fn new(fb: Framebuffer) -> Result<Self, ()> {
         Self {
             base_ptr: fb.base_address as *mut u32,
-            pitch: fb.pixels_per_scanline as usize,
+            pixels_per_scanline: fb.pixels_per_scanline as usize,
             width: fb.width as usize,
             height: fb.height as usize,
             pixel_format: fb.pixel_format,
+            rotation: Rotation::Deg0,
         }
         .validate()
     }
 
+    /// The same pixels seen through `rotation`; width and height swap for
+    /// quarter turns.
+    pub fn rotated(self, rotation: Rotation) -> Self {
+        let (width, height) = self.physical_size();
+        let (width, height) = if rotation.is_quarter_turn() {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        Self {
+            width,
+            height,
+            rotation,
+            ..self
+        }
+    }
+
     pub fn empty() -> Self {
         Self {
             base_ptr: ptr::null_mut(),
-            pitch: 0,
+            pixels_per_scanline: 0,
             width: 0,
             height: 0,
             pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
         }
     }
 
     pub fn validate(self) -> Result<Self, ()> {
-        if self.base_ptr.is_null() || self.pitch == 0 || self.width == 0 || self.height == 0 {
+        if self.base_ptr.is_null()
+            || self.pixels_per_scanline == 0
+            || self.width == 0
+            || self.height == 0
+        {
             return Err(());
         }
         Ok(self)
     }
+
+    /// Every pixel slot, `pixels_per_scanline` per scanline for as many
+    /// scanlines as the panel has. Index it with [`index`](Self::index).
+    pub fn pixels(&self) -> VolatileSlice<u32> {
+        let (_, scanlines) = self.physical_size();
+        // SAFETY: a surface describes memory valid for
+        // `pixels_per_scanline * scanlines` pixels; the firmware reported it,
+        // or a test allocated it.
+        unsafe { VolatileSlice::new(self.base_ptr, self.pixels_per_scanline * scanlines) }
+    }
+
+    /// Position in [`pixels`](Self::pixels) of the logical pixel (`x`, `y`),
+    /// which must lie inside the surface.
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        let (px, py, _, _) = self.physical_rect(x, y, 1, 1);
+        py * self.pixels_per_scanline + px
+    }
+
+    /// Logical width and height clipped so no row runs past a scanline.
+    pub(super) fn visible_size(&self) -> (usize, usize) {
+        if self.rotation.is_quarter_turn() {
+            (self.width, self.height.min(self.pixels_per_scanline))
+        } else {
+            (self.width.min(self.pixels_per_scanline), self.height)
+        }
+    }
+
+    /// Width and height as the panel scans them out.
+    fn physical_size(&self) -> (usize, usize) {
+        if self.rotation.is_quarter_turn() {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
+    }
+
+    /// The physical rectangle (`x`, `y`, width, height) covering a logical
+    /// one that lies inside the surface.
+    fn physical_rect(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> (usize, usize, usize, usize) {
+        let (panel_width, panel_height) = self.physical_size();
+        match self.rotation {
+            Rotation::Deg0 => (x, y, width, height),
+            Rotation::Deg90 => (panel_width - (y + height), x, height, width),
+            Rotation::Deg180 => (
+                panel_width - (x + width),
+                panel_height - (y + height),
+                width,
+                height,
+            ),
+            Rotation::Deg270 => (y, panel_height - (x + width), height, width),
+        }
+    }
 }
 
-/// Clear the framebuffer to black.
+/// Fill the whole framebuffer with `color`.
 ///
 /// This function is defensive against malformed firmware data.
 /// If the framebuffer geometry does not fit within the reported buffer,
 /// it returns `Err(())` and performs no writes.
-pub fn clear_black(fb: &Framebuffer) -> Result<(), ()> {
+pub fn clear(fb: &Framebuffer, color: FramebufferColor) -> Result<(), ()> {
     let surface = FramebufferSurface::new(*fb)?;
 
     let bytes_per_pixel = core::mem::size_of::<u32>();
@@ -79,26 +183,21 @@ pub fn clear_black(fb: &Framebuffer) -> Result<(), ()> {
     }
 
     // Limit clearing to what actually fits in the buffer
-    let max_rows = max_pixels / surface.pitch;
+    let max_rows = max_pixels / surface.pixels_per_scanline;
     let clear_height = min(surface.height, max_rows);
     if clear_height == 0 {
         return Err(());
     }
 
-    let row_width = min(surface.width, surface.pitch);
+    let row_width = min(surface.width, surface.pixels_per_scanline);
     if row_width == 0 {
         return Err(());
     }
 
-    let color = encode_pixel(surface.pixel_format, FramebufferColor::BLACK);
-
-    unsafe {
-        for y in 0..clear_height {
-            let row_ptr = surface.base_ptr.add(y * surface.pitch);
-            for x in 0..row_width {
-                row_ptr.add(x).write_volatile(color);
-            }
-        }
+    let pixel = encode_pixel(surface.pixel_format, color);
+    let pixels = surface.pixels();
+    for y in 0..clear_height {
+        pixels.fill(y * surface.pixels_per_scanline, row_width, pixel);
     }
 
     Ok(())
@@ -119,85 +218,56 @@ pub fn fill_rect(
         return Ok(());
     }
 
-    if origin_x >= surface.width || origin_y >= surface.height {
-        return Err(());
-    }
-
-    if origin_x >= surface.pitch {
+    let (visible_width, visible_height) = surface.visible_size();
+    if origin_x >= visible_width || origin_y >= visible_height {
         return Err(());
     }
 
-    let max_width = min(
-        surface.width.saturating_sub(origin_x),
-        surface.pitch.saturating_sub(origin_x),
-    );
-    let draw_width = min(width, max_width);
-
-    let max_height = surface.height.saturating_sub(origin_y);
-    let draw_height = min(height, max_height);
-
-    if draw_width == 0 || draw_height == 0 {
-        return Err(());
-    }
+    let draw_width = min(width, visible_width - origin_x);
+    let draw_height = min(height, visible_height - origin_y);
 
+    // A rotated rectangle is still a rectangle, so fill whole physical rows.
+    let (x, y, width, height) = surface.physical_rect(origin_x, origin_y, draw_width, draw_height);
     let pixel = encode_pixel(surface.pixel_format, color);
-
-    unsafe {
-        for row in 0..draw_height {
-            let row_ptr = surface
-                .base_ptr
-                .add((origin_y + row) * surface.pitch + origin_x);
-            for col in 0..draw_width {
-                row_ptr.add(col).write_volatile(pixel);
-            }
-        }
+    let pixels = surface.pixels();
+    for row in 0..height {
+        pixels.fill((y + row) * surface.pixels_per_scanline + x, width, pixel);
     }
 
     Ok(())
 }
 
-/// Draw a single glyph bitmap at the given framebuffer coordinates.
+/// Draw a single glyph in `font` at the given framebuffer coordinates, with
+/// each font pixel expanded to a `scale`×`scale` block.
 pub fn draw_glyph(
     surface: FramebufferSurface,
     start_x: usize,
     start_y: usize,
     byte: u8,
     color: FramebufferColor,
+    font: Font,
+    scale: usize,
 ) -> Result<(), ()> {
     let surface = surface.validate()?;
 
-    let pitch = surface.pitch;
-    let width = surface.width;
-    let height = surface.height;
-
+    let (width, height) = surface.visible_size();
     if start_x >= width || start_y >= height {
         return Err(());
     }
 
-    if start_x >= pitch {
-        return Err(());
-    }
-
+    let scale = scale.max(1);
     let glyph = glyph_for(byte);
-    let draw_width = FONT_WIDTH
-        .min(width.saturating_sub(start_x))
-        .min(pitch.saturating_sub(start_x));
-    let draw_height = FONT_HEIGHT.min(height.saturating_sub(start_y));
-
-    if draw_width == 0 || draw_height == 0 {
-        return Err(());
-    }
+    let draw_width = (font.width * scale).min(width - start_x);
+    let draw_height = (font.height * scale).min(height - start_y);
 
     let pixel = encode_pixel(surface.pixel_format, color);
-
-    unsafe {
-        for (row, bitmap_row) in glyph.iter().copied().enumerate().take(draw_height) {
-            let row_ptr = surface.base_ptr.add((start_y + row) * pitch + start_x);
-            for col in 0..draw_width {
-                let bit = FONT_WIDTH - 1 - col;
-                if (bitmap_row >> bit) & 1 == 1 {
-                    row_ptr.add(col).write_volatile(pixel);
-                }
+    let pixels = surface.pixels();
+    for row in 0..draw_height {
+        let bitmap_row = font.bitmap_row(glyph, row / scale);
+        for col in 0..draw_width {
+            let bit = font.width - 1 - col / scale;
+            if (bitmap_row >> bit) & 1 == 1 {
+                pixels.write(surface.index(start_x + col, start_y + row), pixel);
             }
         }
     }
@@ -205,7 +275,7 @@ pub fn draw_glyph(
     Ok(())
 }
 
-fn encode_pixel(format: PixelFormat, color: FramebufferColor) -> u32 {
+pub(super) fn encode_pixel(format: PixelFormat, color: FramebufferColor) -> u32 {
     let (r, g, b) = color.components();
     match format {
         PixelFormat::Rgb => u32::from_le_bytes([r, g, b, 0xFF]),
@@ -218,6 +288,7 @@ mod tests {
     extern crate alloc;
 
     use super::*;
+    use crate::framebuffer::FONT_8X16;
     use alloc::vec;
 
     #[test]
@@ -244,26 +315,28 @@ mod tests {
     fn framebuffer_surface_validate_rejects_invalid_geometry() {
         let surface = FramebufferSurface {
             base_ptr: core::ptr::null_mut(),
-            pitch: 1,
+            pixels_per_scanline: 1,
             width: 1,
             height: 1,
             pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
         };
         assert!(surface.validate().is_err());
     }
 
     #[test]
     fn fill_rect_blits_only_within_bounds() {
-        let pitch = 5;
+        let scanline = 5;
         let width = 5;
         let height = 4;
-        let mut backing = vec![0u32; pitch * height];
+        let mut backing = vec![0u32; scanline * height];
         let surface = FramebufferSurface {
             base_ptr: backing.as_mut_ptr(),
-            pitch,
+            pixels_per_scanline: scanline,
             width,
             height,
             pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
         };
 
         let color = FramebufferColor::new(0x10, 0x20, 0x30);
@@ -272,7 +345,7 @@ mod tests {
         let encoded = super::encode_pixel(PixelFormat::Rgb, color);
         for row in 0..height {
             for col in 0..width {
-                let idx = row * pitch + col;
+                let idx = row * scanline + col;
                 if (1..4).contains(&col) && (1..3).contains(&row) {
                     assert_eq!(backing[idx], encoded);
                 } else {
@@ -284,16 +357,17 @@ mod tests {
 
     #[test]
     fn fill_rect_rejects_out_of_bounds_origin() {
-        let pitch = 4;
+        let scanline = 4;
         let width = 4;
         let height = 4;
-        let mut backing = vec![0u32; pitch * height];
+        let mut backing = vec![0u32; scanline * height];
         let surface = FramebufferSurface {
             base_ptr: backing.as_mut_ptr(),
-            pitch,
+            pixels_per_scanline: scanline,
             width,
             height,
             pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
         };
 
         let result = super::fill_rect(surface, width + 1, 0, 1, 1, FramebufferColor::WHITE);
@@ -302,16 +376,17 @@ mod tests {
 
     #[test]
     fn fill_rect_rejects_out_of_bounds_row() {
-        let pitch = 4;
+        let scanline = 4;
         let width = 4;
         let height = 4;
-        let mut backing = vec![0u32; pitch * height];
+        let mut backing = vec![0u32; scanline * height];
         let surface = FramebufferSurface {
             base_ptr: backing.as_mut_ptr(),
-            pitch,
+            pixels_per_scanline: scanline,
             width,
             height,
             pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
         };
 
         let result = super::fill_rect(surface, 0, height, 1, 1, FramebufferColor::WHITE);
@@ -319,40 +394,116 @@ mod tests {
     }
 
     #[test]
-    fn fill_rect_rejects_origin_past_pitch() {
-        let pitch = 4;
+    fn fill_rect_rejects_origin_past_scanline() {
+        let scanline = 4;
         let width = 6;
         let height = 4;
-        let mut backing = vec![0u32; pitch * height];
+        let mut backing = vec![0u32; scanline * height];
         let surface = FramebufferSurface {
             base_ptr: backing.as_mut_ptr(),
-            pitch,
+            pixels_per_scanline: scanline,
             width,
             height,
             pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
         };
 
-        let result = super::fill_rect(surface, pitch, 0, 1, 1, FramebufferColor::WHITE);
+        let result = super::fill_rect(surface, scanline, 0, 1, 1, FramebufferColor::WHITE);
         assert!(result.is_err());
     }
 
+    #[test]
+    fn rotated_fill_rect_lands_on_the_turned_panel_pixels() {
+        // A 4×2 panel; the logical rectangle is two pixels along the top row.
+        let lit = |rotation| {
+            let mut backing = vec![0u32; 4 * 2];
+            let surface = FramebufferSurface {
+                base_ptr: backing.as_mut_ptr(),
+                pixels_per_scanline: 4,
+                width: 4,
+                height: 2,
+                pixel_format: PixelFormat::Rgb,
+                rotation: Rotation::Deg0,
+            }
+            .rotated(rotation);
+            super::fill_rect(surface, 0, 0, 2, 1, FramebufferColor::WHITE).unwrap();
+            (0..backing.len())
+                .filter(|&i| backing[i] != 0)
+                .collect::<alloc::vec::Vec<_>>()
+        };
+
+        assert_eq!(lit(Rotation::Deg0), [0, 1]);
+        assert_eq!(lit(Rotation::Deg90), [3, 7]);
+        assert_eq!(lit(Rotation::Deg180), [6, 7]);
+        assert_eq!(lit(Rotation::Deg270), [0, 4]);
+    }
+
     #[test]
     fn draw_glyph_sets_pixels_for_known_character() {
-        let pitch = 8;
+        let scanline = 8;
         let width = 8;
-        let height = FONT_HEIGHT * 2;
-        let mut backing = vec![0u32; pitch * height];
+        let height = FONT_8X16.height * 2;
+        let mut backing = vec![0u32; scanline * height];
         let surface = FramebufferSurface {
             base_ptr: backing.as_mut_ptr(),
-            pitch,
+            pixels_per_scanline: scanline,
             width,
             height,
             pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
         };
 
         let color = FramebufferColor::WHITE;
-        super::draw_glyph(surface, 0, 0, b'A', color).unwrap();
+        super::draw_glyph(surface, 0, 0, b'A', color, FONT_8X16, 1).unwrap();
         let encoded = super::encode_pixel(PixelFormat::Rgb, color);
         assert!(backing.iter().any(|&pixel| pixel == encoded));
     }
+
+    #[test]
+    fn draw_glyph_scales_each_font_pixel() {
+        let scale = 2;
+        let scanline = FONT_8X16.width * scale;
+        let height = FONT_8X16.height * scale;
+        let mut plain = vec![0u32; FONT_8X16.width * FONT_8X16.height];
+        let mut scaled = vec![0u32; scanline * height];
+        let surface = |base: &mut [u32], scanline, height| FramebufferSurface {
+            base_ptr: base.as_mut_ptr(),
+            pixels_per_scanline: scanline,
+            width: scanline,
+            height,
+            pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
+        };
+
+        let color = FramebufferColor::WHITE;
+        super::draw_glyph(
+            surface(&mut plain, FONT_8X16.width, FONT_8X16.height),
+            0,
+            0,
+            b'A',
+            color,
+            FONT_8X16,
+            1,
+        )
+        .unwrap();
+        super::draw_glyph(
+            surface(&mut scaled, scanline, height),
+            0,
+            0,
+            b'A',
+            color,
+            FONT_8X16,
+            scale,
+        )
+        .unwrap();
+
+        for y in 0..height {
+            for x in 0..scanline {
+                assert_eq!(
+                    scaled[y * scanline + x],
+                    plain[(y / scale) * FONT_8X16.width + x / scale]
+                );
+            }
+        }
+    }
 }
@@ -6,36 +6,66 @@
 /// (hex digits, a few letters, punctuation). It is not intended to be a full
 /// terminal or shell font; once richer text output is required, replace or
 /// extend it with a more complete solution.
-pub const FONT_WIDTH: usize = 8;
-pub const FONT_HEIGHT: usize = 16;
+///
+/// Every glyph is stored once as an 8×8 bitmap. The built-in fonts differ only
+/// in how many times each bitmap row is drawn: [`FONT_8X16`] doubles them,
+/// [`FONT_8X8`] draws them as-is.
+pub const GLYPH_ROWS: usize = 8;
+
+/// One glyph bitmap, a byte per row with the leftmost pixel in bit 7.
+pub type Glyph = [u8; GLYPH_ROWS];
+
+/// Geometry of a built-in font.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Font {
+    pub name: &'static str,
+    pub width: usize,
+    pub height: usize,
+    /// Blank pixel rows between text lines.
+    pub line_spacing: usize,
+}
+
+impl Font {
+    /// Bitmap row shown at pixel row `row` of the glyph (`row < height`).
+    pub const fn bitmap_row(&self, glyph: &Glyph, row: usize) -> u8 {
+        glyph[row * GLYPH_ROWS / self.height]
+    }
+}
+
+pub const FONT_8X16: Font = Font {
+    name: "8x16",
+    width: 8,
+    height: 16,
+    line_spacing: 4,
+};
+
+pub const FONT_8X8: Font = Font {
+    name: "8x8",
+    width: 8,
+    height: 8,
+    line_spacing: 2,
+};
 
-const GLYPH_LOOKUP: [&[u8; FONT_HEIGHT]; 128] = build_glyph_lookup();
+/// Control byte drawn as `↪`, marking a wrapped continuation line. The
+/// console never passes it through from its input.
+pub const WRAP_MARKER: u8 = 0x1A;
 
-pub fn glyph_for(byte: u8) -> &'static [u8; FONT_HEIGHT] {
+const GLYPH_LOOKUP: [&Glyph; 128] = build_glyph_lookup();
+
+pub fn glyph_for(byte: u8) -> &'static Glyph {
     GLYPH_LOOKUP
         .get(byte as usize)
         .copied()
         .unwrap_or(&GLYPH_SYM_QUES)
 }
 
-const fn double_rows(rows: [u8; 8]) -> [u8; FONT_HEIGHT] {
-    let mut out = [0u8; FONT_HEIGHT];
-    let mut i = 0;
-    while i < 8 {
-        out[i * 2] = rows[i];
-        out[i * 2 + 1] = rows[i];
-        i += 1;
-    }
-    out
-}
-
 /*
     Explanation: Each byte represents a row of 8 pixels in the glyph bitmap.
     A '1' bit indicates a filled pixel, and a '0' bit indicates a blank pixel.
-    The glyphs are defined in an 8-row format and then doubled to fit the
-    FONT_HEIGHT of 16 for better vertical resolution.
+    The glyphs are defined in an 8-row format; the 8x16 font draws each row
+    twice for better vertical resolution.
 
-    const GLYPH_A: [u8; FONT_HEIGHT] = double_rows([
+    const GLYPH_A: Glyph = [
         0b00000000, // Row 0  =  □□□□□□□□
         0b00011000, // Row 1  =  □□□■■□□□
         0b00111100, // Row 2  =  □□■■■■□□
@@ -44,214 +74,217 @@ const fn double_rows(rows: [u8; 8]) -> [u8; FONT_HEIGHT] {
         0b01111110, // Row 5  =  □■■■■■■□
         0b01100110, // Row 6  =  □■■□□■■□
         0b01100110, // Row 7  =  □■■□□■■□
-    ]);
+    ];
 
     (If you squint a little, you can see the letter 'A' in the pattern above)
 */
 
 /* Punctuation and symbols */
 
-const GLYPH_SYM_SPCE: [u8; FONT_HEIGHT] = [0; FONT_HEIGHT];
-const GLYPH_SYM_EXCL: [u8; FONT_HEIGHT] = double_rows([
+const GLYPH_SYM_SPCE: Glyph = [0; GLYPH_ROWS];
+const GLYPH_SYM_EXCL: Glyph = [
     0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00000000, 0b00011000, 0b00000000,
-]);
-const GLYPH_SYM_AT: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_AT: Glyph = [
     0b00111100, 0b01000010, 0b10111001, 0b10101001, 0b10111101, 0b10011110, 0b01000000, 0b00111100,
-]);
-const GLYPH_SYM_HASH: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_HASH: Glyph = [
     0b00100100, 0b00100100, 0b01111110, 0b00100100, 0b00100100, 0b01111110, 0b00100100, 0b00100100,
-]);
-const GLYPH_SYM_DOLL: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_DOLL: Glyph = [
     0b00001000, 0b00111110, 0b01001000, 0b00111100, 0b00001010, 0b01111100, 0b00001000, 0b00000000,
-]);
-const GLYPH_SYM_PERC: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_PERC: Glyph = [
     0b01100010, 0b01100100, 0b00001000, 0b00010000, 0b00100000, 0b01000110, 0b10000110, 0b00000000,
-]);
-const GLYPH_SYM_CIRC: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_CIRC: Glyph = [
     0b00010000, 0b00101000, 0b01000100, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000,
-]);
-const GLYPH_SYM_AMPR: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_AMPR: Glyph = [
     0b00111000, 0b01000100, 0b01000100, 0b00111000, 0b01001010, 0b01000100, 0b00111010, 0b00000000,
-]);
-const GLYPH_SYM_ASTR: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_ASTR: Glyph = [
     0b00000000, 0b00101000, 0b00010000, 0b01111110, 0b00010000, 0b00101000, 0b00000000, 0b00000000,
-]);
-const GLYPH_SYM_LPAR: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_LPAR: Glyph = [
     0b00001110, 0b00011000, 0b00110000, 0b00110000, 0b00110000, 0b00011000, 0b00001110, 0b00000000,
-]);
-const GLYPH_SYM_RPAR: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_RPAR: Glyph = [
     0b01110000, 0b00110000, 0b00011000, 0b00011000, 0b00011000, 0b00110000, 0b01110000, 0b00000000,
-]);
-const GLYPH_SYM_DASH: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_DASH: Glyph = [
     0b00000000, 0b00000000, 0b00000000, 0b01111110, 0b00000000, 0b00000000, 0b00000000, 0b00000000,
-]);
-const GLYPH_SYM_PLUS: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_PLUS: Glyph = [
     0b00000000, 0b00010000, 0b00010000, 0b01111110, 0b00010000, 0b00010000, 0b00000000, 0b00000000,
-]);
-const GLYPH_SYM_UNDS: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_UNDS: Glyph = [
     0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b01111110, 0b00000000,
-]);
-const GLYPH_SYM_EQLS: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_EQLS: Glyph = [
     0b00000000, 0b00000000, 0b01111110, 0b00000000, 0b01111110, 0b00000000, 0b00000000, 0b00000000,
-]);
-const GLYPH_SYM_LBRC: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_LBRC: Glyph = [
     0b00011110, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011110,
-]);
-const GLYPH_SYM_RBRC: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_RBRC: Glyph = [
     0b00011110, 0b00000110, 0b00000110, 0b00000110, 0b00000110, 0b00000110, 0b00000110, 0b00011110,
-]);
-const GLYPH_SYM_PIPE: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_PIPE: Glyph = [
     0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000,
-]);
-const GLYPH_SYM_BSLS: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_BSLS: Glyph = [
     0b10000000, 0b01000000, 0b00100000, 0b00010000, 0b00001000, 0b00000100, 0b00000010, 0b00000000,
-]);
-const GLYPH_SYM_COLN: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_COLN: Glyph = [
     0b00000000, 0b00011000, 0b00011000, 0b00000000, 0b00000000, 0b00011000, 0b00011000, 0b00000000,
-]);
-const GLYPH_SYM_SEMI: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_SEMI: Glyph = [
     0b00000000, 0b00011000, 0b00011000, 0b00000000, 0b00000000, 0b00011000, 0b00011000, 0b00110000,
-]);
-const GLYPH_SYM_APOS: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_APOS: Glyph = [
     0b00011000, 0b00011000, 0b00011000, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000,
-]);
-const GLYPH_SYM_DQUO: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_DQUO: Glyph = [
     0b00110110, 0b00110110, 0b00110110, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000,
-]);
-const GLYPH_SYM_COMM: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_COMM: Glyph = [
     0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00011000, 0b00011000, 0b00110000,
-]);
-const GLYPH_SYM_PERD: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_PERD: Glyph = [
     0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00011000, 0b00011000,
-]);
-const GLYPH_SYM_QUES: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_WRAP: Glyph = [
+    0b00000000, 0b01100000, 0b01100100, 0b01100110, 0b01111111, 0b00000110, 0b00000100, 0b00000000,
+];
+const GLYPH_SYM_QUES: Glyph = [
     0b00111100, 0b01100110, 0b00000110, 0b00001100, 0b00011000, 0b00000000, 0b00011000, 0b00000000,
-]);
-const GLYPH_SYM_LESS: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_LESS: Glyph = [
     0b00000110, 0b00001100, 0b00011000, 0b00110000, 0b00011000, 0b00001100, 0b00000110, 0b00000000,
-]);
-const GLYPH_SYM_GRTR: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_GRTR: Glyph = [
     0b01100000, 0b00110000, 0b00011000, 0b00001100, 0b00011000, 0b00110000, 0b01100000, 0b00000000,
-]);
-const GLYPH_SYM_FSLS: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_SYM_FSLS: Glyph = [
     0b00000010, 0b00000100, 0b00001000, 0b00010000, 0b00100000, 0b01000000, 0b10000000, 0b00000000,
-]);
+];
 
 /* Letters A-Z */
 
-const GLYPH_A: [u8; FONT_HEIGHT] = double_rows([
+const GLYPH_A: Glyph = [
     0b00011000, 0b00111100, 0b01100110, 0b01100110, 0b01111110, 0b01100110, 0b01100110, 0b01100110,
-]);
-const GLYPH_B: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_B: Glyph = [
     0b01111100, 0b01100110, 0b01100110, 0b01111100, 0b01100110, 0b01100110, 0b01100110, 0b01111100,
-]);
-const GLYPH_C: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_C: Glyph = [
     0b00111100, 0b01100110, 0b01100000, 0b01100000, 0b01100000, 0b01100000, 0b01100110, 0b00111100,
-]);
-const GLYPH_D: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_D: Glyph = [
     0b01111000, 0b01101100, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b01101100, 0b01111000,
-]);
-const GLYPH_E: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_E: Glyph = [
     0b01111110, 0b01100000, 0b01100000, 0b01111100, 0b01100000, 0b01100000, 0b01100000, 0b01111110,
-]);
-const GLYPH_F: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_F: Glyph = [
     0b01111110, 0b01100000, 0b01100000, 0b01111100, 0b01100000, 0b01100000, 0b01100000, 0b01100000,
-]);
-const GLYPH_G: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_G: Glyph = [
     0b00111100, 0b01100110, 0b01100000, 0b01101110, 0b01100110, 0b01100110, 0b01100110, 0b00111100,
-]);
-const GLYPH_H: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_H: Glyph = [
     0b01100110, 0b01100110, 0b01100110, 0b01111110, 0b01100110, 0b01100110, 0b01100110, 0b01100110,
-]);
-const GLYPH_I: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_I: Glyph = [
     0b00111100, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00111100,
-]);
-const GLYPH_J: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_J: Glyph = [
     0b00011110, 0b00000110, 0b00000110, 0b00000110, 0b00000110, 0b01100110, 0b01100110, 0b00111100,
-]);
-const GLYPH_K: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_K: Glyph = [
     0b01100110, 0b01101100, 0b01111000, 0b01110000, 0b01111000, 0b01101100, 0b01100110, 0b01100110,
-]);
-const GLYPH_L: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_L: Glyph = [
     0b01100000, 0b01100000, 0b01100000, 0b01100000, 0b01100000, 0b01100000, 0b01100000, 0b01111110,
-]);
-const GLYPH_M: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_M: Glyph = [
     0b01100110, 0b01111110, 0b01111110, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b01100110,
-]);
-const GLYPH_N: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_N: Glyph = [
     0b01100110, 0b01110110, 0b01111110, 0b01101110, 0b01100110, 0b01100110, 0b01100110, 0b01100110,
-]);
-const GLYPH_O: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_O: Glyph = [
     0b00111100, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b00111100,
-]);
-const GLYPH_P: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_P: Glyph = [
     0b01111100, 0b01100110, 0b01100110, 0b01111100, 0b01100000, 0b01100000, 0b01100000, 0b01100000,
-]);
-const GLYPH_Q: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_Q: Glyph = [
     0b00111100, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b01101110, 0b00111100, 0b00001110,
-]);
-const GLYPH_R: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_R: Glyph = [
     0b01111100, 0b01100110, 0b01100110, 0b01111100, 0b01101100, 0b01100110, 0b01100110, 0b01100110,
-]);
-const GLYPH_S: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_S: Glyph = [
     0b00111100, 0b01100110, 0b01100000, 0b00111100, 0b00000110, 0b00000110, 0b01100110, 0b00111100,
-]);
-const GLYPH_T: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_T: Glyph = [
     0b01111110, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000,
-]);
-const GLYPH_U: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_U: Glyph = [
     0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b00111100,
-]);
-const GLYPH_V: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_V: Glyph = [
     0b01100110, 0b01100110, 0b01100110, 0b00111100, 0b00111100, 0b00111100, 0b00011000, 0b00011000,
-]);
-const GLYPH_W: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_W: Glyph = [
     0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b01111110, 0b01111110, 0b01100110, 0b01100110,
-]);
-const GLYPH_X: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_X: Glyph = [
     0b01100110, 0b01100110, 0b00111100, 0b00011000, 0b00111100, 0b01100110, 0b01100110, 0b01100110,
-]);
-const GLYPH_Y: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_Y: Glyph = [
     0b01100110, 0b01100110, 0b00111100, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000,
-]);
-const GLYPH_Z: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_Z: Glyph = [
     0b01111110, 0b00000110, 0b00001100, 0b00011000, 0b00110000, 0b01100000, 0b01111110, 0b01111110,
-]);
+];
 
 /* Numbers 0-9 */
 
-const GLYPH_0: [u8; FONT_HEIGHT] = double_rows([
+const GLYPH_0: Glyph = [
     0b00111100, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b01100110, 0b00111100,
-]);
-const GLYPH_1: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_1: Glyph = [
     0b00011000, 0b00111000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00011000, 0b00111100,
-]);
-const GLYPH_2: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_2: Glyph = [
     0b00111100, 0b01100110, 0b00000110, 0b00001100, 0b00011000, 0b00110000, 0b01100000, 0b01111110,
-]);
-const GLYPH_3: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_3: Glyph = [
     0b00111100, 0b01100110, 0b00000110, 0b00011100, 0b00000110, 0b00000110, 0b01100110, 0b00111100,
-]);
-const GLYPH_4: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_4: Glyph = [
     0b00001100, 0b00011100, 0b00101100, 0b01001100, 0b01111110, 0b00001100, 0b00001100, 0b00001100,
-]);
-const GLYPH_5: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_5: Glyph = [
     0b01111110, 0b01100000, 0b01100000, 0b01111100, 0b00000110, 0b00000110, 0b01100110, 0b00111100,
-]);
-const GLYPH_6: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_6: Glyph = [
     0b00111100, 0b01100000, 0b01100000, 0b01111100, 0b01100110, 0b01100110, 0b01100110, 0b00111100,
-]);
-const GLYPH_7: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_7: Glyph = [
     0b01111110, 0b00000110, 0b00001100, 0b00011000, 0b00110000, 0b00110000, 0b00110000, 0b00110000,
-]);
-const GLYPH_8: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_8: Glyph = [
     0b00111100, 0b01100110, 0b01100110, 0b00111100, 0b01100110, 0b01100110, 0b01100110, 0b00111100,
-]);
-const GLYPH_9: [u8; FONT_HEIGHT] = double_rows([
+];
+const GLYPH_9: Glyph = [
     0b00111100, 0b01100110, 0b01100110, 0b00111110, 0b00000110, 0b00000110, 0b01100110, 0b00111100,
-]);
+];
 
-const fn build_glyph_lookup() -> [&'static [u8; FONT_HEIGHT]; 128] {
+const fn build_glyph_lookup() -> [&'static Glyph; 128] {
     let mut table = [&GLYPH_SYM_QUES; 128];
 
     table[b'0' as usize] = &GLYPH_0;
@@ -319,6 +352,7 @@ const fn build_glyph_lookup() -> [&'static [u8; FONT_HEIGHT]; 128] {
     table[b'y' as usize] = &GLYPH_Y;
     table[b'z' as usize] = &GLYPH_Z;
 
+    table[WRAP_MARKER as usize] = &GLYPH_SYM_WRAP;
     table[b'!' as usize] = &GLYPH_SYM_EXCL;
     table[b'"' as usize] = &GLYPH_SYM_DQUO;
     table[b'#' as usize] = &GLYPH_SYM_HASH;
@@ -351,3 +385,19 @@ const fn build_glyph_lookup() -> [&'static [u8; FONT_HEIGHT]; 128] {
 
     table
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bitmap_rows_repeat_to_fill_the_font_height() {
+        let glyph = glyph_for(b'A');
+        for row in 0..FONT_8X8.height {
+            assert_eq!(FONT_8X8.bitmap_row(glyph, row), glyph[row]);
+        }
+        for row in 0..FONT_8X16.height {
+            assert_eq!(FONT_8X16.bitmap_row(glyph, row), glyph[row / 2]);
+        }
+    }
+}
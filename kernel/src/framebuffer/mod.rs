@@ -5,11 +5,126 @@ use oxide_abi::Framebuffer;
 mod draw;
 mod font;
 pub mod text;
+mod theme;
 
-pub use draw::FramebufferColor;
-pub use font::{FONT_HEIGHT, FONT_WIDTH, glyph_for};
+pub use draw::{FramebufferColor, FramebufferSurface, Rotation};
+pub use font::{FONT_8X8, FONT_8X16, Font, WRAP_MARKER, glyph_for};
+pub use theme::{Theme, theme};
 
-/// Clear the entire framebuffer to black using defensive bounds checking.
-pub fn clear_framebuffer(fb: &Framebuffer) -> Result<(), ()> {
-    draw::clear_black(fb)
+/// Font used when `font=` is absent or unknown: 8×8 with the `font-8x8`
+/// feature, otherwise 8×16.
+pub const DEFAULT_FONT: Font = if cfg!(feature = "font-8x8") {
+    FONT_8X8
+} else {
+    FONT_8X16
+};
+
+/// Font requested with `font=<8x16|8x8>`, or [`DEFAULT_FONT`].
+pub fn font() -> Font {
+    crate::options::value("font")
+        .and_then(parse_font)
+        .unwrap_or(DEFAULT_FONT)
+}
+
+fn parse_font(value: &str) -> Option<Font> {
+    [FONT_8X16, FONT_8X8]
+        .into_iter()
+        .find(|font| font.name == value)
+}
+
+/// Largest integer glyph scale accepted by `fontscale=`.
+pub const MAX_FONT_SCALE: usize = 4;
+
+/// Glyph scale requested with `fontscale=<n>`, or 1 when absent or invalid.
+pub fn font_scale() -> usize {
+    crate::options::value("fontscale")
+        .and_then(parse_font_scale)
+        .unwrap_or(1)
+}
+
+fn parse_font_scale(value: &str) -> Option<usize> {
+    let scale = value.parse::<usize>().ok()?;
+    (1..=MAX_FONT_SCALE).contains(&scale).then_some(scale)
+}
+
+/// Rotation requested with `rotate=<0|90|180|270>` (degrees clockwise), for
+/// panels mounted sideways or upside down; unrotated when absent or invalid.
+pub fn rotation() -> Rotation {
+    crate::options::value("rotate")
+        .and_then(parse_rotation)
+        .unwrap_or_default()
+}
+
+fn parse_rotation(value: &str) -> Option<Rotation> {
+    match value {
+        "0" => Some(Rotation::Deg0),
+        "90" => Some(Rotation::Deg90),
+        "180" => Some(Rotation::Deg180),
+        "270" => Some(Rotation::Deg270),
+        _ => None,
+    }
+}
+
+/// A surface over `fb`, turned by the [`rotation`] option. Everything drawn on
+/// the screen goes through one of these, so logical coordinates are the same
+/// for the console, the emergency writer, and the crash screen.
+pub fn surface(fb: Framebuffer) -> Result<FramebufferSurface, ()> {
+    Ok(FramebufferSurface::new(fb)?.rotated(rotation()))
+}
+
+/// Fill the entire framebuffer with `color` using defensive bounds checking.
+pub fn clear_framebuffer(fb: &Framebuffer, color: FramebufferColor) -> Result<(), ()> {
+    draw::clear(fb, color)
+}
+
+/// Fill a `width`×`height` rectangle at (`x`, `y`), clipped to the screen.
+pub fn draw_rect(
+    fb: &Framebuffer,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    color: FramebufferColor,
+) -> Result<(), ()> {
+    draw::fill_rect(surface(*fb)?, x, y, width, height, color)
+}
+
+/// Paint a `thickness`-pixel frame along the framebuffer's edges.
+pub fn draw_border(fb: &Framebuffer, thickness: usize, color: FramebufferColor) -> Result<(), ()> {
+    let surface = surface(*fb)?;
+    let (width, height) = (surface.width, surface.height);
+    let thickness = thickness.min(width / 2).min(height / 2);
+    draw::fill_rect(surface, 0, 0, width, thickness, color)?;
+    draw::fill_rect(surface, 0, height - thickness, width, thickness, color)?;
+    draw::fill_rect(surface, 0, 0, thickness, height, color)?;
+    draw::fill_rect(surface, width - thickness, 0, thickness, height, color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_font_scale_accepts_small_integers() {
+        assert_eq!(parse_font_scale("1"), Some(1));
+        assert_eq!(parse_font_scale("3"), Some(3));
+        assert_eq!(parse_font_scale("0"), None);
+        assert_eq!(parse_font_scale("5"), None);
+        assert_eq!(parse_font_scale("2x"), None);
+    }
+
+    #[test]
+    fn parse_rotation_accepts_right_angles() {
+        assert_eq!(parse_rotation("90"), Some(Rotation::Deg90));
+        assert_eq!(parse_rotation("270"), Some(Rotation::Deg270));
+        assert_eq!(parse_rotation("45"), None);
+        assert_eq!(parse_rotation("-90"), None);
+    }
+
+    #[test]
+    fn parse_font_accepts_built_in_names() {
+        assert_eq!(parse_font("8x8"), Some(FONT_8X8));
+        assert_eq!(parse_font("8x16"), Some(FONT_8X16));
+        assert_eq!(parse_font("8x12"), None);
+    }
 }
@@ -1,14 +1,15 @@
-use core::{fmt, ptr};
+#[cfg(test)]
+extern crate alloc;
+
+use core::fmt;
 
 use oxide_abi::Framebuffer;
 
 use super::{
-    FONT_HEIGHT, FONT_WIDTH, FramebufferColor,
-    draw::{self, FramebufferSurface},
+    Font, FramebufferColor, Theme, WRAP_MARKER,
+    draw::{self, FramebufferSurface, Rotation},
 };
 
-const LINE_SPACING: usize = 4;
-
 pub(crate) fn sanitize_byte(byte: u8) -> u8 {
     match byte {
         b'a'..=b'z' => byte.to_ascii_uppercase(),
@@ -24,22 +25,58 @@ pub struct FramebufferConsole {
     surface: FramebufferSurface,
     viewport: Viewport,
     cursor: Cursor,
+    /// The block cursor is painted over the cell at `cursor`.
+    cursor_drawn: bool,
+    theme: Theme,
     color: FramebufferColor,
 }
 
 impl FramebufferConsole {
-    pub fn new(fb: Framebuffer, origin_x: usize, origin_y: usize, color: FramebufferColor) -> Self {
-        let surface = FramebufferSurface::new(fb).unwrap_or_else(|_| FramebufferSurface::empty());
-        let viewport = Viewport::new(surface, origin_x, origin_y);
+    /// Create a console whose `font` glyphs are drawn at integer `scale` in
+    /// the theme's foreground colour.
+    pub fn new(
+        fb: Framebuffer,
+        origin_x: usize,
+        origin_y: usize,
+        theme: Theme,
+        font: Font,
+        scale: usize,
+    ) -> Self {
+        let surface = super::surface(fb).unwrap_or_else(|_| FramebufferSurface::empty());
+        Self::with_surface(surface, origin_x, origin_y, theme, font, scale)
+    }
+
+    /// Like [`new`](Self::new), but drawing into any surface, such as a
+    /// buffer in ordinary memory.
+    pub fn with_surface(
+        surface: FramebufferSurface,
+        origin_x: usize,
+        origin_y: usize,
+        theme: Theme,
+        font: Font,
+        scale: usize,
+    ) -> Self {
+        let viewport = Viewport::new(surface, origin_x, origin_y, font, scale);
 
         Self {
             surface,
             viewport,
             cursor: Cursor::default(),
-            color,
+            cursor_drawn: false,
+            theme,
+            color: theme.foreground,
         }
     }
 
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Colour for glyphs drawn from now on.
+    pub fn set_color(&mut self, color: FramebufferColor) {
+        self.color = color;
+    }
+
     pub fn is_usable(&self) -> bool {
         self.viewport.is_usable()
     }
@@ -48,12 +85,90 @@ impl FramebufferConsole {
         self.viewport.cols
     }
 
+    pub fn rows(&self) -> usize {
+        self.viewport.rows
+    }
+
+    /// The characters on screen, one string per row without trailing
+    /// blanks, read back from the pixels: a pixel is ink when it differs from
+    /// the background. Assumes the console was cleared first.
+    #[cfg(test)]
+    pub(crate) fn screen_text(&self) -> alloc::vec::Vec<alloc::string::String> {
+        let background = draw::encode_pixel(self.surface.pixel_format, self.theme.background);
+        let pixels = self.surface.pixels();
+        let viewport = &self.viewport;
+        let ink = |x: usize, y: usize| pixels.read(self.surface.index(x, y)) != background;
+
+        (0..viewport.rows)
+            .map(|row| {
+                let mut line = alloc::string::String::new();
+                for col in 0..viewport.cols {
+                    let Some((x, y)) = viewport.pixel_position(Cursor { col, row }) else {
+                        continue;
+                    };
+                    let matches = |byte: u8| {
+                        let glyph = super::glyph_for(byte);
+                        (0..viewport.font.height).all(|glyph_row| {
+                            let bits = viewport.font.bitmap_row(glyph, glyph_row);
+                            (0..viewport.font.width).all(|glyph_col| {
+                                let set = (bits >> (viewport.font.width - 1 - glyph_col)) & 1 == 1;
+                                set == ink(
+                                    x + glyph_col * viewport.scale,
+                                    y + glyph_row * viewport.scale,
+                                )
+                            })
+                        })
+                    };
+                    let byte = (0x20..0x7F)
+                        .map(sanitize_byte)
+                        .chain([WRAP_MARKER])
+                        .find(|&byte| matches(byte))
+                        .unwrap_or(b'?');
+                    line.push(if byte == WRAP_MARKER {
+                        '↪'
+                    } else {
+                        byte as char
+                    });
+                }
+                alloc::string::String::from(line.trim_end())
+            })
+            .collect()
+    }
+
+    /// Paint the block cursor over the cell the next glyph goes to, or erase
+    /// it. Writing erases it first, so glyphs never land on the block.
+    pub fn show_cursor(&mut self, on: bool) {
+        if on == self.cursor_drawn {
+            return;
+        }
+        let Some((x, y)) = self.viewport.pixel_position(self.cursor) else {
+            // Past the last column: the next glyph wraps, so there is no cell.
+            self.cursor_drawn = false;
+            return;
+        };
+        let color = if on {
+            self.theme.foreground
+        } else {
+            self.theme.background
+        };
+        let _ = draw::fill_rect(
+            self.surface,
+            x,
+            y,
+            self.viewport.cell_width,
+            self.viewport.font.height * self.viewport.scale,
+            color,
+        );
+        self.cursor_drawn = on;
+    }
+
     pub fn clear(&mut self) -> Result<(), ()> {
         if !self.viewport.is_usable() {
             return Err(());
         }
+        self.show_cursor(false);
 
-        let width = self.viewport.cols.saturating_mul(FONT_WIDTH);
+        let width = self.viewport.cols.saturating_mul(self.viewport.cell_width);
         let height = self.viewport.rows.saturating_mul(self.viewport.line_stride);
         draw::fill_rect(
             self.surface,
@@ -61,7 +176,7 @@ impl FramebufferConsole {
             self.viewport.origin_y,
             width,
             height,
-            FramebufferColor::BLACK,
+            self.theme.background,
         )?;
 
         self.cursor = Cursor::default();
@@ -72,6 +187,7 @@ impl FramebufferConsole {
         if !self.viewport.is_usable() {
             return Err(());
         }
+        self.show_cursor(false);
 
         for &byte in bytes {
             self.put_byte(byte);
@@ -91,7 +207,11 @@ impl FramebufferConsole {
     }
 
     fn put_byte(&mut self, byte: u8) {
-        let b = sanitize_byte(byte);
+        let b = if byte == WRAP_MARKER {
+            byte
+        } else {
+            sanitize_byte(byte)
+        };
 
         match b {
             b'\n' => {
@@ -113,7 +233,15 @@ impl FramebufferConsole {
                 }
 
                 if let Some((x, y)) = self.viewport.pixel_position(self.cursor) {
-                    let _ = draw::draw_glyph(self.surface, x, y, b, self.color);
+                    let _ = draw::draw_glyph(
+                        self.surface,
+                        x,
+                        y,
+                        b,
+                        self.color,
+                        self.viewport.font,
+                        self.viewport.scale,
+                    );
                     self.cursor.col += 1;
                 }
             }
@@ -135,18 +263,15 @@ impl FramebufferConsole {
             return;
         }
 
-        let width_pixels = cols.saturating_mul(FONT_WIDTH);
+        let width_pixels = cols.saturating_mul(self.viewport.cell_width);
         let surface = self.surface;
-        let pitch = surface.pitch;
+        let (visible_width, visible_height) = surface.visible_size();
 
-        if origin_x >= pitch || origin_y >= surface.height {
+        if origin_x >= visible_width || origin_y >= visible_height {
             return;
         }
 
-        let max_width = pitch
-            .saturating_sub(origin_x)
-            .min(surface.width.saturating_sub(origin_x));
-        let draw_width = width_pixels.min(max_width);
+        let draw_width = width_pixels.min(visible_width - origin_x);
         if draw_width == 0 {
             return;
         }
@@ -158,12 +283,12 @@ impl FramebufferConsole {
                 origin_y,
                 draw_width,
                 line_stride,
-                FramebufferColor::BLACK,
+                self.theme.background,
             );
             return;
         }
 
-        let available_rows = surface.height.saturating_sub(origin_y);
+        let available_rows = visible_height - origin_y;
         if available_rows == 0 {
             return;
         }
@@ -178,32 +303,43 @@ impl FramebufferConsole {
                 origin_y,
                 draw_width,
                 line_stride,
-                FramebufferColor::BLACK,
+                self.theme.background,
             );
             return;
         }
 
-        unsafe {
-            for row in 0..scroll_rows {
-                let src_row = origin_y + row + line_stride;
-                if src_row >= surface.height {
-                    break;
+        let pixels = surface.pixels();
+        for row in 0..scroll_rows {
+            let src_row = origin_y + row + line_stride;
+            if src_row >= visible_height {
+                break;
+            }
+            let dst_row = origin_y + row;
+            if surface.rotation == Rotation::Deg0 {
+                pixels.copy_within(
+                    surface.index(origin_x, src_row),
+                    surface.index(origin_x, dst_row),
+                    draw_width,
+                );
+            } else {
+                // Rotated text rows are not contiguous in memory.
+                for x in origin_x..origin_x + draw_width {
+                    pixels.write(
+                        surface.index(x, dst_row),
+                        pixels.read(surface.index(x, src_row)),
+                    );
                 }
-                let dst_row = origin_y + row;
-                let dst_ptr = surface.base_ptr.add(dst_row * pitch + origin_x);
-                let src_ptr = surface.base_ptr.add(src_row * pitch + origin_x);
-                ptr::copy(src_ptr, dst_ptr, draw_width);
             }
         }
 
-        let clear_height = line_stride.min(surface.height.saturating_sub(origin_y + scroll_rows));
+        let clear_height = line_stride.min(visible_height.saturating_sub(origin_y + scroll_rows));
         let _ = draw::fill_rect(
             surface,
             origin_x,
             origin_y + scroll_rows,
             draw_width,
             clear_height,
-            FramebufferColor::BLACK,
+            self.theme.background,
         );
     }
 }
@@ -213,6 +349,7 @@ impl fmt::Write for FramebufferConsole {
         if !self.viewport.is_usable() {
             return Err(fmt::Error);
         }
+        self.show_cursor(false);
 
         for byte in s.bytes() {
             self.put_byte(byte);
@@ -229,30 +366,45 @@ struct Cursor {
 }
 
 struct Viewport {
+    font: Font,
     origin_x: usize,
     origin_y: usize,
     cols: usize,
     rows: usize,
+    scale: usize,
+    cell_width: usize,
     line_stride: usize,
 }
 
 impl Viewport {
-    fn new(surface: FramebufferSurface, origin_x: usize, origin_y: usize) -> Self {
+    fn new(
+        surface: FramebufferSurface,
+        origin_x: usize,
+        origin_y: usize,
+        font: Font,
+        scale: usize,
+    ) -> Self {
+        let scale = scale.max(1);
         let width = surface.width.saturating_sub(origin_x);
         let height = surface.height.saturating_sub(origin_y);
-        let line_stride = FONT_HEIGHT + LINE_SPACING;
-        let cols = width / FONT_WIDTH;
-        let rows = if height < FONT_HEIGHT {
+        let cell_width = font.width * scale;
+        let glyph_height = font.height * scale;
+        let line_stride = (font.height + font.line_spacing) * scale;
+        let cols = width / cell_width;
+        let rows = if height < glyph_height {
             0
         } else {
-            ((height - FONT_HEIGHT) / line_stride) + 1
+            ((height - glyph_height) / line_stride) + 1
         };
 
         Self {
+            font,
             origin_x,
             origin_y,
             cols,
             rows,
+            scale,
+            cell_width,
             line_stride,
         }
     }
@@ -266,7 +418,7 @@ impl Viewport {
             return None;
         }
 
-        let x = self.origin_x + cursor.col * FONT_WIDTH;
+        let x = self.origin_x + cursor.col * self.cell_width;
         let y = self.origin_y + cursor.row * self.line_stride;
         Some((x, y))
     }
@@ -275,6 +427,7 @@ impl Viewport {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::framebuffer::{FONT_8X8, FONT_8X16};
     use oxide_abi::PixelFormat;
 
     #[test]
@@ -290,46 +443,147 @@ mod tests {
     fn viewport_new_calculates_geometry() {
         let surface = FramebufferSurface {
             base_ptr: core::ptr::null_mut(),
-            pitch: 200,
+            pixels_per_scanline: 200,
             width: 160,
             height: 60,
             pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
         };
-        let viewport = Viewport::new(surface, 0, 0);
-        assert_eq!(viewport.cols, 160 / FONT_WIDTH);
-        assert_eq!(viewport.line_stride, FONT_HEIGHT + LINE_SPACING);
+        let viewport = Viewport::new(surface, 0, 0, FONT_8X16, 1);
+        assert_eq!(viewport.cols, 160 / FONT_8X16.width);
+        assert_eq!(
+            viewport.line_stride,
+            FONT_8X16.height + FONT_8X16.line_spacing
+        );
         assert!(viewport.rows >= 1);
     }
 
+    #[test]
+    fn viewport_new_scales_geometry() {
+        let surface = FramebufferSurface {
+            base_ptr: core::ptr::null_mut(),
+            pixels_per_scanline: 3840,
+            width: 3840,
+            height: 2160,
+            pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
+        };
+        let plain = Viewport::new(surface, 0, 0, FONT_8X16, 1);
+        let scaled = Viewport::new(surface, 0, 0, FONT_8X16, 3);
+        assert_eq!(scaled.cols, 3840 / (FONT_8X16.width * 3));
+        assert_eq!(scaled.line_stride, plain.line_stride * 3);
+        assert_eq!(
+            scaled.rows,
+            (2160 - FONT_8X16.height * 3) / scaled.line_stride + 1
+        );
+        assert_eq!(
+            scaled.pixel_position(Cursor { col: 1, row: 1 }),
+            Some((FONT_8X16.width * 3, scaled.line_stride))
+        );
+    }
+
+    #[test]
+    fn viewport_8x8_fits_more_text() {
+        let surface = FramebufferSurface {
+            base_ptr: core::ptr::null_mut(),
+            pixels_per_scanline: 800,
+            width: 800,
+            height: 600,
+            pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
+        };
+        let tall = Viewport::new(surface, 0, 0, FONT_8X16, 1);
+        let small = Viewport::new(surface, 0, 0, FONT_8X8, 1);
+        assert_eq!(small.cols, tall.cols);
+        assert_eq!(small.line_stride, 10);
+        assert_eq!(small.rows, (600 - 8) / 10 + 1);
+        assert!(small.rows > tall.rows);
+    }
+
     #[test]
     fn viewport_pixel_position_within_bounds() {
         let surface = FramebufferSurface {
             base_ptr: core::ptr::null_mut(),
-            pitch: 200,
+            pixels_per_scanline: 200,
             width: 160,
             height: 80,
             pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
         };
-        let viewport = Viewport::new(surface, 10, 20);
+        let viewport = Viewport::new(surface, 10, 20, FONT_8X16, 1);
         let cursor = Cursor { col: 2, row: 1 };
-        let expected_x = 10 + 2 * FONT_WIDTH;
-        let expected_y = 20 + 1 * (FONT_HEIGHT + LINE_SPACING);
+        let expected_x = 10 + 2 * FONT_8X16.width;
+        let expected_y = 20 + 1 * (FONT_8X16.height + FONT_8X16.line_spacing);
         assert_eq!(
             viewport.pixel_position(cursor),
             Some((expected_x, expected_y))
         );
     }
 
+    #[test]
+    fn rotated_console_scrolls_on_a_padded_portrait_surface() {
+        // A 20×64 panel with padded scanlines, turned into a 64×20 surface:
+        // eight 8×8 columns and two rows.
+        let scanline = 24;
+        let mut backing = alloc::vec![0u32; scanline * 64];
+        let surface = FramebufferSurface {
+            base_ptr: backing.as_mut_ptr(),
+            pixels_per_scanline: scanline,
+            width: 20,
+            height: 64,
+            pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
+        }
+        .rotated(Rotation::Deg90);
+        let theme = super::super::theme();
+        let mut console = FramebufferConsole::with_surface(surface, 0, 0, theme, FONT_8X8, 1);
+        console.clear().unwrap();
+        assert_eq!((console.cols(), console.rows()), (8, 2));
+
+        console.write_bytes(b"AB\nCD\nEF").unwrap();
+        assert_eq!(console.screen_text(), ["CD", "EF"]);
+        // Nothing spills into the scanline padding.
+        assert!((0..64).all(|y| {
+            backing[y * scanline + 20..(y + 1) * scanline]
+                .iter()
+                .all(|&pixel| pixel == 0)
+        }));
+    }
+
+    #[test]
+    fn writing_erases_the_cursor_block_first() {
+        let mut backing = alloc::vec![0u32; 32 * 10];
+        let surface = FramebufferSurface {
+            base_ptr: backing.as_mut_ptr(),
+            pixels_per_scanline: 32,
+            width: 32,
+            height: 10,
+            pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
+        };
+        let theme = super::super::theme();
+        let mut console = FramebufferConsole::with_surface(surface, 0, 0, theme, FONT_8X8, 1);
+        console.clear().unwrap();
+
+        console.write_bytes(b"A").unwrap();
+        console.show_cursor(true);
+        assert_eq!(console.screen_text(), ["A?"]);
+
+        console.write_bytes(b"B").unwrap();
+        assert_eq!(console.screen_text(), ["AB"]);
+    }
+
     #[test]
     fn viewport_pixel_position_out_of_bounds_returns_none() {
         let surface = FramebufferSurface {
             base_ptr: core::ptr::null_mut(),
-            pitch: 40,
+            pixels_per_scanline: 40,
             width: 80,
             height: 40,
             pixel_format: PixelFormat::Rgb,
+            rotation: Rotation::Deg0,
         };
-        let viewport = Viewport::new(surface, 0, 0);
+        let viewport = Viewport::new(surface, 0, 0, FONT_8X16, 1);
         let cursor = Cursor {
             col: viewport.cols,
             row: 0,
@@ -0,0 +1,67 @@
+//! Console colour themes selected with `console_theme=`.
+
+use super::FramebufferColor;
+
+/// Colours used by the text console.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Theme {
+    /// Screen fill behind the text, also used when clearing and scrolling.
+    pub background: FramebufferColor,
+    /// Ordinary output (`print!`/`println!`).
+    pub foreground: FramebufferColor,
+    /// Diagnostic output (`diag!`/`debug!`), dimmer than ordinary text.
+    pub diagnostic: FramebufferColor,
+}
+
+impl Theme {
+    /// Light grey on black; the default.
+    pub const DARK: Self = Self {
+        background: FramebufferColor::BLACK,
+        foreground: FramebufferColor::new(0xD0, 0xD0, 0xD0),
+        diagnostic: FramebufferColor::new(0x80, 0x80, 0x80),
+    };
+
+    /// Black on white, for photos of the screen.
+    pub const LIGHT: Self = Self {
+        background: FramebufferColor::WHITE,
+        foreground: FramebufferColor::BLACK,
+        diagnostic: FramebufferColor::new(0x60, 0x60, 0x60),
+    };
+
+    /// Phosphor green on black.
+    pub const GREEN: Self = Self {
+        background: FramebufferColor::BLACK,
+        foreground: FramebufferColor::new(0x33, 0xFF, 0x66),
+        diagnostic: FramebufferColor::new(0x1A, 0x99, 0x3D),
+    };
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::DARK),
+            "light" => Some(Self::LIGHT),
+            "green" => Some(Self::GREEN),
+            _ => None,
+        }
+    }
+}
+
+/// Theme requested with `console_theme=<dark|light|green>`, or dark when
+/// absent or unknown.
+pub fn theme() -> Theme {
+    crate::options::value("console_theme")
+        .and_then(Theme::parse)
+        .unwrap_or(Theme::DARK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_known_themes() {
+        assert_eq!(Theme::parse("light"), Some(Theme::LIGHT));
+        assert_eq!(Theme::parse("green"), Some(Theme::GREEN));
+        assert_eq!(Theme::parse("dark"), Some(Theme::DARK));
+        assert_eq!(Theme::parse("Light"), None);
+    }
+}
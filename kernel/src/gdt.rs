@@ -0,0 +1,193 @@
+//! The kernel's own GDT and TSS.
+//!
+//! Until [`init`] runs the kernel executes on the firmware's GDT, with no TSS
+//! and therefore no interrupt stack table: a double fault caused by a bad
+//! stack pushes its frame onto that same stack and becomes a triple fault.
+//! [`init`] loads a flat GDT (null, 64-bit code, data, TSS) and a TSS whose
+//! IST slots point at dedicated stacks for double faults and NMIs, so those
+//! handlers always start on known-good memory.
+
+use core::{
+    arch::asm,
+    cell::UnsafeCell,
+    mem::size_of,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Selector of the 64-bit ring-0 code segment.
+pub const KERNEL_CODE: u16 = 0x08;
+/// Selector of the ring-0 data segment.
+pub const KERNEL_DATA: u16 = 0x10;
+/// Selector of the TSS descriptor (two GDT slots).
+const TSS_SELECTOR: u16 = 0x18;
+
+/// IST slot (1-based, as `GateOptions::with_ist` takes it) for double faults.
+pub const DOUBLE_FAULT_IST: u8 = 1;
+/// IST slot for NMIs.
+pub const NMI_IST: u8 = 2;
+
+/// Long-mode code: present, ring 0, executable, readable, L=1.
+const CODE_DESCRIPTOR: u64 = 0x00AF_9A00_0000_FFFF;
+/// Data: present, ring 0, writable, 4 KiB granularity.
+const DATA_DESCRIPTOR: u64 = 0x00CF_9200_0000_FFFF;
+/// Available 64-bit TSS, present, ring 0.
+const TSS_TYPE_PRESENT: u64 = 0x89 << 40;
+
+const GDT_ENTRIES: usize = 5;
+
+/// The double-fault report draws the crash screen, so it gets the larger stack.
+const DOUBLE_FAULT_STACK_BYTES: usize = 32 * 1024;
+const NMI_STACK_BYTES: usize = 16 * 1024;
+
+static LOADED: AtomicBool = AtomicBool::new(false);
+
+/// The 64-bit task-state segment; only the IST slots are used.
+#[repr(C, packed(4))]
+struct Tss {
+    _reserved0: u32,
+    rsp: [u64; 3],
+    _reserved1: u64,
+    ist: [u64; 7],
+    _reserved2: u64,
+    _reserved3: u16,
+    iomap_base: u16,
+}
+
+const _: () = assert!(size_of::<Tss>() == 104);
+
+#[repr(C, align(16))]
+struct Stack<const N: usize>([u8; N]);
+
+/// Memory the CPU writes to (the TSS busy bit, the IST stacks); kept out of
+/// read-only data and only touched by [`init`] before it is loaded.
+struct CpuTables {
+    gdt: UnsafeCell<[u64; GDT_ENTRIES]>,
+    tss: UnsafeCell<Tss>,
+    double_fault_stack: UnsafeCell<Stack<DOUBLE_FAULT_STACK_BYTES>>,
+    nmi_stack: UnsafeCell<Stack<NMI_STACK_BYTES>>,
+}
+
+unsafe impl Sync for CpuTables {}
+
+static TABLES: CpuTables = CpuTables {
+    gdt: UnsafeCell::new([0; GDT_ENTRIES]),
+    tss: UnsafeCell::new(Tss {
+        _reserved0: 0,
+        rsp: [0; 3],
+        _reserved1: 0,
+        ist: [0; 7],
+        _reserved2: 0,
+        _reserved3: 0,
+        // Past the TSS limit: no I/O permission bitmap.
+        iomap_base: size_of::<Tss>() as u16,
+    }),
+    double_fault_stack: UnsafeCell::new(Stack([0; DOUBLE_FAULT_STACK_BYTES])),
+    nmi_stack: UnsafeCell::new(Stack([0; NMI_STACK_BYTES])),
+};
+
+#[repr(C, packed)]
+struct GdtPointer {
+    limit: u16,
+    base: u64,
+}
+
+/// The two GDT slots describing a 64-bit TSS at `base` with byte `limit`.
+fn tss_descriptor(base: u64, limit: u32) -> [u64; 2] {
+    let low = u64::from(limit & 0xFFFF)
+        | (base & 0xFF_FFFF) << 16
+        | TSS_TYPE_PRESENT
+        | u64::from((limit >> 16) & 0xF) << 48
+        | ((base >> 24) & 0xFF) << 56;
+    [low, base >> 32]
+}
+
+/// Load the kernel GDT and TSS on the bootstrap CPU and switch every segment
+/// register to it. Runs once; later calls do nothing.
+pub fn init() {
+    if LOADED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    // SAFETY: single-threaded boot with interrupts off; nothing else holds a
+    // reference into `TABLES`, and it is not loaded yet.
+    unsafe {
+        let tss = &mut *TABLES.tss.get();
+        tss.ist[usize::from(DOUBLE_FAULT_IST - 1)] = stack_top(
+            TABLES.double_fault_stack.get().cast(),
+            DOUBLE_FAULT_STACK_BYTES,
+        );
+        tss.ist[usize::from(NMI_IST - 1)] =
+            stack_top(TABLES.nmi_stack.get().cast(), NMI_STACK_BYTES);
+
+        let gdt = &mut *TABLES.gdt.get();
+        let [tss_low, tss_high] =
+            tss_descriptor(TABLES.tss.get() as u64, size_of::<Tss>() as u32 - 1);
+        *gdt = [0, CODE_DESCRIPTOR, DATA_DESCRIPTOR, tss_low, tss_high];
+
+        load(gdt);
+    }
+    crate::diagln!(
+        "GDT and TSS loaded: IST{} double fault ({} KiB), IST{} NMI ({} KiB)",
+        DOUBLE_FAULT_IST,
+        DOUBLE_FAULT_STACK_BYTES / 1024,
+        NMI_IST,
+        NMI_STACK_BYTES / 1024
+    );
+}
+
+/// Whether the kernel's TSS, and with it the IST stacks, is loaded.
+pub fn is_loaded() -> bool {
+    LOADED.load(Ordering::Acquire)
+}
+
+fn stack_top(base: *const u8, bytes: usize) -> u64 {
+    base as u64 + bytes as u64
+}
+
+/// # Safety
+/// `gdt` must stay valid and unchanged for as long as the CPU uses it.
+unsafe fn load(gdt: &[u64; GDT_ENTRIES]) {
+    let pointer = GdtPointer {
+        limit: (size_of::<[u64; GDT_ENTRIES]>() - 1) as u16,
+        base: gdt.as_ptr() as u64,
+    };
+    // SAFETY: the GDT holds flat code and data descriptors matching the
+    // current mode; the far return reloads CS with the new code selector.
+    unsafe {
+        asm!(
+            "lgdt [{pointer}]",
+            "push {code}",
+            "lea {tmp}, [rip + 2f]",
+            "push {tmp}",
+            "retfq",
+            "2:",
+            "mov ds, {data:x}",
+            "mov es, {data:x}",
+            "mov ss, {data:x}",
+            "xor {tmp:e}, {tmp:e}",
+            "mov fs, {tmp:x}",
+            "mov gs, {tmp:x}",
+            "ltr {tss:x}",
+            pointer = in(reg) &pointer,
+            code = in(reg) u64::from(KERNEL_CODE),
+            data = in(reg) u64::from(KERNEL_DATA),
+            tss = in(reg) u64::from(TSS_SELECTOR),
+            tmp = out(reg) _,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tss_descriptor_splits_base_and_limit() {
+        let [low, high] = tss_descriptor(0xFFFF_8000_1234_5678, 103);
+        assert_eq!(low & 0xFFFF, 103);
+        assert_eq!((low >> 16) & 0xFF_FFFF, 0x34_5678);
+        assert_eq!((low >> 40) & 0xFF, 0x89);
+        assert_eq!(low >> 56, 0x12);
+        assert_eq!(high, 0xFFFF_8000);
+    }
+}
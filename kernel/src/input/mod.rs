@@ -0,0 +1,83 @@
+//! Keyboard input from the PS/2 controller.
+//!
+//! IRQ 1 runs [`keyboard_irq`], which reads the scancode, decodes it with
+//! the set 1 [`Decoder`], and pushes each key press onto a lock-free queue.
+//! [`read_key`] takes presses off the queue without blocking, and
+//! [`next_key`] lets a `ktask` wait for one.
+//!
+//! Keyboard input is inactive for now: the IRQ handlers are plain
+//! `extern "C"` functions that end in `ret` rather than `iretq`, so interrupts
+//! stay disabled and no key arrives until the handlers have proper entry
+//! stubs that save the interrupted context.
+
+use core::cell::UnsafeCell;
+
+use crate::{drivers::pic8259, ktask::TaskEvent};
+
+mod ps2;
+mod queue;
+mod scancode;
+
+pub use ps2::Ps2Error;
+pub use scancode::{Decoder, Key, KeyEvent, Modifiers};
+
+/// Legacy IRQ of the PS/2 keyboard port.
+const KEYBOARD_IRQ: u8 = 1;
+
+static KEYS: queue::KeyQueue = queue::KeyQueue::new();
+/// Signalled whenever a key is queued.
+static KEY_READY: TaskEvent = TaskEvent::new();
+
+/// Decoder state, touched only by [`keyboard_irq`].
+struct DecoderCell(UnsafeCell<Decoder>);
+
+// SAFETY: the keyboard handler is the only user and interrupt gates do not
+// nest it.
+unsafe impl Sync for DecoderCell {}
+
+static DECODER: DecoderCell = DecoderCell(UnsafeCell::new(Decoder::new()));
+
+/// Set up the keyboard port and unmask IRQ 1. Call after `interrupts::init`
+/// has installed the handler.
+pub fn init() -> Result<(), Ps2Error> {
+    ps2::init()?;
+    pic8259::unmask(KEYBOARD_IRQ);
+    crate::diagln!("input: PS/2 keyboard on IRQ {}", KEYBOARD_IRQ);
+    Ok(())
+}
+
+/// Decode the keyboard's pending byte and queue the key it completes.
+/// Called from the IRQ 1 handler.
+pub fn keyboard_irq() {
+    let Some(byte) = ps2::read_scancode() else {
+        return;
+    };
+    // SAFETY: see `DecoderCell`.
+    let decoder = unsafe { &mut *DECODER.0.get() };
+    let Some(event) = decoder.feed(byte) else {
+        return;
+    };
+    if KEYS.push(event) {
+        KEY_READY.signal();
+    }
+}
+
+/// The oldest key press not yet read, if any.
+pub fn read_key() -> Option<KeyEvent> {
+    KEYS.pop()
+}
+
+/// Wait, as a `ktask`, for the next key press.
+pub async fn next_key() -> KeyEvent {
+    loop {
+        if let Some(event) = read_key() {
+            return event;
+        }
+        KEY_READY.wait().await;
+    }
+}
+
+/// Key presses lost because nobody read the queue in time.
+pub fn dropped_keys() -> u32 {
+    KEYS.dropped()
+}
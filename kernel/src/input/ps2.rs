@@ -0,0 +1,112 @@
+//! The 8042 PS/2 controller's first (keyboard) port.
+//!
+//! Firmware has normally initialised the controller already; [`init`] only
+//! makes sure the keyboard port is clocked, raises IRQ 1, and translates to
+//! scancode set 1, then discards whatever bytes were left in the buffer.
+
+use crate::port::{inb, outb};
+
+const DATA: u16 = 0x60;
+/// Reads return the status register; writes send a controller command.
+const STATUS_COMMAND: u16 = 0x64;
+
+const STATUS_OUTPUT_FULL: u8 = 1 << 0;
+const STATUS_INPUT_FULL: u8 = 1 << 1;
+/// The byte in the output buffer came from the second (mouse) port.
+const STATUS_AUX: u8 = 1 << 5;
+
+const READ_CONFIG: u8 = 0x20;
+const WRITE_CONFIG: u8 = 0x60;
+const CONFIG_PORT1_IRQ: u8 = 1 << 0;
+const CONFIG_PORT1_CLOCK_OFF: u8 = 1 << 4;
+const CONFIG_TRANSLATE: u8 = 1 << 6;
+
+/// Polls before a controller handshake is given up.
+const SPIN_LIMIT: u32 = 100_000;
+/// Stale bytes drained at init; the controller buffers far fewer.
+const DRAIN_LIMIT: usize = 32;
+
+/// Why the keyboard port could not be set up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ps2Error {
+    /// The status port floats high: no controller behind it.
+    NoController,
+    /// The controller stopped answering mid-command.
+    Timeout,
+}
+
+/// Enable IRQ 1 and set 1 translation on the keyboard port.
+pub(super) fn init() -> Result<(), Ps2Error> {
+    // SAFETY: reading the 8042 status register has no side effects.
+    if unsafe { inb(STATUS_COMMAND) } == 0xFF {
+        return Err(Ps2Error::NoController);
+    }
+    drain();
+
+    command(READ_CONFIG)?;
+    let config = read_data()?;
+    let config = (config | CONFIG_PORT1_IRQ | CONFIG_TRANSLATE) & !CONFIG_PORT1_CLOCK_OFF;
+    command(WRITE_CONFIG)?;
+    write_data(config)?;
+
+    drain();
+    Ok(())
+}
+
+/// The pending keyboard byte, if the controller has one. Mouse bytes are
+/// read and discarded so they cannot block the buffer.
+pub(super) fn read_scancode() -> Option<u8> {
+    // SAFETY: status reads are side-effect free; a data read only consumes
+    // the byte the status register just announced.
+    unsafe {
+        let status = inb(STATUS_COMMAND);
+        if status & STATUS_OUTPUT_FULL == 0 {
+            return None;
+        }
+        let byte = inb(DATA);
+        (status & STATUS_AUX == 0).then_some(byte)
+    }
+}
+
+fn drain() {
+    for _ in 0..DRAIN_LIMIT {
+        // SAFETY: as in `read_scancode`.
+        unsafe {
+            if inb(STATUS_COMMAND) & STATUS_OUTPUT_FULL == 0 {
+                return;
+            }
+            inb(DATA);
+        }
+    }
+}
+
+fn wait_for(ready: impl Fn(u8) -> bool) -> Result<(), Ps2Error> {
+    for _ in 0..SPIN_LIMIT {
+        // SAFETY: status reads are side-effect free.
+        if ready(unsafe { inb(STATUS_COMMAND) }) {
+            return Ok(());
+        }
+        core::hint::spin_loop();
+    }
+    Err(Ps2Error::Timeout)
+}
+
+fn command(byte: u8) -> Result<(), Ps2Error> {
+    wait_for(|status| status & STATUS_INPUT_FULL == 0)?;
+    // SAFETY: the controller is ready for a command byte.
+    unsafe { outb(STATUS_COMMAND, byte) };
+    Ok(())
+}
+
+fn write_data(byte: u8) -> Result<(), Ps2Error> {
+    wait_for(|status| status & STATUS_INPUT_FULL == 0)?;
+    // SAFETY: the controller is ready for the command's data byte.
+    unsafe { outb(DATA, byte) };
+    Ok(())
+}
+
+fn read_data() -> Result<u8, Ps2Error> {
+    wait_for(|status| status & STATUS_OUTPUT_FULL != 0)?;
+    // SAFETY: the controller announced a response byte.
+    Ok(unsafe { inb(DATA) })
+}
@@ -0,0 +1,102 @@
+//! Lock-free queue from the keyboard IRQ handler to readers.
+//!
+//! One producer (the IRQ handler) and one consumer ([`super::read_key`]):
+//! the producer owns `tail`, the consumer owns `head`, and each publishes its
+//! index with release ordering after touching the slot, so neither side ever
+//! waits for the other. Keys that arrive while the queue is full are dropped
+//! and counted.
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+};
+
+use super::KeyEvent;
+
+/// Slots in the queue; a power of two so the indices can wrap freely.
+const CAPACITY: usize = 64;
+
+pub(super) struct KeyQueue {
+    slots: UnsafeCell<[Option<KeyEvent>; CAPACITY]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    dropped: AtomicU32,
+}
+
+// SAFETY: a slot is only written by the producer while it lies outside
+// `head..tail`, and only read by the consumer while it lies inside.
+unsafe impl Sync for KeyQueue {}
+
+impl KeyQueue {
+    pub(super) const fn new() -> Self {
+        Self {
+            slots: UnsafeCell::new([None; CAPACITY]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicU32::new(0),
+        }
+    }
+
+    /// Append `event`; producer side only. Returns `false` if it was dropped.
+    pub(super) fn push(&self, event: KeyEvent) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(self.head.load(Ordering::Acquire)) == CAPACITY {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        // SAFETY: the slot at `tail` is outside `head..tail`, so the consumer
+        // is not reading it.
+        unsafe { (*self.slots.get())[tail % CAPACITY] = Some(event) };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Take the oldest event; consumer side only.
+    pub(super) fn pop(&self) -> Option<KeyEvent> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: the slot at `head` is inside `head..tail`, so the producer
+        // has published it and will not touch it until `head` moves on.
+        let event = unsafe { (*self.slots.get())[head % CAPACITY] };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        event
+    }
+
+    /// Keys dropped because the queue was full.
+    pub(super) fn dropped(&self) -> u32 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Key, Modifiers};
+
+    fn key(byte: u8) -> KeyEvent {
+        KeyEvent {
+            key: Key::Char(byte),
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    #[test]
+    fn queue_is_fifo_and_drops_when_full() {
+        let queue = KeyQueue::new();
+        for byte in 0..CAPACITY as u8 {
+            assert!(queue.push(key(byte)));
+        }
+        assert!(!queue.push(key(b'!')));
+        assert_eq!(queue.dropped(), 1);
+
+        assert_eq!(queue.pop(), Some(key(0)));
+        assert!(queue.push(key(b'z')));
+        for byte in 1..CAPACITY as u8 {
+            assert_eq!(queue.pop(), Some(key(byte)));
+        }
+        assert_eq!(queue.pop(), Some(key(b'z')));
+        assert_eq!(queue.pop(), None);
+    }
+}
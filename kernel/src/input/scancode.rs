@@ -0,0 +1,287 @@
+//! Scancode set 1 decoding.
+//!
+//! The PS/2 controller translates whatever the keyboard speaks into set 1,
+//! so one byte per make code (release is the same code with bit 7 set),
+//! `0xE0` before the extended keys, and the six-byte `0xE1` sequence for
+//! Pause. [`Decoder`] tracks the modifiers and turns key presses into
+//! [`KeyEvent`]s; releases only update modifier state.
+
+const RELEASE: u8 = 0x80;
+const EXTENDED: u8 = 0xE0;
+/// Prefix of Pause, which sends `E1 1D 45` on press and `E1 9D C5` on release.
+const PAUSE: u8 = 0xE1;
+const PAUSE_TAIL: u8 = 2;
+
+const LEFT_SHIFT: u8 = 0x2A;
+const RIGHT_SHIFT: u8 = 0x36;
+const CTRL: u8 = 0x1D;
+const ALT: u8 = 0x38;
+const CAPS_LOCK: u8 = 0x3A;
+
+/// ASCII for make codes `0x00..=0x53` without and with Shift; zero where the
+/// key does not type a character.
+#[rustfmt::skip]
+const UNSHIFTED: [u8; 0x54] = [
+    0, 0, b'1', b'2', b'3', b'4', b'5', b'6', b'7', b'8', b'9', b'0', b'-', b'=', 0, 0,
+    b'q', b'w', b'e', b'r', b't', b'y', b'u', b'i', b'o', b'p', b'[', b']', 0, 0, b'a', b's',
+    b'd', b'f', b'g', b'h', b'j', b'k', b'l', b';', b'\'', b'`', 0, b'\\', b'z', b'x', b'c', b'v',
+    b'b', b'n', b'm', b',', b'.', b'/', 0, b'*', 0, b' ', 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, b'7', b'8', b'9', b'-', b'4', b'5', b'6', b'+', b'1',
+    b'2', b'3', b'0', b'.',
+];
+#[rustfmt::skip]
+const SHIFTED: [u8; 0x54] = [
+    0, 0, b'!', b'@', b'#', b'$', b'%', b'^', b'&', b'*', b'(', b')', b'_', b'+', 0, 0,
+    b'Q', b'W', b'E', b'R', b'T', b'Y', b'U', b'I', b'O', b'P', b'{', b'}', 0, 0, b'A', b'S',
+    b'D', b'F', b'G', b'H', b'J', b'K', b'L', b':', b'"', b'~', 0, b'|', b'Z', b'X', b'C', b'V',
+    b'B', b'N', b'M', b'<', b'>', b'?', 0, b'*', 0, b' ', 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, b'7', b'8', b'9', b'-', b'4', b'5', b'6', b'+', b'1',
+    b'2', b'3', b'0', b'.',
+];
+
+/// A key, after Shift and Caps Lock have been applied to characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    /// A printable ASCII character.
+    Char(u8),
+    Enter,
+    Backspace,
+    Tab,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    /// Function key F1–F12.
+    Function(u8),
+}
+
+/// Modifiers held when a key was pressed; left and right count alike.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+/// One key press (or typematic repeat).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+/// Set 1 decoder state carried between bytes.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    extended: bool,
+    skip: u8,
+    left_shift: bool,
+    right_shift: bool,
+    left_ctrl: bool,
+    right_ctrl: bool,
+    left_alt: bool,
+    right_alt: bool,
+    caps_lock: bool,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            extended: false,
+            skip: 0,
+            left_shift: false,
+            right_shift: false,
+            left_ctrl: false,
+            right_ctrl: false,
+            left_alt: false,
+            right_alt: false,
+            caps_lock: false,
+        }
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            shift: self.left_shift || self.right_shift,
+            ctrl: self.left_ctrl || self.right_ctrl,
+            alt: self.left_alt || self.right_alt,
+        }
+    }
+
+    /// Feed one byte from the controller; returns the key it completes, if
+    /// it completes a press.
+    pub fn feed(&mut self, byte: u8) -> Option<KeyEvent> {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+        match byte {
+            EXTENDED => {
+                self.extended = true;
+                return None;
+            }
+            PAUSE => {
+                self.skip = PAUSE_TAIL;
+                return None;
+            }
+            _ => {}
+        }
+
+        let extended = core::mem::take(&mut self.extended);
+        let pressed = byte & RELEASE == 0;
+        let code = byte & !RELEASE;
+
+        // Modifier keys change state and are not reported themselves.
+        let held = match (extended, code) {
+            (false, LEFT_SHIFT) => Some(&mut self.left_shift),
+            (false, RIGHT_SHIFT) => Some(&mut self.right_shift),
+            (false, CTRL) => Some(&mut self.left_ctrl),
+            (true, CTRL) => Some(&mut self.right_ctrl),
+            (false, ALT) => Some(&mut self.left_alt),
+            (true, ALT) => Some(&mut self.right_alt),
+            // Print Screen wraps itself in fake Shift codes; ignore them.
+            (true, LEFT_SHIFT | RIGHT_SHIFT) => return None,
+            _ => None,
+        };
+        if let Some(held) = held {
+            *held = pressed;
+            return None;
+        }
+        if !pressed {
+            return None;
+        }
+        if !extended && code == CAPS_LOCK {
+            self.caps_lock = !self.caps_lock;
+            return None;
+        }
+
+        let key = if extended {
+            extended_key(code)?
+        } else {
+            self.plain_key(code)?
+        };
+        Some(KeyEvent {
+            key,
+            modifiers: self.modifiers(),
+        })
+    }
+
+    fn plain_key(&self, code: u8) -> Option<Key> {
+        match code {
+            0x01 => return Some(Key::Escape),
+            0x0E => return Some(Key::Backspace),
+            0x0F => return Some(Key::Tab),
+            0x1C => return Some(Key::Enter),
+            0x3B..=0x44 => return Some(Key::Function(code - 0x3B + 1)),
+            0x57 => return Some(Key::Function(11)),
+            0x58 => return Some(Key::Function(12)),
+            _ => {}
+        }
+
+        let index = usize::from(code);
+        let plain = *UNSHIFTED.get(index)?;
+        if plain == 0 {
+            return None;
+        }
+        // Caps Lock inverts Shift for letters only.
+        let shift = self.modifiers().shift ^ (self.caps_lock && plain.is_ascii_lowercase());
+        Some(Key::Char(if shift { SHIFTED[index] } else { plain }))
+    }
+}
+
+fn extended_key(code: u8) -> Option<Key> {
+    Some(match code {
+        0x1C => Key::Enter,
+        0x35 => Key::Char(b'/'),
+        0x47 => Key::Home,
+        0x48 => Key::Up,
+        0x49 => Key::PageUp,
+        0x4B => Key::Left,
+        0x4D => Key::Right,
+        0x4F => Key::End,
+        0x50 => Key::Down,
+        0x51 => Key::PageDown,
+        0x52 => Key::Insert,
+        0x53 => Key::Delete,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(decoder: &mut Decoder, bytes: &[u8]) -> [Option<KeyEvent>; 8] {
+        let mut out = [None; 8];
+        for (slot, &byte) in out.iter_mut().zip(bytes) {
+            *slot = decoder.feed(byte);
+        }
+        out
+    }
+
+    #[test]
+    fn shift_caps_and_ctrl_shape_characters() {
+        let mut decoder = Decoder::new();
+        let plain = Modifiers::default();
+        // a, Shift+a, Caps Lock then a and Shift+a.
+        let out = keys(
+            &mut decoder,
+            &[0x1E, 0x9E, 0x2A, 0x1E, 0xAA, 0x3A, 0x1E, 0xBA],
+        );
+        assert_eq!(out[0].map(|event| event.key), Some(Key::Char(b'a')));
+        assert_eq!(out[1], None);
+        assert_eq!(
+            out[3],
+            Some(KeyEvent {
+                key: Key::Char(b'A'),
+                modifiers: Modifiers {
+                    shift: true,
+                    ..plain
+                }
+            })
+        );
+        assert_eq!(out[6].map(|event| event.key), Some(Key::Char(b'A')));
+
+        // Right Ctrl (extended) + c with Caps Lock still on; digits ignore it.
+        let out = keys(&mut decoder, &[0xE0, 0x1D, 0x2E, 0xE0, 0x9D, 0x02]);
+        assert_eq!(
+            out[2],
+            Some(KeyEvent {
+                key: Key::Char(b'C'),
+                modifiers: Modifiers {
+                    ctrl: true,
+                    ..plain
+                }
+            })
+        );
+        assert_eq!(out[5].map(|event| event.key), Some(Key::Char(b'1')));
+    }
+
+    #[test]
+    fn extended_keys_and_pause_decode() {
+        let mut decoder = Decoder::new();
+        // Up, Pause, a fake Shift from Print Screen, F12.
+        let out = keys(
+            &mut decoder,
+            &[0xE0, 0x48, 0xE1, 0x1D, 0x45, 0xE0, 0x2A, 0x58],
+        );
+        assert_eq!(out[1].map(|event| event.key), Some(Key::Up));
+        assert!(out[2..7].iter().all(Option::is_none));
+        assert_eq!(out[7].map(|event| event.key), Some(Key::Function(12)));
+        assert!(!decoder.modifiers().shift);
+
+        // Keypad 7 without the prefix, Home with it.
+        assert_eq!(
+            decoder.feed(0x47).map(|event| event.key),
+            Some(Key::Char(b'7'))
+        );
+        decoder.feed(0xE0);
+        assert_eq!(decoder.feed(0x47).map(|event| event.key), Some(Key::Home));
+    }
+}
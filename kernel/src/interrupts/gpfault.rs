@@ -0,0 +1,77 @@
+//! The general-protection entry stub and recoverable MSR reads.
+//!
+//! Like page faults, #GP gets a naked stub so the crash screen can show the
+//! frame. The stub also recovers from one kind of #GP: a `rdmsr` issued by
+//! [`rdmsr_safe`] for an MSR the CPU (or hypervisor) does not implement.
+//! While such a read is in flight a flag is set; a #GP that finds it set
+//! clears it, skips the two-byte `rdmsr`, and returns.
+
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::FaultFrame;
+
+/// Set once the stub is in the loaded IDT; before that a #GP would reach
+/// the firmware's handler.
+static ARMED: AtomicBool = AtomicBool::new(false);
+/// Set around the `rdmsr` in [`rdmsr_safe`]; cleared by the stub if it faults.
+static PROBING: AtomicBool = AtomicBool::new(false);
+
+pub(super) fn arm() {
+    ARMED.store(true, Ordering::Release);
+}
+
+/// IDT entry for vector 0x0D.
+#[unsafe(naked)]
+pub(super) extern "C" fn entry() {
+    core::arch::naked_asm!(
+        "cmp byte ptr [rip + {probing}], 0",
+        "je 2f",
+        "mov byte ptr [rip + {probing}], 0",
+        // Resume after the `rdmsr` and drop the error code.
+        "add qword ptr [rsp + 8], 2",
+        "add rsp, 8",
+        "iretq",
+        "2:",
+        "mov rdi, rsp",
+        "and rsp, -16",
+        "call {report}",
+        "ud2",
+        probing = sym PROBING,
+        report = sym report,
+    );
+}
+
+extern "C" fn report(frame: &FaultFrame) -> ! {
+    super::report_fatal_trap_with_frame("General Protection Fault", 0x0D, Some(frame));
+    super::halt_cpu();
+}
+
+/// Read `msr`, or `None` if the CPU raised #GP because it does not
+/// implement it. Always `None` before `interrupts::init` has loaded the
+/// kernel's IDT.
+pub fn rdmsr_safe(msr: u32) -> Option<u64> {
+    if !ARMED.load(Ordering::Acquire) {
+        return None;
+    }
+    super::without_interrupts(|| {
+        PROBING.store(true, Ordering::SeqCst);
+        let (high, low): (u32, u32);
+        // SAFETY: a #GP from this `rdmsr` is caught by `entry` above; not
+        // `nomem`, since the stub writes `PROBING`.
+        unsafe {
+            asm!(
+                "rdmsr",
+                in("ecx") msr,
+                out("eax") low,
+                out("edx") high,
+                options(nostack, preserves_flags),
+            );
+        }
+        PROBING
+            .swap(false, Ordering::SeqCst)
+            .then_some(((high as u64) << 32) | low as u64)
+    })
+}
@@ -1,8 +1,23 @@
 //! Interrupt Descriptor Table setup and gate management primitives.
 //!
 use core::cell::UnsafeCell;
-use core::sync::atomic::{AtomicBool, Ordering};
-use core::{arch::asm, mem::size_of};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use core::{
+    arch::asm,
+    mem::{align_of, offset_of, size_of},
+};
+
+mod gpfault;
+mod pagefault;
+mod readiness;
+mod shadow;
+
+use crate::{drivers::pic8259, gdt};
+
+pub use gpfault::rdmsr_safe;
+pub use pagefault::{FaultFrame, PageFaultCode};
+pub use readiness::{Readiness, enable};
+pub use shadow::{TableCheck, verify_tables};
 
 /// Total number of entries supported by the Interrupt Descriptor Table.
 const IDT_ENTRIES: usize = 256;
@@ -43,12 +58,51 @@ pub enum InterruptInitError {
     AlreadyInitialized,
 }
 
+/// Errors that can occur while changing gates in the live IDT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateUpdateError {
+    /// `init` has not configured the IDT yet.
+    NotInitialized,
+}
+
+/// Install `handler` as a ring-0 interrupt gate for `vector` in the live IDT.
+///
+/// Safe to call after `init` while interrupts may be enabled: the entry is
+/// rewritten with its present bit clear and only marked present once complete,
+/// so no CPU can dispatch through a half-written gate. The IDTR is unchanged,
+/// and CPUs do not cache gate contents, so no reload is needed.
+pub fn set_handler(vector: u8, handler: InterruptHandler) -> Result<(), GateUpdateError> {
+    let entry = IdtEntry::new(handler.address(), read_cs(), GateOptions::interrupt());
+    update_live_gate(vector, entry)
+}
+
+/// Remove the handler for `vector` from the live IDT, leaving the gate not present.
+pub fn clear_handler(vector: u8) -> Result<(), GateUpdateError> {
+    update_live_gate(vector, IdtEntry::missing())
+}
+
+fn update_live_gate(vector: u8, entry: IdtEntry) -> Result<(), GateUpdateError> {
+    if !IDT_CONFIGURED.load(Ordering::Acquire) {
+        return Err(GateUpdateError::NotInitialized);
+    }
+
+    without_interrupts(|| unsafe {
+        IDT_STORAGE.with_mut(|idt| idt.write_gate(vector, entry));
+        shadow::record_gate(vector, entry);
+    });
+    Ok(())
+}
+
 /// Prepare and load the Interrupt Descriptor Table for the calling CPU.
 ///
 /// The IDT entries are configured exactly once (on the first caller) and the
 /// finalised table is then loaded for every core that invokes this routine.
 /// The optional `core_index` allows the caller to log which CPU performed the
 /// load; pass `None` when initialising from the bootstrap processor.
+///
+/// The first caller also remaps and masks the legacy PICs before the IDT is
+/// loaded, so no IRQ can arrive on an exception vector whoever brings
+/// interrupts up.
 pub fn init(core_index: Option<usize>) -> Result<(), InterruptInitError> {
     let code_selector = read_cs();
 
@@ -56,6 +110,10 @@ pub fn init(core_index: Option<usize>) -> Result<(), InterruptInitError> {
         .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
         .is_ok();
 
+    if first_config {
+        pic8259::init();
+    }
+
     unsafe {
         if first_config {
             IDT_STORAGE.with_mut(|idt| {
@@ -64,7 +122,11 @@ pub fn init(core_index: Option<usize>) -> Result<(), InterruptInitError> {
             });
         }
         IDT_STORAGE.load();
+        if first_config {
+            IDT_STORAGE.with_mut(|idt| shadow::capture(idt));
+        }
     }
+    gpfault::arm();
 
     log_installation(first_config, core_index);
 
@@ -95,6 +157,23 @@ impl Idt {
         self.entries[vector as usize] = IdtEntry::missing();
     }
 
+    /// Replace an entry that a CPU may be dispatching through.
+    ///
+    /// Entries are 16-byte aligned, so each half is written with one atomic
+    /// store: first the low half with the present bit clear, then the high
+    /// half, then the low half again with the requested present bit.
+    fn write_gate(&mut self, vector: u8, entry: IdtEntry) {
+        let (low, high) = entry.to_words();
+        let slot = (&mut self.entries[vector as usize] as *mut IdtEntry).cast::<u64>();
+
+        // SAFETY: `slot` points at a 16-byte, 16-byte-aligned entry we own.
+        unsafe {
+            slot.write_volatile(low & !IdtEntry::PRESENT_BIT);
+            slot.add(1).write_volatile(high);
+            slot.write_volatile(low);
+        }
+    }
+
     /// # Safety
     /// Caller must ensure the table remains valid for the lifetime of the active CPU.
     ///
@@ -248,7 +327,31 @@ struct IdtEntry {
     zero: u32,
 }
 
+// The CPU reads these structures directly, so a refactor must not change
+// their layout. The kernel still runs on the firmware's GDT; descriptor and
+// TSS types get the same treatment when they are introduced.
+const _: () = {
+    assert!(size_of::<IdtEntry>() == 16);
+    assert!(offset_of!(IdtEntry, offset_low) == 0);
+    assert!(offset_of!(IdtEntry, selector) == 2);
+    assert!(offset_of!(IdtEntry, ist) == 4);
+    assert!(offset_of!(IdtEntry, type_attr) == 5);
+    assert!(offset_of!(IdtEntry, offset_mid) == 6);
+    assert!(offset_of!(IdtEntry, offset_high) == 8);
+    assert!(offset_of!(IdtEntry, zero) == 12);
+
+    assert!(size_of::<IdtPointer>() == 10);
+    assert!(offset_of!(IdtPointer, limit) == 0);
+    assert!(offset_of!(IdtPointer, base) == 2);
+
+    assert!(size_of::<Idt>() == 16 * IDT_ENTRIES);
+    assert!(align_of::<Idt>() == 16);
+};
+
 impl IdtEntry {
+    /// Present flag within the low quadword (bit 7 of `type_attr`, byte 5).
+    const PRESENT_BIT: u64 = 1 << 47;
+
     const fn missing() -> Self {
         Self {
             offset_low: 0,
@@ -278,6 +381,124 @@ impl IdtEntry {
     }
 }
 
+impl IdtEntry {
+    /// Rebuild an entry from the quadwords produced by [`IdtEntry::to_words`].
+    fn from_words(low: u64, high: u64) -> Self {
+        Self {
+            offset_low: low as u16,
+            selector: (low >> 16) as u16,
+            ist: (low >> 32) as u8,
+            type_attr: (low >> 40) as u8,
+            offset_mid: (low >> 48) as u16,
+            offset_high: high as u32,
+            zero: (high >> 32) as u32,
+        }
+    }
+
+    /// Split the entry into its little-endian low and high quadwords.
+    fn to_words(self) -> (u64, u64) {
+        let offset_low = self.offset_low;
+        let selector = self.selector;
+        let offset_mid = self.offset_mid;
+        let offset_high = self.offset_high;
+        let zero = self.zero;
+
+        let low = offset_low as u64
+            | (selector as u64) << 16
+            | (self.ist as u64) << 32
+            | (self.type_attr as u64) << 40
+            | (offset_mid as u64) << 48;
+        let high = offset_high as u64 | (zero as u64) << 32;
+        (low, high)
+    }
+}
+
+/// RFLAGS.IF, the maskable-interrupt enable flag.
+const RFLAGS_IF: u64 = 1 << 9;
+
+/// Live [`IrqGuard`]s on this CPU. Only the bootstrap CPU runs, so a single
+/// counter stands in for a per-CPU one.
+static MASKED_DEPTH: AtomicU32 = AtomicU32::new(0);
+/// Interrupt handlers currently running on this CPU.
+static HANDLER_DEPTH: AtomicU32 = AtomicU32::new(0);
+
+/// How many [`IrqGuard`] critical sections are open on this CPU.
+pub fn masked_depth() -> u32 {
+    MASKED_DEPTH.load(Ordering::Relaxed)
+}
+
+/// Whether this CPU is running an interrupt handler.
+pub fn in_interrupt() -> bool {
+    HANDLER_DEPTH.load(Ordering::Relaxed) != 0
+}
+
+/// Marks the body of a handler, so [`in_interrupt`] holds while it runs.
+///
+/// Fatal exception handlers never return to the interrupted code, so their
+/// scope is never closed.
+struct HandlerScope;
+
+impl HandlerScope {
+    #[inline(always)]
+    fn enter() -> Self {
+        HANDLER_DEPTH.fetch_add(1, Ordering::Relaxed);
+        Self
+    }
+}
+
+impl Drop for HandlerScope {
+    #[inline(always)]
+    fn drop(&mut self) {
+        HANDLER_DEPTH.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Masks interrupts on this CPU while alive and restores the saved interrupt
+/// flag when dropped, so nested critical sections only re-enable interrupts
+/// at the outermost level.
+pub struct IrqGuard {
+    enabled: bool,
+}
+
+impl IrqGuard {
+    /// Save RFLAGS.IF and mask interrupts.
+    #[inline(always)]
+    pub fn save() -> Self {
+        let rflags: u64;
+        unsafe {
+            asm!("pushfq", "pop {}", "cli", out(reg) rflags, options(nomem));
+        }
+        MASKED_DEPTH.fetch_add(1, Ordering::Relaxed);
+        Self {
+            enabled: rflags & RFLAGS_IF != 0,
+        }
+    }
+
+    /// Whether interrupts were enabled when the guard was taken.
+    pub fn were_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl Drop for IrqGuard {
+    #[inline(always)]
+    fn drop(&mut self) {
+        MASKED_DEPTH.fetch_sub(1, Ordering::Relaxed);
+        if self.enabled {
+            unsafe {
+                asm!("sti", options(nomem, nostack));
+            }
+        }
+    }
+}
+
+/// Run `f` with maskable interrupts disabled on this CPU, restoring the
+/// previous interrupt flag afterwards.
+pub fn without_interrupts<R>(f: impl FnOnce() -> R) -> R {
+    let _guard = IrqGuard::save();
+    f()
+}
+
 /// Reads the current code segment selector.
 fn read_cs() -> u16 {
     let selector: u16;
@@ -296,6 +517,8 @@ fn configure_exceptions(idt: &mut Idt, selector: u16) {
         selector,
         GateOptions::interrupt(),
     );
+    install_gate(idt, 0x01, debug_handler, selector, GateOptions::interrupt());
+    install_gate(idt, 0x02, nmi_handler, selector, fault_stack(gdt::NMI_IST));
     install_gate(idt, 0x03, breakpoint_handler, selector, GateOptions::trap());
     install_gate(
         idt,
@@ -309,24 +532,34 @@ fn configure_exceptions(idt: &mut Idt, selector: u16) {
         0x08,
         double_fault_handler,
         selector,
-        GateOptions::interrupt(),
+        fault_stack(gdt::DOUBLE_FAULT_IST),
     );
     install_gate(
         idt,
         0x0D,
-        general_protection_handler,
+        gpfault::entry,
         selector,
         GateOptions::interrupt(),
     );
     install_gate(
         idt,
         0x0E,
-        page_fault_handler,
+        pagefault::entry,
         selector,
         GateOptions::interrupt(),
     );
 }
 
+/// Interrupt-gate options that switch to IST slot `ist` once the kernel's TSS
+/// is loaded, and stay on the current stack before that.
+fn fault_stack(ist: u8) -> GateOptions {
+    if gdt::is_loaded() {
+        GateOptions::interrupt().with_ist(ist)
+    } else {
+        GateOptions::interrupt()
+    }
+}
+
 /// Configure a minimal set of legacy IRQ vectors with diagnostic stubs.
 fn configure_irqs(idt: &mut Idt, selector: u16) {
     install_gate(idt, 0x20, timer_handler, selector, GateOptions::interrupt());
@@ -337,6 +570,20 @@ fn configure_irqs(idt: &mut Idt, selector: u16) {
         selector,
         GateOptions::interrupt(),
     );
+    install_gate(
+        idt,
+        pic8259::vector(7),
+        master_spurious_handler,
+        selector,
+        GateOptions::interrupt(),
+    );
+    install_gate(
+        idt,
+        pic8259::vector(15),
+        slave_spurious_handler,
+        selector,
+        GateOptions::interrupt(),
+    );
 }
 
 fn install_gate(
@@ -372,16 +619,28 @@ fn log_installation(first_config: bool, core_index: Option<usize>) {
 }
 
 fn report_fatal_trap(name: &str, vector: u8) {
-    crate::println!("EXCEPTION: {}", name);
-    crate::diagln!("Trap vector: {:#04x}", vector);
-
-    if vector == 0x0E {
-        let fault_addr = read_cr2();
-        crate::diagln!("Fault address (CR2): {:#018x}", fault_addr);
-        crate::diagln!("Page-fault error code capture not yet implemented.");
-    }
+    report_fatal_trap_with_frame(name, vector, None);
+}
 
-    crate::diagln!("Register dump unavailable (handler stubs pending full context capture).");
+/// Report a fatal exception, with the frame the CPU pushed when the entry
+/// stub captured one.
+fn report_fatal_trap_with_frame(name: &str, vector: u8, frame: Option<&FaultFrame>) {
+    core::mem::forget(HandlerScope::enter());
+    crate::emergency::enter(format_args!(
+        "{} ({:#04x}) while handling a fault",
+        name, vector
+    ));
+
+    crate::status::finish(oxide_abi::BootStatus::Exception, vector as u64);
+    crate::console::show_crash(
+        format_args!("EXCEPTION: {}", name),
+        Some(crate::console::Trap {
+            vector,
+            cr2: (vector == 0x0E).then(read_cr2),
+            frame: frame.copied(),
+        }),
+    );
+    crate::power::teardown(crate::power::Teardown::BestEffort);
 }
 
 #[cold]
@@ -397,44 +656,61 @@ extern "C" fn invalid_opcode_handler() {
 }
 
 #[cold]
-extern "C" fn double_fault_handler() {
-    report_fatal_trap("Double Fault", 0x08);
+extern "C" fn nmi_handler() {
+    report_fatal_trap("Non-Maskable Interrupt", 0x02);
     halt_cpu();
 }
 
 #[cold]
-extern "C" fn general_protection_handler() {
-    report_fatal_trap("General Protection Fault", 0x0D);
+extern "C" fn double_fault_handler() {
+    report_fatal_trap("Double Fault", 0x08);
     halt_cpu();
 }
 
 #[cold]
-extern "C" fn page_fault_handler() {
-    report_fatal_trap("Page Fault", 0x0E);
+extern "C" fn debug_handler() {
+    report_fatal_trap("Debug", 0x01);
+    crate::debug::hwbp::report_hit();
     halt_cpu();
 }
 
 extern "C" fn breakpoint_handler() {
-    crate::debug!("Breakpoint interrupt\n");
+    let _scope = HandlerScope::enter();
+    crate::debug_irq!("Breakpoint interrupt\n");
 }
 
 extern "C" fn timer_handler() {
-    crate::debug!("Timer IRQ\n");
+    let _scope = HandlerScope::enter();
+    crate::console::cursor_tick();
+    pic8259::end_of_interrupt(0);
 }
 
 extern "C" fn keyboard_handler() {
-    crate::debug!("Keyboard IRQ\n");
+    let _scope = HandlerScope::enter();
+    crate::input::keyboard_irq();
+    pic8259::end_of_interrupt(1);
 }
 
-fn halt_cpu() -> ! {
-    crate::println!("Halting CPU.");
-    loop {
-        unsafe {
-            asm!("hlt", options(nomem, nostack));
-        }
+/// IRQ 7 is either a real parallel-port interrupt or a spurious one.
+extern "C" fn master_spurious_handler() {
+    let _scope = HandlerScope::enter();
+    if !pic8259::is_spurious(7) {
+        pic8259::end_of_interrupt(7);
+    }
+}
+
+/// IRQ 15 is either a real secondary-ATA interrupt or a spurious one.
+extern "C" fn slave_spurious_handler() {
+    let _scope = HandlerScope::enter();
+    if !pic8259::is_spurious(15) {
+        pic8259::end_of_interrupt(15);
     }
 }
 
+fn halt_cpu() -> ! {
+    crate::power::after_fatal()
+}
+
 fn read_cr2() -> u64 {
     let value: u64;
     unsafe {
@@ -616,6 +892,7 @@ mod tests {
 
         let expected = [
             (0x00u8, super::GateOptions::interrupt()),
+            (0x01u8, super::GateOptions::interrupt()),
             (0x03u8, super::GateOptions::trap()),
             (0x06u8, super::GateOptions::interrupt()),
             (0x08u8, super::GateOptions::interrupt()),
@@ -666,6 +943,82 @@ mod tests {
         }
     }
 
+    #[test]
+    fn idt_entry_encodes_known_gate_bytes() {
+        let handler = 0xFFFF_8000_1234_5678usize;
+
+        let interrupt = super::IdtEntry::new(handler, 0x0008, super::GateOptions::interrupt());
+        let bytes: [u8; 16] = unsafe { core::mem::transmute(interrupt) };
+        assert_eq!(
+            bytes,
+            [
+                0x78, 0x56, 0x08, 0x00, 0x00, 0x8E, 0x34, 0x12, 0x00, 0x80, 0xFF, 0xFF, 0x00, 0x00,
+                0x00, 0x00,
+            ]
+        );
+
+        let trap = super::GateOptions::trap().with_privilege(3).with_ist(1);
+        let bytes: [u8; 16] =
+            unsafe { core::mem::transmute(super::IdtEntry::new(handler, 0x0010, trap)) };
+        assert_eq!(&bytes[2..6], &[0x10, 0x00, 0x01, 0xEF]);
+
+        let missing: [u8; 16] = unsafe { core::mem::transmute(super::IdtEntry::missing()) };
+        assert_eq!(missing, [0; 16]);
+    }
+
+    #[test]
+    fn idt_entry_words_match_memory_layout() {
+        let options = super::GateOptions::trap().with_ist(2);
+        let entry = super::IdtEntry::new(0xFFFF_8000_1234_5678, 0x0008, options);
+        let (low, high) = entry.to_words();
+        let bytes: [u8; 16] = unsafe { core::mem::transmute(entry) };
+
+        assert_eq!(low.to_le_bytes(), bytes[..8]);
+        assert_eq!(high.to_le_bytes(), bytes[8..]);
+        assert_ne!(low & super::IdtEntry::PRESENT_BIT, 0);
+        assert_eq!(
+            super::IdtEntry::missing().to_words().0 & super::IdtEntry::PRESENT_BIT,
+            0
+        );
+    }
+
+    #[test]
+    fn write_gate_replaces_entry_in_place() {
+        let mut idt = super::Idt::new();
+        let entry = super::IdtEntry::new(
+            dummy_handler as *const () as usize,
+            0x0008,
+            super::GateOptions::interrupt(),
+        );
+
+        idt.write_gate(0x30, entry);
+        assert_eq!(idt.entries[0x30].to_words(), entry.to_words());
+
+        idt.write_gate(0x30, super::IdtEntry::missing());
+        assert_eq!(idt.entries[0x30].to_words(), (0, 0));
+    }
+
+    #[test]
+    fn set_handler_requires_configured_idt() {
+        assert_eq!(
+            super::clear_handler(0x30),
+            Err(super::GateUpdateError::NotInitialized)
+        );
+    }
+
+    #[test]
+    fn idt_pointer_encodes_limit_then_base() {
+        let pointer = super::IdtPointer {
+            limit: 0x0FFF,
+            base: 0xFFFF_8000_0000_1000,
+        };
+        let bytes: [u8; 10] = unsafe { core::mem::transmute(pointer) };
+        assert_eq!(
+            bytes,
+            [0xFF, 0x0F, 0x00, 0x10, 0x00, 0x00, 0x00, 0x80, 0xFF, 0xFF]
+        );
+    }
+
     #[test]
     fn sanity_test() {
         // this should unconditionally pass
@@ -0,0 +1,119 @@
+//! The page-fault entry stub and error-code decoding.
+//!
+//! Page faults get a naked stub instead of a plain `extern "C"` handler: it
+//! hands the frame the CPU pushed (error code, RIP, CS, RFLAGS, RSP, SS) to
+//! [`report`], so the crash screen can say what kind of access faulted and
+//! where. The fault is still fatal; the stub never returns.
+
+use core::fmt;
+
+/// What the CPU pushes for an exception that carries an error code.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FaultFrame {
+    pub error_code: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+/// IDT entry for vector 0x0E: pass the frame to [`report`] on an aligned
+/// stack.
+#[unsafe(naked)]
+pub(super) extern "C" fn entry() {
+    core::arch::naked_asm!(
+        "mov rdi, rsp",
+        "and rsp, -16",
+        "call {report}",
+        "ud2",
+        report = sym report,
+    );
+}
+
+extern "C" fn report(frame: &FaultFrame) -> ! {
+    super::report_fatal_trap_with_frame("Page Fault", 0x0E, Some(frame));
+    super::halt_cpu();
+}
+
+/// A page-fault error code, displayed as the access that faulted, e.g.
+/// `not-present write in kernel mode`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageFaultCode(pub u64);
+
+impl PageFaultCode {
+    const PRESENT: u64 = 1 << 0;
+    const WRITE: u64 = 1 << 1;
+    const USER: u64 = 1 << 2;
+    const RESERVED: u64 = 1 << 3;
+    const FETCH: u64 = 1 << 4;
+    const PROTECTION_KEY: u64 = 1 << 5;
+    const SHADOW_STACK: u64 = 1 << 6;
+
+    fn has(self, bit: u64) -> bool {
+        self.0 & bit != 0
+    }
+}
+
+impl fmt::Display for PageFaultCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = if self.has(Self::FETCH) {
+            "instruction fetch"
+        } else if self.has(Self::WRITE) {
+            "write"
+        } else {
+            "read"
+        };
+        write!(
+            f,
+            "{} {} in {} mode",
+            if self.has(Self::PRESENT) {
+                "protection-violating"
+            } else {
+                "not-present"
+            },
+            access,
+            if self.has(Self::USER) {
+                "user"
+            } else {
+                "kernel"
+            }
+        )?;
+        for (bit, what) in [
+            (Self::RESERVED, "reserved bit set in a paging entry"),
+            (Self::PROTECTION_KEY, "protection key"),
+            (Self::SHADOW_STACK, "shadow stack"),
+        ] {
+            if self.has(bit) {
+                write!(f, ", {}", what)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn error_codes_describe_the_faulting_access() {
+        assert_eq!(
+            format!("{}", PageFaultCode(0b0_0010)),
+            "not-present write in kernel mode"
+        );
+        assert_eq!(
+            format!("{}", PageFaultCode(0b1_1001)),
+            "protection-violating instruction fetch in kernel mode, \
+             reserved bit set in a paging entry"
+        );
+        assert_eq!(
+            format!("{}", PageFaultCode(0b0_0101)),
+            "protection-violating read in user mode"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! The audited point at which maskable interrupts may be turned on.
+//!
+//! `kernel_main` starts with `cli` and nothing re-enables interrupts by
+//! accident: [`enable`] is the only place that executes `sti`, and only after
+//! every prerequisite in [`Readiness`] holds. Until then it logs what is
+//! missing, which doubles as the to-do list for interrupt bring-up.
+
+use core::arch::asm;
+
+use crate::drivers::pic8259;
+
+/// Legacy IRQ of the PIT, the kernel's tick device.
+const TIMER_IRQ: u8 = 0;
+
+/// Prerequisites for running with interrupts enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Readiness {
+    /// The IDT is loaded and still matches its shadow copy.
+    pub idt_loaded: bool,
+    /// The 8259s deliver on 0x20–0x2F instead of the exception vectors.
+    pub pic_remapped: bool,
+    /// A TSS with IST stacks exists, so a double fault or NMI gets a known
+    /// good stack.
+    pub ist_ready: bool,
+    /// A tick device has been programmed and its IRQ unmasked.
+    pub timer_configured: bool,
+    /// Console output from a handler cannot deadlock or tear a line.
+    pub console_irq_safe: bool,
+    /// Handler stubs save the interrupted context and return with `iretq`.
+    pub handlers_resume: bool,
+}
+
+impl Readiness {
+    /// Evaluate every prerequisite on the current CPU.
+    pub fn check() -> Self {
+        Self {
+            idt_loaded: super::verify_tables(false).is_some_and(|check| check.is_clean()),
+            pic_remapped: pic8259::is_remapped(),
+            ist_ready: crate::gdt::is_loaded(),
+            timer_configured: pic8259::is_remapped() && !pic8259::is_masked(TIMER_IRQ),
+            // The console lock masks interrupts and a busy console defers
+            // handler output to a ring replayed by the lock holder.
+            console_irq_safe: true,
+            // Handlers are plain `extern "C"` functions that end in `ret`.
+            handlers_resume: false,
+        }
+    }
+
+    fn items(&self) -> [(&'static str, bool); 6] {
+        [
+            ("IDT loaded", self.idt_loaded),
+            ("PIC remapped", self.pic_remapped),
+            ("IST stacks", self.ist_ready),
+            ("timer configured", self.timer_configured),
+            ("console IRQ-safe", self.console_irq_safe),
+            ("handlers resume", self.handlers_resume),
+        ]
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.items().iter().all(|&(_, ok)| ok)
+    }
+
+    /// Print the checklist; under `debug` every item, otherwise nothing.
+    pub fn log(&self) {
+        crate::diagln!(
+            "interrupts: {}",
+            if self.is_ready() {
+                "ready to enable"
+            } else {
+                "staying disabled"
+            }
+        );
+        for (name, ok) in self.items() {
+            crate::diagln!("  [{}] {}", if ok { "x" } else { " " }, name);
+        }
+    }
+}
+
+/// Enable maskable interrupts if every prerequisite holds.
+///
+/// Returns the failed checklist otherwise, leaving interrupts masked.
+pub fn enable() -> Result<(), Readiness> {
+    let readiness = Readiness::check();
+    readiness.log();
+    if !readiness.is_ready() {
+        return Err(readiness);
+    }
+
+    // SAFETY: every prerequisite for taking interrupts was just verified.
+    unsafe { asm!("sti", options(nomem, nostack)) };
+    crate::println!("Kernel: interrupts enabled.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readiness_requires_every_item() {
+        let ready = Readiness {
+            idt_loaded: true,
+            pic_remapped: true,
+            ist_ready: true,
+            timer_configured: true,
+            console_irq_safe: true,
+            handlers_resume: true,
+        };
+        assert!(ready.is_ready());
+        assert!(
+            !Readiness {
+                ist_ready: false,
+                ..ready
+            }
+            .is_ready()
+        );
+    }
+}
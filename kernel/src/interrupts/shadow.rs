@@ -0,0 +1,212 @@
+//! Known-good copies of the descriptor tables, for detecting wild writes.
+//!
+//! After the IDT is installed, the IDTR, GDTR, every IDT entry, and the GDT's
+//! descriptors are copied here. [`verify_tables`] compares the live state with
+//! these shadows and can restore it. Gate changes made through
+//! `set_handler`/`clear_handler` update the shadow as well.
+
+use core::{arch::asm, cell::UnsafeCell};
+
+use super::{IDT_ENTRIES, IDT_STORAGE, Idt, IdtEntry, IdtPointer, without_interrupts};
+
+/// GDT descriptors compared; the firmware's GDT is well below this.
+const MAX_GDT_ENTRIES: usize = 32;
+
+struct Shadow {
+    captured: bool,
+    idtr: (u16, u64),
+    idt: [(u64, u64); IDT_ENTRIES],
+    gdtr: (u16, u64),
+    gdt: [u64; MAX_GDT_ENTRIES],
+    gdt_len: usize,
+}
+
+struct ShadowCell(UnsafeCell<Shadow>);
+
+// SAFETY: written during single-threaded IDT bring-up and gate updates, which
+// run with interrupts masked on the only CPU.
+unsafe impl Sync for ShadowCell {}
+
+static SHADOW: ShadowCell = ShadowCell(UnsafeCell::new(Shadow {
+    captured: false,
+    idtr: (0, 0),
+    idt: [(0, 0); IDT_ENTRIES],
+    gdtr: (0, 0),
+    gdt: [0; MAX_GDT_ENTRIES],
+    gdt_len: 0,
+}));
+
+/// Outcome of comparing the live descriptor tables with their shadows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TableCheck {
+    pub idtr_changed: bool,
+    pub idt_mismatches: usize,
+    pub gdtr_changed: bool,
+    pub gdt_mismatches: usize,
+    /// The shadows were written back and the registers reloaded.
+    pub repaired: bool,
+}
+
+impl TableCheck {
+    pub fn is_clean(&self) -> bool {
+        !self.idtr_changed
+            && self.idt_mismatches == 0
+            && !self.gdtr_changed
+            && self.gdt_mismatches == 0
+    }
+
+    /// Print the result; a clean check is only a diagnostic.
+    pub fn log(&self) {
+        if self.is_clean() {
+            crate::diagln!("descriptor tables match their shadows");
+            return;
+        }
+        crate::println!(
+            "DESCRIPTOR TABLES CHANGED: idtr {}, {} idt entries, gdtr {}, {} gdt entries{}",
+            if self.idtr_changed { "moved" } else { "ok" },
+            self.idt_mismatches,
+            if self.gdtr_changed { "moved" } else { "ok" },
+            self.gdt_mismatches,
+            if self.repaired { " (restored)" } else { "" }
+        );
+    }
+}
+
+/// Snapshot the loaded IDT and GDT as the known-good state.
+pub(super) fn capture(idt: &Idt) {
+    let shadow = unsafe { &mut *SHADOW.0.get() };
+
+    shadow.idtr = read_idtr();
+    for (slot, entry) in shadow.idt.iter_mut().zip(idt.entries.iter()) {
+        *slot = entry.to_words();
+    }
+
+    shadow.gdtr = read_gdtr();
+    let (limit, base) = shadow.gdtr;
+    shadow.gdt_len = ((limit as usize + 1) / 8).min(MAX_GDT_ENTRIES);
+    for index in 0..shadow.gdt_len {
+        // SAFETY: the GDTR describes memory the CPU itself reads descriptors from.
+        shadow.gdt[index] = unsafe { ((base as *const u64).add(index)).read_volatile() };
+    }
+
+    shadow.captured = true;
+}
+
+/// Keep the shadow in step with a deliberate gate change.
+pub(super) fn record_gate(vector: u8, entry: IdtEntry) {
+    let shadow = unsafe { &mut *SHADOW.0.get() };
+    shadow.idt[vector as usize] = entry.to_words();
+}
+
+/// Compare the live IDTR, GDTR, and table contents with the shadows, and
+/// restore them if `repair` is set and anything differs.
+///
+/// Returns `None` before `interrupts::init` has captured the shadows. Meant
+/// to be run periodically once a timer or watchdog exists.
+pub fn verify_tables(repair: bool) -> Option<TableCheck> {
+    let shadow = unsafe { &*SHADOW.0.get() };
+    if !shadow.captured {
+        return None;
+    }
+
+    without_interrupts(|| {
+        let live_idt = unsafe { &*IDT_STORAGE.0.get() };
+        let (_, gdt_base) = shadow.gdtr;
+        let mut check = TableCheck {
+            idtr_changed: read_idtr() != shadow.idtr,
+            idt_mismatches: count_mismatches(
+                live_idt.entries.iter().map(|entry| entry.to_words()),
+                shadow.idt.iter().copied(),
+            ),
+            gdtr_changed: read_gdtr() != shadow.gdtr,
+            gdt_mismatches: count_mismatches(
+                (0..shadow.gdt_len)
+                    .map(|index| unsafe { ((gdt_base as *const u64).add(index)).read_volatile() }),
+                shadow.gdt[..shadow.gdt_len].iter().copied(),
+            ),
+            repaired: false,
+        };
+
+        if repair && !check.is_clean() {
+            unsafe { restore(shadow) };
+            check.repaired = true;
+        }
+        Some(check)
+    })
+}
+
+/// Write the shadows back and reload IDTR and GDTR.
+unsafe fn restore(shadow: &Shadow) {
+    unsafe {
+        IDT_STORAGE.with_mut(|idt| {
+            for (vector, &(low, high)) in shadow.idt.iter().enumerate() {
+                if idt.entries[vector].to_words() != (low, high) {
+                    idt.write_gate(vector as u8, IdtEntry::from_words(low, high));
+                }
+            }
+        });
+
+        let (_, gdt_base) = shadow.gdtr;
+        for (index, &descriptor) in shadow.gdt[..shadow.gdt_len].iter().enumerate() {
+            ((gdt_base as *mut u64).add(index)).write_volatile(descriptor);
+        }
+
+        let (limit, base) = shadow.gdtr;
+        let gdtr = IdtPointer { limit, base };
+        asm!("lgdt [{0}]", in(reg) &gdtr, options(nostack, preserves_flags));
+        let (limit, base) = shadow.idtr;
+        let idtr = IdtPointer { limit, base };
+        asm!("lidt [{0}]", in(reg) &idtr, options(nostack, preserves_flags));
+    }
+}
+
+fn count_mismatches<T: PartialEq>(
+    live: impl Iterator<Item = T>,
+    shadow: impl Iterator<Item = T>,
+) -> usize {
+    live.zip(shadow)
+        .filter(|(live, shadow)| live != shadow)
+        .count()
+}
+
+fn read_idtr() -> (u16, u64) {
+    let mut pointer = IdtPointer { limit: 0, base: 0 };
+    unsafe { asm!("sidt [{0}]", in(reg) &mut pointer, options(nostack, preserves_flags)) };
+    let IdtPointer { limit, base } = pointer;
+    (limit, base)
+}
+
+fn read_gdtr() -> (u16, u64) {
+    let mut pointer = IdtPointer { limit: 0, base: 0 };
+    unsafe { asm!("sgdt [{0}]", in(reg) &mut pointer, options(nostack, preserves_flags)) };
+    let IdtPointer { limit, base } = pointer;
+    (limit, base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mismatches_are_counted_pairwise() {
+        assert_eq!(
+            count_mismatches([1, 2, 3].into_iter(), [1, 0, 3].into_iter()),
+            1
+        );
+        assert_eq!(
+            count_mismatches([0u64; 4].into_iter(), [0u64; 4].into_iter()),
+            0
+        );
+    }
+
+    #[test]
+    fn entry_words_round_trip() {
+        let entry = IdtEntry::new(
+            0xFFFF_8000_1234_5678,
+            0x0008,
+            super::super::GateOptions::trap(),
+        );
+        let (low, high) = entry.to_words();
+        assert_eq!(IdtEntry::from_words(low, high).to_words(), (low, high));
+    }
+}
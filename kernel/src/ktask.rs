@@ -0,0 +1,428 @@
+//! Cooperative async tasks for kernel code.
+//!
+//! `ktask::spawn(async { ... })` runs a future on the kernel executor
+//! without a stack of its own. There is no heap, so each task lives in one
+//! of [`TASK_SLOTS`] fixed slots of [`TASK_BYTES`] bytes; a larger future is
+//! refused. A task is polled again once its waker fires: [`sleep`] wakes it
+//! when its deadline passes, and a [`TaskEvent`] wakes it when an interrupt
+//! handler signals. Between polls [`run`] spins with backoff, as the `sync`
+//! primitives do, until a scheduler can put the CPU to sleep instead.
+
+use core::{
+    cell::UnsafeCell,
+    future::Future,
+    mem::{self, MaybeUninit},
+    pin::Pin,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicU64, Ordering},
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+    time::Duration,
+};
+
+use crate::{
+    sync::backoff::Backoff,
+    time::{self, Instant},
+};
+
+/// Tasks the executor holds at once.
+pub const TASK_SLOTS: usize = 16;
+
+/// Largest future, in bytes, a slot can hold.
+pub const TASK_BYTES: usize = 256;
+
+const SLOT_FREE: u8 = 0;
+const SLOT_CLAIMED: u8 = 1;
+const SLOT_LIVE: u8 = 2;
+
+/// `wake_at` value for a task with no timer pending.
+const NO_DEADLINE: u64 = u64::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpawnError {
+    /// Every slot holds a live task.
+    Full,
+    /// The future is larger than [`TASK_BYTES`] or more strictly aligned
+    /// than a slot.
+    TooLarge { bytes: usize },
+}
+
+/// Index of a spawned task's slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaskId(pub usize);
+
+#[repr(C, align(16))]
+struct TaskStorage([MaybeUninit<u8>; TASK_BYTES]);
+
+/// Type-erased operations on the future stored in a slot.
+struct TaskVTable {
+    poll: unsafe fn(*mut u8, &mut Context<'_>) -> Poll<()>,
+    drop: unsafe fn(*mut u8),
+}
+
+impl TaskVTable {
+    const fn of<F: Future<Output = ()>>() -> Self {
+        Self {
+            poll: |future, cx| unsafe { Pin::new_unchecked(&mut *future.cast::<F>()) }.poll(cx),
+            drop: |future| unsafe { ptr::drop_in_place(future.cast::<F>()) },
+        }
+    }
+}
+
+struct TaskSlot {
+    state: AtomicU8,
+    /// Set by the task's waker; cleared just before the task is polled.
+    woken: AtomicBool,
+    /// Earliest `sleep` deadline of the task, in clock nanoseconds.
+    wake_at: AtomicU64,
+    vtable: UnsafeCell<Option<&'static TaskVTable>>,
+    storage: UnsafeCell<TaskStorage>,
+}
+
+// SAFETY: `storage` and `vtable` are written only by the spawner holding the
+// slot in `SLOT_CLAIMED`, and read only by the executor once it is live.
+unsafe impl Sync for TaskSlot {}
+
+impl TaskSlot {
+    const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(SLOT_FREE),
+            woken: AtomicBool::new(false),
+            wake_at: AtomicU64::new(NO_DEADLINE),
+            vtable: UnsafeCell::new(None),
+            storage: UnsafeCell::new(TaskStorage([MaybeUninit::uninit(); TASK_BYTES])),
+        }
+    }
+
+    fn waker(&'static self) -> Waker {
+        // SAFETY: the vtable's functions only touch the slot's atomics.
+        unsafe { Waker::from_raw(RawWaker::new(ptr::from_ref(self).cast(), &WAKER_VTABLE)) }
+    }
+
+    fn wake(&self) {
+        self.woken.store(true, Ordering::Release);
+    }
+}
+
+static WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(
+    |slot| RawWaker::new(slot, &WAKER_VTABLE),
+    |slot| unsafe { (*slot.cast::<TaskSlot>()).wake() },
+    |slot| unsafe { (*slot.cast::<TaskSlot>()).wake() },
+    |_| {},
+);
+
+/// The executor's slot behind `waker`, if it is one of ours.
+fn slot_of(waker: &Waker) -> Option<&'static TaskSlot> {
+    ptr::eq(waker.vtable(), &WAKER_VTABLE)
+        // SAFETY: our wakers always point at a slot of a `'static` executor.
+        .then(|| unsafe { &*waker.data().cast::<TaskSlot>() })
+}
+
+/// A fixed set of task slots and the loop that polls them.
+pub struct Executor {
+    slots: [TaskSlot; TASK_SLOTS],
+}
+
+impl Executor {
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { TaskSlot::new() }; TASK_SLOTS],
+        }
+    }
+
+    /// Store `future` in a free slot; it is first polled by the next run.
+    ///
+    /// May be called from a running task.
+    pub fn spawn<F>(&'static self, future: F) -> Result<TaskId, SpawnError>
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        if mem::size_of::<F>() > TASK_BYTES || mem::align_of::<F>() > mem::align_of::<TaskStorage>()
+        {
+            return Err(SpawnError::TooLarge {
+                bytes: mem::size_of::<F>(),
+            });
+        }
+
+        let (index, slot) = self
+            .slots
+            .iter()
+            .enumerate()
+            .find(|(_, slot)| {
+                slot.state
+                    .compare_exchange(
+                        SLOT_FREE,
+                        SLOT_CLAIMED,
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+            })
+            .ok_or(SpawnError::Full)?;
+
+        // SAFETY: the slot is claimed, so nothing else touches its storage,
+        // and the size and alignment were checked above.
+        unsafe {
+            (*slot.storage.get())
+                .0
+                .as_mut_ptr()
+                .cast::<F>()
+                .write(future);
+            *slot.vtable.get() = Some(&const { TaskVTable::of::<F>() });
+        }
+        slot.wake_at.store(NO_DEADLINE, Ordering::Relaxed);
+        slot.woken.store(true, Ordering::Relaxed);
+        slot.state.store(SLOT_LIVE, Ordering::Release);
+        Ok(TaskId(index))
+    }
+
+    /// Poll every woken task until none is woken; returns the number of
+    /// tasks still live.
+    pub fn run_until_idle(&'static self) -> usize {
+        loop {
+            let mut polled = false;
+            let mut live = 0;
+            for slot in &self.slots {
+                if slot.state.load(Ordering::Acquire) != SLOT_LIVE {
+                    continue;
+                }
+                live += 1;
+                if !slot.woken.swap(false, Ordering::AcqRel) {
+                    continue;
+                }
+                polled = true;
+                if self.poll_slot(slot).is_ready() {
+                    live -= 1;
+                }
+            }
+            if !polled {
+                return live;
+            }
+        }
+    }
+
+    /// Run until every task has finished, waking sleepers as their deadlines
+    /// pass.
+    pub fn run(&'static self) {
+        let mut backoff = Backoff::new();
+        while self.run_until_idle() > 0 {
+            if self.fire_timers(time::now()) > 0 {
+                backoff.reset();
+            } else {
+                backoff.snooze();
+            }
+        }
+    }
+
+    /// Wake every task whose `sleep` deadline is at or before `now`.
+    fn fire_timers(&self, now: Instant) -> usize {
+        let mut fired = 0;
+        for slot in &self.slots {
+            let wake_at = slot.wake_at.load(Ordering::Acquire);
+            if wake_at <= now.as_nanos()
+                && slot
+                    .wake_at
+                    .compare_exchange(wake_at, NO_DEADLINE, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+            {
+                slot.wake();
+                fired += 1;
+            }
+        }
+        fired
+    }
+
+    fn poll_slot(&self, slot: &'static TaskSlot) -> Poll<()> {
+        let waker = slot.waker();
+        let mut cx = Context::from_waker(&waker);
+        // SAFETY: the slot is live, so its vtable and storage hold a future
+        // of the vtable's type, and only the executor polls it.
+        unsafe {
+            let vtable = (*slot.vtable.get()).expect("live task without a vtable");
+            let future = (*slot.storage.get()).0.as_mut_ptr().cast::<u8>();
+            let poll = (vtable.poll)(future, &mut cx);
+            if poll.is_ready() {
+                (vtable.drop)(future);
+                *slot.vtable.get() = None;
+                slot.wake_at.store(NO_DEADLINE, Ordering::Relaxed);
+                slot.state.store(SLOT_FREE, Ordering::Release);
+            }
+            poll
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static KERNEL_EXECUTOR: Executor = Executor::new();
+
+/// Spawn `future` on the kernel executor.
+pub fn spawn<F>(future: F) -> Result<TaskId, SpawnError>
+where
+    F: Future<Output = ()> + 'static,
+{
+    KERNEL_EXECUTOR.spawn(future)
+}
+
+/// Run the kernel executor until every spawned task has finished.
+pub fn run() {
+    KERNEL_EXECUTOR.run();
+}
+
+/// Complete once `duration` has passed.
+///
+/// Without a clock source no deadline can pass, so the sleep completes at
+/// once instead of leaving [`run`] spinning forever.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        deadline: time::now() + duration,
+    }
+}
+
+/// Future returned by [`sleep`].
+pub struct Sleep {
+    deadline: Instant,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if time::current_source().is_none() || time::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        match slot_of(cx.waker()) {
+            Some(slot) => {
+                slot.wake_at
+                    .fetch_min(self.deadline.as_nanos(), Ordering::AcqRel);
+            }
+            // Not on our executor: ask to be polled again.
+            None => cx.waker().wake_by_ref(),
+        }
+        Poll::Pending
+    }
+}
+
+/// A flag an interrupt handler sets to wake one waiting task.
+///
+/// [`TaskEvent::signal`] never blocks or allocates, so it is safe from any
+/// handler. Signals that arrive with nobody waiting are kept until the next
+/// [`TaskEvent::wait`].
+pub struct TaskEvent {
+    signalled: AtomicBool,
+    waiter: AtomicPtr<TaskSlot>,
+}
+
+impl TaskEvent {
+    pub const fn new() -> Self {
+        Self {
+            signalled: AtomicBool::new(false),
+            waiter: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Set the flag and wake the task waiting on it, if any.
+    pub fn signal(&self) {
+        self.signalled.store(true, Ordering::Release);
+        let waiter = self.waiter.swap(ptr::null_mut(), Ordering::AcqRel);
+        if !waiter.is_null() {
+            // SAFETY: only `TaskEvent::wait` stores pointers, to `'static` slots.
+            unsafe { (*waiter).wake() };
+        }
+    }
+
+    /// Complete once the event is signalled, consuming the signal.
+    pub fn wait(&self) -> TaskEventWait<'_> {
+        TaskEventWait { event: self }
+    }
+}
+
+impl Default for TaskEvent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Future returned by [`TaskEvent::wait`].
+pub struct TaskEventWait<'a> {
+    event: &'a TaskEvent,
+}
+
+impl Future for TaskEventWait<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let event = self.event;
+        if event.signalled.swap(false, Ordering::AcqRel) {
+            return Poll::Ready(());
+        }
+        match slot_of(cx.waker()) {
+            Some(slot) => event
+                .waiter
+                .store(ptr::from_ref(slot).cast_mut(), Ordering::Release),
+            None => cx.waker().wake_by_ref(),
+        }
+        // A signal that raced the registration would otherwise be missed.
+        if event.signalled.swap(false, Ordering::AcqRel) {
+            event.waiter.store(ptr::null_mut(), Ordering::Release);
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::boxed::Box;
+    use core::sync::atomic::AtomicU32;
+
+    #[test]
+    fn tasks_wait_on_events_and_timers() {
+        let executor: &'static Executor = Box::leak(Box::new(Executor::new()));
+        static EVENT: TaskEvent = TaskEvent::new();
+        static STEPS: AtomicU32 = AtomicU32::new(0);
+
+        executor
+            .spawn(async {
+                STEPS.fetch_add(1, Ordering::Relaxed);
+                EVENT.wait().await;
+                STEPS.fetch_add(1, Ordering::Relaxed);
+            })
+            .unwrap();
+        assert_eq!(executor.run_until_idle(), 1);
+        assert_eq!(STEPS.load(Ordering::Relaxed), 1);
+
+        EVENT.signal();
+        assert_eq!(executor.run_until_idle(), 0);
+        assert_eq!(STEPS.load(Ordering::Relaxed), 2);
+
+        // The host has no clock source, so a sleep ends at once.
+        executor
+            .spawn(async {
+                sleep(Duration::from_millis(5)).await;
+                STEPS.fetch_add(1, Ordering::Relaxed);
+            })
+            .unwrap();
+        assert_eq!(executor.run_until_idle(), 0);
+        assert_eq!(STEPS.load(Ordering::Relaxed), 3);
+
+        let slot = &executor.slots[0];
+        slot.wake_at.store(5_000_000, Ordering::Relaxed);
+        assert_eq!(executor.fire_timers(Instant::ZERO), 0);
+        assert_eq!(executor.fire_timers(Instant::from_nanos(5_000_000)), 1);
+        assert!(slot.woken.swap(false, Ordering::Relaxed));
+
+        let big = [0u8; TASK_BYTES];
+        let result = executor.spawn(async move {
+            sleep(Duration::ZERO).await;
+            assert_eq!(big.len(), TASK_BYTES);
+        });
+        assert!(matches!(result, Err(SpawnError::TooLarge { .. })));
+    }
+}
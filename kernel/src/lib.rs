@@ -1,6 +1,17 @@
 #![no_std]
 #![cfg_attr(not(test), no_main)]
-use oxide_abi::BootAbi;
+// Some unit tests predate these lints and are kept as written.
+#![cfg_attr(
+    test,
+    allow(
+        clippy::manual_contains,
+        clippy::identity_op,
+        function_casts_as_integer
+    )
+)]
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use oxide_abi::{BootAbi, BootStage, BootStatus};
 
 use crate::interrupts::InterruptInitError;
 use crate::memory::{
@@ -8,13 +19,42 @@ use crate::memory::{
     init,
 };
 
+#[cfg(all(feature = "dep-loader", feature = "standalone"))]
+compile_error!("`dep-loader` and `standalone` select different entry paths; enable one");
+
 mod boot;
 mod console;
+mod cpu;
+pub mod debug;
+mod drivers;
+mod emergency;
 mod framebuffer;
+mod gdt;
+pub mod input;
 pub mod interrupts;
+pub mod ktask;
 mod memory;
+#[cfg(any(test, feature = "multiboot2"))]
+mod multiboot2;
 mod options;
+mod port;
+mod power;
+#[cfg(feature = "qemu-test")]
+mod qemu;
+mod serial;
+mod status;
+mod sync;
 mod time;
+mod volatile;
+
+/// Set on entry to [`kernel_main`]; see [`started`].
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether [`kernel_main`] has taken over from the loader. The loader's
+/// panic handler hands panics to [`report_panic`] from then on.
+pub fn started() -> bool {
+    STARTED.load(Ordering::Relaxed)
+}
 
 /// Kernel entry point called from the UEFI loader.
 ///
@@ -22,76 +62,176 @@ mod time;
 /// - `boot_abi_ptr` points to a valid `BootAbi`
 /// - Memory is identity-mapped at entry
 /// - Interrupts may be enabled by firmware
+///
+/// The processor-state assumptions are checked by
+/// [`boot::verify_machine_state`].
 #[unsafe(no_mangle)]
 pub extern "C" fn kernel_main(boot_abi_ptr: *const BootAbi) -> ! {
+    // Record the handoff state before touching it.
+    let entry_state = boot::MachineState::capture();
+    STARTED.store(true, Ordering::Relaxed);
+
     // Disable interrupts before doing anything else
     unsafe {
         core::arch::asm!("cli");
     }
 
-    match kernel_run(boot_abi_ptr) {
-        Ok(()) => halt(), // This should not actually be possible, as the kernel never exits
+    match kernel_run(boot_abi_ptr, entry_state) {
+        Ok(()) => {
+            // Boot has nothing further to run yet; `onhalt=` picks what happens next.
+            status::finish(BootStatus::Halted, 0);
+            match power::halt_action() {
+                power::HaltAction::Halt => halt(),
+                power::HaltAction::Reboot => power::reboot(),
+                power::HaltAction::PowerOff => power::power_off(),
+            }
+        }
         Err(e) => fatal(e), // Fatal error; halt the system
     }
 }
 
 fn halt() -> ! {
     crate::println!("System halted.");
-    loop {
-        core::hint::spin_loop();
-    }
+    status::end_session();
+    emergency::halt();
 }
 
 fn fatal(e: KernelError) -> ! {
-    crate::println!("Fatal kernel error: {:?}", e);
-    halt();
+    emergency::enter(format_args!("fatal kernel error: {:?}", e));
+
+    status::finish(BootStatus::Fatal, 0);
+    console::show_crash(format_args!("Fatal kernel error: {:?}", e), None);
+    power::teardown(power::Teardown::BestEffort);
+    power::after_fatal();
 }
 
-fn kernel_run(boot_abi_ptr: *const BootAbi) -> Result<(), KernelError> {
+fn kernel_run(
+    boot_abi_ptr: *const BootAbi,
+    entry_state: boot::MachineState,
+) -> Result<(), KernelError> {
     // SAFETY: caller (the UEFI loader) must ensure the pointer is valid at entry
     let boot_abi = unsafe { &*boot_abi_ptr };
 
     boot::validate_boot_abi(boot_abi)?;
 
     let framebuffer = boot_abi.framebuffer;
-    let memory_map = boot_abi.memory_map;
+    let mut memory_map = boot_abi.memory_map;
+    let map_fixup = boot::reconcile_memory_map(&mut memory_map);
+
+    status::init(boot_abi.status_page_phys);
+    status::stage(BootStage::KernelEntry);
+    debug::probes::oxide_probe_kernel_entry();
+
+    emergency::init(framebuffer);
+
+    // Checked once the emergency writer can show which assumption broke.
+    boot::verify_machine_state(&entry_state)?;
 
     options::init(boot_abi.options);
+    memory::failtest::init();
+    debug::screendump::init(framebuffer);
 
     // Clear the framebuffer to assert control
-    framebuffer::clear_framebuffer(&framebuffer).expect("framebuffer clear failed");
+    let theme = framebuffer::theme();
+    framebuffer::clear_framebuffer(&framebuffer, theme.background)
+        .expect("framebuffer clear failed");
+
+    // Apply before the first early allocation so nothing lands in a withheld range.
+    let memory_overrides = memory::overrides::apply(&memory_map);
 
     if let Ok(storage) = init::bootstrap_console_storage(&memory_map) {
-        let _ = console::init(framebuffer, framebuffer::FramebufferColor::WHITE, storage);
+        let _ = console::init(framebuffer, theme, storage);
     }
 
-    time::init_tsc_monotonic(boot_abi.tsc_frequency_hz);
+    status::stage(BootStage::Console);
+
+    time::init(boot_abi.tsc_frequency_hz);
+    cpu::microcode::apply_boot_blob(boot_abi.microcode, boot_abi.microcode_xxh32);
+    cpu::mitigations::init();
+    cpu::pmu::init();
+    cpu::pmu::phase_done("early");
 
     crate::println!("Oxide kernel starting...");
     crate::println!("Kernel: Entering epoch 1: Spark.");
 
-    let (freq, unit) = human_readable_hz(boot_abi.tsc_frequency_hz);
-    crate::diagln!("Detected CPU frequency: {:.2} {}", freq, unit);
-
-    init::initialize(&memory_map, &framebuffer)?;
+    crate::diagln!(
+        "Detected CPU frequency: {}",
+        console::format::fmt_hz(boot_abi.tsc_frequency_hz)
+    );
+    debug::bootinfo::show_if_requested(boot_abi);
 
+    entry_state.log();
+    if let Some(fixup) = map_fixup {
+        fixup.log();
+    }
+    memory_overrides.log();
+    memory::failtest::log();
+    memory::memtest::run_if_requested(&memory_map);
+
+    init::initialize(
+        &memory_map,
+        &framebuffer,
+        boot_abi.memory_attributes,
+        boot_abi.loader_image,
+    )?;
+
+    status::stage(BootStage::Memory);
+    cpu::pmu::phase_done("memory");
+    debug::probes::oxide_probe_memory_init_done();
     crate::diagln!("Memory subsystem init complete.");
 
+    gdt::init();
     interrupts::init(None)?;
 
+    status::stage(BootStage::Interrupts);
+    cpu::telemetry::log();
+    let boot_cycles = cpu::telemetry::CycleSample::read();
+    cpu::pmu::phase_done("interrupts");
+    debug::probes::oxide_probe_interrupts_init_done();
     crate::diagln!("Interrupt subsystem init complete.");
 
+    if let Err(err) = input::init() {
+        crate::diagln!("input: no PS/2 keyboard ({:?})", err);
+    }
+
+    if let Some(check) = interrupts::verify_tables(false) {
+        check.log();
+    }
+
     crate::println!("Kernel: Entering epoch 2: Foundation.");
+    status::stage(BootStage::Epoch2);
+    cpu::pmu::phase_done("epoch 2");
+    debug::probes::oxide_probe_epoch2();
+
+    // Interrupts stay masked until every prerequisite is met; the checklist
+    // is logged under `debug`.
+    let _ = interrupts::enable();
+    cpu::telemetry::log_frequency_since(boot_cycles, boot_abi.tsc_frequency_hz);
+    console::log_capture_summary();
+    debug::memview::show_if_requested(&framebuffer);
+    // Tasks spawned during bring-up run to completion before boot ends.
+    ktask::run();
 
     Ok(())
 }
 
 #[cfg(all(not(test), not(feature = "dep-loader")))]
 #[panic_handler]
-fn panic(_info: &core::panic::PanicInfo) -> ! {
-    loop {
-        core::hint::spin_loop();
-    }
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    report_panic(info)
+}
+
+/// Report a panic and finish like any other fatal path.
+///
+/// The kernel's own panic handler in standalone builds; in loader builds the
+/// loader's handler calls it once the kernel has [`started`].
+pub fn report_panic(info: &core::panic::PanicInfo) -> ! {
+    emergency::enter(format_args!("panic: {}", info));
+
+    status::finish(BootStatus::Panic, 0);
+    console::show_crash(format_args!("KERNEL PANIC: {}", info), None);
+    power::teardown(power::Teardown::BestEffort);
+    power::after_fatal();
 }
 
 #[derive(Debug)]
@@ -126,44 +266,3 @@ impl From<InterruptInitError> for KernelError {
         KernelError::InterruptInit(err)
     }
 }
-
-fn human_readable_hz(freq_hz: u64) -> (f64, &'static str) {
-    const KHZ: f64 = 1_000.0;
-    const MHZ: f64 = 1_000_000.0;
-    const GHZ: f64 = 1_000_000_000.0;
-
-    let freq = freq_hz as f64;
-    if freq >= GHZ {
-        (freq / GHZ, "GHz")
-    } else if freq >= MHZ {
-        (freq / MHZ, "MHz")
-    } else if freq >= KHZ {
-        (freq / KHZ, "kHz")
-    } else {
-        (freq, "Hz")
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::human_readable_hz;
-
-    #[test]
-    fn test_human_readable_hz() {
-        let (freq, unit) = human_readable_hz(3_200_000_000);
-        assert_eq!(freq, 3.2);
-        assert_eq!(unit, "GHz");
-
-        let (freq, unit) = human_readable_hz(1_500_000);
-        assert_eq!(freq, 1.5);
-        assert_eq!(unit, "MHz");
-
-        let (freq, unit) = human_readable_hz(750_000);
-        assert_eq!(freq, 750.0);
-        assert_eq!(unit, "kHz");
-
-        let (freq, unit) = human_readable_hz(500);
-        assert_eq!(freq, 500.0);
-        assert_eq!(unit, "Hz");
-    }
-}
@@ -0,0 +1,121 @@
+//! Per-subsystem accounting of the physical frames the kernel has claimed.
+//!
+//! Allocation sites charge the frames they take to a [`Subsystem`], so the
+//! question "where did the memory go" has an answer during bring-up. Nothing
+//! returns frames yet; counters only grow until a subsystem starts freeing.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    console::format::fmt_size,
+    memory::{frame::FRAME_SIZE, paging::PhysFrameAlloc},
+};
+
+/// Owners that physical frames are charged to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Subsystem {
+    /// Console history buffer.
+    Console,
+    /// The kernel's copy of the firmware memory map.
+    MemoryMap,
+    /// Free/reserved run storage for the runtime physical allocator.
+    AllocatorMetadata,
+    /// Page tables, including the fixmap table.
+    Paging,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 4] = [
+        Subsystem::Console,
+        Subsystem::MemoryMap,
+        Subsystem::AllocatorMetadata,
+        Subsystem::Paging,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Subsystem::Console => "console",
+            Subsystem::MemoryMap => "memory map",
+            Subsystem::AllocatorMetadata => "allocator metadata",
+            Subsystem::Paging => "paging",
+        }
+    }
+}
+
+static FRAMES: [AtomicU64; Subsystem::ALL.len()] =
+    [const { AtomicU64::new(0) }; Subsystem::ALL.len()];
+
+/// Record `frames` frames as owned by `subsystem`.
+pub fn charge(subsystem: Subsystem, frames: u64) {
+    FRAMES[subsystem as usize].fetch_add(frames, Ordering::Relaxed);
+}
+
+/// Frames currently charged to `subsystem`.
+pub fn frames(subsystem: Subsystem) -> u64 {
+    FRAMES[subsystem as usize].load(Ordering::Relaxed)
+}
+
+/// Print the frames and bytes charged to each subsystem.
+pub fn log() {
+    crate::println!("physical memory by subsystem:");
+    for subsystem in Subsystem::ALL {
+        let frames = frames(subsystem);
+        crate::println!(
+            "  {:<18} {:>6} frames ({})",
+            subsystem.name(),
+            frames,
+            fmt_size(frames * FRAME_SIZE)
+        );
+    }
+}
+
+/// Frame allocator adapter that charges every frame it hands out.
+pub struct Accounted<'a, A: PhysFrameAlloc> {
+    inner: &'a mut A,
+    subsystem: Subsystem,
+}
+
+impl<'a, A: PhysFrameAlloc> Accounted<'a, A> {
+    pub fn new(inner: &'a mut A, subsystem: Subsystem) -> Self {
+        Self { inner, subsystem }
+    }
+}
+
+impl<A: PhysFrameAlloc> PhysFrameAlloc for Accounted<'_, A> {
+    fn allocate_frame(&mut self) -> Option<u64> {
+        let frame = self.inner.allocate_frame()?;
+        charge(self.subsystem, 1);
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Counter(u64);
+
+    impl PhysFrameAlloc for Counter {
+        fn allocate_frame(&mut self) -> Option<u64> {
+            (self.0 > 0).then(|| {
+                self.0 -= 1;
+                self.0 * FRAME_SIZE
+            })
+        }
+    }
+
+    #[test]
+    fn accounted_allocator_charges_successful_allocations() {
+        let before = frames(Subsystem::Paging);
+        let mut inner = Counter(2);
+        let mut alloc = Accounted::new(&mut inner, Subsystem::Paging);
+
+        assert!(alloc.allocate_frame().is_some());
+        assert!(alloc.allocate_frame().is_some());
+        assert!(alloc.allocate_frame().is_none());
+        assert_eq!(frames(Subsystem::Paging) - before, 2);
+
+        charge(Subsystem::Console, 3);
+        assert!(frames(Subsystem::Console) >= 3);
+    }
+}
@@ -8,14 +8,19 @@
     reservation carving.
 */
 
+use crate::interrupts::{self, IrqGuard, without_interrupts};
 use crate::memory::{
     error::{PhysAllocError, PhysAllocInitError},
     frame::FRAME_SIZE,
     map::MemoryMapIter,
+    reservation::{self, Owners},
+    segments::Segments,
+    zone::{self, Zone},
 };
 use core::{
     cell::UnsafeCell,
     cmp::{max, min},
+    sync::atomic::{AtomicBool, Ordering},
 };
 use oxide_abi::{EfiMemoryType, MemoryMap};
 
@@ -35,10 +40,11 @@ impl PhysFrame {
 }
 
 /// Represents a region that must remain reserved and unavailable for allocation.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct ReservedRegion {
     pub start: u64,
     pub end: u64,
+    pub owners: Owners,
 }
 
 /// Capacities required to host allocator bookkeeping structures.
@@ -85,7 +91,10 @@ pub fn runtime_storage_plan(
     let boundary_count = conventional_regions
         .saturating_add(reservation_count)
         .saturating_mul(2);
-    let free_slots = boundary_count.max(conventional_regions);
+    // Runs are also split where they cross a zone boundary.
+    let free_slots = boundary_count
+        .max(conventional_regions)
+        .saturating_add(zone::ZONE_BOUNDARIES.len());
 
     let reserved_slots = reservation_count.saturating_add(conventional_regions.max(4));
 
@@ -106,12 +115,15 @@ pub fn runtime_storage_plan(
 
 struct AllocatorCell {
     inner: UnsafeCell<Option<PhysicalAllocator<'static>>>,
+    /// Set while a [`with`](Self::with) closure runs.
+    busy: AtomicBool,
 }
 
 impl AllocatorCell {
     const fn new() -> Self {
         Self {
             inner: UnsafeCell::new(None),
+            busy: AtomicBool::new(false),
         }
     }
 
@@ -119,26 +131,37 @@ impl AllocatorCell {
         &self,
         map: MemoryMap,
         reservations: &[ReservedRegion],
-        free_storage: &'static mut [Option<PhysFrame>],
-        reserved_storage: &'static mut [Option<ReservedRegion>],
+        free_storage: Segments<'static, Option<PhysFrame>>,
+        reserved_storage: Segments<'static, ReservedRegion>,
     ) -> Result<(), PhysAllocInitError> {
+        let _irq = IrqGuard::save();
         let slot = unsafe { &mut *self.inner.get() };
         if slot.is_some() {
             return Err(PhysAllocInitError::AlreadyInitialized);
         }
 
-        let allocator =
+        let mut allocator =
             PhysicalAllocator::from_memory_map(map, reservations, free_storage, reserved_storage)?;
+        allocator.set_low_threshold(super::pressure::threshold());
 
         *slot = Some(allocator);
         Ok(())
     }
 
+    /// Interrupts stay masked for the duration of `f`, so a handler cannot
+    /// observe the allocator mid-update on this CPU.
     fn with<R>(&self, f: impl FnOnce(&mut PhysicalAllocator<'static>) -> R) -> Option<R> {
-        unsafe {
+        without_interrupts(|| unsafe {
+            self.busy.store(true, Ordering::Relaxed);
             let slot = &mut *self.inner.get();
-            slot.as_mut().map(f)
-        }
+            let result = slot.as_mut().map(f);
+            self.busy.store(false, Ordering::Relaxed);
+            result
+        })
+    }
+
+    fn is_busy(&self) -> bool {
+        self.busy.load(Ordering::Relaxed)
     }
 }
 
@@ -150,17 +173,208 @@ static GLOBAL_ALLOCATOR: AllocatorCell = AllocatorCell::new();
 pub fn initialize_runtime_allocator(
     map: MemoryMap,
     reservations: &[ReservedRegion],
-    free_storage: &'static mut [Option<PhysFrame>],
-    reserved_storage: &'static mut [Option<ReservedRegion>],
+    free_storage: Segments<'static, Option<PhysFrame>>,
+    reserved_storage: Segments<'static, ReservedRegion>,
 ) -> Result<(), PhysAllocInitError> {
     GLOBAL_ALLOCATOR.initialize(map, reservations, free_storage, reserved_storage)
 }
 
+/// Deepest [`IrqGuard`] nesting a caller may already hold when it enters the
+/// runtime allocator: its own critical section, but nothing buried further.
+pub const MAX_MASKED_DEPTH: u32 = 1;
+
 /// Execute a closure with mutable access to the global physical allocator.
+///
+/// Interrupt handlers, callers nested more than [`MAX_MASKED_DEPTH`]
+/// critical sections deep, and closures re-entering the allocator are
+/// refused: once a spinlock guards the allocator, each of them could spin on
+/// a lock its own CPU holds. Debug builds panic; release builds return
+/// `None`. No handler allocates memory today.
 pub fn with_runtime_allocator<R>(
     f: impl FnOnce(&mut PhysicalAllocator<'static>) -> R,
 ) -> Option<R> {
-    GLOBAL_ALLOCATOR.with(f)
+    let access = access_policy(
+        interrupts::in_interrupt(),
+        interrupts::masked_depth(),
+        GLOBAL_ALLOCATOR.is_busy(),
+    );
+    if let Err(reason) = access {
+        debug_assert!(false, "runtime allocator: {}", reason);
+        return None;
+    }
+    let result = GLOBAL_ALLOCATOR.with(f);
+    if super::paranoid::ENABLED {
+        super::paranoid::check_canaries();
+    }
+    super::pressure::dispatch();
+    result
+}
+
+fn access_policy(in_interrupt: bool, masked_depth: u32, busy: bool) -> Result<(), &'static str> {
+    if in_interrupt {
+        return Err("called from an interrupt handler");
+    }
+    if busy {
+        return Err("re-entered from inside an allocator closure");
+    }
+    if masked_depth > MAX_MASKED_DEPTH {
+        return Err("called too many critical sections deep");
+    }
+    Ok(())
+}
+
+/// Log fragmentation metrics for the global physical allocator, if installed.
+pub fn log_fragmentation() {
+    if let Some(report) = with_runtime_allocator(|allocator| allocator.fragmentation()) {
+        report.log();
+    }
+}
+
+/// Free, allocated, and peak allocated frames of the global allocator, if
+/// installed.
+pub fn usage() -> Option<MemoryUsage> {
+    with_runtime_allocator(|allocator| allocator.usage())
+}
+
+/// Free memory held by one zone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ZoneStats {
+    pub free_frames: u64,
+    pub free_runs: usize,
+    pub largest_run: u64,
+}
+
+/// Print the free memory in each zone of the global allocator, if installed.
+pub fn log_zones() {
+    let Some(stats) = with_runtime_allocator(|allocator| allocator.zone_stats()) else {
+        return;
+    };
+    for zone in Zone::ALL {
+        let zone_stats = stats[zone.index()];
+        crate::println!(
+            "  zone {:<6} {} free frames in {} runs, largest {}",
+            zone,
+            zone_stats.free_frames,
+            zone_stats.free_runs,
+            zone_stats.largest_run
+        );
+    }
+}
+
+/// Merge adjacent free runs in the global allocator, returning how many merges
+/// happened. Cheap enough to run from an idle loop once one exists.
+pub fn coalesce_free_runs() -> Option<Result<usize, PhysAllocError>> {
+    with_runtime_allocator(|allocator| allocator.coalesce())
+}
+
+/// Number of power-of-two size classes in [`FragmentationReport::histogram`].
+pub const FRAGMENTATION_CLASSES: usize = 11;
+
+/// Snapshot of how the allocator's free memory is split into runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FragmentationReport {
+    pub free_frames: u64,
+    pub free_runs: usize,
+    /// Frames in the largest free run.
+    pub largest_run: u64,
+    /// `histogram[k]` counts free runs of `2^k` up to `2^(k+1) - 1` frames;
+    /// the last class also holds every larger run.
+    pub histogram: [usize; FRAGMENTATION_CLASSES],
+}
+
+impl FragmentationReport {
+    fn record(&mut self, frames: u64) {
+        self.free_frames += frames;
+        self.free_runs += 1;
+        self.largest_run = max(self.largest_run, frames);
+
+        let class = (frames.ilog2() as usize).min(FRAGMENTATION_CLASSES - 1);
+        self.histogram[class] += 1;
+    }
+
+    /// Share of free memory outside the largest run, from 0 (one contiguous
+    /// run) towards 100 (scattered single frames).
+    pub fn fragmentation_percent(&self) -> u64 {
+        if self.free_frames == 0 {
+            return 0;
+        }
+        100 - self.largest_run * 100 / self.free_frames
+    }
+
+    /// Print the metrics and the non-empty histogram classes.
+    pub fn log(&self) {
+        crate::println!(
+            "phys allocator: {} free frames in {} runs, largest {} frames, {}% fragmented",
+            self.free_frames,
+            self.free_runs,
+            self.largest_run,
+            self.fragmentation_percent()
+        );
+        for (class, &runs) in self.histogram.iter().enumerate() {
+            if runs == 0 {
+                continue;
+            }
+            let plus = if class == FRAGMENTATION_CLASSES - 1 {
+                "+"
+            } else {
+                ""
+            };
+            crate::println!("  {:>5}{} frames: {} runs", 1u64 << class, plus, runs);
+        }
+    }
+}
+
+/// Frame counts reported by [`PhysicalAllocator::usage`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub free_frames: u64,
+    /// Frames handed out and not yet freed.
+    pub allocated_frames: u64,
+    /// Highest `allocated_frames` seen since the allocator was built.
+    pub peak_allocated_frames: u64,
+}
+
+/// Usage counters and the low-memory threshold check.
+#[derive(Clone, Copy, Debug, Default)]
+struct Watermark {
+    usage: MemoryUsage,
+    /// Free frames below which pressure is raised; 0 disables it.
+    low_threshold: u64,
+    /// Pressure was raised and free memory has not recovered since.
+    low: bool,
+}
+
+impl Watermark {
+    /// Record an allocation; true when it is the one that crossed below the
+    /// threshold.
+    fn allocated(&mut self, frames: u64) -> bool {
+        let usage = &mut self.usage;
+        usage.free_frames = usage.free_frames.saturating_sub(frames);
+        usage.allocated_frames += frames;
+        usage.peak_allocated_frames = max(usage.peak_allocated_frames, usage.allocated_frames);
+
+        let crossed = !self.low && usage.free_frames < self.low_threshold;
+        self.low |= crossed;
+        crossed
+    }
+
+    fn freed(&mut self, frames: u64) {
+        self.usage.free_frames += frames;
+        self.usage.allocated_frames = self.usage.allocated_frames.saturating_sub(frames);
+        self.rearm();
+    }
+
+    /// Reset the free count after reservations changed it.
+    fn recount(&mut self, free_frames: u64) {
+        self.usage.free_frames = free_frames;
+        self.rearm();
+    }
+
+    fn rearm(&mut self) {
+        if self.usage.free_frames >= self.low_threshold {
+            self.low = false;
+        }
+    }
 }
 
 /// Describes the operations supported by the kernel's physical frame allocator.
@@ -171,11 +385,12 @@ pub struct PhysicalAllocator<'a> {
     free: FrameRunList<'a>,
     /// Regions that must remain reserved and cannot be handed out.
     reserved: ReservedList<'a>,
+    watermark: Watermark,
 }
 
 /// Backing storage wrapper for free frame runs.
 struct FrameRunList<'a> {
-    entries: &'a mut [Option<PhysFrame>],
+    entries: Segments<'a, Option<PhysFrame>>,
     len: usize,
 }
 
@@ -276,7 +491,7 @@ impl FrameSpan {
 }
 
 impl<'a> FrameRunList<'a> {
-    fn new(storage: &'a mut [Option<PhysFrame>]) -> Self {
+    fn new(mut storage: Segments<'a, Option<PhysFrame>>) -> Self {
         storage.fill(None);
         Self {
             entries: storage,
@@ -292,11 +507,12 @@ impl<'a> FrameRunList<'a> {
         self.len
     }
 
-    fn as_slice(&self) -> &[Option<PhysFrame>] {
-        self.entries
+    /// Record `frame`, split so that every run lies in a single zone.
+    fn push(&mut self, frame: PhysFrame) -> Result<(), PhysAllocError> {
+        zone::split(frame).try_for_each(|piece| self.push_run(piece))
     }
 
-    fn push(&mut self, frame: PhysFrame) -> Result<(), PhysAllocError> {
+    fn push_run(&mut self, frame: PhysFrame) -> Result<(), PhysAllocError> {
         if frame.count == 0 {
             return Ok(());
         }
@@ -331,7 +547,12 @@ impl<'a> FrameRunList<'a> {
         self.push_span(merged_span)
     }
 
-    fn allocate_count(&mut self, frames: u64) -> Result<Option<PhysFrame>, PhysAllocError> {
+    /// Take `frames` from the first run in `zone` large enough to hold them.
+    fn allocate_count(
+        &mut self,
+        frames: u64,
+        zone: Zone,
+    ) -> Result<Option<PhysFrame>, PhysAllocError> {
         if frames == 0 {
             return Err(PhysAllocError::UnsupportedFrameCount { frames });
         }
@@ -342,7 +563,7 @@ impl<'a> FrameRunList<'a> {
                 None => continue,
             };
 
-            if run.count < frames {
+            if run.count < frames || Zone::of(run.start) != zone {
                 continue;
             }
 
@@ -456,23 +677,58 @@ impl<'a> FrameRunList<'a> {
         Ok(())
     }
 
+    /// Merge runs that end exactly where another begins, returning the
+    /// number of merges. Pushing firmware descriptors and carving reservations
+    /// leave such neighbours behind; `insert` only merges overlapping runs.
+    /// Runs meeting at a zone boundary stay separate.
+    fn coalesce_adjacent(&mut self) -> Result<usize, PhysAllocError> {
+        let mut merged = 0;
+        let mut idx = 0;
+
+        while idx < self.entries.len() {
+            let Some(run) = self.entries[idx] else {
+                idx += 1;
+                continue;
+            };
+
+            let span = FrameSpan::from_frame(run)?;
+            let successor = self
+                .entries
+                .iter()
+                .position(|slot| matches!(slot, Some(other) if other.start == span.end))
+                .filter(|_| !zone::is_boundary(span.end));
+
+            match successor {
+                Some(next) => {
+                    let next_span = self.take_span(next)?;
+                    self.entries[idx] = Some(span.merge(next_span)?.into_frame()?);
+                    merged += 1;
+                }
+                None => idx += 1,
+            }
+        }
+
+        Ok(merged)
+    }
+
     fn iter(&self) -> FreeRegionIter<'_> {
         FreeRegionIter {
-            entries: self.as_slice(),
+            entries: &self.entries,
             index: 0,
         }
     }
 }
 
-/// Backing storage wrapper for reserved regions.
+/// Backing storage wrapper for reserved regions, kept sorted and disjoint
+/// by [`reservation::insert`].
 struct ReservedList<'a> {
-    entries: &'a mut [Option<ReservedRegion>],
+    entries: Segments<'a, ReservedRegion>,
     len: usize,
 }
 
 impl<'a> ReservedList<'a> {
-    fn new(storage: &'a mut [Option<ReservedRegion>]) -> Self {
-        storage.fill(None);
+    fn new(mut storage: Segments<'a, ReservedRegion>) -> Self {
+        storage.fill(ReservedRegion::default());
         Self {
             entries: storage,
             len: 0,
@@ -487,10 +743,7 @@ impl<'a> ReservedList<'a> {
         self.len
     }
 
-    fn as_slice(&self) -> &[Option<ReservedRegion>] {
-        self.entries
-    }
-
+    /// Record `region`, merging it with any reservation it overlaps.
     fn push(&mut self, region: ReservedRegion) -> Result<(), PhysAllocError> {
         if region.start >= region.end {
             return Err(PhysAllocError::InvalidRegion {
@@ -499,23 +752,17 @@ impl<'a> ReservedList<'a> {
             });
         }
 
-        for slot in self.entries.iter_mut() {
-            if slot.is_none() {
-                *slot = Some(region);
-                self.len += 1;
-                return Ok(());
-            }
-        }
-
-        Err(PhysAllocError::StorageExhausted {
-            capacity: self.capacity(),
-        })
+        let capacity = self.capacity();
+        reservation::insert(&mut self.entries, &mut self.len, region)
+            .map(|_| ())
+            .map_err(|_| PhysAllocError::StorageExhausted { capacity })
     }
 
     fn iter(&self) -> ReservedRegionIter<'_> {
         ReservedRegionIter {
-            entries: self.as_slice(),
+            entries: &self.entries,
             index: 0,
+            len: self.len,
         }
     }
 }
@@ -526,8 +773,8 @@ impl<'a> PhysicalAllocator<'a> {
     pub fn from_memory_map(
         map: MemoryMap,
         reservations: &[ReservedRegion],
-        free_storage: &'a mut [Option<PhysFrame>],
-        reserved_storage: &'a mut [Option<ReservedRegion>],
+        free_storage: Segments<'a, Option<PhysFrame>>,
+        reserved_storage: Segments<'a, ReservedRegion>,
     ) -> Result<Self, PhysAllocInitError> {
         if map.map_size == 0 || map.entry_count == 0 {
             return Err(PhysAllocInitError::Empty);
@@ -577,11 +824,14 @@ impl<'a> PhysicalAllocator<'a> {
             [("used", reserved_count), ("free", free_remaining)]
         );
 
-        Ok(Self {
+        let mut allocator = Self {
             map,
             free,
             reserved,
-        })
+            watermark: Watermark::default(),
+        };
+        allocator.recount_free();
+        Ok(allocator)
     }
 
     /// Allocate a single 4 KiB frame.
@@ -589,17 +839,55 @@ impl<'a> PhysicalAllocator<'a> {
         self.allocate_order(0)
     }
 
-    /// Allocate `2^order` contiguous frames (order 0 = 1 frame, order 9 = 512 frames / 2 MiB).
+    /// Allocate `2^order` contiguous frames (order 0 = 1 frame, order 9 = 512 frames / 2 MiB),
+    /// trying the zones in [`Zone::DEFAULT_ORDER`].
     pub fn allocate_order(&mut self, order: u8) -> Result<PhysFrame, PhysAllocError> {
+        self.allocate_from(order, Zone::DEFAULT_ORDER)
+    }
+
+    /// Allocate `2^order` contiguous frames that a device limited to `zone`
+    /// can address: from `zone` itself, or failing that a lower zone.
+    pub fn allocate_in(&mut self, zone: Zone, order: u8) -> Result<PhysFrame, PhysAllocError> {
+        self.allocate_from(order, zone.fallbacks())
+    }
+
+    fn allocate_from(
+        &mut self,
+        order: u8,
+        zones: impl IntoIterator<Item = Zone>,
+    ) -> Result<PhysFrame, PhysAllocError> {
         let frames = match 1u64.checked_shl(order as u32) {
             Some(count) if count > 0 => count,
             _ => return Err(PhysAllocError::UnsupportedFrameCount { frames: 0 }),
         };
+        if super::failtest::frame_alloc_fails() {
+            return Err(PhysAllocError::OutOfMemory);
+        }
 
-        match self.free.allocate_count(frames)? {
-            Some(frame) => Ok(frame),
-            None => Err(PhysAllocError::OutOfMemory),
+        let mut found = None;
+        for zone in zones {
+            found = self.free.allocate_count(frames, zone)?;
+            if found.is_some() {
+                break;
+            }
+        }
+        let frame = found.ok_or(PhysAllocError::OutOfMemory)?;
+        let reserved = &self.reserved;
+        super::sanitize::verify(
+            "runtime allocator",
+            &self.map,
+            frame.start,
+            frame.count,
+            |start, end| {
+                reserved
+                    .iter()
+                    .find(|region| region.start < end && start < region.end)
+            },
+        );
+        if self.watermark.allocated(frame.count) {
+            super::pressure::raise();
         }
+        Ok(frame)
     }
 
     /// Free a previously allocated run of frames.
@@ -608,13 +896,100 @@ impl<'a> PhysicalAllocator<'a> {
             return Ok(());
         }
 
-        self.free.insert(frame)
+        self.free.insert(frame)?;
+        self.watermark.freed(frame.count);
+        Ok(())
     }
 
     /// Mark an arbitrary region as reserved after initialization.
     pub fn reserve(&mut self, region: ReservedRegion) -> Result<(), PhysAllocError> {
         self.reserved.push(region)?;
-        self.free.subtract_range(region.start, region.end)
+        self.free.subtract_range(region.start, region.end)?;
+        self.recount_free();
+        Ok(())
+    }
+
+    /// The kernel's copy of the firmware memory map.
+    pub fn memory_map(&self) -> MemoryMap {
+        self.map
+    }
+
+    /// Frame counts, including the peak number of frames allocated at once.
+    pub fn usage(&self) -> MemoryUsage {
+        self.watermark.usage
+    }
+
+    /// Raise low-memory pressure once fewer than `frames` remain free;
+    /// 0 disables it.
+    pub fn set_low_threshold(&mut self, frames: u64) {
+        self.watermark.low_threshold = frames;
+        self.watermark.rearm();
+    }
+
+    fn recount_free(&mut self) {
+        let free_frames = self.free_regions().map(|run| run.count).sum();
+        self.watermark.recount(free_frames);
+    }
+
+    /// Merge adjacent free runs, returning how many merges happened.
+    pub fn coalesce(&mut self) -> Result<usize, PhysAllocError> {
+        self.free.coalesce_adjacent()
+    }
+
+    /// Summarise the free runs: totals, largest run, and a size histogram.
+    pub fn fragmentation(&self) -> FragmentationReport {
+        let mut report = FragmentationReport::default();
+        for run in self.free_regions() {
+            report.record(run.count);
+        }
+        report
+    }
+
+    /// Free frames, runs, and largest run in each zone, indexed by
+    /// [`Zone::index`].
+    pub fn zone_stats(&self) -> [ZoneStats; Zone::COUNT] {
+        let mut stats = [ZoneStats::default(); Zone::COUNT];
+        for run in self.free_regions() {
+            let zone = &mut stats[Zone::of(run.start).index()];
+            zone.free_frames += run.count;
+            zone.free_runs += 1;
+            zone.largest_run = max(zone.largest_run, run.count);
+        }
+        stats
+    }
+
+    /// Whether every byte of `[start, end)` lies in free runs, which may be
+    /// split across several uncoalesced neighbours. Empty ranges are not free.
+    pub fn is_free(&self, start: u64, end: u64) -> bool {
+        if start >= end {
+            return false;
+        }
+
+        let mut cursor = start;
+        while cursor < end {
+            let run_end = self.free_regions().find_map(|run| {
+                let run_end = span_end(run.start, run.count)?;
+                (run.start <= cursor && cursor < run_end).then_some(run_end)
+            });
+            match run_end {
+                Some(run_end) => cursor = run_end,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Owners of the reservation containing `addr`, or `None` if no
+    /// reservation covers it.
+    pub fn owner_of(&self, addr: u64) -> Option<Owners> {
+        self.reserved_regions()
+            .find(|region| region.start <= addr && addr < region.end)
+            .map(|region| region.owners)
+    }
+
+    /// The free run with the most frames, or `None` when nothing is free.
+    pub fn largest_free_run(&self) -> Option<PhysFrame> {
+        self.free_regions().max_by_key(|run| run.count)
     }
 
     /// Iterate over all free ranges currently tracked by the allocator.
@@ -628,9 +1003,9 @@ impl<'a> PhysicalAllocator<'a> {
     }
 }
 
-/// Iterator over free regions. Placeholder until the backing store is decided.
+/// Iterator over free regions.
 pub struct FreeRegionIter<'a> {
-    entries: &'a [Option<PhysFrame>],
+    entries: &'a Segments<'a, Option<PhysFrame>>,
     index: usize,
 }
 
@@ -649,24 +1024,23 @@ impl<'a> Iterator for FreeRegionIter<'a> {
     }
 }
 
-/// Iterator over reserved regions.
+/// Iterator over reserved regions, in address order.
 pub struct ReservedRegionIter<'a> {
-    entries: &'a [Option<ReservedRegion>],
+    entries: &'a Segments<'a, ReservedRegion>,
     index: usize,
+    len: usize,
 }
 
 impl<'a> Iterator for ReservedRegionIter<'a> {
     type Item = ReservedRegion;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while self.index < self.entries.len() {
-            let item = self.entries[self.index];
-            self.index += 1;
-            if let Some(region) = item {
-                return Some(region);
-            }
+        if self.index >= self.len {
+            return None;
         }
-        None
+        let region = self.entries[self.index];
+        self.index += 1;
+        Some(region)
     }
 }
 
@@ -735,6 +1109,15 @@ mod tests {
         (map, backing)
     }
 
+    #[test]
+    fn access_policy_refuses_handlers_reentry_and_deep_nesting() {
+        assert_eq!(access_policy(false, 0, false), Ok(()));
+        assert_eq!(access_policy(false, MAX_MASKED_DEPTH, false), Ok(()));
+        assert!(access_policy(true, 0, false).is_err());
+        assert!(access_policy(false, 0, true).is_err());
+        assert!(access_policy(false, MAX_MASKED_DEPTH + 1, false).is_err());
+    }
+
     #[test]
     fn runtime_storage_plan_errors_on_empty_map() {
         let map = MemoryMap {
@@ -761,7 +1144,7 @@ mod tests {
         let (map, _backing) = build_map(descriptors);
 
         let plan = runtime_storage_plan(&map, 3).unwrap();
-        assert_eq!(plan.free_slots, (2 + 3) * 2);
+        assert_eq!(plan.free_slots, (2 + 3) * 2 + zone::ZONE_BOUNDARIES.len());
         assert_eq!(plan.reserved_slots, 3 + 4);
     }
 
@@ -788,7 +1171,7 @@ mod tests {
     #[test]
     fn frame_run_list_insert_coalesces_runs() {
         let mut storage = vec![None; 4];
-        let mut runs = FrameRunList::new(&mut storage);
+        let mut runs = FrameRunList::new(storage.as_mut_slice().into());
         runs.insert(PhysFrame::new(FRAME_SIZE, 2)).unwrap();
         runs.insert(PhysFrame::new(FRAME_SIZE * 2, 2)).unwrap();
 
@@ -801,10 +1184,10 @@ mod tests {
     #[test]
     fn frame_run_list_allocate_and_split() {
         let mut storage = vec![None; 2];
-        let mut runs = FrameRunList::new(&mut storage);
+        let mut runs = FrameRunList::new(storage.as_mut_slice().into());
         runs.insert(PhysFrame::new(FRAME_SIZE, 4)).unwrap();
 
-        let alloc = runs.allocate_count(2).unwrap();
+        let alloc = runs.allocate_count(2, Zone::Low).unwrap();
         assert_eq!(alloc.unwrap().start, FRAME_SIZE);
         let remaining: Vec<_> = runs.iter().collect();
         assert_eq!(remaining.len(), 1);
@@ -815,7 +1198,7 @@ mod tests {
     #[test]
     fn frame_run_list_subtract_splits_range() {
         let mut storage = vec![None; 4];
-        let mut runs = FrameRunList::new(&mut storage);
+        let mut runs = FrameRunList::new(storage.as_mut_slice().into());
         runs.insert(PhysFrame::new(FRAME_SIZE, 4)).unwrap();
 
         runs.subtract_range(FRAME_SIZE * 2, FRAME_SIZE * 3).unwrap();
@@ -829,10 +1212,10 @@ mod tests {
     #[test]
     fn frame_run_list_rejects_zero_allocation() {
         let mut storage = vec![None; 1];
-        let mut runs = FrameRunList::new(&mut storage);
+        let mut runs = FrameRunList::new(storage.as_mut_slice().into());
         runs.insert(PhysFrame::new(FRAME_SIZE, 1)).unwrap();
         assert_eq!(
-            runs.allocate_count(0).unwrap_err(),
+            runs.allocate_count(0, Zone::Low).unwrap_err(),
             PhysAllocError::UnsupportedFrameCount { frames: 0 }
         );
     }
@@ -844,15 +1227,16 @@ mod tests {
         let reservations = [ReservedRegion {
             start: FRAME_SIZE * 2,
             end: FRAME_SIZE * 3,
+            owners: Owners::BOOT,
         }];
         let mut free_storage = vec![None; 8];
-        let mut reserved_storage = vec![None; 8];
+        let mut reserved_storage = vec![ReservedRegion::default(); 8];
 
         let mut allocator = PhysicalAllocator::from_memory_map(
             map,
             &reservations,
-            free_storage.as_mut_slice(),
-            reserved_storage.as_mut_slice(),
+            free_storage.as_mut_slice().into(),
+            reserved_storage.as_mut_slice().into(),
         )
         .unwrap();
 
@@ -869,6 +1253,7 @@ mod tests {
             .reserve(ReservedRegion {
                 start: FRAME_SIZE * 3,
                 end: FRAME_SIZE * 4,
+                owners: Owners::FRAMEBUFFER,
             })
             .unwrap();
         let mut remaining: Vec<_> = allocator.free_regions().collect();
@@ -878,6 +1263,164 @@ mod tests {
         assert_eq!(remaining[1], PhysFrame::new(FRAME_SIZE * 4, 1));
     }
 
+    #[test]
+    fn coalesce_merges_adjacent_runs_and_reports_fragmentation() {
+        let descriptors = vec![
+            descriptor(EfiMemoryType::ConventionalMemory, FRAME_SIZE * 4, 4),
+            descriptor(EfiMemoryType::ConventionalMemory, FRAME_SIZE, 3),
+            descriptor(EfiMemoryType::ConventionalMemory, FRAME_SIZE * 16, 1),
+        ];
+        let (map, _backing) = build_map(descriptors);
+        let mut free_storage = vec![None; 8];
+        let mut reserved_storage = vec![ReservedRegion::default(); 8];
+        let mut allocator = PhysicalAllocator::from_memory_map(
+            map,
+            &[],
+            free_storage.as_mut_slice().into(),
+            reserved_storage.as_mut_slice().into(),
+        )
+        .unwrap();
+
+        let before = allocator.fragmentation();
+        assert_eq!(before.free_runs, 3);
+        assert_eq!(before.largest_run, 4);
+
+        assert_eq!(allocator.coalesce().unwrap(), 1);
+        assert_eq!(allocator.coalesce().unwrap(), 0);
+
+        let after = allocator.fragmentation();
+        assert_eq!(after.free_frames, 8);
+        assert_eq!(after.free_runs, 2);
+        assert_eq!(after.largest_run, 7);
+        assert_eq!(after.fragmentation_percent(), 13);
+        assert_eq!(after.histogram[0], 1);
+        assert_eq!(after.histogram[2], 1);
+        assert_eq!(FragmentationReport::default().fragmentation_percent(), 0);
+    }
+
+    #[test]
+    fn range_queries_follow_free_and_reserved_lists() {
+        let descriptors = vec![
+            descriptor(EfiMemoryType::ConventionalMemory, FRAME_SIZE, 3),
+            descriptor(EfiMemoryType::ConventionalMemory, FRAME_SIZE * 4, 4),
+        ];
+        let (map, _backing) = build_map(descriptors);
+        let mut free_storage = vec![None; 8];
+        let mut reserved_storage = vec![ReservedRegion::default(); 8];
+        let reservations = [ReservedRegion {
+            start: FRAME_SIZE * 6,
+            end: FRAME_SIZE * 7,
+            owners: Owners::CONSOLE,
+        }];
+        let allocator = PhysicalAllocator::from_memory_map(
+            map,
+            &reservations,
+            free_storage.as_mut_slice().into(),
+            reserved_storage.as_mut_slice().into(),
+        )
+        .unwrap();
+
+        // Spans the uncoalesced boundary between the two descriptors.
+        assert!(allocator.is_free(FRAME_SIZE * 2, FRAME_SIZE * 6));
+        assert!(!allocator.is_free(FRAME_SIZE * 5, FRAME_SIZE * 7));
+        assert!(!allocator.is_free(0, FRAME_SIZE * 2));
+        assert!(!allocator.is_free(FRAME_SIZE * 2, FRAME_SIZE * 2));
+
+        assert_eq!(
+            allocator.owner_of(FRAME_SIZE * 6 + 8),
+            Some(Owners::CONSOLE)
+        );
+        assert_eq!(allocator.owner_of(FRAME_SIZE * 7), None);
+        assert_eq!(
+            allocator.largest_free_run(),
+            Some(PhysFrame::new(FRAME_SIZE, 3))
+        );
+    }
+
+    #[test]
+    fn watermark_tracks_peak_and_raises_pressure_once_per_crossing() {
+        let descriptors = vec![descriptor(EfiMemoryType::ConventionalMemory, FRAME_SIZE, 8)];
+        let (map, _backing) = build_map(descriptors);
+        let mut free_storage = vec![None; 8];
+        let mut reserved_storage = vec![ReservedRegion::default(); 8];
+        let mut allocator = PhysicalAllocator::from_memory_map(
+            map,
+            &[],
+            free_storage.as_mut_slice().into(),
+            reserved_storage.as_mut_slice().into(),
+        )
+        .unwrap();
+        assert_eq!(allocator.usage().free_frames, 8);
+
+        let run = allocator.allocate_order(2).unwrap();
+        let single = allocator.allocate().unwrap();
+        allocator.free(run).unwrap();
+        assert_eq!(
+            allocator.usage(),
+            MemoryUsage {
+                free_frames: 7,
+                allocated_frames: 1,
+                peak_allocated_frames: 5,
+            }
+        );
+        allocator.free(single).unwrap();
+
+        let mut watermark = Watermark {
+            low_threshold: 4,
+            ..Watermark::default()
+        };
+        watermark.recount(8);
+        assert!(!watermark.allocated(4));
+        assert!(watermark.allocated(1));
+        assert!(!watermark.allocated(1));
+        watermark.freed(2);
+        assert!(watermark.allocated(1));
+    }
+
+    #[test]
+    fn zoned_requests_stay_within_reach() {
+        const MIB: u64 = 1024 * 1024;
+        let descriptors = vec![
+            descriptor(EfiMemoryType::ConventionalMemory, MIB - 2 * FRAME_SIZE, 4),
+            descriptor(EfiMemoryType::ConventionalMemory, 4096 * MIB, 2),
+        ];
+        let (map, _backing) = build_map(descriptors);
+        let mut free_storage = vec![None; 8];
+        let mut reserved_storage = vec![ReservedRegion::default(); 8];
+        let mut allocator = PhysicalAllocator::from_memory_map(
+            map,
+            &[],
+            free_storage.as_mut_slice().into(),
+            reserved_storage.as_mut_slice().into(),
+        )
+        .unwrap();
+
+        let stats = allocator.zone_stats();
+        assert_eq!(stats.map(|zone| zone.free_frames), [2, 2, 2]);
+
+        // Ordinary requests leave the first megabyte for last.
+        assert_eq!(allocator.allocate_order(1).unwrap().start, MIB);
+        assert_eq!(allocator.allocate_order(1).unwrap().start, 4096 * MIB);
+
+        assert_eq!(
+            allocator.allocate_in(Zone::Dma32, 0).unwrap().start,
+            MIB - 2 * FRAME_SIZE
+        );
+        assert_eq!(
+            allocator.allocate_in(Zone::Low, 1).unwrap_err(),
+            PhysAllocError::OutOfMemory
+        );
+
+        // Freed neighbours across the 1 MiB boundary are not merged.
+        allocator
+            .free(PhysFrame::new(MIB - 2 * FRAME_SIZE, 1))
+            .unwrap();
+        allocator.free(PhysFrame::new(MIB, 2)).unwrap();
+        allocator.coalesce().unwrap();
+        assert_eq!(allocator.zone_stats()[Zone::Low.index()].free_runs, 1);
+        assert_eq!(allocator.zone_stats()[Zone::Dma32.index()].largest_run, 2);
+    }
+
     #[test]
     fn align_helpers_behave_as_expected() {
         assert_eq!(align_down(FRAME_SIZE * 3 + 123), FRAME_SIZE * 3);
@@ -0,0 +1,208 @@
+//! Runtime-services protections from the firmware's memory attributes table.
+//!
+//! The loader copies `EFI_MEMORY_ATTRIBUTES_TABLE` and passes it as
+//! `BootAbi::memory_attributes`. Each descriptor covers part of a runtime
+//! image; `EFI_MEMORY_RO` marks its code and read-only data, `EFI_MEMORY_XP`
+//! its data. Once identity paging is up, [`apply`] maps those pages
+//! read-only or no-execute instead of leaving them read-write-execute, so a
+//! stray kernel write cannot patch firmware code and a stray jump cannot run
+//! firmware data. `EFI_MEMORY_RP` (read-protect) is not honored; the regions
+//! stay readable.
+
+use oxide_abi::{MemoryDescriptor, PhysRange};
+
+use super::paging::{self, PhysFrameAlloc, Protection};
+use crate::console::format::fmt_hex;
+
+const EFI_MEMORY_XP: u64 = 0x0000_0000_0000_4000;
+const EFI_MEMORY_RO: u64 = 0x0000_0000_0002_0000;
+
+/// Header: version, entry count, descriptor size, flags.
+const HEADER_LEN: usize = 16;
+const PAGE_SIZE: u64 = 4096;
+
+/// Why the attributes table was ignored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttributesError {
+    /// The table is shorter than its header says.
+    Truncated,
+    /// Unknown version, or descriptors smaller than `MemoryDescriptor`.
+    BadHeader,
+}
+
+/// One region and the restrictions the firmware asks for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Region {
+    start: u64,
+    end: u64,
+    protection: Protection,
+}
+
+/// What [`apply`] did, for the boot log.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub regions: usize,
+    pub pages: u64,
+}
+
+/// Restrict the runtime regions described by `table` in the page tables
+/// rooted at `pml4_phys`.
+///
+/// `no_execute` says whether `EFER.NXE` is on; without it `EFI_MEMORY_XP`
+/// is skipped, since the NX bit would be reserved.
+///
+/// # Safety
+/// `table` must be the loader's copy (validated at handoff and identity
+/// mapped), and `pml4_phys` must be the live, identity-accessible PML4.
+pub unsafe fn apply<A: PhysFrameAlloc>(
+    alloc: &mut A,
+    pml4_phys: u64,
+    table: PhysRange,
+    no_execute: bool,
+) -> Result<Summary, AttributesError> {
+    // SAFETY: guaranteed by the caller.
+    let bytes = unsafe { core::slice::from_raw_parts(table.phys as *const u8, table.len as usize) };
+
+    let mut summary = Summary::default();
+    for region in regions(bytes)? {
+        let protection = Protection {
+            no_execute: region.protection.no_execute && no_execute,
+            ..region.protection
+        };
+        if protection == Protection::default() {
+            continue;
+        }
+        // SAFETY: guaranteed by the caller; the kernel never writes to or
+        // executes from runtime-services memory.
+        match unsafe {
+            paging::protect_range(alloc, pml4_phys, region.start, region.end, protection)
+        } {
+            Ok(pages) => {
+                summary.regions += 1;
+                summary.pages += pages;
+            }
+            Err(err) => crate::println!(
+                "memory attributes: {}..{} left unprotected: {:?}",
+                fmt_hex(region.start, 16),
+                fmt_hex(region.end, 16),
+                err
+            ),
+        }
+    }
+    Ok(summary)
+}
+
+/// Parse the table into regions, rejecting it as a whole if the header is
+/// inconsistent.
+fn regions(table: &[u8]) -> Result<impl Iterator<Item = Region> + '_, AttributesError> {
+    let word = |index: usize| -> Result<u32, AttributesError> {
+        table
+            .get(index * 4..index * 4 + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .ok_or(AttributesError::Truncated)
+    };
+    let version = word(0)?;
+    let count = word(1)? as usize;
+    let descriptor_size = word(2)? as usize;
+
+    if !(1..=2).contains(&version) || descriptor_size < size_of::<MemoryDescriptor>() {
+        return Err(AttributesError::BadHeader);
+    }
+    let len = count
+        .checked_mul(descriptor_size)
+        .and_then(|bytes| bytes.checked_add(HEADER_LEN))
+        .ok_or(AttributesError::Truncated)?;
+    if len > table.len() {
+        return Err(AttributesError::Truncated);
+    }
+
+    Ok((0..count).filter_map(move |index| {
+        let offset = HEADER_LEN + index * descriptor_size;
+        // SAFETY: the length check above keeps every descriptor in bounds.
+        let descriptor = unsafe {
+            table
+                .as_ptr()
+                .add(offset)
+                .cast::<MemoryDescriptor>()
+                .read_unaligned()
+        };
+        let end = descriptor
+            .number_of_pages
+            .checked_mul(PAGE_SIZE)
+            .and_then(|bytes| descriptor.physical_start.checked_add(bytes))?;
+        Some(Region {
+            start: descriptor.physical_start,
+            end,
+            protection: Protection {
+                read_only: descriptor.attribute & EFI_MEMORY_RO != 0,
+                no_execute: descriptor.attribute & EFI_MEMORY_XP != 0,
+            },
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn table(version: u32, descriptor_size: u32, descriptors: &[(u64, u64, u64)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for word in [version, descriptors.len() as u32, descriptor_size, 0] {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        for &(start, pages, attribute) in descriptors {
+            let mut entry = [0u8; 48];
+            entry[8..16].copy_from_slice(&start.to_le_bytes());
+            entry[24..32].copy_from_slice(&pages.to_le_bytes());
+            entry[32..40].copy_from_slice(&attribute.to_le_bytes());
+            bytes.extend_from_slice(&entry[..descriptor_size as usize]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn regions_follow_descriptor_size_and_attributes() {
+        let bytes = table(
+            2,
+            48,
+            &[
+                (0x7F00_0000, 2, EFI_MEMORY_RO),
+                (0x7F00_2000, 1, EFI_MEMORY_XP),
+            ],
+        );
+        let parsed: Vec<_> = regions(&bytes).unwrap().collect();
+        assert_eq!(
+            parsed,
+            [
+                Region {
+                    start: 0x7F00_0000,
+                    end: 0x7F00_2000,
+                    protection: Protection {
+                        read_only: true,
+                        no_execute: false,
+                    },
+                },
+                Region {
+                    start: 0x7F00_2000,
+                    end: 0x7F00_3000,
+                    protection: Protection {
+                        read_only: false,
+                        no_execute: true,
+                    },
+                },
+            ]
+        );
+
+        assert_eq!(
+            regions(&table(1, 32, &[(0, 1, 0)])).err(),
+            Some(AttributesError::BadHeader)
+        );
+        assert_eq!(
+            regions(&bytes[..bytes.len() - 1]).err(),
+            Some(AttributesError::Truncated)
+        );
+    }
+}
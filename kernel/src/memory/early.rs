@@ -3,11 +3,16 @@ use core::cell::UnsafeCell;
 use oxide_abi::{EfiMemoryType, MemoryMap};
 
 use crate::memory::{
-    allocator::ReservedRegion, error::MemoryInitError, frame::FRAME_SIZE, map::MemoryMapIter,
+    allocator::ReservedRegion,
+    error::MemoryInitError,
+    frame::FRAME_SIZE,
+    map::MemoryMapIter,
+    reservation::{self, Owners},
 };
 
-const MAX_EARLY_RESERVATIONS: usize = 16;
+pub(crate) const MAX_EARLY_RESERVATIONS: usize = 16;
 
+/// Early reservations, sorted and disjoint under the shared overlap policy.
 struct ReservationList {
     entries: [ReservedRegion; MAX_EARLY_RESERVATIONS],
     len: usize,
@@ -16,41 +21,23 @@ struct ReservationList {
 impl ReservationList {
     const fn new() -> Self {
         Self {
-            entries: [ReservedRegion { start: 0, end: 0 }; MAX_EARLY_RESERVATIONS],
+            entries: [ReservedRegion {
+                start: 0,
+                end: 0,
+                owners: Owners::NONE,
+            }; MAX_EARLY_RESERVATIONS],
             len: 0,
         }
     }
 
-    fn push(&mut self, region: ReservedRegion) -> Result<(), MemoryInitError> {
+    /// Record `region`, merging it with any reservation it overlaps.
+    fn push(&mut self, region: ReservedRegion) -> Result<ReservedRegion, MemoryInitError> {
         if region.start >= region.end {
             return Err(MemoryInitError::TooLarge);
         }
 
-        if self.len >= MAX_EARLY_RESERVATIONS {
-            return Err(MemoryInitError::TooLarge);
-        }
-
-        // Keep reservations sorted by start for predictable iteration.
-        let mut index = self.len;
-        for i in 0..self.len {
-            if self.entries[i].start > region.start {
-                index = i;
-                break;
-            }
-        }
-
-        // Shift elements to make room when inserting in the middle.
-        if index < self.len {
-            let mut j = self.len;
-            while j > index {
-                self.entries[j] = self.entries[j - 1];
-                j -= 1;
-            }
-        }
-
-        self.entries[index] = region;
-        self.len += 1;
-        Ok(())
+        reservation::insert(&mut self.entries, &mut self.len, region)
+            .map_err(|_| MemoryInitError::TooLarge)
     }
 
     fn overlaps(&self, region: ReservedRegion) -> Option<ReservedRegion> {
@@ -81,13 +68,21 @@ unsafe impl Sync for ReservationCell {}
 static EARLY_RESERVATIONS: ReservationCell =
     ReservationCell(UnsafeCell::new(ReservationList::new()));
 
-/// Allocate a physical region during early boot and record it as reserved.
-pub fn allocate_region(map: &MemoryMap, bytes: usize) -> Result<ReservedRegion, MemoryInitError> {
+/// Allocate a physical region during early boot and record it as reserved
+/// on behalf of `owners`.
+pub fn allocate_region(
+    map: &MemoryMap,
+    bytes: usize,
+    owners: Owners,
+) -> Result<ReservedRegion, MemoryInitError> {
     if bytes == 0 {
         return Err(MemoryInitError::TooLarge);
     }
 
     let alloc_bytes = align_up(bytes as u64, FRAME_SIZE).ok_or(MemoryInitError::TooLarge)?;
+    if super::failtest::frame_alloc_fails() {
+        return Err(MemoryInitError::OutOfFrames);
+    }
 
     let iter = MemoryMapIter::new(map);
     for descriptor in iter {
@@ -122,6 +117,7 @@ pub fn allocate_region(map: &MemoryMap, bytes: usize) -> Result<ReservedRegion,
             let candidate_region = ReservedRegion {
                 start: candidate,
                 end,
+                owners,
             };
 
             if let Some(existing) = find_overlap(candidate_region) {
@@ -129,21 +125,36 @@ pub fn allocate_region(map: &MemoryMap, bytes: usize) -> Result<ReservedRegion,
                 continue;
             }
 
-            unsafe {
-                reserve(candidate_region)?;
-            }
-            return Ok(candidate_region);
+            return unsafe { reserve(candidate_region) };
         }
     }
 
     Err(MemoryInitError::OutOfFrames)
 }
 
+/// Reserve a fixed physical range for `owners`, widened to whole frames.
+///
+/// A range overlapping existing reservations is merged with them; the
+/// merged reservation is returned. Fails only when the list is full.
+pub fn reserve_range(
+    start: u64,
+    end: u64,
+    owners: Owners,
+) -> Result<ReservedRegion, MemoryInitError> {
+    let region = ReservedRegion {
+        start: start & !(FRAME_SIZE - 1),
+        end: align_up(end, FRAME_SIZE).ok_or(MemoryInitError::TooLarge)?,
+        owners,
+    };
+
+    unsafe { reserve(region) }
+}
+
 pub(crate) fn contains_address(addr: u64) -> Option<ReservedRegion> {
     unsafe { (*EARLY_RESERVATIONS.0.get()).contains(addr) }
 }
 
-/// Iterate over all early reservations in insertion order.
+/// Iterate over all early reservations in address order.
 pub fn for_each<F>(mut f: F)
 where
     F: FnMut(ReservedRegion),
@@ -154,16 +165,20 @@ where
     }
 }
 
-unsafe fn reserve(region: ReservedRegion) -> Result<(), MemoryInitError> {
+unsafe fn reserve(region: ReservedRegion) -> Result<ReservedRegion, MemoryInitError> {
     let list = unsafe { &mut *EARLY_RESERVATIONS.0.get() };
-
-    if list.overlaps(region).is_some() {
-        return Err(MemoryInitError::TooLarge);
-    }
-
     list.push(region)
 }
 
+/// First early reservation intersecting `[start, end)`, if any.
+pub(crate) fn overlapping(start: u64, end: u64) -> Option<ReservedRegion> {
+    find_overlap(ReservedRegion {
+        start,
+        end,
+        owners: Owners::NONE,
+    })
+}
+
 fn find_overlap(region: ReservedRegion) -> Option<ReservedRegion> {
     unsafe { (*EARLY_RESERVATIONS.0.get()).overlaps(region) }
 }
@@ -227,16 +242,19 @@ mod tests {
         list.push(ReservedRegion {
             start: FRAME_SIZE * 3,
             end: FRAME_SIZE * 4,
+            owners: Owners::NONE,
         })
         .unwrap();
         list.push(ReservedRegion {
             start: FRAME_SIZE,
             end: FRAME_SIZE * 2,
+            owners: Owners::NONE,
         })
         .unwrap();
         list.push(ReservedRegion {
             start: FRAME_SIZE * 5,
             end: FRAME_SIZE * 6,
+            owners: Owners::NONE,
         })
         .unwrap();
 
@@ -253,6 +271,7 @@ mod tests {
             list.push(ReservedRegion {
                 start: FRAME_SIZE * (idx as u64 + 1),
                 end: FRAME_SIZE * (idx as u64 + 2),
+                owners: Owners::NONE,
             })
             .unwrap();
         }
@@ -260,6 +279,7 @@ mod tests {
         let overflow = list.push(ReservedRegion {
             start: FRAME_SIZE * 100,
             end: FRAME_SIZE * 101,
+            owners: Owners::NONE,
         });
         assert_eq!(overflow, Err(MemoryInitError::TooLarge));
     }
@@ -270,8 +290,8 @@ mod tests {
         let descriptors = vec![descriptor(EfiMemoryType::ConventionalMemory, FRAME_SIZE, 8)];
         let (map, _backing) = build_map(descriptors);
 
-        let first = allocate_region(&map, FRAME_SIZE as usize).unwrap();
-        let second = allocate_region(&map, FRAME_SIZE as usize).unwrap();
+        let first = allocate_region(&map, FRAME_SIZE as usize, Owners::CONSOLE).unwrap();
+        let second = allocate_region(&map, FRAME_SIZE as usize, Owners::CONSOLE).unwrap();
 
         assert_eq!(first.start, FRAME_SIZE);
         assert_eq!(first.end, FRAME_SIZE * 2);
@@ -284,13 +304,39 @@ mod tests {
         reset_reservations();
     }
 
+    #[test]
+    fn reservation_list_merges_overlaps() {
+        let mut list = ReservationList::new();
+        list.push(ReservedRegion {
+            start: FRAME_SIZE,
+            end: FRAME_SIZE * 3,
+            owners: Owners::COMMAND_LINE,
+        })
+        .unwrap();
+        let merged = list.push(ReservedRegion {
+            start: FRAME_SIZE * 2,
+            end: FRAME_SIZE * 4,
+            owners: Owners::BAD_MEMORY,
+        });
+
+        assert_eq!(
+            merged,
+            Ok(ReservedRegion {
+                start: FRAME_SIZE,
+                end: FRAME_SIZE * 4,
+                owners: Owners::COMMAND_LINE.union(Owners::BAD_MEMORY),
+            })
+        );
+        assert_eq!(list.iter().count(), 1);
+    }
+
     #[test]
     fn allocate_region_rejects_zero_bytes() {
         reset_reservations();
         let descriptors = vec![descriptor(EfiMemoryType::ConventionalMemory, FRAME_SIZE, 1)];
         let (map, _backing) = build_map(descriptors);
 
-        let result = allocate_region(&map, 0);
+        let result = allocate_region(&map, 0, Owners::CONSOLE);
         assert_eq!(result, Err(MemoryInitError::TooLarge));
         reset_reservations();
     }
@@ -1,3 +1,5 @@
+use crate::console::format::fmt_hex;
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum PagingError {
     OutOfFrames,
@@ -12,17 +14,33 @@ impl core::fmt::Debug for PagingError {
             PagingError::AddressOverflow(start, size) => {
                 write!(
                     f,
-                    "PagingError::AddressOverflow(start: {:#x}, size: {:#x})",
-                    start, size
+                    "PagingError::AddressOverflow(start: {}, size: {})",
+                    fmt_hex(*start, 16),
+                    fmt_hex(*size, 16)
                 )
             }
             PagingError::UnsupportedAddress(addr) => {
-                write!(f, "PagingError::UnsupportedAddress({:#x})", addr)
+                write!(f, "PagingError::UnsupportedAddress({})", fmt_hex(*addr, 16))
             }
         }
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FixmapError {
+    Unavailable,
+    SlotsBusy,
+}
+
+impl core::fmt::Debug for FixmapError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FixmapError::Unavailable => write!(f, "FixmapError::Unavailable"),
+            FixmapError::SlotsBusy => write!(f, "FixmapError::SlotsBusy"),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum MemoryInitError {
     NoUsableMemory,
@@ -30,12 +48,17 @@ pub enum MemoryInitError {
     OutOfFrames,
     NonContiguous { expected: u64, found: u64 },
     TooLarge,
+    StorageFragmented { slots: usize },
+    FrameCursorsExhausted { capacity: usize },
     StackDescriptorMissing(u64),
     StackRangeOverflow(u32),
     IdentityRangeOverflow { start: u64, end: u64 },
     Allocator(PhysAllocInitError),
     AllocatorUnavailable,
     Paging(PagingError),
+    MapCopyOverlap { source: u64, dest: u64 },
+    MapCopyCorrupt { expected: u64, found: u64 },
+    BeyondPhysWidth { start: u64, end: u64, bits: u8 },
 }
 
 impl core::fmt::Debug for MemoryInitError {
@@ -46,10 +69,23 @@ impl core::fmt::Debug for MemoryInitError {
             MemoryInitError::OutOfFrames => write!(f, "MemoryInitError::OutOfFrames"),
             MemoryInitError::NonContiguous { expected, found } => write!(
                 f,
-                "MemoryInitError::NonContiguous {{ expected: {:#x}, found: {:#x} }}",
-                expected, found
+                "MemoryInitError::NonContiguous {{ expected: {}, found: {} }}",
+                fmt_hex(*expected, 16),
+                fmt_hex(*found, 16)
             ),
             MemoryInitError::TooLarge => write!(f, "MemoryInitError::TooLarge"),
+            MemoryInitError::StorageFragmented { slots } => {
+                write!(
+                    f,
+                    "MemoryInitError::StorageFragmented {{ slots: {} }}",
+                    slots
+                )
+            }
+            MemoryInitError::FrameCursorsExhausted { capacity } => write!(
+                f,
+                "MemoryInitError::FrameCursorsExhausted {{ capacity: {} }}",
+                capacity
+            ),
             MemoryInitError::StackDescriptorMissing(id) => {
                 write!(f, "MemoryInitError::StackDescriptorMissing({})", id)
             }
@@ -58,8 +94,9 @@ impl core::fmt::Debug for MemoryInitError {
             }
             MemoryInitError::IdentityRangeOverflow { start, end } => write!(
                 f,
-                "MemoryInitError::IdentityRangeOverflow {{ start: {:#x}, end: {:#x} }}",
-                start, end
+                "MemoryInitError::IdentityRangeOverflow {{ start: {}, end: {} }}",
+                fmt_hex(*start, 16),
+                fmt_hex(*end, 16)
             ),
             MemoryInitError::Allocator(err) => {
                 write!(f, "MemoryInitError::Allocator({:?})", err)
@@ -68,6 +105,25 @@ impl core::fmt::Debug for MemoryInitError {
                 write!(f, "MemoryInitError::AllocatorUnavailable")
             }
             MemoryInitError::Paging(err) => write!(f, "MemoryInitError::Paging({:?})", err),
+            MemoryInitError::MapCopyOverlap { source, dest } => write!(
+                f,
+                "MemoryInitError::MapCopyOverlap {{ source: {}, dest: {} }}",
+                fmt_hex(*source, 16),
+                fmt_hex(*dest, 16)
+            ),
+            MemoryInitError::MapCopyCorrupt { expected, found } => write!(
+                f,
+                "MemoryInitError::MapCopyCorrupt {{ expected: {}, found: {} }}",
+                fmt_hex(*expected, 16),
+                fmt_hex(*found, 16)
+            ),
+            MemoryInitError::BeyondPhysWidth { start, end, bits } => write!(
+                f,
+                "MemoryInitError::BeyondPhysWidth {{ start: {}, end: {}, bits: {} }}",
+                fmt_hex(*start, 16),
+                fmt_hex(*end, 16),
+                bits
+            ),
         }
     }
 }
@@ -77,6 +133,7 @@ pub enum FrameAllocError {
     OutOfFrames,
     NonContiguous { expected: u64, found: u64 },
     InvalidRequest,
+    CursorsExhausted { capacity: usize },
 }
 
 impl core::fmt::Debug for FrameAllocError {
@@ -85,10 +142,16 @@ impl core::fmt::Debug for FrameAllocError {
             FrameAllocError::OutOfFrames => write!(f, "FrameAllocError::OutOfFrames"),
             FrameAllocError::NonContiguous { expected, found } => write!(
                 f,
-                "FrameAllocError::NonContiguous {{ expected: {:#x}, found: {:#x} }}",
-                expected, found
+                "FrameAllocError::NonContiguous {{ expected: {}, found: {} }}",
+                fmt_hex(*expected, 16),
+                fmt_hex(*found, 16)
             ),
             FrameAllocError::InvalidRequest => write!(f, "FrameAllocError::InvalidRequest"),
+            FrameAllocError::CursorsExhausted { capacity } => write!(
+                f,
+                "FrameAllocError::CursorsExhausted {{ capacity: {} }}",
+                capacity
+            ),
         }
     }
 }
@@ -114,13 +177,15 @@ impl core::fmt::Debug for PhysAllocError {
             ),
             PhysAllocError::RangeOverflow { start, end } => write!(
                 f,
-                "PhysAllocError::RangeOverflow {{ start: {:#x}, end: {:#x} }}",
-                start, end
+                "PhysAllocError::RangeOverflow {{ start: {}, end: {} }}",
+                fmt_hex(*start, 16),
+                fmt_hex(*end, 16)
             ),
             PhysAllocError::RangeMisaligned { start, end } => write!(
                 f,
-                "PhysAllocError::RangeMisaligned {{ start: {:#x}, end: {:#x} }}",
-                start, end
+                "PhysAllocError::RangeMisaligned {{ start: {}, end: {} }}",
+                fmt_hex(*start, 16),
+                fmt_hex(*end, 16)
             ),
             PhysAllocError::StorageExhausted { capacity } => write!(
                 f,
@@ -129,8 +194,9 @@ impl core::fmt::Debug for PhysAllocError {
             ),
             PhysAllocError::InvalidRegion { start, end } => write!(
                 f,
-                "PhysAllocError::InvalidRegion {{ start: {:#x}, end: {:#x} }}",
-                start, end
+                "PhysAllocError::InvalidRegion {{ start: {}, end: {} }}",
+                fmt_hex(*start, 16),
+                fmt_hex(*end, 16)
             ),
         }
     }
@@ -162,8 +228,10 @@ impl core::fmt::Debug for PhysAllocInitError {
             ),
             PhysAllocInitError::ReservationConflict { start, end, error } => write!(
                 f,
-                "PhysAllocInitError::ReservationConflict {{ start: {:#x}, end: {:#x}, error: {:?} }}",
-                start, end, error
+                "PhysAllocInitError::ReservationConflict {{ start: {}, end: {}, error: {:?} }}",
+                fmt_hex(*start, 16),
+                fmt_hex(*end, 16),
+                error
             ),
             PhysAllocInitError::AlreadyInitialized => {
                 write!(f, "PhysAllocInitError::AlreadyInitialized")
@@ -0,0 +1,128 @@
+//! Deterministic fault injection for memory bring-up (`failtest` feature).
+//!
+//! The error branches in `memory::init` and `paging` only run on machines
+//! that are short of memory or hand over odd maps. With the `failtest`
+//! feature, the command line can force them:
+//!
+//! - `failtest=frame:<n>` fails the `n`th frame allocation (1-based), counted
+//!   across the early region allocator, the bootstrap `FrameAllocator`, and
+//!   the runtime `PhysicalAllocator`.
+//! - `failtest=map:<start>-<end>` fails any identity or fixmap mapping that
+//!   touches `[start, end)`.
+//!
+//! Both may be given together. Injected failures return the error the real
+//! failure would, so the caller's handling is what gets exercised. Without
+//! the feature every check is a constant `false`.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use oxide_util::cmdline::parse_u64;
+
+use crate::console::format::fmt_hex;
+
+/// 1-based index of the frame allocation to fail; zero disables it.
+static FAIL_FRAME: AtomicU64 = AtomicU64::new(0);
+static FRAME_ALLOCS: AtomicU64 = AtomicU64::new(0);
+static FAIL_MAP_START: AtomicU64 = AtomicU64::new(0);
+static FAIL_MAP_END: AtomicU64 = AtomicU64::new(0);
+
+/// A parsed `failtest=` value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Injection {
+    Frame(u64),
+    Map { start: u64, end: u64 },
+}
+
+/// Arm the injections named on the command line.
+///
+/// Must run before the first early allocation, which is before the console
+/// exists; [`log`] reports the result once it does.
+pub fn init() {
+    if !cfg!(feature = "failtest") {
+        return;
+    }
+
+    for injection in crate::options::values("failtest").filter_map(parse) {
+        match injection {
+            Injection::Frame(n) => FAIL_FRAME.store(n, Ordering::Relaxed),
+            Injection::Map { start, end } => {
+                FAIL_MAP_START.store(start, Ordering::Relaxed);
+                FAIL_MAP_END.store(end, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Print the armed injections and any `failtest=` values that were ignored.
+pub fn log() {
+    if !cfg!(feature = "failtest") {
+        return;
+    }
+
+    for value in crate::options::values("failtest") {
+        match parse(value) {
+            Some(Injection::Frame(n)) => {
+                crate::println!("failtest: failing frame allocation #{}", n)
+            }
+            Some(Injection::Map { start, end }) => {
+                crate::println!(
+                    "failtest: failing mappings in {}..{}",
+                    fmt_hex(start, 16),
+                    fmt_hex(end, 16)
+                )
+            }
+            None => crate::println!("failtest: ignoring invalid failtest={}", value),
+        }
+    }
+}
+
+/// Count a frame allocation and report whether it should fail.
+#[inline]
+pub fn frame_alloc_fails() -> bool {
+    if !cfg!(feature = "failtest") {
+        return false;
+    }
+    let target = FAIL_FRAME.load(Ordering::Relaxed);
+    target != 0 && FRAME_ALLOCS.fetch_add(1, Ordering::Relaxed) + 1 == target
+}
+
+/// Report whether mapping `[addr, addr + len)` should fail.
+#[inline]
+pub fn map_fails(addr: u64, len: u64) -> bool {
+    if !cfg!(feature = "failtest") {
+        return false;
+    }
+    let start = FAIL_MAP_START.load(Ordering::Relaxed);
+    let end = FAIL_MAP_END.load(Ordering::Relaxed);
+    start < end && addr < end && addr.saturating_add(len) > start
+}
+
+fn parse(value: &str) -> Option<Injection> {
+    if let Some(n) = value.strip_prefix("frame:") {
+        return parse_u64(n).filter(|&n| n > 0).map(Injection::Frame);
+    }
+    let (start, end) = value.strip_prefix("map:")?.split_once('-')?;
+    let (start, end) = (parse_u64(start)?, parse_u64(end)?);
+    (start < end).then_some(Injection::Map { start, end })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_frame_and_map_injections() {
+        assert_eq!(parse("frame:3"), Some(Injection::Frame(3)));
+        assert_eq!(parse("frame:0"), None);
+        assert_eq!(
+            parse("map:0x200000-0x400000"),
+            Some(Injection::Map {
+                start: 0x20_0000,
+                end: 0x40_0000
+            })
+        );
+        assert_eq!(parse("map:0x400000-0x200000"), None);
+        assert_eq!(parse("map:0x1000"), None);
+        assert_eq!(parse("pages:1"), None);
+    }
+}
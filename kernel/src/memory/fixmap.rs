@@ -0,0 +1,264 @@
+//! Fixed virtual window for device registers and short-lived mappings.
+//!
+//! The top 2 MiB of the address space is backed by one page table created
+//! during paging bring-up. Each 4 KiB page of that window is a slot that can
+//! be pointed at any physical frame without allocating. The first slots are
+//! enumerated at compile time ([`FixedSlot`]) so early drivers get stable
+//! virtual addresses independent of the identity-map policy; the remaining
+//! temporary slots back [`with_frame_mapped`], which lets code read firmware
+//! structures (ACPI, SMBIOS, initrd) without relying on the identity map.
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::{
+    console::{ConsoleStorage, format::fmt_hex},
+    memory::{
+        error::{FixmapError, PagingError},
+        frame::FRAME_SIZE,
+        paging::{self, CacheMode, PhysFrameAlloc},
+    },
+};
+
+/// Virtual address of slot 0; the window spans PML4/PDPT/PD index 511.
+pub const FIXMAP_BASE: u64 = 0xFFFF_FFFF_FFE0_0000;
+
+const WINDOW_SLOTS: usize = 512;
+
+/// Pages covering the console history buffer.
+const EARLY_CONSOLE_PAGES: usize = ConsoleStorage::required_bytes().div_ceil(FRAME_SIZE as usize);
+
+/// Permanent mappings with compile-time virtual addresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FixedSlot {
+    /// Local APIC registers (one page).
+    Lapic,
+    /// I/O APIC registers (one page); mapped once ACPI discovery finds it.
+    IoApic,
+    /// HPET registers (one page); mapped once ACPI discovery finds it.
+    Hpet,
+    /// The console history buffer carved before the allocator exists.
+    EarlyConsole,
+}
+
+impl FixedSlot {
+    const ALL: [FixedSlot; 4] = [
+        FixedSlot::Lapic,
+        FixedSlot::IoApic,
+        FixedSlot::Hpet,
+        FixedSlot::EarlyConsole,
+    ];
+
+    /// Number of 4 KiB pages reserved for this slot.
+    pub const fn pages(self) -> usize {
+        match self {
+            FixedSlot::Lapic | FixedSlot::IoApic | FixedSlot::Hpet => 1,
+            FixedSlot::EarlyConsole => EARLY_CONSOLE_PAGES,
+        }
+    }
+
+    const fn first_index(self) -> usize {
+        match self {
+            FixedSlot::Lapic => 0,
+            FixedSlot::IoApic => 1,
+            FixedSlot::Hpet => 2,
+            FixedSlot::EarlyConsole => 3,
+        }
+    }
+
+    const fn cache_mode(self) -> CacheMode {
+        match self {
+            FixedSlot::Lapic | FixedSlot::IoApic | FixedSlot::Hpet => CacheMode::Uncached,
+            FixedSlot::EarlyConsole => CacheMode::WriteBack,
+        }
+    }
+
+    /// Fixed virtual address of the slot's first page.
+    pub const fn address(self) -> u64 {
+        slot_address(self.first_index())
+    }
+}
+
+/// First window index not claimed by a [`FixedSlot`].
+const FIXED_END: usize = FixedSlot::EarlyConsole.first_index() + EARLY_CONSOLE_PAGES;
+
+/// Slots handed out by [`with_frame_mapped`]; several allow nested use.
+const TEMP_SLOTS: usize = 4;
+
+const _: () = assert!(
+    FIXED_END + TEMP_SLOTS <= WINDOW_SLOTS,
+    "fixmap window holds 512 slots"
+);
+
+/// Physical address of the fixmap page table, zero until [`init`] runs.
+static FIXMAP_TABLE: AtomicU64 = AtomicU64::new(0);
+
+static TEMP_BUSY: [AtomicBool; TEMP_SLOTS] = [const { AtomicBool::new(false) }; TEMP_SLOTS];
+
+/// Create the fixmap page table under the active PML4.
+///
+/// The window is checked by reading the PML4's own fixmap entry through a
+/// temporary slot and comparing it with the identity-mapped view.
+///
+/// # Safety
+/// `pml4_phys` must be the PML4 currently loaded in CR3.
+pub unsafe fn init<A: PhysFrameAlloc>(alloc: &mut A, pml4_phys: u64) -> Result<(), PagingError> {
+    let table = unsafe { paging::ensure_page_table(alloc, pml4_phys, FIXMAP_BASE)? };
+    FIXMAP_TABLE.store(table, Ordering::Release);
+
+    let entry_phys = pml4_phys + 511 * 8;
+    let expected = unsafe { core::ptr::read_volatile(entry_phys as *const u64) };
+    let seen = crate::memory::with_frame_mapped(entry_phys, |virt| unsafe {
+        core::ptr::read_volatile(virt as *const u64)
+    });
+    if seen != Ok(expected) {
+        FIXMAP_TABLE.store(0, Ordering::Release);
+        return Err(PagingError::UnsupportedAddress(FIXMAP_BASE));
+    }
+    Ok(())
+}
+
+/// Point `slot` at the physically contiguous range starting at `phys` and
+/// return the slot's virtual address.
+///
+/// Remapping a slot replaces its previous target.
+///
+/// # Safety
+/// `phys` must be frame-aligned and cover `slot.pages()` frames of the kind
+/// the slot expects (device registers or RAM); no reference may still rely on
+/// the previous mapping.
+pub unsafe fn map_fixed(slot: FixedSlot, phys: u64) -> Result<u64, FixmapError> {
+    let table = FIXMAP_TABLE.load(Ordering::Acquire);
+    if table == 0 {
+        return Err(FixmapError::Unavailable);
+    }
+
+    for page in 0..slot.pages() {
+        let index = slot.first_index() + page;
+        let frame = phys + (page as u64) * FRAME_SIZE;
+        unsafe {
+            paging::set_page(
+                table,
+                index,
+                slot_address(index),
+                Some((frame, slot.cache_mode())),
+            )
+        };
+    }
+    Ok(slot.address())
+}
+
+/// Map the fixed slots whose targets are known during paging bring-up: the
+/// local APIC and the console history buffer.
+pub fn map_boot_slots(console_storage: Option<u64>) {
+    for slot in FixedSlot::ALL {
+        crate::debugln!(
+            "fixmap: {:?} at {} ({} page(s))",
+            slot,
+            fmt_hex(slot.address(), 16),
+            slot.pages()
+        );
+    }
+
+    if let Some(lapic) = local_apic_base() {
+        match unsafe { map_fixed(FixedSlot::Lapic, lapic) } {
+            Ok(virt) => crate::diagln!(
+                "fixmap: LAPIC {} -> {}",
+                fmt_hex(lapic, 8),
+                fmt_hex(virt, 16)
+            ),
+            Err(err) => crate::println!("fixmap: LAPIC mapping failed: {:?}", err),
+        }
+    }
+
+    if let Some(storage) = console_storage {
+        match unsafe { map_fixed(FixedSlot::EarlyConsole, storage) } {
+            Ok(virt) => crate::diagln!(
+                "fixmap: console storage {} -> {}",
+                fmt_hex(storage, 8),
+                fmt_hex(virt, 16)
+            ),
+            Err(err) => crate::println!("fixmap: console storage mapping failed: {:?}", err),
+        }
+    }
+}
+
+/// Virtual address of fixmap slot `index`.
+pub const fn slot_address(index: usize) -> u64 {
+    FIXMAP_BASE + (index as u64) * FRAME_SIZE
+}
+
+/// Temporarily map the frame containing `phys` and run `f` with a pointer to
+/// the byte at `phys`.
+///
+/// The pointer is valid up to the end of that frame and only for the duration
+/// of `f`; the mapping is removed before this returns.
+pub fn with_frame_mapped<R>(phys: u64, f: impl FnOnce(*mut u8) -> R) -> Result<R, FixmapError> {
+    let table = FIXMAP_TABLE.load(Ordering::Acquire);
+    if table == 0 {
+        return Err(FixmapError::Unavailable);
+    }
+
+    let temp = TEMP_BUSY
+        .iter()
+        .position(|busy| {
+            busy.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        })
+        .ok_or(FixmapError::SlotsBusy)?;
+
+    let index = FIXED_END + temp;
+    let virt = slot_address(index);
+    let frame = phys & !(FRAME_SIZE - 1);
+    let offset = phys - frame;
+
+    // SAFETY: the slot is exclusively ours until its busy flag is released,
+    // and `table` covers the fixmap window.
+    unsafe { paging::set_page(table, index, virt, Some((frame, CacheMode::WriteBack))) };
+    let result = f((virt + offset) as *mut u8);
+    unsafe { paging::set_page(table, index, virt, None) };
+
+    TEMP_BUSY[temp].store(false, Ordering::Release);
+    Ok(result)
+}
+
+const IA32_APIC_BASE: u32 = 0x1B;
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+const APIC_BASE_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+const CPUID_APIC_PRESENT: u32 = 1 << 9;
+
+/// Physical base of the local APIC, if the CPU has one and it is enabled.
+fn local_apic_base() -> Option<u64> {
+    if core::arch::x86_64::__cpuid(1).edx & CPUID_APIC_PRESENT == 0 {
+        return None;
+    }
+
+    // SAFETY: IA32_APIC_BASE exists whenever CPUID reports an APIC.
+    let value = unsafe { crate::cpu::rdmsr(IA32_APIC_BASE) };
+    (value & APIC_BASE_ENABLE != 0).then_some(value & APIC_BASE_MASK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_slots_are_disjoint_and_ordered() {
+        let mut next = 0;
+        for slot in FixedSlot::ALL {
+            assert_eq!(slot.first_index(), next);
+            next += slot.pages();
+        }
+        assert_eq!(next, FIXED_END);
+        assert_eq!(FixedSlot::Lapic.address(), FIXMAP_BASE);
+        assert_eq!(FixedSlot::EarlyConsole.address(), slot_address(3));
+        assert_eq!(slot_address(WINDOW_SLOTS - 1), u64::MAX - FRAME_SIZE + 1);
+    }
+
+    #[test]
+    fn with_frame_mapped_requires_init() {
+        assert_eq!(
+            with_frame_mapped(0x1000, |_| ()),
+            Err(FixmapError::Unavailable)
+        );
+    }
+}
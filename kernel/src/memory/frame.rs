@@ -1,128 +1,208 @@
-use crate::memory::{early, error::FrameAllocError, map::MemoryMapIter};
+use crate::memory::{
+    early,
+    error::FrameAllocError,
+    map::{MemoryMapIter, descriptor_range},
+    sanitize,
+};
 use oxide_abi::{EfiMemoryType, MemoryMap};
 
 /// Size of a physical memory frame in bytes (4 KiB).
 pub const FRAME_SIZE: u64 = 4096;
 
-/// Iterator-backed helper for walking usable frames prior to the runtime allocator.
+/// Descriptors the early allocator can have partly used at once.
+const MAX_CURSORS: usize = 16;
+
+/// Bump allocator over the conventional descriptors of the firmware map.
+///
+/// Each descriptor has its own cursor: frames below it are handed out (or
+/// reserved), frames from it to the descriptor's end are free. A request
+/// takes the first descriptor whose free tail holds the whole run, skipping
+/// early reservations, so a contiguous request that does not fit one
+/// descriptor leaves it intact for later, smaller requests. Runs never span
+/// descriptors. Only used descriptors need a cursor; an untouched one is
+/// free from its start. Once all `MAX_CURSORS` are taken, untouched
+/// descriptors can no longer be used, and a request only they could serve
+/// fails with `CursorsExhausted` rather than `OutOfFrames`.
 pub struct FrameAllocator<'a> {
-    iter: UsableFrameIter<'a>,
+    map: &'a MemoryMap,
+    cursors: [Cursor; MAX_CURSORS],
+    len: usize,
+    /// A range still in use that may sit in conventional memory.
+    avoid: Option<(u64, u64)>,
 }
 
-struct RunTracker {
-    required: usize,
-    start: Option<u64>,
-    previous: u64,
-    length: usize,
+#[derive(Clone, Copy, Default)]
+struct Cursor {
+    /// Index of the descriptor in the map.
+    descriptor: usize,
+    /// First frame not yet handed out.
+    next: u64,
 }
 
-impl RunTracker {
-    fn new(required: usize) -> Self {
+impl<'a> FrameAllocator<'a> {
+    /// Create a frame allocator over the provided firmware memory map.
+    pub fn new(map: &'a MemoryMap) -> Self {
         Self {
-            required,
-            start: None,
-            previous: 0,
-            length: 0,
-        }
-    }
-
-    fn update(&mut self, frame: u64, gaps: &mut GapTracker) {
-        match self.start {
-            None => self.restart(frame),
-            Some(_) => {
-                let expected = self.previous.checked_add(FRAME_SIZE);
-                match expected {
-                    Some(next) if frame == next => {
-                        self.previous = frame;
-                        self.length += 1;
-                    }
-                    Some(next) => {
-                        gaps.record((next, frame));
-                        self.restart(frame);
-                    }
-                    None => {
-                        let hinted = self.previous.saturating_add(FRAME_SIZE);
-                        gaps.record((hinted, frame));
-                        self.restart(frame);
-                    }
-                }
-            }
+            map,
+            cursors: [Cursor::default(); MAX_CURSORS],
+            len: 0,
+            avoid: None,
         }
     }
 
-    fn restart(&mut self, frame: u64) {
-        self.start = Some(frame);
-        self.previous = frame;
-        self.length = 1;
+    /// Never hand out frames overlapping `[start, end)`, such as the
+    /// firmware memory map while it is being copied.
+    pub fn avoid(&mut self, start: u64, end: u64) {
+        self.avoid = Some((start, end));
     }
 
-    fn is_complete(&self) -> bool {
-        self.length >= self.required && self.start.is_some()
+    /// Allocate a single physical memory frame.
+    pub fn alloc(&mut self) -> Option<u64> {
+        self.alloc_contiguous(1).ok()
     }
 
-    fn start_address(&self) -> Option<u64> {
-        self.start
-    }
-}
+    /// Allocate `frame_count` contiguous frames, returning the physical start address.
+    pub fn alloc_contiguous(&mut self, frame_count: usize) -> Result<u64, FrameAllocError> {
+        debug_assert!(frame_count > 0);
+        if frame_count == 0 {
+            return Err(FrameAllocError::InvalidRequest);
+        }
+        if super::failtest::frame_alloc_fails() {
+            return Err(FrameAllocError::OutOfFrames);
+        }
+        let bytes = (frame_count as u64)
+            .checked_mul(FRAME_SIZE)
+            .ok_or(FrameAllocError::OutOfFrames)?;
+
+        let map = self.map;
+        let mut out_of_cursors = false;
+        for (descriptor, (start, end)) in usable_ranges(map) {
+            let next = self.cursor(descriptor).unwrap_or(start);
+            let Some(run) = fit(next, end, bytes, self.avoid) else {
+                continue;
+            };
+            if self.advance(descriptor, run + bytes).is_err() {
+                out_of_cursors = true;
+                continue;
+            }
+            self.verify(run, frame_count as u64);
+            return Ok(run);
+        }
 
-struct GapTracker {
-    first_gap: Option<(u64, u64)>,
-}
+        if out_of_cursors {
+            return Err(FrameAllocError::CursorsExhausted {
+                capacity: MAX_CURSORS,
+            });
+        }
 
-impl GapTracker {
-    fn new() -> Self {
-        Self { first_gap: None }
+        if self.remaining() >= frame_count as u64
+            && let Some((expected, found)) = self.first_gap()
+        {
+            return Err(FrameAllocError::NonContiguous { expected, found });
+        }
+        Err(FrameAllocError::OutOfFrames)
     }
 
-    fn record(&mut self, gap: (u64, u64)) {
-        if self.first_gap.is_none() {
-            self.first_gap = Some(gap);
-        }
+    /// Frames still available: everything from each cursor to its
+    /// descriptor's end, minus early reservations.
+    pub fn remaining(&self) -> u64 {
+        self.free_ranges()
+            .map(|(start, end)| {
+                let mut reserved = 0;
+                early::for_each(|region| {
+                    let overlap_start = region.start.max(start);
+                    let overlap_end = region.end.min(end);
+                    if overlap_start < overlap_end {
+                        reserved += (align_up(overlap_end) - (overlap_start & !(FRAME_SIZE - 1)))
+                            / FRAME_SIZE;
+                    }
+                });
+                ((end - start) / FRAME_SIZE).saturating_sub(reserved)
+            })
+            .sum()
     }
 
-    fn first(&self) -> Option<(u64, u64)> {
-        self.first_gap
+    /// Free tail `[cursor, end)` of every descriptor that has one.
+    fn free_ranges(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        usable_ranges(self.map).filter_map(|(descriptor, (start, end))| {
+            let next = self.cursor(descriptor).unwrap_or(start);
+            (next < end).then_some((next, end))
+        })
     }
-}
 
-impl<'a> FrameAllocator<'a> {
-    /// Create a frame allocator over the provided firmware memory map.
-    pub fn new(map: &'a MemoryMap) -> Self {
-        Self {
-            iter: UsableFrameIter::new(map),
-        }
+    /// The end of the first free tail and the start of the one after it.
+    fn first_gap(&self) -> Option<(u64, u64)> {
+        let mut ranges = self.free_ranges();
+        let (_, expected) = ranges.next()?;
+        let (found, _) = ranges.next()?;
+        Some((expected, found))
     }
 
-    /// Allocate a single physical memory frame.
-    pub fn alloc(&mut self) -> Option<u64> {
-        self.iter.next()
+    fn cursor(&self, descriptor: usize) -> Option<u64> {
+        self.cursors[..self.len]
+            .iter()
+            .find(|cursor| cursor.descriptor == descriptor)
+            .map(|cursor| cursor.next)
     }
 
-    /// Allocate `frame_count` contiguous frames, returning the physical start address.
-    pub fn alloc_contiguous(&mut self, frame_count: usize) -> Result<u64, FrameAllocError> {
-        debug_assert!(frame_count > 0);
-        if frame_count == 0 {
-            return Err(FrameAllocError::InvalidRequest);
+    /// Move `descriptor`'s cursor to `next`; fails when a new cursor is
+    /// needed and all are in use.
+    fn advance(&mut self, descriptor: usize, next: u64) -> Result<(), ()> {
+        if let Some(cursor) = self.cursors[..self.len]
+            .iter_mut()
+            .find(|cursor| cursor.descriptor == descriptor)
+        {
+            cursor.next = next;
+            return Ok(());
         }
+        let slot = self.cursors.get_mut(self.len).ok_or(())?;
+        *slot = Cursor { descriptor, next };
+        self.len += 1;
+        Ok(())
+    }
 
-        let mut run = RunTracker::new(frame_count);
-        let mut gaps = GapTracker::new();
+    fn verify(&self, start: u64, frames: u64) {
+        sanitize::verify(
+            "early frame allocator",
+            self.map,
+            start,
+            frames,
+            early::overlapping,
+        );
+    }
+}
 
-        for frame in self.iter.by_ref() {
-            run.update(frame, &mut gaps);
+/// Frame-aligned `[start, end)` of each conventional descriptor, by index.
+fn usable_ranges(map: &MemoryMap) -> impl Iterator<Item = (usize, (u64, u64))> + '_ {
+    MemoryMapIter::new(map)
+        .enumerate()
+        .filter(|(_, desc)| desc.typ == EfiMemoryType::ConventionalMemory as u32)
+        .filter_map(|(index, desc)| {
+            let (start, end) = descriptor_range(desc)?;
+            let start = align_up(start).max(FRAME_SIZE);
+            (start < end).then_some((index, (start, end)))
+        })
+}
 
-            if run.is_complete()
-                && let Some(start) = run.start_address()
-            {
-                return Ok(start);
-            }
+/// First `bytes`-long run in `[start, end)` clear of early reservations
+/// and of `avoid`.
+fn fit(start: u64, end: u64, bytes: u64, avoid: Option<(u64, u64)>) -> Option<u64> {
+    let mut candidate = start;
+    loop {
+        let run_end = candidate.checked_add(bytes)?;
+        if run_end > end {
+            return None;
         }
-
-        if let Some((expected, found)) = gaps.first() {
-            return Err(FrameAllocError::NonContiguous { expected, found });
+        let blocked_until = match avoid {
+            Some((avoid_start, avoid_end)) if avoid_start < run_end && candidate < avoid_end => {
+                Some(avoid_end)
+            }
+            _ => early::overlapping(candidate, run_end).map(|region| region.end),
+        };
+        match blocked_until {
+            Some(blocked_end) => candidate = align_up(blocked_end).max(candidate + FRAME_SIZE),
+            None => return Some(candidate),
         }
-
-        Err(FrameAllocError::OutOfFrames)
     }
 }
 
@@ -343,6 +423,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn contiguous_request_leaves_too_small_descriptor_for_single_frames() {
+        let descriptors = vec![
+            descriptor(EfiMemoryType::ConventionalMemory, FRAME_SIZE, 2),
+            descriptor(EfiMemoryType::LoaderData, FRAME_SIZE * 3, 7),
+            descriptor(EfiMemoryType::ConventionalMemory, FRAME_SIZE * 10, 4),
+        ];
+        let (map, _backing) = build_map(descriptors);
+        let mut allocator = FrameAllocator::new(&map);
+        assert_eq!(allocator.remaining(), 6);
+
+        assert_eq!(allocator.alloc(), Some(FRAME_SIZE));
+        // One frame is left below; the run comes from the next descriptor.
+        assert_eq!(allocator.alloc_contiguous(3), Ok(FRAME_SIZE * 10));
+        assert_eq!(allocator.alloc(), Some(FRAME_SIZE * 2));
+        assert_eq!(allocator.remaining(), 1);
+        assert_eq!(
+            allocator.alloc_contiguous(2),
+            Err(FrameAllocError::OutOfFrames)
+        );
+        assert_eq!(allocator.alloc(), Some(FRAME_SIZE * 13));
+        assert_eq!(allocator.remaining(), 0);
+        assert_eq!(allocator.alloc(), None);
+    }
+
+    #[test]
+    fn untouched_descriptor_past_cursor_limit_reports_exhaustion() {
+        let descriptors = (0..=MAX_CURSORS as u64)
+            .map(|index| {
+                descriptor(
+                    EfiMemoryType::ConventionalMemory,
+                    FRAME_SIZE * (1 + 2 * index),
+                    1,
+                )
+            })
+            .collect();
+        let (map, _backing) = build_map(descriptors);
+        let mut allocator = FrameAllocator::new(&map);
+
+        for index in 0..MAX_CURSORS as u64 {
+            assert_eq!(allocator.alloc(), Some(FRAME_SIZE * (1 + 2 * index)));
+        }
+        assert_eq!(
+            allocator.alloc_contiguous(1),
+            Err(FrameAllocError::CursorsExhausted {
+                capacity: MAX_CURSORS
+            })
+        );
+    }
+
     #[test]
     fn alloc_contiguous_reports_out_of_frames() {
         let descriptors = vec![descriptor(EfiMemoryType::ConventionalMemory, FRAME_SIZE, 2)];
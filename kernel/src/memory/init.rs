@@ -1,13 +1,22 @@
-use core::{mem, ptr, slice};
+use core::{
+    mem, slice,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
-use crate::console::ConsoleStorage;
+use crate::console::{ConsoleStorage, format::fmt_hex};
+use crate::memory::accounting::{self, Accounted, Subsystem};
 use crate::memory::allocator::{self, ReservedRegion};
+use crate::memory::attributes;
 use crate::memory::early;
 use crate::memory::error::{FrameAllocError, MemoryInitError, PagingError};
+use crate::memory::fixmap;
 use crate::memory::frame::{FRAME_SIZE, FrameAllocator, UsableFrameIter};
-use crate::memory::map::{descriptor_range, find_descriptor_containing};
-use crate::memory::paging::{HUGE_PAGE_SIZE, install_identity_paging};
-use oxide_abi::{Framebuffer, MemoryMap};
+use crate::memory::map::{MemoryMapIter, descriptor_range, find_descriptor_containing};
+use crate::memory::paging::{self, HUGE_PAGE_SIZE, install_identity_paging};
+use crate::memory::paranoid;
+use crate::memory::reservation::{self, Owners};
+use crate::memory::segments::{MAX_SEGMENTS, Segments};
+use oxide_abi::{Framebuffer, MemoryMap, PhysRange};
 
 const LOW_IDENTITY_LIMIT: u64 = 1024 * 1024 * 1024; // 1 GiB
 /// Identity ranges are limited because the install path only needs a few
@@ -15,6 +24,13 @@ const LOW_IDENTITY_LIMIT: u64 = 1024 * 1024 * 1024; // 1 GiB
 /// This keeps the staging structure stack-allocated with predictable size.
 const MAX_IDENTITY_RANGES: usize = 4;
 
+/// Frames for the console history once the runtime allocator is up: 32
+/// frames, several hundred lines against the early carve-out's 128.
+const CONSOLE_HISTORY_ORDER: u8 = 5;
+
+/// Physical base of the console history buffer, zero if it was never carved.
+static CONSOLE_STORAGE_PHYS: AtomicU64 = AtomicU64::new(0);
+
 struct IdentityRanges {
     entries: [(u64, u64); MAX_IDENTITY_RANGES],
     len: usize,
@@ -39,9 +55,9 @@ impl IdentityRanges {
 
         if self.len >= MAX_IDENTITY_RANGES {
             crate::diagln!(
-                "IDENTITY RANGE CAP HIT WHILE STAGING [{:#x}, {:#x}]",
-                range.0,
-                range.1
+                "IDENTITY RANGE CAP HIT WHILE STAGING [{}, {}]",
+                fmt_hex(range.0, 16),
+                fmt_hex(range.1, 16)
             );
             return Err(MemoryInitError::IdentityRangeOverflow {
                 start: range.0,
@@ -59,8 +75,13 @@ impl IdentityRanges {
     }
 }
 
-const MAX_RESERVATIONS: usize = 8;
+/// Identity ranges, every early reservation, the framebuffer, and every
+/// segment of the two allocator storage lists.
+const MAX_RESERVATIONS: usize =
+    MAX_IDENTITY_RANGES + early::MAX_EARLY_RESERVATIONS + 1 + 2 * MAX_SEGMENTS;
 
+/// Reservations handed to the runtime allocator, sorted and disjoint under
+/// the shared overlap policy.
 struct ReservationList {
     entries: [ReservedRegion; MAX_RESERVATIONS],
     len: usize,
@@ -69,40 +90,31 @@ struct ReservationList {
 impl ReservationList {
     fn new() -> Self {
         Self {
-            entries: [ReservedRegion { start: 0, end: 0 }; MAX_RESERVATIONS],
+            entries: [ReservedRegion::default(); MAX_RESERVATIONS],
             len: 0,
         }
     }
 
-    fn push(&mut self, range: (u64, u64)) -> Result<(), MemoryInitError> {
-        let (start, end) = range;
+    fn push(&mut self, region: ReservedRegion) -> Result<(), MemoryInitError> {
+        let ReservedRegion { start, end, .. } = region;
         if start >= end {
             return Ok(());
         }
 
-        let region = ReservedRegion { start, end };
-
-        if self.entries[..self.len].contains(&region) {
-            return Ok(());
-        }
-
-        if self.len >= MAX_RESERVATIONS {
+        if reservation::insert(&mut self.entries, &mut self.len, region).is_err() {
             crate::diagln!(
-                "RESERVATION CAP HIT WHILE STAGING [{:#x}, {:#x}]",
-                start,
-                end
+                "RESERVATION CAP HIT WHILE STAGING [{}, {}]",
+                fmt_hex(start, 16),
+                fmt_hex(end, 16)
             );
             return Err(MemoryInitError::IdentityRangeOverflow { start, end });
         }
-
-        self.entries[self.len] = region;
-        self.len += 1;
         Ok(())
     }
 
-    fn extend(&mut self, ranges: &[(u64, u64)]) -> Result<(), MemoryInitError> {
-        for &range in ranges {
-            self.push(range)?;
+    fn extend(&mut self, ranges: &[(u64, u64)], owners: Owners) -> Result<(), MemoryInitError> {
+        for &(start, end) in ranges {
+            self.push(ReservedRegion { start, end, owners })?;
         }
         Ok(())
     }
@@ -120,6 +132,7 @@ fn stage_identity_ranges(
     memory_map: &MemoryMap,
     map_copy_range: (u64, u64),
     rsp: u64,
+    loader_image: PhysRange,
 ) -> Result<IdentityRanges, MemoryInitError> {
     let mut identity_ranges = IdentityRanges::new();
     identity_ranges.push(map_copy_range)?;
@@ -127,15 +140,17 @@ fn stage_identity_ranges(
     let (stack_start, stack_end) = loader_stack_info(memory_map, rsp)?;
     identity_ranges.push((stack_start, stack_end))?;
 
-    let code_addr = initialize as usize as u64;
-    if let Some(((code_start, code_end), _code_type)) =
+    let code_addr = initialize as *const () as usize as u64;
+    if let Some(image) = loader_image_range(loader_image, code_addr) {
+        identity_ranges.push(image)?;
+    } else if let Some(((code_start, code_end), _code_type)) =
         kernel_code_identity_range(memory_map, code_addr)
     {
         identity_ranges.push((code_start, code_end))?;
     } else {
         crate::println!(
-            "WARNING: KERNEL CODE ADDRESS {:#x} MISSING FROM MEMORY MAP.",
-            code_addr
+            "WARNING: KERNEL CODE ADDRESS {} MISSING FROM MEMORY MAP.",
+            fmt_hex(code_addr, 16)
         );
     }
 
@@ -149,12 +164,12 @@ fn stage_reservations(
     framebuffer: &Framebuffer,
 ) -> Result<ReservationList, MemoryInitError> {
     let mut reservations = ReservationList::new();
-    reservations.extend(identity_ranges)?;
+    reservations.extend(identity_ranges, Owners::BOOT)?;
 
     let mut early_reservation_error = None;
     early::for_each(|region| {
         if early_reservation_error.is_none()
-            && let Err(err) = reservations.push((region.start, region.end))
+            && let Err(err) = reservations.push(region)
         {
             early_reservation_error = Some(err);
         }
@@ -173,7 +188,11 @@ fn stage_reservations(
             ))
         })?;
 
-    reservations.push((framebuffer.base_address, framebuffer_end))?;
+    reservations.push(ReservedRegion {
+        start: framebuffer.base_address,
+        end: framebuffer_end,
+        owners: Owners::FRAMEBUFFER,
+    })?;
 
     Ok(reservations)
 }
@@ -183,7 +202,8 @@ fn bring_up_allocator(
     kernel_memory_map: MemoryMap,
     reservations: &mut ReservationList,
 ) -> Result<(), MemoryInitError> {
-    let reservation_hint = reservations.len() + 2;
+    // Room for the allocator's own storage, however many segments it takes.
+    let reservation_hint = reservations.len() + 2 * MAX_SEGMENTS;
     let storage_plan = allocator::runtime_storage_plan(&kernel_memory_map, reservation_hint)
         .map_err(MemoryInitError::Allocator)?;
 
@@ -196,21 +216,28 @@ fn bring_up_allocator(
         ]
     );
 
-    let StorageSlice {
-        slice: free_storage,
-        region: free_region,
-    } = unsafe {
-        carve_option_storage::<allocator::PhysFrame>(frame_allocator, storage_plan.free_slots)?
+    let free_storage = unsafe {
+        carve_storage::<Option<allocator::PhysFrame>>(
+            frame_allocator,
+            storage_plan.free_slots,
+            None,
+        )?
     };
-    reservations.push((free_region.start, free_region.end))?;
-
-    let StorageSlice {
-        slice: reserved_storage,
-        region: reserved_region,
-    } = unsafe {
-        carve_option_storage::<ReservedRegion>(frame_allocator, storage_plan.reserved_slots)?
+    let reserved_storage = unsafe {
+        carve_storage(
+            frame_allocator,
+            storage_plan.reserved_slots,
+            ReservedRegion::default(),
+        )?
     };
-    reservations.push((reserved_region.start, reserved_region.end))?;
+    for &region in free_storage
+        .regions()
+        .iter()
+        .chain(reserved_storage.regions())
+    {
+        reservations.push(region)?;
+        accounting::charge(Subsystem::AllocatorMetadata, region_frames(region));
+    }
 
     crate::debugln!(
         "runtime allocator storage carved: reservations now {}",
@@ -220,26 +247,58 @@ fn bring_up_allocator(
     allocator::initialize_runtime_allocator(
         kernel_memory_map,
         reservations.as_slice(),
-        free_storage,
-        reserved_storage,
+        free_storage.segments,
+        reserved_storage.segments,
     )?;
 
     crate::diagln!("runtime allocator initialized");
 
+    // Firmware often splits contiguous RAM across descriptors; merge those now.
+    if let Some(Ok(merged)) = allocator::coalesce_free_runs() {
+        crate::debugln!("runtime allocator coalesced {} adjacent runs", merged);
+    }
+    if crate::options::debug_enabled() {
+        allocator::log_fragmentation();
+        allocator::log_zones();
+    }
+
     Ok(())
 }
 
 fn install_identity_mappings(
     identity_ranges: &[(u64, u64)],
     framebuffer: &Framebuffer,
+    memory_attributes: PhysRange,
 ) -> Result<(), MemoryInitError> {
     let paging_result = allocator::with_runtime_allocator(|alloc| unsafe {
-        install_identity_paging(alloc, framebuffer, LOW_IDENTITY_LIMIT, identity_ranges)
+        let alloc = &mut Accounted::new(alloc, Subsystem::Paging);
+        let pml4 =
+            install_identity_paging(alloc, framebuffer, LOW_IDENTITY_LIMIT, identity_ranges)?;
+        fixmap::init(alloc, pml4)?;
+        let protected = (memory_attributes.len != 0).then(|| {
+            let no_execute = crate::cpu::enable_no_execute();
+            crate::cpu::enable_write_protect();
+            attributes::apply(alloc, pml4, memory_attributes, no_execute)
+        });
+        Ok(protected)
     });
 
     match paging_result {
         Some(result) => {
-            let _cr3 = result.map_err(MemoryInitError::Paging)?;
+            let protected = result.map_err(MemoryInitError::Paging)?;
+            let console_storage = CONSOLE_STORAGE_PHYS.load(Ordering::Relaxed);
+            fixmap::map_boot_slots((console_storage != 0).then_some(console_storage));
+            match protected {
+                Some(Ok(summary)) => crate::diagln!(
+                    "memory attributes: {} runtime regions, {} pages restricted",
+                    summary.regions,
+                    summary.pages
+                ),
+                Some(Err(err)) => {
+                    crate::println!("memory attributes: table ignored: {:?}", err)
+                }
+                None => {}
+            }
             Ok(())
         }
         None => {
@@ -249,15 +308,25 @@ fn install_identity_mappings(
     }
 }
 
-struct StorageSlice<T: 'static> {
-    slice: &'static mut [Option<T>],
-    region: ReservedRegion,
+/// Allocator metadata carved from early frames, with the physical range of
+/// each segment; unused entries of `regions` are empty.
+struct CarvedStorage<T: 'static> {
+    segments: Segments<'static, T>,
+    regions: [ReservedRegion; MAX_SEGMENTS],
+}
+
+impl<T> CarvedStorage<T> {
+    fn regions(&self) -> &[ReservedRegion] {
+        &self.regions[..self.segments.segment_count()]
+    }
 }
 
 /// Reserve physical memory for the console's history buffer prior to allocator bring-up.
 pub fn bootstrap_console_storage(map: &MemoryMap) -> Result<ConsoleStorage, MemoryInitError> {
     let bytes = ConsoleStorage::required_bytes();
-    let region = early::allocate_region(map, bytes)?;
+    let region = early::allocate_region(map, bytes, Owners::CONSOLE)?;
+    CONSOLE_STORAGE_PHYS.store(region.start, Ordering::Relaxed);
+    accounting::charge(Subsystem::Console, region_frames(region));
 
     // SAFETY: The reserved region remains identity mapped during initialization
     // and is tracked via the early reservation list to prevent reuse.
@@ -265,19 +334,67 @@ pub fn bootstrap_console_storage(map: &MemoryMap) -> Result<ConsoleStorage, Memo
     Ok(storage)
 }
 
-/// Allocate a slice of `Option<T>` from physical memory frames and expose it as a
-/// leaked `'static` reference for the runtime allocator metadata.
+/// Move the console history from its early carve-out into a larger block
+/// from the runtime allocator. The early region stays reserved; on any
+/// failure the console keeps using it.
+fn grow_console_history() {
+    let Some(Ok(frame)) =
+        allocator::with_runtime_allocator(|alloc| alloc.allocate_order(CONSOLE_HISTORY_ORDER))
+    else {
+        crate::diagln!("console history: no frames; keeping the early buffer");
+        return;
+    };
+    let release = |frame| {
+        allocator::with_runtime_allocator(|alloc| alloc.free(frame));
+    };
+
+    let bytes = frame.count * FRAME_SIZE;
+    // Only the low identity range is mapped for general use.
+    if frame.start + bytes > LOW_IDENTITY_LIMIT {
+        release(frame);
+        crate::diagln!("console history: frames above the identity map; keeping the early buffer");
+        return;
+    }
+
+    // SAFETY: the frames are identity mapped and owned by the console from
+    // here on; they are returned below if the console refuses them.
+    let storage = unsafe { ConsoleStorage::from_physical_region(frame.start, bytes) };
+    match crate::console::grow_history(storage) {
+        Ok(lines) => {
+            accounting::charge(Subsystem::Console, frame.count);
+            crate::diagln!(
+                "console history: {} lines at {}",
+                lines,
+                fmt_hex(frame.start, 16)
+            );
+        }
+        Err(_) => release(frame),
+    }
+}
+
+fn region_frames(region: ReservedRegion) -> u64 {
+    (region.end - region.start) / FRAME_SIZE
+}
+
+/// Allocate `slots` values of `T` from physical memory frames, fill them
+/// with `empty`, and expose them as leaked `'static` segments for the runtime
+/// allocator metadata.
+///
+/// One contiguous carve-out is preferred. When no descriptor has room for it,
+/// the storage is split into the largest pieces that fit, up to
+/// [`MAX_SEGMENTS`]. No slot ever straddles two pieces.
 ///
 /// # Safety
-/// The caller must ensure that the returned physical range remains identity-mapped
-/// and is never reclaimed for other purposes.
-unsafe fn carve_option_storage<T: Copy + 'static>(
+/// The caller must ensure that the returned physical ranges remain identity-mapped
+/// and are never reclaimed for other purposes.
+unsafe fn carve_storage<T: Copy + 'static>(
     allocator: &mut FrameAllocator,
     slots: usize,
-) -> Result<StorageSlice<T>, MemoryInitError> {
+    empty: T,
+) -> Result<CarvedStorage<T>, MemoryInitError> {
     debug_assert!(slots > 0);
 
-    let element_size = mem::size_of::<Option<T>>();
+    let element_size = mem::size_of::<T>();
     if element_size == 0 {
         return Err(MemoryInitError::TooLarge);
     }
@@ -287,39 +404,91 @@ unsafe fn carve_option_storage<T: Copy + 'static>(
         .ok_or(MemoryInitError::TooLarge)?;
 
     let frame_bytes = FRAME_SIZE as usize;
-    let frames = bytes.div_ceil(frame_bytes).max(1);
-
-    let phys_start = allocator
-        .alloc_contiguous(frames)
-        .map_err(|err| match err {
-            FrameAllocError::OutOfFrames => MemoryInitError::OutOfFrames,
-            FrameAllocError::NonContiguous { expected, found } => {
-                MemoryInitError::NonContiguous { expected, found }
-            }
-            FrameAllocError::InvalidRequest => MemoryInitError::EmptyMemoryMap,
-        })?;
+    // With `paranoid`, each carve-out keeps a canary word on either side.
+    let guard = paranoid::GUARD_BYTES;
+    let min_frames = (element_size + 2 * guard).div_ceil(frame_bytes);
+    let mut chunk = bytes.div_ceil(frame_bytes).max(1);
+
+    let mut carved = CarvedStorage {
+        segments: Segments::new(),
+        regions: [ReservedRegion::default(); MAX_SEGMENTS],
+    };
+    let mut left = slots;
+    while left > 0 {
+        let frames = chunk.min((left * element_size + 2 * guard).div_ceil(frame_bytes));
+        if carved.segments.segment_count() == MAX_SEGMENTS {
+            return Err(MemoryInitError::StorageFragmented { slots });
+        }
 
-    let phys_end = phys_start + (frames as u64 * FRAME_SIZE);
-    let slice_ptr = phys_start as *mut Option<T>;
-    let storage = unsafe { slice::from_raw_parts_mut(slice_ptr, slots) };
-    storage.fill(None);
+        let phys_start = match allocator.alloc_contiguous(frames) {
+            Ok(start) => start,
+            Err(FrameAllocError::NonContiguous { .. }) if frames > min_frames => {
+                chunk = frames - 1;
+                continue;
+            }
+            Err(err) => return Err(frame_alloc_error(err)),
+        };
+
+        let count = ((frames * frame_bytes - 2 * guard) / element_size).min(left);
+        let slots_start = phys_start + guard as u64;
+        let slice_ptr = slots_start as *mut T;
+        let storage = unsafe { slice::from_raw_parts_mut(slice_ptr, count) };
+        storage.fill(empty);
+        if paranoid::ENABLED {
+            // SAFETY: both words lie inside the frames just carved.
+            unsafe {
+                paranoid::place_canaries(slots_start, slots_start + (count * element_size) as u64)
+            };
+        }
 
-    Ok(StorageSlice {
-        slice: storage,
-        region: ReservedRegion {
+        carved.regions[carved.segments.segment_count()] = ReservedRegion {
             start: phys_start,
-            end: phys_end,
-        },
-    })
+            end: phys_start + (frames as u64 * FRAME_SIZE),
+            owners: Owners::ALLOCATOR,
+        };
+        if carved.segments.push(storage).is_err() {
+            return Err(MemoryInitError::StorageFragmented { slots });
+        }
+        left -= count;
+    }
+
+    if carved.segments.segment_count() > 1 {
+        crate::diagln!(
+            "allocator storage for {} slots split across {} carve-outs",
+            slots,
+            carved.segments.segment_count()
+        );
+    }
+
+    Ok(carved)
+}
+
+fn frame_alloc_error(err: FrameAllocError) -> MemoryInitError {
+    match err {
+        FrameAllocError::OutOfFrames => MemoryInitError::OutOfFrames,
+        FrameAllocError::NonContiguous { expected, found } => {
+            MemoryInitError::NonContiguous { expected, found }
+        }
+        FrameAllocError::InvalidRequest => MemoryInitError::EmptyMemoryMap,
+        FrameAllocError::CursorsExhausted { capacity } => {
+            MemoryInitError::FrameCursorsExhausted { capacity }
+        }
+    }
 }
 
 /// Perform early kernel memory initialisation and install identity paging.
 pub fn initialize(
     memory_map: &MemoryMap,
     framebuffer: &Framebuffer,
+    memory_attributes: PhysRange,
+    loader_image: PhysRange,
 ) -> Result<(), MemoryInitError> {
     crate::diagln!("memory init: starting");
 
+    let phys_bits = crate::cpu::phys_addr_bits();
+    check_phys_width(memory_map, phys_bits)?;
+    paging::set_phys_addr_bits(phys_bits);
+    crate::diagln!("memory init: {}-bit physical addresses", phys_bits);
     ensure_usable_memory(memory_map)?;
 
     let mut frame_allocator = FrameAllocator::new(memory_map);
@@ -331,20 +500,43 @@ pub fn initialize(
 
     let rsp = current_stack_pointer();
 
-    let identity_ranges = stage_identity_ranges(memory_map, map_copy_range, rsp)?;
+    let identity_ranges = stage_identity_ranges(memory_map, map_copy_range, rsp, loader_image)?;
 
     let mut reservations = stage_reservations(identity_ranges.as_slice(), framebuffer)?;
 
     bring_up_allocator(&mut frame_allocator, kernel_memory_map, &mut reservations)?;
+    crate::debug_structured!(
+        "early frame allocator retired:",
+        [("remaining_frames", frame_allocator.remaining())]
+    );
 
-    install_identity_mappings(identity_ranges.as_slice(), framebuffer)?;
+    install_identity_mappings(identity_ranges.as_slice(), framebuffer, memory_attributes)?;
 
     crate::diagln!("identity paging installed");
+
+    grow_console_history();
+    if crate::options::debug_enabled() {
+        accounting::log();
+    }
     crate::diagln!("memory init: completed");
 
     Ok(())
 }
 
+/// Refuse a map describing memory past the CPU's `bits`-wide physical
+/// address space: firmware and CPU disagree about the machine, and masking
+/// such an address into a page-table entry would alias lower memory.
+fn check_phys_width(memory_map: &MemoryMap, bits: u8) -> Result<(), MemoryInitError> {
+    let limit = 1u64 << bits.min(paging::MAX_PHYS_ADDR_BITS);
+    let beyond = MemoryMapIter::new(memory_map)
+        .filter_map(descriptor_range)
+        .find(|&(_, end)| end > limit);
+    if let Some((start, end)) = beyond {
+        return Err(MemoryInitError::BeyondPhysWidth { start, end, bits });
+    }
+    Ok(())
+}
+
 fn ensure_usable_memory(memory_map: &MemoryMap) -> Result<(), MemoryInitError> {
     if UsableFrameIter::new(memory_map).next().is_some() {
         Ok(())
@@ -374,6 +566,24 @@ fn loader_stack_info(memory_map: &MemoryMap, rsp: u64) -> Result<(u64, u64), Mem
     Ok(range)
 }
 
+/// The loader-reported image range, if it holds the kernel's code.
+fn loader_image_range(loader_image: PhysRange, code_addr: u64) -> Option<(u64, u64)> {
+    if loader_image.len == 0 {
+        return None;
+    }
+    let end = loader_image.phys + loader_image.len;
+    if !(loader_image.phys..end).contains(&code_addr) {
+        crate::println!(
+            "WARNING: loader image {}..{} does not contain kernel code {}; inferring it.",
+            fmt_hex(loader_image.phys, 16),
+            fmt_hex(end, 16),
+            fmt_hex(code_addr, 16)
+        );
+        return None;
+    }
+    Some((loader_image.phys, end))
+}
+
 fn kernel_code_identity_range(memory_map: &MemoryMap, code_addr: u64) -> Option<((u64, u64), u32)> {
     let descriptor = find_descriptor_containing(memory_map, code_addr)?;
     let range = descriptor_range(descriptor)?;
@@ -401,6 +611,45 @@ struct CopiedMemoryMap {
     phys_range: (u64, u64),
 }
 
+/// Copy `src` into `dest` one descriptor (`stride` bytes) at a time,
+/// hashing the bytes read, then hash `dest` again and compare, so a copy that
+/// does not read back intact is caught before the kernel relies on it.
+fn copy_verified(src: &[u8], dest: &mut [u8], stride: usize) -> Result<(), MemoryInitError> {
+    let mut expected = Fnv1a::new();
+    for (from, to) in src
+        .chunks(stride.max(1))
+        .zip(dest.chunks_mut(stride.max(1)))
+    {
+        to.copy_from_slice(from);
+        expected.update(from);
+    }
+
+    let mut found = Fnv1a::new();
+    found.update(dest);
+    if found.0 != expected.0 {
+        return Err(MemoryInitError::MapCopyCorrupt {
+            expected: expected.0,
+            found: found.0,
+        });
+    }
+    Ok(())
+}
+
+/// 64-bit FNV-1a, enough to notice a copy that went astray.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
 fn copy_memory_map(
     original: &MemoryMap,
     alloc: &mut FrameAllocator,
@@ -420,26 +669,38 @@ fn copy_memory_map(
         return Err(MemoryInitError::EmptyMemoryMap);
     }
 
+    // The map may itself sit in conventional memory; keep the copy off it.
+    let source = original.descriptors_phys;
+    let source_end = source
+        .checked_add(map_size)
+        .ok_or(MemoryInitError::TooLarge)?;
+    alloc.avoid(source, source_end);
+
     let first = alloc
         .alloc_contiguous(frame_count)
-        .map_err(|err| match err {
-            FrameAllocError::OutOfFrames => MemoryInitError::OutOfFrames,
-            FrameAllocError::NonContiguous { expected, found } => {
-                MemoryInitError::NonContiguous { expected, found }
-            }
-            FrameAllocError::InvalidRequest => MemoryInitError::EmptyMemoryMap,
-        })?;
+        .map_err(frame_alloc_error)?;
 
     let copy_bytes = map_size as usize;
-    let dest_ptr = first as *mut u8;
-    let src_ptr = original.descriptors_phys as *const u8;
-
-    unsafe {
-        ptr::copy_nonoverlapping(src_ptr, dest_ptr, copy_bytes);
+    if first < source_end && source < first + map_size {
+        return Err(MemoryInitError::MapCopyOverlap {
+            source,
+            dest: first,
+        });
     }
 
+    // SAFETY: both ranges are identity mapped, `copy_bytes` long, and
+    // disjoint (checked above); nothing else writes either during the copy.
+    let (src, dest) = unsafe {
+        (
+            slice::from_raw_parts(source as *const u8, copy_bytes),
+            slice::from_raw_parts_mut(first as *mut u8, copy_bytes),
+        )
+    };
+    copy_verified(src, dest, original.entry_size as usize)?;
+
     let mut map = *original;
     map.descriptors_phys = first;
+    accounting::charge(Subsystem::MemoryMap, frame_count as u64);
 
     let phys_end = first + (frame_count as u64 * FRAME_SIZE);
 
@@ -448,3 +709,131 @@ fn copy_memory_map(
         phys_range: (first, phys_end),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::{vec, vec::Vec};
+    use oxide_abi::{EfiMemoryType, MemoryDescriptor};
+
+    #[repr(C, align(4096))]
+    #[derive(Clone, Copy)]
+    struct Page([u8; FRAME_SIZE as usize]);
+
+    /// A map whose only conventional descriptor covers `pages`, with the
+    /// descriptor itself stored in the first page.
+    fn map_inside_its_own_memory(pages: &mut [Page]) -> MemoryMap {
+        let count = pages.len() as u64;
+        map_in_first_page(pages, &[(EfiMemoryType::ConventionalMemory, 0, count)])
+    }
+
+    /// A map of `(type, first page, page count)` descriptors over `pages`,
+    /// stored in the first page.
+    fn map_in_first_page(pages: &mut [Page], layout: &[(EfiMemoryType, u64, u64)]) -> MemoryMap {
+        let base = pages.as_ptr() as u64;
+        let table = pages.as_mut_ptr().cast::<MemoryDescriptor>();
+        for (index, &(typ, first, count)) in layout.iter().enumerate() {
+            let descriptor = MemoryDescriptor {
+                typ: typ as u32,
+                _pad: 0,
+                physical_start: base + first * FRAME_SIZE,
+                virtual_start: 0,
+                number_of_pages: count,
+                attribute: 0,
+            };
+            unsafe { table.add(index).write(descriptor) };
+        }
+        let entry_size = mem::size_of::<MemoryDescriptor>();
+        MemoryMap {
+            descriptors_phys: base,
+            map_size: (entry_size * layout.len()) as u64,
+            entry_size: entry_size as u32,
+            entry_version: 1,
+            entry_count: layout.len() as u32,
+        }
+    }
+
+    #[test]
+    fn check_phys_width_rejects_memory_past_maxphyaddr() {
+        let mut pages: Vec<Page> = vec![Page([0; FRAME_SIZE as usize]); 2];
+        let map = map_inside_its_own_memory(&mut pages);
+        let end = pages.as_ptr() as u64 + 2 * FRAME_SIZE;
+
+        assert!(check_phys_width(&map, paging::MAX_PHYS_ADDR_BITS).is_ok());
+        assert!(matches!(
+            check_phys_width(&map, 12),
+            Err(MemoryInitError::BeyondPhysWidth { end: found, bits: 12, .. }) if found == end
+        ));
+    }
+
+    #[test]
+    fn copy_memory_map_never_lands_on_its_source() {
+        let mut pages: Vec<Page> = vec![Page([0; FRAME_SIZE as usize]); 2];
+        let map = map_inside_its_own_memory(&mut pages);
+        let mut alloc = FrameAllocator::new(&map);
+
+        let copied = copy_memory_map(&map, &mut alloc).unwrap();
+        assert_eq!(
+            copied.map.descriptors_phys,
+            map.descriptors_phys + FRAME_SIZE
+        );
+        let descriptor = mem::size_of::<MemoryDescriptor>();
+        assert_eq!(pages[1].0[..descriptor], pages[0].0[..descriptor]);
+
+        // The only free space is the source's own frame.
+        let mut pages: Vec<Page> = vec![Page([0; FRAME_SIZE as usize]); 1];
+        let map = map_inside_its_own_memory(&mut pages);
+        let mut alloc = FrameAllocator::new(&map);
+        assert!(matches!(
+            copy_memory_map(&map, &mut alloc),
+            Err(MemoryInitError::OutOfFrames)
+        ));
+    }
+
+    #[test]
+    fn copy_verified_copies_partial_strides() {
+        let src: Vec<u8> = (0..100).collect();
+        let mut dest = vec![0u8; 100];
+        assert!(copy_verified(&src, &mut dest, 48).is_ok());
+        assert_eq!(src, dest);
+    }
+
+    #[test]
+    fn carve_storage_splits_across_descriptors_when_fragmented() {
+        use EfiMemoryType::{ConventionalMemory, LoaderData};
+
+        let mut pages: Vec<Page> = vec![Page([0xAA; FRAME_SIZE as usize]); 6];
+        let base = pages.as_ptr() as u64;
+        let map = map_in_first_page(
+            &mut pages,
+            &[
+                (LoaderData, 0, 1),
+                (ConventionalMemory, 1, 2),
+                (LoaderData, 3, 1),
+                (ConventionalMemory, 4, 2),
+            ],
+        );
+        let mut alloc = FrameAllocator::new(&map);
+
+        // Three frames of slots, but no descriptor holds more than two.
+        let slots = 3 * FRAME_SIZE as usize / mem::size_of::<u64>();
+        let carved = unsafe { carve_storage(&mut alloc, slots, 7u64) }.unwrap();
+        assert_eq!(carved.segments.len(), slots);
+        assert_eq!(
+            carved.regions(),
+            [
+                (base + FRAME_SIZE, base + 3 * FRAME_SIZE),
+                (base + 4 * FRAME_SIZE, base + 5 * FRAME_SIZE),
+            ]
+            .map(|(start, end)| ReservedRegion {
+                start,
+                end,
+                owners: Owners::ALLOCATOR,
+            })
+        );
+        assert!(carved.segments.iter().all(|&slot| slot == 7));
+        assert!(pages[5].0.iter().all(|&byte| byte == 0xAA));
+    }
+}
@@ -1,9 +1,11 @@
-use crate::memory::frame::FRAME_SIZE;
+use crate::memory::{frame::FRAME_SIZE, paranoid};
 use oxide_abi::{MemoryDescriptor, MemoryMap};
 
 /// Iterator over firmware memory descriptors backed by a raw buffer.
 pub struct MemoryMapIter<'a> {
     base: usize,
+    /// End of the descriptor buffer, for the `paranoid` bounds check.
+    end: usize,
     entry_size: usize,
     remaining: u32,
     _marker: core::marker::PhantomData<&'a ()>,
@@ -14,6 +16,7 @@ impl<'a> MemoryMapIter<'a> {
     pub fn new(map: &'a MemoryMap) -> Self {
         Self {
             base: map.descriptors_phys as usize,
+            end: map.descriptors_phys.saturating_add(map.map_size) as usize,
             entry_size: map.entry_size as usize,
             remaining: map.entry_count,
             _marker: core::marker::PhantomData,
@@ -49,6 +52,9 @@ impl<'a> Iterator for MemoryMapIter<'a> {
             return None;
         }
 
+        if paranoid::ENABLED {
+            paranoid::check_descriptor(self.base, self.end, self.entry_size);
+        }
         let desc = unsafe { &*(self.base as *const MemoryDescriptor) };
         self.base = self.base.wrapping_add(self.entry_size);
         self.remaining -= 1;
@@ -145,4 +151,31 @@ mod tests {
 
         assert_eq!(collected, vec![0x1000, 0x2000, 0x3000]);
     }
+
+    #[test]
+    fn memory_map_iter_honours_oversized_stride_and_slack() {
+        // Firmware commonly pads descriptors to 48 bytes and leaves slack
+        // after the last one.
+        const STRIDE: usize = 48;
+        let starts = [0x1000u64, 0x5000, 0x9000];
+        let mut backing = vec![0u64; (STRIDE * starts.len() + 16) / 8];
+        for (index, &start) in starts.iter().enumerate() {
+            let slot = backing.as_mut_ptr() as usize + index * STRIDE;
+            let desc = descriptor(EfiMemoryType::ConventionalMemory, start, 1);
+            unsafe { (slot as *mut MemoryDescriptor).write(desc) };
+        }
+        let mut map = MemoryMap {
+            descriptors_phys: backing.as_ptr() as u64,
+            map_size: (backing.len() * 8) as u64,
+            entry_size: STRIDE as u32,
+            entry_version: 1,
+            entry_count: 4,
+        };
+
+        assert!(crate::boot::reconcile_memory_map(&mut map).is_some());
+        let collected: Vec<u64> = MemoryMapIter::new(&map)
+            .map(|desc| desc.physical_start)
+            .collect();
+        assert_eq!(collected, starts);
+    }
 }
@@ -0,0 +1,260 @@
+//! Destructive RAM test run over free conventional memory (`memtest=<patterns>`).
+//!
+//! Every frame the early frame allocator would hand out is tested with each
+//! selected pattern before any allocator takes ownership. Each pass writes
+//! the whole range before reading any of it back, and the values depend on
+//! the physical address, so two frames that decode to the same cells fail
+//! instead of each reading back what was just written. Failing frames are
+//! coalesced into runs and recorded as early reservations, so neither the
+//! early nor the runtime allocator ever uses them.
+
+use core::ptr;
+
+use oxide_abi::MemoryMap;
+use oxide_util::cmdline::parse_u64;
+
+use crate::memory::{
+    allocator::ReservedRegion,
+    early,
+    frame::{FRAME_SIZE, UsableFrameIter},
+    reservation::Owners,
+};
+
+const WORDS_PER_FRAME: usize = (FRAME_SIZE / 8) as usize;
+
+/// Distinct bad runs tracked in one pass; further failures are only counted.
+const MAX_BAD_RUNS: usize = 8;
+
+/// Write/readback patterns, applied in this order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pattern {
+    /// The word at `addr` holds a single set bit at position
+    /// `(addr / 8) % 64`, then its inverse.
+    WalkingOnes,
+    /// Each word holds its own physical address, catching aliased address lines.
+    AddressInAddress,
+}
+
+const PATTERNS: [Pattern; 2] = [Pattern::WalkingOnes, Pattern::AddressInAddress];
+
+/// The value a pass stores in the word at a physical address.
+type Fill = fn(u64) -> u64;
+
+const WALKING_ONES: [Fill; 2] = [walking_one, walking_zero];
+const ADDRESS_IN_ADDRESS: [Fill; 1] = [own_address];
+
+impl Pattern {
+    /// The passes that make up the pattern, in order.
+    fn passes(self) -> &'static [Fill] {
+        match self {
+            Pattern::WalkingOnes => &WALKING_ONES,
+            Pattern::AddressInAddress => &ADDRESS_IN_ADDRESS,
+        }
+    }
+}
+
+fn walking_one(addr: u64) -> u64 {
+    1u64 << ((addr / 8) % 64)
+}
+
+fn walking_zero(addr: u64) -> u64 {
+    !walking_one(addr)
+}
+
+fn own_address(addr: u64) -> u64 {
+    addr
+}
+
+/// Summary of a memory test run.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemtestReport {
+    pub frames_tested: u64,
+    pub bad_frames: u64,
+    /// Bad frames that could not be recorded as reservations and may be reused.
+    pub unreserved_frames: u64,
+}
+
+/// Run the memory test if `memtest=<n>` requests one or more patterns.
+pub fn run_if_requested(map: &MemoryMap) -> Option<MemtestReport> {
+    let patterns =
+        parse_u64(crate::options::value("memtest")?)?.min(PATTERNS.len() as u64) as usize;
+    if patterns == 0 {
+        return None;
+    }
+
+    crate::println!(
+        "memtest: testing free memory with {} pattern(s)...",
+        patterns
+    );
+    let (report, work) = crate::cpu::pmu::measure(|| run(map, &PATTERNS[..patterns]));
+    crate::println!(
+        "memtest: {} frames tested, {} bad, {} left unreserved",
+        report.frames_tested,
+        report.bad_frames,
+        report.unreserved_frames
+    );
+    if let Some(work) = work {
+        crate::diagln!(
+            "memtest: {} instructions, {} cycles",
+            work.instructions,
+            work.cycles
+        );
+    }
+    Some(report)
+}
+
+fn run(map: &MemoryMap, patterns: &[Pattern]) -> MemtestReport {
+    let mut report = MemtestReport {
+        frames_tested: UsableFrameIter::new(map).count() as u64,
+        ..MemtestReport::default()
+    };
+    let mut runs = BadRuns::new();
+
+    for pattern in patterns {
+        for &pass in pattern.passes() {
+            for frame in UsableFrameIter::new(map) {
+                // SAFETY: see `frame_words`.
+                fill(unsafe { frame_words(frame) }, frame, pass);
+            }
+            for frame in UsableFrameIter::new(map) {
+                // SAFETY: see `frame_words`.
+                if !verify(unsafe { frame_words(frame) }, frame, pass) && runs.record(frame) {
+                    report.bad_frames += 1;
+                }
+            }
+        }
+    }
+
+    report.unreserved_frames = runs.overflow_frames;
+    for run in runs.iter() {
+        if early::reserve_range(run.start, run.end, Owners::BAD_MEMORY).is_err() {
+            report.unreserved_frames += (run.end - run.start) / FRAME_SIZE;
+        }
+    }
+
+    report
+}
+
+/// The words of `frame`.
+///
+/// # Safety
+/// `frame` must come from `UsableFrameIter`: free conventional memory,
+/// identity-mapped by firmware and not yet owned by anything.
+unsafe fn frame_words(frame: u64) -> &'static mut [u64] {
+    unsafe { core::slice::from_raw_parts_mut(frame as *mut u64, WORDS_PER_FRAME) }
+}
+
+/// Store `pass` into `words`, which start at physical address `base`.
+fn fill(words: &mut [u64], base: u64, pass: Fill) {
+    for (addr, word) in (base..).step_by(8).zip(words.iter_mut()) {
+        unsafe { ptr::write_volatile(word, pass(addr)) };
+    }
+}
+
+/// Whether `words` still hold what [`fill`] stored, returning false on any
+/// mismatch.
+fn verify(words: &[u64], base: u64, pass: Fill) -> bool {
+    (base..)
+        .step_by(8)
+        .zip(words)
+        .all(|(addr, word)| unsafe { ptr::read_volatile(word) } == pass(addr))
+}
+
+/// Failing frames coalesced into contiguous runs.
+struct BadRuns {
+    runs: [ReservedRegion; MAX_BAD_RUNS],
+    len: usize,
+    overflow_frames: u64,
+}
+
+impl BadRuns {
+    fn new() -> Self {
+        Self {
+            runs: [ReservedRegion::default(); MAX_BAD_RUNS],
+            len: 0,
+            overflow_frames: 0,
+        }
+    }
+
+    /// Add `frame`, returning false if an earlier pass already recorded it.
+    fn record(&mut self, frame: u64) -> bool {
+        if self.runs[..self.len]
+            .iter()
+            .any(|run| run.start <= frame && frame < run.end)
+        {
+            return false;
+        }
+        if let Some(last) = self.runs[..self.len].last_mut()
+            && last.end == frame
+        {
+            last.end += FRAME_SIZE;
+            return true;
+        }
+
+        if self.len == MAX_BAD_RUNS {
+            self.overflow_frames += 1;
+            return true;
+        }
+
+        self.runs[self.len] = ReservedRegion {
+            start: frame,
+            end: frame + FRAME_SIZE,
+            owners: Owners::BAD_MEMORY,
+        };
+        self.len += 1;
+        true
+    }
+
+    fn iter(&self) -> impl Iterator<Item = ReservedRegion> + '_ {
+        self.runs[..self.len].iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn patterns_pass_on_working_memory() {
+        let mut words = vec![0u64; WORDS_PER_FRAME];
+        for pattern in PATTERNS {
+            for &pass in pattern.passes() {
+                fill(&mut words, 0x10_0000, pass);
+                assert!(verify(&words, 0x10_0000, pass));
+            }
+        }
+        assert_eq!(words[1], 0x10_0008);
+    }
+
+    #[test]
+    fn aliased_frames_fail_the_earlier_one() {
+        // Two physical frames decoding to the same cells: the second fill
+        // overwrites the first before either is verified.
+        let mut cells = vec![0u64; WORDS_PER_FRAME];
+        fill(&mut cells, 0x10_0000, own_address);
+        fill(&mut cells, 0x20_0000, own_address);
+        assert!(!verify(&cells, 0x10_0000, own_address));
+        assert!(verify(&cells, 0x20_0000, own_address));
+    }
+
+    #[test]
+    fn bad_runs_coalesce_adjacent_frames() {
+        let mut runs = BadRuns::new();
+        runs.record(FRAME_SIZE);
+        runs.record(FRAME_SIZE * 2);
+        runs.record(FRAME_SIZE * 5);
+        for index in 0..MAX_BAD_RUNS as u64 {
+            runs.record(FRAME_SIZE * (10 + index * 2));
+        }
+
+        let collected: alloc::vec::Vec<_> = runs.iter().collect();
+        assert_eq!(collected.len(), MAX_BAD_RUNS);
+        assert_eq!(collected[0].start, FRAME_SIZE);
+        assert_eq!(collected[0].end, FRAME_SIZE * 3);
+        assert_eq!(collected[1].start, FRAME_SIZE * 5);
+        assert_eq!(runs.overflow_frames, 2);
+    }
+}
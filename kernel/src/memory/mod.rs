@@ -1,7 +1,21 @@
+pub mod accounting;
 pub mod allocator;
+pub mod attributes;
 pub mod early;
 pub mod error;
+pub mod failtest;
+pub mod fixmap;
 pub mod frame;
 pub mod init;
 pub mod map;
+pub mod memtest;
+pub mod overrides;
 pub mod paging;
+pub mod paranoid;
+pub mod pressure;
+pub mod reservation;
+pub mod sanitize;
+pub mod segments;
+pub mod zone;
+
+pub use fixmap::with_frame_mapped;
@@ -0,0 +1,133 @@
+//! Command-line overrides of the usable physical memory layout.
+//!
+//! - `mem=<size>` withholds all conventional memory at or above `<size>`.
+//! - `memmap=exclude:<start>+<len>` withholds a specific range; the option may
+//!   be repeated.
+//!
+//! Sizes and addresses accept decimal or `0x` hex with an optional `K`, `M`,
+//! or `G` suffix. Both overrides become early reservations, so the early frame
+//! allocator, console storage, and the runtime allocator all skip them.
+
+use oxide_abi::{EfiMemoryType, MemoryMap};
+use oxide_util::cmdline::parse_size;
+
+use crate::console::format::fmt_hex;
+use crate::memory::{early, frame::FRAME_SIZE, map::MemoryMapIter, reservation::Owners};
+
+/// Upper bound on `memmap=` exclusions honoured from the command line.
+pub const MAX_EXCLUSIONS: usize = 8;
+
+/// Outcome of applying the command-line overrides, for logging once the
+/// console is available.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OverrideReport {
+    /// Frame-aligned address above which memory was withheld by `mem=`.
+    pub mem_limit: Option<u64>,
+    /// Number of `memmap=exclude:` ranges reserved.
+    pub excluded: usize,
+    /// Number of override values that were malformed or could not be reserved.
+    pub rejected: usize,
+}
+
+impl OverrideReport {
+    /// Emit the applied overrides as diagnostics; rejected values are always shown.
+    pub fn log(&self) {
+        if let Some(limit) = self.mem_limit {
+            crate::diagln!("mem= caps usable memory below {}", fmt_hex(limit, 16));
+        }
+        if self.excluded > 0 {
+            crate::diagln!("memmap= excluded {} range(s)", self.excluded);
+        }
+        if self.rejected > 0 {
+            crate::println!(
+                "memory overrides: ignored {} invalid value(s)",
+                self.rejected
+            );
+        }
+    }
+}
+
+/// Reserve the ranges requested by `mem=` and `memmap=` before any allocator
+/// hands out frames.
+pub fn apply(map: &MemoryMap) -> OverrideReport {
+    let mut report = OverrideReport::default();
+    let top = highest_usable_address(map);
+    let mut ceiling = top;
+
+    if let Some(value) = crate::options::value("mem") {
+        match parse_size(value).filter(|&limit| limit > 0) {
+            Some(limit) => {
+                let limit = limit & !(FRAME_SIZE - 1);
+                if limit < top && early::reserve_range(limit, top, Owners::COMMAND_LINE).is_err() {
+                    report.rejected += 1;
+                } else {
+                    report.mem_limit = Some(limit);
+                    ceiling = ceiling.min(limit);
+                }
+            }
+            None => report.rejected += 1,
+        }
+    }
+
+    for value in crate::options::values("memmap") {
+        let Some((start, end)) = parse_exclusion(value) else {
+            report.rejected += 1;
+            continue;
+        };
+
+        if start >= ceiling {
+            // Already withheld by `mem=` or beyond usable memory.
+            continue;
+        }
+
+        if report.excluded >= MAX_EXCLUSIONS
+            || early::reserve_range(start, end.min(ceiling), Owners::COMMAND_LINE).is_err()
+        {
+            report.rejected += 1;
+            continue;
+        }
+        report.excluded += 1;
+    }
+
+    report
+}
+
+/// End of the highest conventional memory descriptor.
+fn highest_usable_address(map: &MemoryMap) -> u64 {
+    MemoryMapIter::new(map)
+        .filter(|descriptor| descriptor.typ == EfiMemoryType::ConventionalMemory as u32)
+        .filter_map(|descriptor| {
+            descriptor
+                .number_of_pages
+                .checked_mul(FRAME_SIZE)
+                .and_then(|size| descriptor.physical_start.checked_add(size))
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Parse `exclude:<start>+<len>` into a half-open `[start, end)` range.
+fn parse_exclusion(value: &str) -> Option<(u64, u64)> {
+    let range = value.strip_prefix("exclude:")?;
+    let (start, len) = range.split_once('+')?;
+    let start = parse_size(start)?;
+    let len = parse_size(len).filter(|&len| len > 0)?;
+    Some((start, start.checked_add(len)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_exclusion_builds_half_open_range() {
+        assert_eq!(
+            parse_exclusion("exclude:0x1000000+16M"),
+            Some((0x100_0000, 0x200_0000))
+        );
+        assert_eq!(parse_exclusion("exclude:4096+0"), None);
+        assert_eq!(parse_exclusion("include:0+4K"), None);
+        assert_eq!(parse_exclusion("exclude:4096"), None);
+        assert_eq!(parse_exclusion("exclude:0xFFFFFFFFFFFFF000+8K"), None);
+    }
+}
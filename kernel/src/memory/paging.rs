@@ -1,6 +1,10 @@
 #![allow(dead_code)]
 
-use crate::memory::{allocator::PhysicalAllocator, error::PagingError, frame::FrameAllocator};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::memory::{
+    allocator::PhysicalAllocator, error::PagingError, failtest, frame::FrameAllocator,
+};
 use oxide_abi::Framebuffer;
 
 /// 4 KiB page size.
@@ -14,15 +18,63 @@ const ENTRIES: usize = 512;
 const PTE_PRESENT: u64 = 1 << 0;
 const PTE_WRITABLE: u64 = 1 << 1;
 // const PTE_USER: u64 = 1 << 2;
-// const PTE_WRITE_THROUGH: u64 = 1 << 3;
-// const PTE_CACHE_DISABLE: u64 = 1 << 4;
+const PTE_WRITE_THROUGH: u64 = 1 << 3;
+const PTE_CACHE_DISABLE: u64 = 1 << 4;
 // const PTE_ACCESSED: u64 = 1 << 5;
 // const PTE_DIRTY: u64 = 1 << 6;
 const PTE_PS: u64 = 1 << 7; // Page Size (1 = 2MiB at PD level)
+const PTE_NO_EXECUTE: u64 = 1 << 63; // requires EFER.NXE
+/// Flags a 2 MiB entry passes on to the 4 KiB entries it is split into.
+const PTE_INHERITED: u64 =
+    PTE_PRESENT | PTE_WRITABLE | PTE_WRITE_THROUGH | PTE_CACHE_DISABLE | PTE_NO_EXECUTE;
+
+/// Architectural limit on MAXPHYADDR.
+pub const MAX_PHYS_ADDR_BITS: u8 = 52;
+
+/// Physical address width the entry masks are cut to. Starts at the
+/// architectural limit; [`set_phys_addr_bits`] narrows it to the CPU's.
+static PHYS_ADDR_BITS: AtomicU8 = AtomicU8::new(MAX_PHYS_ADDR_BITS);
+
+/// Record the CPU's physical address width (MAXPHYADDR).
+pub fn set_phys_addr_bits(bits: u8) {
+    PHYS_ADDR_BITS.store(bits.min(MAX_PHYS_ADDR_BITS), Ordering::Relaxed);
+}
+
+/// First physical address the CPU cannot reach.
+pub fn phys_addr_limit() -> u64 {
+    1 << PHYS_ADDR_BITS.load(Ordering::Relaxed)
+}
+
+/// Address bits of a page-table entry mapping (or pointing at) `align`-sized
+/// frames.
+fn addr_mask(align: u64) -> u64 {
+    (phys_addr_limit() - 1) & !(align - 1)
+}
+
+fn addr_mask_4k() -> u64 {
+    addr_mask(PAGE_SIZE)
+}
 
-// masks and helpers
-const ADDR_MASK_4K: u64 = 0x000f_ffff_ffff_f000;
-const ADDR_MASK_2M: u64 = 0x000f_ffff_ffe0_0000;
+fn addr_mask_2m() -> u64 {
+    addr_mask(HUGE_PAGE_SIZE)
+}
+
+/// Caching policy for a 4 KiB mapping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Normal RAM.
+    WriteBack,
+    /// Device registers (LAPIC, IOAPIC, HPET).
+    Uncached,
+}
+
+/// Restrictions to apply to already-mapped pages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Protection {
+    pub read_only: bool,
+    /// Only meaningful once `EFER.NXE` is set; the bit is reserved otherwise.
+    pub no_execute: bool,
+}
 
 /// A single 4 KiB page table with 512 entries (PML4, PDPT, PD, or PT).
 #[repr(C, align(4096))]
@@ -70,6 +122,9 @@ impl PhysFrameAlloc for PhysicalAllocator<'_> {
 /// - Physical memory is identity-mapped at entry (VA == PA) for the regions we touch
 /// - Interrupts are disabled (recommended)
 /// - UEFI boot: CR4.PAE=1, EFER.LME=1, paging already enabled
+/// - 4-level paging (CR4.LA57=0). Under 5-level paging this PML4 would be
+///   read as a PML5 and every mapping would land in the wrong place, so
+///   `boot::verify_machine_state` refuses to boot before anything gets here.
 pub unsafe fn install_identity_paging<A: PhysFrameAlloc>(
     alloc: &mut A,
     fb: &Framebuffer,
@@ -89,7 +144,7 @@ pub unsafe fn install_identity_paging<A: PhysFrameAlloc>(
     }
 
     // Write PML4[0] to point to our PDPT
-    pml4.entries[0] = (pdpt_phys & ADDR_MASK_4K) | PTE_PRESENT | PTE_WRITABLE;
+    pml4.entries[0] = (pdpt_phys & addr_mask_4k()) | PTE_PRESENT | PTE_WRITABLE;
 
     // map low memory region
     map_identity_range_2mib(alloc, pdpt, 0, low_bytes)?;
@@ -138,7 +193,8 @@ fn map_identity_range_2mib<A: PhysFrameAlloc>(
         // We only wired PML4[0]; that covers the lower canonical half (0..512GiB)
         let pml4_index = ((addr >> 39) & 0x1ff) as usize;
 
-        if pml4_index != 0 {
+        if pml4_index != 0 || addr >= phys_addr_limit() || failtest::map_fails(addr, HUGE_PAGE_SIZE)
+        {
             return Err(PagingError::UnsupportedAddress(addr));
         }
 
@@ -149,7 +205,7 @@ fn map_identity_range_2mib<A: PhysFrameAlloc>(
         let pd = phys_as_table_mut(pd_phys);
 
         // Map the 2 MiB page at PD level
-        pd.entries[pd_index] = (addr & ADDR_MASK_2M) | PTE_PRESENT | PTE_WRITABLE | PTE_PS;
+        pd.entries[pd_index] = (addr & addr_mask_2m()) | PTE_PRESENT | PTE_WRITABLE | PTE_PS;
 
         addr = addr
             .checked_add(HUGE_PAGE_SIZE)
@@ -171,12 +227,152 @@ fn ensure_pd<A: PhysFrameAlloc>(
         unsafe {
             pd.zero();
         }
-        pdpt.entries[index] = (pd_phys & ADDR_MASK_4K) | PTE_PRESENT | PTE_WRITABLE;
+        pdpt.entries[index] = (pd_phys & addr_mask_4k()) | PTE_PRESENT | PTE_WRITABLE;
     }
-    let pd_phys = pdpt.entries[index] & ADDR_MASK_4K;
+    let pd_phys = pdpt.entries[index] & addr_mask_4k();
     Ok(pd_phys)
 }
 
+/// Ensure a page table covering the 2 MiB window containing `virt` exists,
+/// allocating intermediate tables as needed, and return its physical address.
+///
+/// Fails with `UnsupportedAddress` if the window is already mapped by a 2 MiB page.
+///
+/// # Safety
+/// `pml4_phys` must point to a valid, identity-accessible PML4 table.
+pub unsafe fn ensure_page_table<A: PhysFrameAlloc>(
+    alloc: &mut A,
+    pml4_phys: u64,
+    virt: u64,
+) -> Result<u64, PagingError> {
+    let pml4 = phys_as_table_mut(pml4_phys);
+    let pdpt_phys = ensure_table(alloc, pml4, ((virt >> 39) & 0x1ff) as usize)?;
+    let pdpt = phys_as_table_mut(pdpt_phys);
+    let pd_phys = ensure_pd(alloc, pdpt, ((virt >> 30) & 0x1ff) as usize)?;
+    let pd = phys_as_table_mut(pd_phys);
+
+    let pd_index = ((virt >> 21) & 0x1ff) as usize;
+    if pd.entries[pd_index] & PTE_PS != 0 || failtest::map_fails(virt, PAGE_SIZE) {
+        return Err(PagingError::UnsupportedAddress(virt));
+    }
+    ensure_table(alloc, pd, pd_index)
+}
+
+/// Point entry `index` of the page table at `pt_phys` to the 4 KiB frame `phys`
+/// with the given caching, or clear it when `phys` is `None`, then flush the
+/// TLB entry for `virt`.
+///
+/// # Safety
+/// `pt_phys` must be a page table returned by [`ensure_page_table`] for the
+/// window containing `virt`, and no live reference may depend on the old mapping.
+pub unsafe fn set_page(pt_phys: u64, index: usize, virt: u64, phys: Option<(u64, CacheMode)>) {
+    let pt = phys_as_table_mut(pt_phys);
+    pt.entries[index] = match phys {
+        Some((phys, CacheMode::WriteBack)) => (phys & addr_mask_4k()) | PTE_PRESENT | PTE_WRITABLE,
+        Some((phys, CacheMode::Uncached)) => {
+            (phys & addr_mask_4k())
+                | PTE_PRESENT
+                | PTE_WRITABLE
+                | PTE_CACHE_DISABLE
+                | PTE_WRITE_THROUGH
+        }
+        None => 0,
+    };
+    invlpg(virt);
+}
+
+/// Apply `protection` to every mapped 4 KiB page overlapping `[start, end)`,
+/// splitting 2 MiB pages as needed, and return how many pages changed.
+///
+/// Unmapped pages are skipped; protections only ever tighten.
+///
+/// # Safety
+/// `pml4_phys` must point to a valid, identity-accessible PML4 table, and
+/// nothing may still write to (or execute from) the pages being restricted.
+pub unsafe fn protect_range<A: PhysFrameAlloc>(
+    alloc: &mut A,
+    pml4_phys: u64,
+    start: u64,
+    end: u64,
+    protection: Protection,
+) -> Result<u64, PagingError> {
+    let mut changed = 0;
+    let mut virt = align_down(start, PAGE_SIZE);
+    while virt < end {
+        if let Some(pt_phys) = mapped_page_table(alloc, pml4_phys, virt)? {
+            let entry = &mut phys_as_table_mut(pt_phys).entries[((virt >> 12) & 0x1ff) as usize];
+            if *entry & PTE_PRESENT != 0 {
+                let mut restricted = *entry;
+                if protection.read_only {
+                    restricted &= !PTE_WRITABLE;
+                }
+                if protection.no_execute {
+                    restricted |= PTE_NO_EXECUTE;
+                }
+                if restricted != *entry {
+                    *entry = restricted;
+                    invlpg(virt);
+                    changed += 1;
+                }
+            }
+        }
+        virt = match virt.checked_add(PAGE_SIZE) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Ok(changed)
+}
+
+/// The page table mapping `virt`, splitting a 2 MiB page into 4 KiB pages
+/// with the same flags if necessary, or `None` when `virt` is unmapped.
+fn mapped_page_table<A: PhysFrameAlloc>(
+    alloc: &mut A,
+    pml4_phys: u64,
+    virt: u64,
+) -> Result<Option<u64>, PagingError> {
+    let mut table = phys_as_table_mut(pml4_phys);
+    for shift in [39, 30] {
+        let entry = table.entries[((virt >> shift) & 0x1ff) as usize];
+        if entry & PTE_PRESENT == 0 {
+            return Ok(None);
+        }
+        table = phys_as_table_mut(entry & addr_mask_4k());
+    }
+
+    let pde = &mut table.entries[((virt >> 21) & 0x1ff) as usize];
+    if *pde & PTE_PRESENT == 0 {
+        return Ok(None);
+    }
+    if *pde & PTE_PS != 0 {
+        let pt_phys = alloc.allocate_frame().ok_or(PagingError::OutOfFrames)?;
+        let base = *pde & addr_mask_2m();
+        let flags = *pde & PTE_INHERITED;
+        let pt = phys_as_table_mut(pt_phys);
+        for (index, entry) in pt.entries.iter_mut().enumerate() {
+            *entry = (base + index as u64 * PAGE_SIZE) | flags;
+        }
+        *pde = (pt_phys & addr_mask_4k()) | PTE_PRESENT | PTE_WRITABLE;
+        invlpg(align_down(virt, HUGE_PAGE_SIZE));
+    }
+    Ok(Some(*pde & addr_mask_4k()))
+}
+
+fn ensure_table<A: PhysFrameAlloc>(
+    alloc: &mut A,
+    table: &mut PageTable,
+    index: usize,
+) -> Result<u64, PagingError> {
+    if table.entries[index] & PTE_PRESENT == 0 {
+        let phys = alloc.allocate_frame().ok_or(PagingError::OutOfFrames)?;
+        unsafe {
+            phys_as_table_mut(phys).zero();
+        }
+        table.entries[index] = (phys & addr_mask_4k()) | PTE_PRESENT | PTE_WRITABLE;
+    }
+    Ok(table.entries[index] & addr_mask_4k())
+}
+
 fn phys_as_table_mut(phys: u64) -> &'static mut PageTable {
     let ptr = phys as *mut PageTable;
     unsafe { &mut *ptr }
@@ -194,10 +390,21 @@ fn align_up(addr: u64, align: u64) -> u64 {
     (addr + align - 1) & !(align - 1)
 }
 
+#[cfg(not(test))]
+fn invlpg(virt: u64) {
+    unsafe {
+        core::arch::asm!("invlpg [{0}]", in(reg) virt, options(nostack, preserves_flags));
+    }
+}
+
+/// Host tests operate on heap-backed tables that are never loaded into CR3.
+#[cfg(test)]
+fn invlpg(_virt: u64) {}
+
 /// Load CR3 with the physical address of the PML4 table.
 /// # Safety: `pml4_phys` must point to a valid PML4 table (4 KiB aligned).
 fn load_cr3(pml4_phys: u64) {
-    let val = pml4_phys & ADDR_MASK_4K;
+    let val = pml4_phys & addr_mask_4k();
     unsafe {
         core::arch::asm!(
             "mov cr3, {0}",
@@ -205,4 +412,158 @@ fn load_cr3(pml4_phys: u64) {
             options(nostack, preserves_flags),
         );
     }
+
+    if super::paranoid::ENABLED {
+        let read_back: u64;
+        unsafe {
+            core::arch::asm!("mov {0}, cr3", out(reg) read_back, options(nomem, nostack, preserves_flags));
+        }
+        super::paranoid::check_cr3(val, read_back, addr_mask_4k());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::{boxed::Box, vec::Vec};
+
+    /// Hands out heap-backed, page-aligned tables; on the host "physical"
+    /// addresses are just their pointers.
+    struct HostFrames(Vec<Box<PageTable>>);
+
+    impl PhysFrameAlloc for HostFrames {
+        fn allocate_frame(&mut self) -> Option<u64> {
+            let table = Box::new(PageTable {
+                entries: [0xdead_beef; ENTRIES],
+            });
+            let phys = &*table as *const PageTable as u64;
+            self.0.push(table);
+            Some(phys)
+        }
+    }
+
+    #[test]
+    fn ensure_page_table_builds_and_reuses_hierarchy() {
+        let mut frames = HostFrames(Vec::new());
+        let pml4_phys = frames.allocate_frame().unwrap();
+        unsafe { phys_as_table_mut(pml4_phys).zero() };
+
+        let virt = 0xFFFF_FFFF_FFE0_0000;
+        let pt = unsafe { ensure_page_table(&mut frames, pml4_phys, virt) }.unwrap();
+        assert_eq!(frames.0.len(), 4);
+        assert!(
+            phys_as_table_mut(pt)
+                .entries
+                .iter()
+                .all(|&entry| entry == 0)
+        );
+
+        let again = unsafe { ensure_page_table(&mut frames, pml4_phys, virt + PAGE_SIZE) }.unwrap();
+        assert_eq!(again, pt);
+        assert_eq!(frames.0.len(), 4);
+
+        unsafe {
+            set_page(
+                pt,
+                1,
+                virt + PAGE_SIZE,
+                Some((0x1234_5000, CacheMode::WriteBack)),
+            )
+        };
+        assert_eq!(
+            phys_as_table_mut(pt).entries[1],
+            0x1234_5000 | PTE_PRESENT | PTE_WRITABLE
+        );
+        unsafe {
+            set_page(
+                pt,
+                2,
+                virt + 2 * PAGE_SIZE,
+                Some((0xFEE0_0000, CacheMode::Uncached)),
+            )
+        };
+        assert_eq!(
+            phys_as_table_mut(pt).entries[2],
+            0xFEE0_0000 | PTE_PRESENT | PTE_WRITABLE | PTE_CACHE_DISABLE | PTE_WRITE_THROUGH
+        );
+        unsafe { set_page(pt, 1, virt + PAGE_SIZE, None) };
+        assert_eq!(phys_as_table_mut(pt).entries[1], 0);
+    }
+
+    #[test]
+    fn ensure_page_table_rejects_huge_page_window() {
+        let mut frames = HostFrames(Vec::new());
+        let pml4_phys = frames.allocate_frame().unwrap();
+        unsafe { phys_as_table_mut(pml4_phys).zero() };
+
+        let pdpt_phys = unsafe { ensure_page_table(&mut frames, pml4_phys, 0) }
+            .map(|_| phys_as_table_mut(pml4_phys).entries[0] & addr_mask_4k())
+            .unwrap();
+        let pd_phys = phys_as_table_mut(pdpt_phys).entries[0] & addr_mask_4k();
+        phys_as_table_mut(pd_phys).entries[1] = HUGE_PAGE_SIZE | PTE_PRESENT | PTE_PS;
+
+        assert_eq!(
+            unsafe { ensure_page_table(&mut frames, pml4_phys, HUGE_PAGE_SIZE) },
+            Err(PagingError::UnsupportedAddress(HUGE_PAGE_SIZE))
+        );
+    }
+
+    #[test]
+    fn protect_range_splits_huge_pages_and_tightens_flags() {
+        let mut frames = HostFrames(Vec::new());
+        let pml4_phys = frames.allocate_frame().unwrap();
+        unsafe { phys_as_table_mut(pml4_phys).zero() };
+        let pdpt_phys = unsafe { ensure_page_table(&mut frames, pml4_phys, 0) }
+            .map(|_| phys_as_table_mut(pml4_phys).entries[0] & addr_mask_4k())
+            .unwrap();
+        let pd_phys = phys_as_table_mut(pdpt_phys).entries[0] & addr_mask_4k();
+        phys_as_table_mut(pd_phys).entries[1] =
+            HUGE_PAGE_SIZE | PTE_PRESENT | PTE_WRITABLE | PTE_PS;
+        let allocated = frames.0.len();
+
+        let start = HUGE_PAGE_SIZE + 3 * PAGE_SIZE;
+        let protection = Protection {
+            read_only: true,
+            no_execute: true,
+        };
+        let changed = unsafe {
+            protect_range(
+                &mut frames,
+                pml4_phys,
+                start,
+                start + PAGE_SIZE + 1,
+                protection,
+            )
+        };
+        assert_eq!(changed, Ok(2));
+        assert_eq!(frames.0.len(), allocated + 1);
+
+        let pde = phys_as_table_mut(pd_phys).entries[1];
+        assert_eq!(pde & PTE_PS, 0);
+        let pt = phys_as_table_mut(pde & addr_mask_4k());
+        assert_eq!(
+            pt.entries[2],
+            (start - PAGE_SIZE) | PTE_PRESENT | PTE_WRITABLE
+        );
+        assert_eq!(pt.entries[3], start | PTE_PRESENT | PTE_NO_EXECUTE);
+        assert_eq!(
+            pt.entries[4],
+            (start + PAGE_SIZE) | PTE_PRESENT | PTE_NO_EXECUTE
+        );
+
+        // Already protected, and the unmapped window next door is skipped.
+        let again = unsafe {
+            protect_range(
+                &mut frames,
+                pml4_phys,
+                start,
+                3 * HUGE_PAGE_SIZE,
+                protection,
+            )
+        };
+        assert_eq!(again, Ok(507));
+        assert_eq!(frames.0.len(), allocated + 1);
+    }
 }
@@ -0,0 +1,132 @@
+//! Extra runtime checks for hardware bring-up (`paranoid` feature).
+//!
+//! The kernel trusts the firmware's memory map, its own allocator metadata,
+//! and the page tables it loads. A bug in any of them tends to surface much
+//! later as an unrelated fault. With the `paranoid` feature the kernel checks
+//! them as it goes:
+//!
+//! - every memory-map descriptor read lies inside the map buffer, is aligned,
+//!   and is at least a `MemoryDescriptor` wide;
+//! - each carve-out of allocator metadata sits between two canary words,
+//!   verified after every runtime allocator call;
+//! - CR3 reads back as the table just loaded.
+//!
+//! A failed check panics with a message naming what it found. Without the
+//! feature [`ENABLED`] is `false` and the calls compile to nothing.
+
+use core::{
+    mem,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use oxide_abi::MemoryDescriptor;
+
+use super::segments::MAX_SEGMENTS;
+use crate::console::format::fmt_hex;
+
+/// Whether the checks run in this build. Host tests hand out heap memory as
+/// "physical" frames and free it afterwards, so they never arm canaries.
+pub const ENABLED: bool = cfg!(all(feature = "paranoid", not(test)));
+
+const CANARY_BYTES: usize = mem::size_of::<u64>();
+
+/// Bytes a caller leaves free on each side of a guarded region; zero when
+/// the checks are off.
+pub const GUARD_BYTES: usize = if ENABLED { CANARY_BYTES } else { 0 };
+
+const CANARY: u64 = 0x0D1D_E5CA_FE0F_A11E;
+
+/// Two words for each segment of the free-run and reservation storage.
+const MAX_CANARIES: usize = 2 * 2 * MAX_SEGMENTS;
+
+static CANARIES: [AtomicU64; MAX_CANARIES] = [const { AtomicU64::new(0) }; MAX_CANARIES];
+static CANARY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Write canaries just before `start` and at `end`, and remember both.
+/// Regions past [`MAX_CANARIES`] / 2 go unguarded.
+///
+/// # Safety
+/// The word below `start` and the word at `end` must be identity mapped,
+/// 8-byte aligned, and owned by the caller for the life of the kernel.
+pub unsafe fn place_canaries(start: u64, end: u64) {
+    for addr in [start - CANARY_BYTES as u64, end] {
+        let index = CANARY_COUNT.load(Ordering::Relaxed);
+        if index == MAX_CANARIES {
+            return;
+        }
+        // SAFETY: the caller owns the word at `addr`.
+        unsafe { (addr as *mut u64).write_volatile(CANARY) };
+        CANARIES[index].store(addr, Ordering::Relaxed);
+        CANARY_COUNT.store(index + 1, Ordering::Relaxed);
+    }
+}
+
+/// The address of the first canary that no longer holds its value.
+fn corrupted_canary() -> Option<u64> {
+    let count = CANARY_COUNT.load(Ordering::Relaxed);
+    CANARIES[..count]
+        .iter()
+        .map(|addr| addr.load(Ordering::Relaxed))
+        // SAFETY: `place_canaries` callers keep these words mapped and owned.
+        .find(|&addr| unsafe { (addr as *const u64).read_volatile() } != CANARY)
+}
+
+/// Panic if anything wrote over a canary.
+pub fn check_canaries() {
+    if let Some(addr) = corrupted_canary() {
+        panic!(
+            "paranoid: allocator metadata canary at {} overwritten",
+            fmt_hex(addr, 16)
+        );
+    }
+}
+
+/// Panic unless a descriptor at `addr` of `entry_size` bytes fits inside the
+/// memory map buffer ending at `end`.
+pub fn check_descriptor(addr: usize, end: usize, entry_size: usize) {
+    let fits = entry_size >= mem::size_of::<MemoryDescriptor>()
+        && addr.is_multiple_of(mem::align_of::<MemoryDescriptor>())
+        && addr
+            .checked_add(mem::size_of::<MemoryDescriptor>())
+            .is_some_and(|last| last <= end);
+    if !fits {
+        panic!(
+            "paranoid: memory map descriptor at {} ({} bytes) outside the map ending at {}",
+            fmt_hex(addr as u64, 16),
+            entry_size,
+            fmt_hex(end as u64, 16)
+        );
+    }
+}
+
+/// Panic unless CR3 holds the table address just loaded.
+pub fn check_cr3(loaded: u64, read_back: u64, address_mask: u64) {
+    if read_back & address_mask != loaded & address_mask {
+        panic!(
+            "paranoid: CR3 reads {} after loading {}",
+            fmt_hex(read_back, 16),
+            fmt_hex(loaded, 16)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn overwritten_canary_is_reported() {
+        let mut words = vec![0u64; 6];
+        let start = words.as_mut_ptr() as u64 + 8;
+        let end = start + 4 * 8;
+        unsafe { place_canaries(start, end) };
+        assert_eq!(corrupted_canary(), None);
+
+        words[5] = 0;
+        assert_eq!(corrupted_canary(), Some(end));
+        CANARY_COUNT.store(0, Ordering::Relaxed);
+    }
+}
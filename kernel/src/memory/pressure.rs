@@ -0,0 +1,115 @@
+//! Low-memory callbacks for the runtime allocator.
+//!
+//! Subsystems holding memory they could give back (caches, back buffers)
+//! register a callback during bring-up. When an allocation leaves fewer free
+//! frames than the threshold (`lowmem=<frames>`, default
+//! [`DEFAULT_THRESHOLD`]), the allocator raises pressure, and the callbacks run
+//! once its closure has returned, so they may free frames themselves. Pressure
+//! fires once per crossing and re-arms when free memory is back at the
+//! threshold.
+
+#![allow(dead_code)]
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use oxide_util::cmdline::parse_u64;
+
+use super::allocator::with_runtime_allocator;
+
+/// Maximum number of registered low-memory callbacks.
+pub const MAX_CALLBACKS: usize = 8;
+
+/// Free frames below which pressure is raised without `lowmem=`: 1 MiB.
+pub const DEFAULT_THRESHOLD: u64 = 256;
+
+/// A subsystem's low-memory callback; receives the free frame count.
+#[derive(Clone, Copy)]
+pub struct LowMemoryCallback {
+    pub name: &'static str,
+    pub run: fn(u64),
+}
+
+/// Errors reported when registering a callback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PressureError {
+    /// All [`MAX_CALLBACKS`] slots are taken.
+    TooManyCallbacks,
+}
+
+struct CallbackTable {
+    callbacks: [Option<LowMemoryCallback>; MAX_CALLBACKS],
+    len: usize,
+}
+
+impl CallbackTable {
+    const fn new() -> Self {
+        Self {
+            callbacks: [None; MAX_CALLBACKS],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, callback: LowMemoryCallback) -> Result<(), PressureError> {
+        let slot = self
+            .callbacks
+            .get_mut(self.len)
+            .ok_or(PressureError::TooManyCallbacks)?;
+        *slot = Some(callback);
+        self.len += 1;
+        Ok(())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = LowMemoryCallback> + '_ {
+        self.callbacks[..self.len].iter().flatten().copied()
+    }
+}
+
+struct CallbackCell(UnsafeCell<CallbackTable>);
+
+unsafe impl Sync for CallbackCell {}
+
+static CALLBACKS: CallbackCell = CallbackCell(UnsafeCell::new(CallbackTable::new()));
+static PENDING: AtomicBool = AtomicBool::new(false);
+static DISPATCHING: AtomicBool = AtomicBool::new(false);
+
+/// Register `run` to be called when free memory runs low.
+///
+/// Only called during single-threaded bring-up.
+pub fn register(name: &'static str, run: fn(u64)) -> Result<(), PressureError> {
+    unsafe { (*CALLBACKS.0.get()).push(LowMemoryCallback { name, run }) }
+}
+
+/// The threshold requested with `lowmem=<frames>`, default
+/// [`DEFAULT_THRESHOLD`]; `lowmem=0` disables the callbacks.
+pub fn threshold() -> u64 {
+    crate::options::value("lowmem")
+        .and_then(parse_u64)
+        .unwrap_or(DEFAULT_THRESHOLD)
+}
+
+/// Called by the allocator when an allocation crosses the threshold.
+pub(super) fn raise() {
+    PENDING.store(true, Ordering::Release);
+}
+
+/// Run the callbacks if pressure was raised. Called after every allocator
+/// closure; the callbacks' own allocator calls do not dispatch again.
+pub(super) fn dispatch() {
+    if !PENDING.load(Ordering::Acquire) || DISPATCHING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    PENDING.store(false, Ordering::Release);
+
+    let free = with_runtime_allocator(|allocator| allocator.usage().free_frames).unwrap_or(0);
+    crate::diagln!("low memory: {} frames free", free);
+    let callbacks = unsafe { &*CALLBACKS.0.get() };
+    for callback in callbacks.iter() {
+        crate::debugln!("low memory: notifying {}", callback.name);
+        (callback.run)(free);
+    }
+
+    DISPATCHING.store(false, Ordering::Release);
+}
@@ -0,0 +1,202 @@
+//! The overlap policy shared by every reservation list.
+//!
+//! The early list, the list staged for the runtime allocator, and the
+//! allocator's own list all keep their reservations sorted by start and
+//! pairwise disjoint. A new reservation that overlaps existing ones is merged
+//! with them into a single entry covering the union, owned by everyone who
+//! reserved a part of it. Reservations that merely touch stay separate, so
+//! each keeps an exact owner. Re-reserving a range is therefore harmless, and
+//! only running out of slots is an error.
+
+use core::fmt;
+
+use super::{allocator::ReservedRegion, segments::Slots};
+
+/// Who reserved a range; merged reservations carry several owners.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Owners(u8);
+
+impl Owners {
+    pub const NONE: Self = Self(0);
+    /// Identity ranges the kernel runs from: its memory-map copy, the loader
+    /// stack, and the kernel image.
+    pub const BOOT: Self = Self(1 << 0);
+    /// The console history buffer.
+    pub const CONSOLE: Self = Self(1 << 1);
+    /// Frames that failed the boot-time memory test.
+    pub const BAD_MEMORY: Self = Self(1 << 2);
+    /// `mem=` and `memmap=` exclusions.
+    pub const COMMAND_LINE: Self = Self(1 << 3);
+    pub const FRAMEBUFFER: Self = Self(1 << 4);
+    /// The runtime allocator's free and reserved lists.
+    pub const ALLOCATOR: Self = Self(1 << 5);
+
+    const NAMES: [(Self, &'static str); 6] = [
+        (Self::BOOT, "boot"),
+        (Self::CONSOLE, "console"),
+        (Self::BAD_MEMORY, "bad memory"),
+        (Self::COMMAND_LINE, "command line"),
+        (Self::FRAMEBUFFER, "framebuffer"),
+        (Self::ALLOCATOR, "allocator"),
+    ];
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl fmt::Display for Owners {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (owner, name) in Self::NAMES {
+            if self.contains(owner) {
+                if !first {
+                    f.write_str("+")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        if first {
+            f.write_str("unowned")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every slot of a reservation list is in use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ListFull;
+
+/// Add `region` to the sorted, disjoint reservations in `entries[..*len]`.
+///
+/// Existing reservations that overlap `region` are absorbed into one entry,
+/// which is returned. Fails only when `region` needs a slot of its own and
+/// `entries` has none left; `entries` is unchanged in that case.
+pub fn insert<S: Slots<ReservedRegion> + ?Sized>(
+    entries: &mut S,
+    len: &mut usize,
+    region: ReservedRegion,
+) -> Result<ReservedRegion, ListFull> {
+    let live = *len;
+    let first = (0..live)
+        .position(|index| entries[index].end > region.start)
+        .unwrap_or(live);
+
+    let mut merged = region;
+    let mut last = first;
+    while last < live && entries[last].start < merged.end {
+        let existing = entries[last];
+        merged.start = merged.start.min(existing.start);
+        merged.end = merged.end.max(existing.end);
+        merged.owners = merged.owners.union(existing.owners);
+        last += 1;
+    }
+
+    match last - first {
+        0 => {
+            if live >= entries.slot_count() {
+                return Err(ListFull);
+            }
+            entries.shift(first..live, first + 1);
+            *len = live + 1;
+        }
+        absorbed => {
+            entries.shift(last..live, first + 1);
+            *len = live - (absorbed - 1);
+        }
+    }
+    entries[first] = merged;
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::format;
+
+    fn region(start: u64, end: u64, owners: Owners) -> ReservedRegion {
+        ReservedRegion { start, end, owners }
+    }
+
+    #[test]
+    fn insert_merges_overlaps_and_keeps_touching_regions_apart() {
+        let mut entries = [ReservedRegion::default(); 4];
+        let mut len = 0;
+
+        // Adjacent: both stay, sorted by start.
+        insert(
+            &mut entries,
+            &mut len,
+            region(0x3000, 0x4000, Owners::CONSOLE),
+        )
+        .unwrap();
+        insert(&mut entries, &mut len, region(0x2000, 0x3000, Owners::BOOT)).unwrap();
+        assert_eq!(
+            entries[..len],
+            [
+                region(0x2000, 0x3000, Owners::BOOT),
+                region(0x3000, 0x4000, Owners::CONSOLE)
+            ]
+        );
+
+        // Contained: the range is unchanged but gains the owner.
+        let merged = insert(
+            &mut entries,
+            &mut len,
+            region(0x3000, 0x3800, Owners::BAD_MEMORY),
+        );
+        assert_eq!(
+            merged,
+            Ok(region(
+                0x3000,
+                0x4000,
+                Owners::CONSOLE.union(Owners::BAD_MEMORY)
+            ))
+        );
+        assert_eq!(len, 2);
+
+        // Partial overlap spanning both: one entry covering the union.
+        insert(
+            &mut entries,
+            &mut len,
+            region(0x6000, 0x7000, Owners::FRAMEBUFFER),
+        )
+        .unwrap();
+        let merged = insert(
+            &mut entries,
+            &mut len,
+            region(0x2800, 0x5000, Owners::COMMAND_LINE),
+        );
+        assert_eq!(merged.unwrap().start, 0x2000);
+        assert_eq!(merged.unwrap().end, 0x5000);
+        assert_eq!(len, 2);
+        assert_eq!(entries[1], region(0x6000, 0x7000, Owners::FRAMEBUFFER));
+        assert_eq!(
+            format!("{}", entries[0].owners),
+            "boot+console+bad memory+command line"
+        );
+
+        // Full only matters when a new slot is needed.
+        insert(&mut entries, &mut len, region(0x8000, 0x9000, Owners::NONE)).unwrap();
+        insert(&mut entries, &mut len, region(0xA000, 0xB000, Owners::NONE)).unwrap();
+        assert_eq!(
+            insert(&mut entries, &mut len, region(0xC000, 0xD000, Owners::NONE)),
+            Err(ListFull)
+        );
+        assert!(
+            insert(
+                &mut entries,
+                &mut len,
+                region(0x8000, 0x9000, Owners::ALLOCATOR)
+            )
+            .is_ok()
+        );
+    }
+}
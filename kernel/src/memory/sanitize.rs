@@ -0,0 +1,168 @@
+//! Debug-build checks on every frame run the allocators hand out.
+//!
+//! A bug in the storage plan or in `subtract_range` would hand out memory
+//! that the firmware, the loader, or another subsystem still owns, and the
+//! corruption would surface far from its cause. With debug assertions on,
+//! both allocators check each run at the moment they return it: every byte
+//! must lie in a `ConventionalMemory` descriptor and outside every
+//! reservation. A bad run panics on the spot, naming the allocator, the run,
+//! and what it collided with. Release builds skip the checks.
+
+use core::fmt;
+
+use oxide_abi::{EfiMemoryType, MemoryMap};
+
+use crate::console::format::fmt_hex;
+
+use super::{
+    allocator::ReservedRegion,
+    frame::FRAME_SIZE,
+    map::{descriptor_range, find_descriptor_containing},
+};
+
+/// Why a frame run must not have been handed out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Violation {
+    /// No descriptor covers `addr`.
+    Unmapped { addr: u64 },
+    /// `addr` lies in a descriptor of raw type `typ`.
+    NotConventional { addr: u64, typ: u32 },
+    /// The run overlaps a reservation.
+    Reserved(ReservedRegion),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Unmapped { addr } => {
+                write!(f, "{} is not in the memory map", fmt_hex(*addr, 16))
+            }
+            Violation::NotConventional { addr, typ } => {
+                write!(f, "{} is in a type {} descriptor", fmt_hex(*addr, 16), typ)
+            }
+            Violation::Reserved(region) => write!(
+                f,
+                "overlaps reservation {}..{} ({})",
+                fmt_hex(region.start, 16),
+                fmt_hex(region.end, 16),
+                region.owners
+            ),
+        }
+    }
+}
+
+/// Panic unless the `frames` frames at `start` are conventional memory
+/// outside every reservation.
+///
+/// `overlapping(start, end)` returns a reservation that intersects
+/// `[start, end)`, if any. `source` names the allocator in the panic message.
+pub fn verify(
+    source: &str,
+    map: &MemoryMap,
+    start: u64,
+    frames: u64,
+    overlapping: impl FnOnce(u64, u64) -> Option<ReservedRegion>,
+) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+
+    let end = start.saturating_add(frames.saturating_mul(FRAME_SIZE));
+    if let Some(violation) = find_violation(map, start, end, overlapping) {
+        panic!(
+            "{} handed out {}..{} ({} frames): {}",
+            source,
+            fmt_hex(start, 16),
+            fmt_hex(end, 16),
+            frames,
+            violation
+        );
+    }
+}
+
+fn find_violation(
+    map: &MemoryMap,
+    start: u64,
+    end: u64,
+    overlapping: impl FnOnce(u64, u64) -> Option<ReservedRegion>,
+) -> Option<Violation> {
+    // The run may span several adjacent descriptors; walk them in turn.
+    let mut addr = start;
+    while addr < end {
+        let Some(desc) = find_descriptor_containing(map, addr) else {
+            return Some(Violation::Unmapped { addr });
+        };
+        if desc.typ != EfiMemoryType::ConventionalMemory as u32 {
+            return Some(Violation::NotConventional {
+                addr,
+                typ: desc.typ,
+            });
+        }
+        addr = descriptor_range(desc).map_or(end, |(_, desc_end)| desc_end);
+    }
+
+    overlapping(start, end).map(Violation::Reserved)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use crate::memory::reservation::Owners;
+    use alloc::{boxed::Box, vec};
+    use oxide_abi::MemoryDescriptor;
+
+    fn descriptor(typ: EfiMemoryType, physical_start: u64, pages: u64) -> MemoryDescriptor {
+        MemoryDescriptor {
+            typ: typ as u32,
+            _pad: 0,
+            physical_start,
+            virtual_start: 0,
+            number_of_pages: pages,
+            attribute: 0,
+        }
+    }
+
+    #[test]
+    fn find_violation_reports_foreign_and_reserved_frames() {
+        let descriptors: Box<[MemoryDescriptor]> = vec![
+            descriptor(EfiMemoryType::ConventionalMemory, 0x1000, 2),
+            descriptor(EfiMemoryType::ConventionalMemory, 0x3000, 2),
+            descriptor(EfiMemoryType::LoaderData, 0x5000, 1),
+        ]
+        .into_boxed_slice();
+        let map = MemoryMap {
+            descriptors_phys: descriptors.as_ptr() as u64,
+            map_size: (descriptors.len() * core::mem::size_of::<MemoryDescriptor>()) as u64,
+            entry_size: core::mem::size_of::<MemoryDescriptor>() as u32,
+            entry_version: 1,
+            entry_count: descriptors.len() as u32,
+        };
+        let reservation = ReservedRegion {
+            start: 0x4000,
+            end: 0x5000,
+            owners: Owners::FRAMEBUFFER,
+        };
+        let overlapping = |start: u64, end: u64| {
+            (reservation.start < end && start < reservation.end).then_some(reservation)
+        };
+
+        assert_eq!(find_violation(&map, 0x1000, 0x4000, overlapping), None);
+        assert_eq!(
+            find_violation(&map, 0x3000, 0x5000, overlapping),
+            Some(Violation::Reserved(reservation))
+        );
+        assert_eq!(
+            find_violation(&map, 0x4000, 0x6000, overlapping),
+            Some(Violation::NotConventional {
+                addr: 0x5000,
+                typ: EfiMemoryType::LoaderData as u32
+            })
+        );
+        assert_eq!(
+            find_violation(&map, 0x6000, 0x7000, overlapping),
+            Some(Violation::Unmapped { addr: 0x6000 })
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Allocator metadata spread over several physical carve-outs.
+//!
+//! The runtime allocator's free and reserved lists are plain arrays, but on a
+//! badly fragmented firmware map no descriptor may have room for either one.
+//! [`Segments`] chains up to [`MAX_SEGMENTS`] separately carved slices and
+//! indexes them as one array, so the lists only need each piece to be
+//! contiguous.
+
+use core::ops::{Index, IndexMut, Range};
+
+/// Carve-outs a single list may be split across.
+pub const MAX_SEGMENTS: usize = 8;
+
+/// Indexable storage: the interface [`reservation::insert`] needs from a
+/// list, whether it is one array or several segments.
+///
+/// [`reservation::insert`]: super::reservation::insert
+pub trait Slots<T>: IndexMut<usize, Output = T> {
+    /// Number of slots, used or not.
+    fn slot_count(&self) -> usize;
+
+    /// Copy `src` so it starts at `dest`, like `slice::copy_within`.
+    fn shift(&mut self, src: Range<usize>, dest: usize);
+}
+
+impl<T: Copy> Slots<T> for [T] {
+    fn slot_count(&self) -> usize {
+        self.len()
+    }
+
+    fn shift(&mut self, src: Range<usize>, dest: usize) {
+        self.copy_within(src, dest);
+    }
+}
+
+impl<T: Copy, const N: usize> Slots<T> for [T; N] {
+    fn slot_count(&self) -> usize {
+        N
+    }
+
+    fn shift(&mut self, src: Range<usize>, dest: usize) {
+        self.copy_within(src, dest);
+    }
+}
+
+/// Up to [`MAX_SEGMENTS`] slices addressed as one array, in the order they
+/// were added.
+pub struct Segments<'a, T> {
+    parts: [&'a mut [T]; MAX_SEGMENTS],
+    count: usize,
+    len: usize,
+}
+
+impl<'a, T> Segments<'a, T> {
+    pub fn new() -> Self {
+        Self {
+            parts: core::array::from_fn(|_| Default::default()),
+            count: 0,
+            len: 0,
+        }
+    }
+
+    /// Append `part`, or hand it back when every segment is in use.
+    pub fn push(&mut self, part: &'a mut [T]) -> Result<(), &'a mut [T]> {
+        if self.count == MAX_SEGMENTS {
+            return Err(part);
+        }
+        self.len += part.len();
+        self.parts[self.count] = part;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Total slots across every segment.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Number of segments in use.
+    pub fn segment_count(&self) -> usize {
+        self.count
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let (part, offset) = self.locate(index)?;
+        Some(&self.parts[part][offset])
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let (part, offset) = self.locate(index)?;
+        Some(&mut self.parts[part][offset])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.parts[..self.count].iter().flat_map(|part| part.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.parts[..self.count]
+            .iter_mut()
+            .flat_map(|part| part.iter_mut())
+    }
+
+    fn locate(&self, mut index: usize) -> Option<(usize, usize)> {
+        for (part, slice) in self.parts[..self.count].iter().enumerate() {
+            if index < slice.len() {
+                return Some((part, index));
+            }
+            index -= slice.len();
+        }
+        None
+    }
+}
+
+impl<T: Copy> Segments<'_, T> {
+    pub fn fill(&mut self, value: T) {
+        for part in &mut self.parts[..self.count] {
+            part.fill(value);
+        }
+    }
+}
+
+impl<T> Default for Segments<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T> From<&'a mut [T]> for Segments<'a, T> {
+    fn from(part: &'a mut [T]) -> Self {
+        let mut segments = Self::new();
+        let _ = segments.push(part);
+        segments
+    }
+}
+
+impl<T> Index<usize> for Segments<'_, T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(slot) => slot,
+            None => panic!("segment index {} out of range {}", index, self.len),
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Segments<'_, T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let len = self.len;
+        match self.get_mut(index) {
+            Some(slot) => slot,
+            None => panic!("segment index {} out of range {}", index, len),
+        }
+    }
+}
+
+impl<T: Copy> Slots<T> for Segments<'_, T> {
+    fn slot_count(&self) -> usize {
+        self.len
+    }
+
+    fn shift(&mut self, src: Range<usize>, dest: usize) {
+        assert!(src.start <= src.end && src.end <= self.len);
+        assert!(dest <= self.len - src.len());
+
+        // Walk away from the destination so no source slot is overwritten
+        // before it has been read.
+        if dest < src.start {
+            for offset in 0..src.len() {
+                self[dest + offset] = self[src.start + offset];
+            }
+        } else {
+            for offset in (0..src.len()).rev() {
+                self[dest + offset] = self[src.start + offset];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_index_and_shift_across_boundaries() {
+        let mut first = [0u32; 2];
+        let mut second = [0u32; 1];
+        let mut third = [0u32; 3];
+        let mut segments = Segments::new();
+        segments.push(&mut first).unwrap();
+        segments.push(&mut second).unwrap();
+        segments.push(&mut third).unwrap();
+        assert_eq!(segments.len(), 6);
+        assert_eq!(segments.get(6), None);
+
+        for (value, slot) in segments.iter_mut().enumerate() {
+            *slot = value as u32;
+        }
+        assert_eq!(segments[2], 2);
+
+        segments.shift(1..4, 2);
+        let shifted: [u32; 6] = core::array::from_fn(|index| segments[index]);
+        assert_eq!(shifted, [0, 1, 1, 2, 3, 5]);
+
+        segments.shift(2..6, 0);
+        let shifted: [u32; 6] = core::array::from_fn(|index| segments[index]);
+        assert_eq!(shifted, [1, 2, 3, 5, 3, 5]);
+    }
+}
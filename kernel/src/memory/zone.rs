@@ -0,0 +1,134 @@
+//! Physical memory zones.
+//!
+//! Some devices cannot address all of memory: ISA DMA reaches only the first
+//! megabyte and many 32-bit DMA engines only the first 4 GiB. The runtime
+//! allocator keeps every free run inside one zone, so a request can be
+//! limited to memory such a device can reach.
+
+use core::fmt;
+
+use super::allocator::PhysFrame;
+use super::frame::FRAME_SIZE;
+
+/// Addresses where one zone ends and the next begins.
+pub const ZONE_BOUNDARIES: [u64; 2] = [LOW_LIMIT, DMA32_LIMIT];
+
+const LOW_LIMIT: u64 = 1024 * 1024;
+const DMA32_LIMIT: u64 = 4 * 1024 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Zone {
+    /// Below 1 MiB: legacy ISA DMA and real-mode trampolines.
+    Low,
+    /// 1 MiB up to 4 GiB: devices with 32-bit DMA addresses.
+    Dma32,
+    /// Everything above 4 GiB.
+    Normal,
+}
+
+impl Zone {
+    pub const COUNT: usize = 3;
+    pub const ALL: [Zone; Self::COUNT] = [Zone::Low, Zone::Dma32, Zone::Normal];
+
+    /// Order ordinary requests try the zones in: 32-bit memory first, as
+    /// before zones existed, and the scarce first megabyte last.
+    pub const DEFAULT_ORDER: [Zone; Self::COUNT] = [Zone::Dma32, Zone::Normal, Zone::Low];
+
+    /// The zone containing `addr`.
+    pub const fn of(addr: u64) -> Zone {
+        if addr < LOW_LIMIT {
+            Zone::Low
+        } else if addr < DMA32_LIMIT {
+            Zone::Dma32
+        } else {
+            Zone::Normal
+        }
+    }
+
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Zones a request limited to `self` may use: `self` first, then each
+    /// lower zone, since a device that reaches a zone reaches those below it.
+    pub fn fallbacks(self) -> impl Iterator<Item = Zone> {
+        Self::ALL[..=self.index()].iter().rev().copied()
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Zone::Low => "low",
+            Zone::Dma32 => "dma32",
+            Zone::Normal => "normal",
+        }
+    }
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Split `frame` at the zone boundaries it crosses, lowest piece first.
+pub fn split(frame: PhysFrame) -> impl Iterator<Item = PhysFrame> {
+    let end = frame
+        .start
+        .saturating_add(frame.count.saturating_mul(FRAME_SIZE));
+    let mut start = frame.start;
+    core::iter::from_fn(move || {
+        if start >= end {
+            return None;
+        }
+        let piece_end = ZONE_BOUNDARIES
+            .iter()
+            .copied()
+            .find(|&boundary| boundary > start && boundary < end)
+            .unwrap_or(end);
+        let piece = PhysFrame::new(start, (piece_end - start) / FRAME_SIZE);
+        start = piece_end;
+        Some(piece)
+    })
+}
+
+/// Whether `addr` separates two zones, so runs meeting there stay apart.
+pub fn is_boundary(addr: u64) -> bool {
+    ZONE_BOUNDARIES.contains(&addr)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn split_cuts_runs_at_each_boundary() {
+        let frame = PhysFrame::new(
+            LOW_LIMIT - FRAME_SIZE,
+            (DMA32_LIMIT - LOW_LIMIT) / FRAME_SIZE + 3,
+        );
+        let pieces: Vec<_> = split(frame).collect();
+        assert_eq!(
+            pieces,
+            [
+                PhysFrame::new(LOW_LIMIT - FRAME_SIZE, 1),
+                PhysFrame::new(LOW_LIMIT, (DMA32_LIMIT - LOW_LIMIT) / FRAME_SIZE),
+                PhysFrame::new(DMA32_LIMIT, 2),
+            ]
+        );
+        assert_eq!(
+            pieces
+                .iter()
+                .map(|piece| Zone::of(piece.start))
+                .collect::<Vec<_>>(),
+            Zone::ALL
+        );
+        assert_eq!(split(PhysFrame::new(0, 4)).count(), 1);
+        assert_eq!(
+            Zone::Dma32.fallbacks().collect::<Vec<_>>(),
+            [Zone::Dma32, Zone::Low]
+        );
+    }
+}
@@ -0,0 +1,501 @@
+//! Multiboot2 boot-information adapter.
+//!
+//! GRUB (or another Multiboot2 loader) hands the kernel a tag list instead of
+//! a `BootAbi`. This module converts that list into a `BootAbi` so boot
+//! validation and initialisation stay shared with the UEFI loader path.
+//!
+//! This is not a boot path yet. The image carries no Multiboot2 header, and
+//! there is no 32-bit to long-mode trampoline or linker script, so GRUB cannot
+//! load the kernel; those have to land together, with a GRUB boot test.
+//!
+//! Only the information the kernel consumes is translated: command line,
+//! bootloader name (reported as the firmware vendor), a 32-bpp direct-colour
+//! framebuffer, and the memory map. The EFI memory map tag is used as-is when
+//! present; otherwise the legacy map is converted into EFI descriptors.
+
+use core::mem::size_of;
+
+use oxide_abi::{
+    ABI_CMDLINE_CAP, ABI_VENDOR_CAP, ABI_VERSION, BootAbi, EfiMemoryType, Firmware, Framebuffer,
+    MemoryDescriptor, MemoryMap, Options, PhysRange, PixelFormat,
+};
+use oxide_util::cmdline::has_flag;
+
+const TAG_END: u32 = 0;
+const TAG_CMDLINE: u32 = 1;
+const TAG_BOOTLOADER_NAME: u32 = 2;
+const TAG_MEMORY_MAP: u32 = 6;
+const TAG_FRAMEBUFFER: u32 = 8;
+const TAG_EFI_MEMORY_MAP: u32 = 17;
+
+const FRAMEBUFFER_TYPE_RGB: u8 = 1;
+const PAGE_SIZE: u64 = 4096;
+
+const EMPTY_DESCRIPTOR: MemoryDescriptor = MemoryDescriptor {
+    typ: 0,
+    _pad: 0,
+    physical_start: 0,
+    virtual_start: 0,
+    number_of_pages: 0,
+    attribute: 0,
+};
+
+/// Errors raised while translating Multiboot2 boot information.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Multiboot2Error {
+    Truncated,
+    MissingFramebuffer,
+    UnsupportedFramebuffer(&'static str),
+    MissingMemoryMap,
+    TooManyRegions,
+}
+
+/// Build a `BootAbi` from a Multiboot2 boot information block.
+///
+/// `scratch` receives converted descriptors when only the legacy memory map is
+/// present; it must outlive the returned `BootAbi`, which points into it (or
+/// into `info` for the EFI memory map).
+pub fn boot_abi_from_info(
+    info: &[u8],
+    scratch: &mut [MemoryDescriptor],
+) -> Result<BootAbi, Multiboot2Error> {
+    let mut options = Options::default();
+    let mut firmware = Firmware {
+        revision: 0,
+        vendor: [0; ABI_VENDOR_CAP],
+        vendor_len: 0,
+        vendor_truncated: 0,
+    };
+    let mut framebuffer = None;
+    let mut efi_map = None;
+    let mut legacy_map = None;
+
+    for (typ, body) in Tags::new(info)? {
+        match typ {
+            TAG_CMDLINE => apply_cmdline(&mut options, c_str(body)),
+            TAG_BOOTLOADER_NAME => {
+                let name = c_str(body);
+                let len = name.len().min(ABI_VENDOR_CAP);
+                firmware.vendor[..len].copy_from_slice(&name[..len]);
+                firmware.vendor_len = len as u8;
+                firmware.vendor_truncated = (name.len() > len) as u8;
+            }
+            TAG_FRAMEBUFFER => framebuffer = Some(parse_framebuffer(body)?),
+            TAG_EFI_MEMORY_MAP => efi_map = Some(parse_efi_map(body)?),
+            TAG_MEMORY_MAP => legacy_map = Some(body),
+            _ => {}
+        }
+    }
+
+    let memory_map = match (efi_map, legacy_map) {
+        (Some(map), _) => map,
+        (None, Some(body)) => convert_legacy_map(body, scratch)?,
+        (None, None) => return Err(Multiboot2Error::MissingMemoryMap),
+    };
+
+    Ok(BootAbi {
+        version: ABI_VERSION,
+        options,
+        firmware,
+        framebuffer: framebuffer.ok_or(Multiboot2Error::MissingFramebuffer)?,
+        tsc_frequency_hz: 0,
+        memory_map,
+        status_page_phys: 0,
+        microcode: PhysRange::default(),
+        memory_attributes: PhysRange::default(),
+        loader_image: PhysRange::default(),
+        microcode_xxh32: 0,
+    })
+}
+
+/// Kernel entry for the Multiboot2 path.
+///
+/// Expects to be called in long mode with the low 4 GiB identity-mapped by the
+/// image's 32-bit bootstrap; that trampoline and the standalone linker script
+/// are not part of this crate yet.
+#[cfg(feature = "multiboot2")]
+#[unsafe(no_mangle)]
+pub extern "C" fn multiboot2_main(magic: u32, info_phys: u64) -> ! {
+    use core::{cell::UnsafeCell, mem::MaybeUninit};
+
+    /// Value a Multiboot2 loader leaves in EAX when entering the kernel.
+    const BOOTLOADER_MAGIC: u32 = 0x36D7_6289;
+    const SCRATCH_DESCRIPTORS: usize = 128;
+
+    struct HandoffCell {
+        abi: UnsafeCell<MaybeUninit<BootAbi>>,
+        scratch: UnsafeCell<[MaybeUninit<MemoryDescriptor>; SCRATCH_DESCRIPTORS]>,
+    }
+
+    // SAFETY: only touched once, on the boot CPU, before anything else runs.
+    unsafe impl Sync for HandoffCell {}
+
+    static HANDOFF: HandoffCell = HandoffCell {
+        abi: UnsafeCell::new(MaybeUninit::uninit()),
+        scratch: UnsafeCell::new([MaybeUninit::uninit(); SCRATCH_DESCRIPTORS]),
+    };
+
+    if magic != BOOTLOADER_MAGIC {
+        crate::emergency::halt();
+    }
+
+    // SAFETY: the loader guarantees `info_phys` points at a boot information
+    // block whose first word is its total size, and `HANDOFF` is unused until
+    // this point.
+    let (info, scratch, abi) = unsafe {
+        let total = core::ptr::read_unaligned(info_phys as *const u32) as usize;
+        let scratch = &mut *HANDOFF.scratch.get();
+        for slot in scratch.iter_mut() {
+            slot.write(EMPTY_DESCRIPTOR);
+        }
+        (
+            core::slice::from_raw_parts(info_phys as *const u8, total),
+            &mut *(scratch as *mut _ as *mut [MemoryDescriptor; SCRATCH_DESCRIPTORS]),
+            &mut *HANDOFF.abi.get(),
+        )
+    };
+
+    match boot_abi_from_info(info, scratch) {
+        Ok(parsed) => crate::kernel_main(abi.write(parsed)),
+        Err(_) => crate::emergency::halt(),
+    }
+}
+
+fn apply_cmdline(options: &mut Options, cmdline: &[u8]) {
+    let len = cmdline.len().min(ABI_CMDLINE_CAP);
+    options.cmdline[..len].copy_from_slice(&cmdline[..len]);
+    options.cmdline_len = len as u16;
+
+    let text = core::str::from_utf8(&cmdline[..len]).unwrap_or("");
+    if has_flag(text, "debug") {
+        options.debug = 1;
+    }
+    if has_flag(text, "quiet") {
+        options.quiet = 1;
+    }
+}
+
+fn parse_framebuffer(body: &[u8]) -> Result<Framebuffer, Multiboot2Error> {
+    let base_address = read_u64(body, 0)?;
+    let pitch = read_u32(body, 8)?;
+    let width = read_u32(body, 12)?;
+    let height = read_u32(body, 16)?;
+    let bpp = *body.get(20).ok_or(Multiboot2Error::Truncated)?;
+    let kind = *body.get(21).ok_or(Multiboot2Error::Truncated)?;
+
+    if kind != FRAMEBUFFER_TYPE_RGB {
+        return Err(Multiboot2Error::UnsupportedFramebuffer(
+            "not a direct-colour framebuffer",
+        ));
+    }
+    if bpp != 32 {
+        return Err(Multiboot2Error::UnsupportedFramebuffer(
+            "only 32 bits per pixel is supported",
+        ));
+    }
+    // Multiboot2 gives the pitch in bytes; the ABI counts whole pixels.
+    if pitch % 4 != 0 {
+        return Err(Multiboot2Error::UnsupportedFramebuffer(
+            "pitch is not a whole number of pixels",
+        ));
+    }
+
+    let red_position = *body.get(24).ok_or(Multiboot2Error::Truncated)?;
+    let blue_position = *body.get(28).ok_or(Multiboot2Error::Truncated)?;
+    let pixel_format = match (red_position, blue_position) {
+        (0, 16) => PixelFormat::Rgb,
+        (16, 0) => PixelFormat::Bgr,
+        _ => {
+            return Err(Multiboot2Error::UnsupportedFramebuffer(
+                "unsupported channel layout",
+            ));
+        }
+    };
+
+    Ok(Framebuffer {
+        base_address,
+        buffer_size: pitch as u64 * height as u64,
+        width,
+        height,
+        pixels_per_scanline: pitch / 4,
+        pixel_format,
+    })
+}
+
+fn parse_efi_map(body: &[u8]) -> Result<MemoryMap, Multiboot2Error> {
+    let entry_size = read_u32(body, 0)?;
+    let entry_version = read_u32(body, 4)?;
+    let descriptors = body.get(8..).ok_or(Multiboot2Error::Truncated)?;
+    if entry_size == 0 {
+        return Err(Multiboot2Error::Truncated);
+    }
+
+    let entry_count = (descriptors.len() / entry_size as usize) as u32;
+    Ok(MemoryMap {
+        descriptors_phys: descriptors.as_ptr() as u64,
+        map_size: entry_count as u64 * entry_size as u64,
+        entry_size,
+        entry_version,
+        entry_count,
+    })
+}
+
+fn convert_legacy_map(
+    body: &[u8],
+    scratch: &mut [MemoryDescriptor],
+) -> Result<MemoryMap, Multiboot2Error> {
+    let entry_size = read_u32(body, 0)? as usize;
+    let entries = body.get(8..).ok_or(Multiboot2Error::Truncated)?;
+    if entry_size < 20 {
+        return Err(Multiboot2Error::Truncated);
+    }
+
+    let mut count = 0;
+    for entry in entries.chunks_exact(entry_size) {
+        let base = read_u64(entry, 0)?;
+        let length = read_u64(entry, 8)?;
+        let typ = legacy_type(read_u32(entry, 16)?);
+
+        // Usable ranges shrink to whole pages; everything else grows to cover them.
+        let (start, end) = if typ == EfiMemoryType::ConventionalMemory {
+            (
+                base.next_multiple_of(PAGE_SIZE),
+                base.saturating_add(length) & !(PAGE_SIZE - 1),
+            )
+        } else {
+            (
+                base & !(PAGE_SIZE - 1),
+                base.saturating_add(length).next_multiple_of(PAGE_SIZE),
+            )
+        };
+        if start >= end {
+            continue;
+        }
+
+        let slot = scratch
+            .get_mut(count)
+            .ok_or(Multiboot2Error::TooManyRegions)?;
+        *slot = MemoryDescriptor {
+            typ: typ as u32,
+            _pad: 0,
+            physical_start: start,
+            virtual_start: 0,
+            number_of_pages: (end - start) / PAGE_SIZE,
+            attribute: 0,
+        };
+        count += 1;
+    }
+
+    let entry_size = size_of::<MemoryDescriptor>() as u32;
+    Ok(MemoryMap {
+        descriptors_phys: scratch.as_ptr() as u64,
+        map_size: count as u64 * entry_size as u64,
+        entry_size,
+        entry_version: 1,
+        entry_count: count as u32,
+    })
+}
+
+fn legacy_type(typ: u32) -> EfiMemoryType {
+    match typ {
+        1 => EfiMemoryType::ConventionalMemory,
+        3 => EfiMemoryType::ACPIReclaimMemory,
+        4 => EfiMemoryType::ACPIMemoryNVS,
+        5 => EfiMemoryType::UnusableMemory,
+        _ => EfiMemoryType::ReservedMemoryType,
+    }
+}
+
+/// Iterator over `(type, body)` pairs of a boot information block.
+struct Tags<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Tags<'a> {
+    fn new(info: &'a [u8]) -> Result<Self, Multiboot2Error> {
+        let total = read_u32(info, 0)? as usize;
+        let data = info
+            .get(..total)
+            .filter(|data| data.len() >= 8)
+            .ok_or(Multiboot2Error::Truncated)?;
+        Ok(Self { data, offset: 8 })
+    }
+}
+
+impl<'a> Iterator for Tags<'a> {
+    type Item = (u32, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let typ = read_u32(self.data, self.offset).ok()?;
+        let size = read_u32(self.data, self.offset + 4).ok()? as usize;
+        if typ == TAG_END || size < 8 {
+            return None;
+        }
+
+        let body = self.data.get(self.offset + 8..self.offset + size)?;
+        self.offset += size.next_multiple_of(8);
+        Some((typ, body))
+    }
+}
+
+fn c_str(body: &[u8]) -> &[u8] {
+    let end = body.iter().position(|&b| b == 0).unwrap_or(body.len());
+    &body[..end]
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Multiboot2Error> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(Multiboot2Error::Truncated)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, Multiboot2Error> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::vec::Vec;
+
+    struct InfoBuilder(Vec<u8>);
+
+    impl InfoBuilder {
+        fn new() -> Self {
+            Self(alloc::vec![0; 8])
+        }
+
+        fn tag(mut self, typ: u32, body: &[u8]) -> Self {
+            self.0.extend_from_slice(&typ.to_le_bytes());
+            self.0
+                .extend_from_slice(&(8 + body.len() as u32).to_le_bytes());
+            self.0.extend_from_slice(body);
+            while !self.0.len().is_multiple_of(8) {
+                self.0.push(0);
+            }
+            self
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            self = self.tag(TAG_END, &[]);
+            let total = self.0.len() as u32;
+            self.0[..4].copy_from_slice(&total.to_le_bytes());
+            self.0
+        }
+    }
+
+    fn framebuffer_body(red: u8, blue: u8) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&0xE000_0000u64.to_le_bytes());
+        body.extend_from_slice(&(1024u32 * 4).to_le_bytes());
+        body.extend_from_slice(&1024u32.to_le_bytes());
+        body.extend_from_slice(&768u32.to_le_bytes());
+        body.extend_from_slice(&[32, FRAMEBUFFER_TYPE_RGB, 0, 0]);
+        body.extend_from_slice(&[red, 8, 8, 8, blue, 8]);
+        body
+    }
+
+    fn legacy_entry(base: u64, length: u64, typ: u32) -> Vec<u8> {
+        let mut entry = Vec::new();
+        entry.extend_from_slice(&base.to_le_bytes());
+        entry.extend_from_slice(&length.to_le_bytes());
+        entry.extend_from_slice(&typ.to_le_bytes());
+        entry.extend_from_slice(&0u32.to_le_bytes());
+        entry
+    }
+
+    #[test]
+    fn legacy_boot_info_converts_to_boot_abi() {
+        let mut map = Vec::new();
+        map.extend_from_slice(&24u32.to_le_bytes());
+        map.extend_from_slice(&0u32.to_le_bytes());
+        map.extend(legacy_entry(0, 0x9_FC00, 1));
+        map.extend(legacy_entry(0xF_0000, 0x1_0000, 2));
+        map.extend(legacy_entry(0x10_0000, 0x7F0_0800, 1));
+
+        let info = InfoBuilder::new()
+            .tag(TAG_CMDLINE, b"debug clocksource=pit\0")
+            .tag(TAG_BOOTLOADER_NAME, b"GRUB 2.12\0")
+            .tag(TAG_FRAMEBUFFER, &framebuffer_body(16, 0))
+            .tag(TAG_MEMORY_MAP, &map)
+            .finish();
+
+        let mut scratch = [EMPTY_DESCRIPTOR; 8];
+        let abi = boot_abi_from_info(&info, &mut scratch).unwrap();
+
+        assert_eq!(abi.version, ABI_VERSION);
+        assert_eq!(abi.options.debug, 1);
+        assert_eq!(
+            &abi.options.cmdline[..abi.options.cmdline_len as usize],
+            b"debug clocksource=pit"
+        );
+        assert_eq!(
+            &abi.firmware.vendor[..abi.firmware.vendor_len as usize],
+            b"GRUB 2.12"
+        );
+        assert_eq!(abi.framebuffer.pixel_format, PixelFormat::Bgr);
+        assert_eq!(abi.framebuffer.pixels_per_scanline, 1024);
+        assert_eq!(abi.memory_map.entry_count, 3);
+        assert!(crate::boot::validate_boot_abi(&abi).is_ok());
+
+        assert_eq!(scratch[0].number_of_pages, 0x9F);
+        assert_eq!(scratch[1].typ, EfiMemoryType::ReservedMemoryType as u32);
+        assert_eq!(scratch[2].physical_start, 0x10_0000);
+        assert_eq!(scratch[2].number_of_pages, 0x7F00);
+    }
+
+    #[test]
+    fn efi_memory_map_is_used_in_place() {
+        let descriptor_size = size_of::<MemoryDescriptor>() as u32;
+        let mut map = Vec::new();
+        map.extend_from_slice(&descriptor_size.to_le_bytes());
+        map.extend_from_slice(&1u32.to_le_bytes());
+        map.extend(core::iter::repeat_n(0u8, descriptor_size as usize * 2));
+
+        let info = InfoBuilder::new()
+            .tag(TAG_FRAMEBUFFER, &framebuffer_body(0, 16))
+            .tag(TAG_EFI_MEMORY_MAP, &map)
+            .finish();
+
+        let abi = boot_abi_from_info(&info, &mut []).unwrap();
+        assert_eq!(abi.memory_map.entry_count, 2);
+        assert_eq!(abi.memory_map.entry_size, descriptor_size);
+        assert_eq!(abi.framebuffer.pixel_format, PixelFormat::Rgb);
+    }
+
+    #[test]
+    fn missing_tags_are_reported() {
+        let info = InfoBuilder::new()
+            .tag(TAG_FRAMEBUFFER, &framebuffer_body(0, 16))
+            .finish();
+        assert_eq!(
+            boot_abi_from_info(&info, &mut []).unwrap_err(),
+            Multiboot2Error::MissingMemoryMap
+        );
+
+        let mut paletted = framebuffer_body(0, 16);
+        paletted[21] = 0;
+        let info = InfoBuilder::new().tag(TAG_FRAMEBUFFER, &paletted).finish();
+        assert!(matches!(
+            boot_abi_from_info(&info, &mut []),
+            Err(Multiboot2Error::UnsupportedFramebuffer(_))
+        ));
+
+        let mut odd_pitch = framebuffer_body(0, 16);
+        odd_pitch[8..12].copy_from_slice(&4098u32.to_le_bytes());
+        let info = InfoBuilder::new().tag(TAG_FRAMEBUFFER, &odd_pitch).finish();
+        assert!(matches!(
+            boot_abi_from_info(&info, &mut []),
+            Err(Multiboot2Error::UnsupportedFramebuffer(reason)) if reason.contains("pitch")
+        ));
+
+        assert_eq!(
+            boot_abi_from_info(&[0; 4], &mut []).unwrap_err(),
+            Multiboot2Error::Truncated
+        );
+    }
+}
@@ -1,17 +1,81 @@
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-use oxide_abi::Options;
+use oxide_abi::{ABI_CMDLINE_CAP, Options};
+use oxide_util::cmdline::{find_value, find_values, has_flag};
 
 static DEBUG: AtomicBool = AtomicBool::new(false);
 static QUIET: AtomicBool = AtomicBool::new(false);
+static QUIET_SUMMARY: AtomicBool = AtomicBool::new(false);
 
-/// Capture bootloader-supplied debug and quiet flags for later queries.
+struct CmdlineCell(UnsafeCell<Cmdline>);
+
+unsafe impl Sync for CmdlineCell {}
+
+struct Cmdline {
+    bytes: [u8; ABI_CMDLINE_CAP],
+    len: usize,
+}
+
+static CMDLINE: CmdlineCell = CmdlineCell(UnsafeCell::new(Cmdline {
+    bytes: [0; ABI_CMDLINE_CAP],
+    len: 0,
+}));
+
+/// Capture bootloader-supplied debug and quiet flags and the raw command line
+/// for later queries.
 pub fn init(opts: Options) {
     let debug = opts.debug != 0;
     let quiet = opts.quiet != 0;
 
     DEBUG.store(debug, Ordering::Relaxed);
     QUIET.store(quiet, Ordering::Relaxed);
+
+    let len = (opts.cmdline_len as usize).min(ABI_CMDLINE_CAP);
+    let len = match core::str::from_utf8(&opts.cmdline[..len]) {
+        Ok(_) => len,
+        Err(err) => err.valid_up_to(),
+    };
+
+    unsafe {
+        let cmdline = &mut *CMDLINE.0.get();
+        cmdline.bytes = opts.cmdline;
+        cmdline.len = len;
+    }
+
+    // `quiet=summary` implies quiet; the loader only recognises the bare flag.
+    let summary = value("quiet") == Some("summary");
+    QUIET_SUMMARY.store(summary, Ordering::Relaxed);
+    if summary {
+        QUIET.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Returns the raw command line passed by the loader.
+pub fn cmdline() -> &'static str {
+    // SAFETY: the command line is written once during early boot and only
+    // read afterwards; `init` truncates it to a valid UTF-8 prefix.
+    unsafe {
+        let cmdline = &*CMDLINE.0.get();
+        core::str::from_utf8_unchecked(&cmdline.bytes[..cmdline.len])
+    }
+}
+
+/// Returns the value of the last `key=value` token on the command line.
+pub fn value(key: &str) -> Option<&'static str> {
+    find_value(cmdline(), key)
+}
+
+/// Returns true when the bare token `name` is on the command line.
+pub fn flag(name: &str) -> bool {
+    has_flag(cmdline(), name)
+}
+
+/// Iterate over the values of every `key=value` token on the command line.
+pub fn values(key: &str) -> impl Iterator<Item = &'static str> + '_ {
+    find_values(cmdline(), key)
 }
 
 /// Returns true when debug output should be emitted.
@@ -32,30 +96,74 @@ pub fn diagnostics_enabled() -> bool {
     debug_enabled() && !quiet_enabled()
 }
 
+/// Returns true when diagnostics suppressed by quiet mode are counted for the
+/// capture summary (`quiet=summary`).
+#[inline]
+pub fn diagnostics_captured() -> bool {
+    debug_enabled() && QUIET_SUMMARY.load(Ordering::Relaxed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_options_flags() {
-        init(Options { debug: 1, quiet: 0 });
+        init(Options {
+            debug: 1,
+            quiet: 0,
+            ..Options::default()
+        });
         assert!(debug_enabled());
         assert!(!quiet_enabled());
         assert!(diagnostics_enabled());
 
-        init(Options { debug: 0, quiet: 1 });
+        init(Options {
+            debug: 0,
+            quiet: 1,
+            ..Options::default()
+        });
         assert!(!debug_enabled());
         assert!(quiet_enabled());
         assert!(!diagnostics_enabled());
 
-        init(Options { debug: 1, quiet: 1 });
+        init(Options {
+            debug: 1,
+            quiet: 1,
+            ..Options::default()
+        });
         assert!(debug_enabled());
         assert!(quiet_enabled());
         assert!(!diagnostics_enabled());
 
-        init(Options { debug: 0, quiet: 0 });
+        init(Options {
+            debug: 0,
+            quiet: 0,
+            ..Options::default()
+        });
         assert!(!debug_enabled());
         assert!(!quiet_enabled());
         assert!(!diagnostics_enabled());
+
+        let mut cmdline = [0u8; ABI_CMDLINE_CAP];
+        let text = b"debug quiet=summary serial";
+        cmdline[..text.len()].copy_from_slice(text);
+        init(Options {
+            debug: 1,
+            quiet: 0,
+            cmdline_len: text.len() as u16,
+            cmdline,
+        });
+        assert!(quiet_enabled());
+        assert!(!diagnostics_enabled());
+        assert!(diagnostics_captured());
+        assert!(flag("serial"));
+        assert!(!flag("quiet"));
+
+        init(Options {
+            debug: 1,
+            ..Options::default()
+        });
+        assert!(!diagnostics_captured());
     }
 }
@@ -0,0 +1,28 @@
+//! Raw x86 I/O port access.
+
+use core::arch::asm;
+
+/// Write a byte to an I/O port.
+///
+/// # Safety
+/// The caller must ensure the write has no unintended side effects on the
+/// device behind `port`.
+#[inline(always)]
+pub unsafe fn outb(port: u16, value: u8) {
+    unsafe {
+        asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+    }
+}
+
+/// Read a byte from an I/O port.
+///
+/// # Safety
+/// The caller must ensure reading `port` has no unintended side effects.
+#[inline(always)]
+pub unsafe fn inb(port: u16) -> u8 {
+    let value: u8;
+    unsafe {
+        asm!("in al, dx", in("dx") port, out("al") value, options(nomem, nostack, preserves_flags));
+    }
+    value
+}
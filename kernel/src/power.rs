@@ -0,0 +1,333 @@
+//! Reboot, power-off, and the ordered teardown that precedes them.
+//!
+//! Subsystems that leave hardware in a state the next boot could trip over
+//! (a programmed timer, output still parked in a buffer) register a
+//! [`ShutdownHook`] during bring-up. [`reboot`] and [`power_off`] run the hooks
+//! in reverse registration order, so later subsystems, which may depend on
+//! earlier ones, are torn down first. Fatal paths run the same hooks in
+//! [`Teardown::BestEffort`] mode, then do what `onpanic=` asks.
+
+use core::{
+    arch::asm,
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    framebuffer::FramebufferColor,
+    port::{inb, outb},
+    time::Instant,
+};
+
+/// Maximum number of registered shutdown hooks.
+pub const MAX_HOOKS: usize = 16;
+
+/// PCI reset control register (Intel PIIX/ICH and compatibles).
+const RESET_CONTROL: u16 = 0xCF9;
+/// Request a hard reset: first select it, then trigger it.
+const RESET_HARD: u8 = 0x02;
+const RESET_TRIGGER: u8 = 0x04;
+
+const KBC_STATUS: u16 = 0x64;
+const KBC_INPUT_FULL: u8 = 0x02;
+/// 8042 command pulsing the CPU reset line.
+const KBC_PULSE_RESET: u8 = 0xFE;
+
+/// Why the hooks are running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Teardown {
+    /// A deliberate reboot or power-off; hooks may do their full cleanup.
+    Orderly,
+    /// A panic or fatal error; hooks should only touch hardware and avoid
+    /// anything that could fault or block.
+    BestEffort,
+}
+
+/// A subsystem's teardown callback.
+#[derive(Clone, Copy)]
+pub struct ShutdownHook {
+    pub name: &'static str,
+    pub run: fn(Teardown),
+}
+
+/// Errors reported when registering a hook.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerError {
+    /// All [`MAX_HOOKS`] slots are taken.
+    TooManyHooks,
+}
+
+struct HookTable {
+    hooks: [Option<ShutdownHook>; MAX_HOOKS],
+    len: usize,
+}
+
+impl HookTable {
+    const fn new() -> Self {
+        Self {
+            hooks: [None; MAX_HOOKS],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, hook: ShutdownHook) -> Result<(), PowerError> {
+        let slot = self
+            .hooks
+            .get_mut(self.len)
+            .ok_or(PowerError::TooManyHooks)?;
+        *slot = Some(hook);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Registered hooks, most recently registered first.
+    fn teardown_order(&self) -> impl Iterator<Item = ShutdownHook> + '_ {
+        self.hooks[..self.len].iter().rev().flatten().copied()
+    }
+}
+
+struct HookCell(UnsafeCell<HookTable>);
+
+unsafe impl Sync for HookCell {}
+
+static HOOKS: HookCell = HookCell(UnsafeCell::new(HookTable::new()));
+static TORN_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Register `run` to be called before the machine resets or powers off.
+///
+/// Only called during single-threaded bring-up.
+pub fn register(name: &'static str, run: fn(Teardown)) -> Result<(), PowerError> {
+    unsafe { (*HOOKS.0.get()).push(ShutdownHook { name, run }) }
+}
+
+/// Run every registered hook once, newest first.
+///
+/// Later calls return immediately, so a fault inside a hook that lands on a
+/// fatal path does not re-enter the teardown.
+pub fn teardown(mode: Teardown) {
+    if TORN_DOWN.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let hooks = unsafe { &*HOOKS.0.get() };
+    for hook in hooks.teardown_order() {
+        if mode == Teardown::Orderly {
+            crate::diagln!("power: shutting down {}", hook.name);
+        }
+        (hook.run)(mode);
+    }
+}
+
+/// Tear down subsystems and reset the machine.
+pub fn reboot() -> ! {
+    crate::println!("Rebooting...");
+    teardown(Teardown::Orderly);
+    crate::status::end_session();
+    reset()
+}
+
+/// Tear down subsystems and stop the machine.
+///
+/// Without ACPI the kernel cannot remove power itself. Under `qemu-test` the
+/// session ends; otherwise the CPU halts and the machine can be switched off.
+pub fn power_off() -> ! {
+    teardown(Teardown::Orderly);
+    crate::status::end_session();
+
+    crate::println!("It is now safe to turn off the machine.");
+    crate::emergency::halt()
+}
+
+/// What the kernel does once it has nothing left to run, from `onhalt=`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HaltAction {
+    Halt,
+    Reboot,
+    PowerOff,
+}
+
+impl HaltAction {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "halt" => Some(Self::Halt),
+            "reboot" => Some(Self::Reboot),
+            "poweroff" => Some(Self::PowerOff),
+            _ => None,
+        }
+    }
+}
+
+/// The action requested with `onhalt=<halt|reboot|poweroff>`, default halt.
+pub fn halt_action() -> HaltAction {
+    crate::options::value("onhalt")
+        .and_then(HaltAction::parse)
+        .unwrap_or(HaltAction::Halt)
+}
+
+/// What a fatal path does once its report is out, from `onpanic=`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PanicAction {
+    /// Halt with the report on screen.
+    Halt,
+    /// Reset after leaving the report up for `delay_secs` seconds.
+    Reboot { delay_secs: u32 },
+    /// Keep the report up and flash a screen border, so a dead machine is
+    /// told apart from a powered-off one.
+    Blink,
+}
+
+impl PanicAction {
+    fn parse(value: &str) -> Option<Self> {
+        match value.split_once(':') {
+            None => match value {
+                "halt" => Some(Self::Halt),
+                "reboot" => Some(Self::Reboot { delay_secs: 0 }),
+                "blink" => Some(Self::Blink),
+                _ => None,
+            },
+            Some(("reboot", delay)) => delay
+                .parse()
+                .ok()
+                .map(|delay_secs| Self::Reboot { delay_secs }),
+            Some(_) => None,
+        }
+    }
+}
+
+/// The action requested with `onpanic=<halt|reboot[:secs]|blink>`, default
+/// halt.
+pub fn panic_action() -> PanicAction {
+    crate::options::value("onpanic")
+        .and_then(PanicAction::parse)
+        .unwrap_or(PanicAction::Halt)
+}
+
+const BLINK_COLORS: [FramebufferColor; 2] = [
+    FramebufferColor::new(0xFF, 0x40, 0x40),
+    FramebufferColor::new(0x20, 0x20, 0x20),
+];
+const BLINK_PERIOD: Duration = Duration::from_millis(500);
+
+/// Finish a fatal path: called after the report and the best-effort
+/// teardown, with interrupts masked.
+pub fn after_fatal() -> ! {
+    crate::status::end_session();
+    match panic_action() {
+        PanicAction::Halt => {
+            crate::println!("System halted.");
+            crate::emergency::halt()
+        }
+        PanicAction::Reboot { delay_secs } => {
+            crate::println!("Rebooting in {} s...", delay_secs);
+            pause(Duration::from_secs(delay_secs.into()));
+            reset()
+        }
+        PanicAction::Blink => {
+            crate::println!("System halted; blinking.");
+            loop {
+                for color in BLINK_COLORS {
+                    crate::emergency::border(color);
+                    pause(BLINK_PERIOD);
+                }
+            }
+        }
+    }
+}
+
+/// `pause` iterations per millisecond when the clock is not running: it may
+/// not be selected yet, or teardown may have stopped the device behind it.
+/// Generous, since one `pause` takes anywhere from 10 to 140 cycles.
+const SPINS_PER_MS: u64 = 1_000_000;
+
+/// Busy-wait for roughly `duration`, without interrupts.
+fn pause(duration: Duration) {
+    let start = crate::time::now();
+    let deadline = start.saturating_add(duration);
+    let spin_limit = (duration.as_millis() as u64).saturating_mul(SPINS_PER_MS);
+    let mut spins = 0;
+    while spins < spin_limit {
+        let now = crate::time::now();
+        if now != Instant::ZERO && now >= deadline {
+            break;
+        }
+        crate::sync::backoff::cpu_relax();
+        spins += 1;
+    }
+}
+
+/// Reset through the chipset, then the keyboard controller, then a triple
+/// fault, and halt if all three are ignored.
+fn reset() -> ! {
+    unsafe {
+        outb(RESET_CONTROL, RESET_HARD);
+        outb(RESET_CONTROL, RESET_HARD | RESET_TRIGGER);
+
+        for _ in 0..0x10000 {
+            if inb(KBC_STATUS) & KBC_INPUT_FULL == 0 {
+                break;
+            }
+            crate::sync::backoff::cpu_relax();
+        }
+        outb(KBC_STATUS, KBC_PULSE_RESET);
+
+        // An empty IDT turns the breakpoint into a triple fault.
+        let empty_idt = [0u8; 10];
+        asm!("lidt [{}]", "int3", in(reg) empty_idt.as_ptr(), options(nostack));
+    }
+    crate::emergency::halt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop(_: Teardown) {}
+
+    fn hook(name: &'static str) -> ShutdownHook {
+        ShutdownHook { name, run: noop }
+    }
+
+    #[test]
+    fn hooks_tear_down_newest_first_and_cap_at_max() {
+        let mut table = HookTable::new();
+        table.push(hook("console")).unwrap();
+        table.push(hook("time")).unwrap();
+
+        let mut order = [""; 2];
+        for (slot, hook) in order.iter_mut().zip(table.teardown_order()) {
+            *slot = hook.name;
+        }
+        assert_eq!(order, ["time", "console"]);
+        assert_eq!(table.teardown_order().count(), 2);
+
+        for _ in table.len..MAX_HOOKS {
+            table.push(hook("filler")).unwrap();
+        }
+        assert_eq!(table.push(hook("extra")), Err(PowerError::TooManyHooks));
+    }
+
+    #[test]
+    fn halt_action_parses_known_values() {
+        assert_eq!(HaltAction::parse("reboot"), Some(HaltAction::Reboot));
+        assert_eq!(HaltAction::parse("poweroff"), Some(HaltAction::PowerOff));
+        assert_eq!(HaltAction::parse("halt"), Some(HaltAction::Halt));
+        assert_eq!(HaltAction::parse("off"), None);
+    }
+
+    #[test]
+    fn panic_action_parses_optional_reboot_delay() {
+        assert_eq!(PanicAction::parse("halt"), Some(PanicAction::Halt));
+        assert_eq!(PanicAction::parse("blink"), Some(PanicAction::Blink));
+        assert_eq!(
+            PanicAction::parse("reboot"),
+            Some(PanicAction::Reboot { delay_secs: 0 })
+        );
+        assert_eq!(
+            PanicAction::parse("reboot:10"),
+            Some(PanicAction::Reboot { delay_secs: 10 })
+        );
+        assert_eq!(PanicAction::parse("reboot:soon"), None);
+        assert_eq!(PanicAction::parse("blink:2"), None);
+    }
+}
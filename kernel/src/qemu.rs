@@ -0,0 +1,26 @@
+//! QEMU test device support (`qemu-test` feature).
+//!
+//! With `-device isa-debug-exit,iobase=0xf4,iosize=0x01`, a byte written to
+//! port 0xF4 terminates QEMU with exit status `(value << 1) | 1`, letting a
+//! test harness tell a clean boot from a crash without scraping the screen.
+
+use oxide_abi::BootStatus;
+
+const DEBUG_EXIT_PORT: u16 = 0xF4;
+
+/// QEMU exits with status 33.
+const EXIT_SUCCESS: u8 = 0x10;
+/// QEMU exits with status 35.
+const EXIT_FAILURE: u8 = 0x11;
+
+/// Terminate QEMU, reporting success only for a deliberate halt.
+///
+/// Returns if the device is absent, so callers still halt afterwards.
+pub fn exit(status: BootStatus) {
+    let code = match status {
+        BootStatus::Halted => EXIT_SUCCESS,
+        _ => EXIT_FAILURE,
+    };
+    // SAFETY: the port is only wired to the debug-exit device under QEMU.
+    unsafe { crate::port::outb(DEBUG_EXIT_PORT, code) };
+}
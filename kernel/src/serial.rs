@@ -0,0 +1,104 @@
+//! Polled output on the first 16550 UART (COM1).
+//!
+//! Only transmit is supported: the port is a side channel for machine-readable
+//! dumps on headless test setups, not a console. [`init`] probes the scratch
+//! register so writes to a missing UART are skipped rather than spun on.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::port::{inb, outb};
+
+const COM1: u16 = 0x3F8;
+
+const DATA: u16 = 0;
+const INTERRUPT_ENABLE: u16 = 1;
+const FIFO_CONTROL: u16 = 2;
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+const SCRATCH: u16 = 7;
+
+/// Divisor latch access bit in the line control register.
+const LCR_DLAB: u8 = 0x80;
+/// 8 data bits, no parity, one stop bit.
+const LCR_8N1: u8 = 0x03;
+/// Enable and clear both FIFOs, 14-byte threshold.
+const FCR_ENABLE_CLEAR: u8 = 0xC7;
+/// DTR and RTS asserted, OUT2 set.
+const MCR_READY: u8 = 0x0B;
+const LSR_THR_EMPTY: u8 = 0x20;
+/// Both the holding and the shift register are empty.
+const LSR_TX_IDLE: u8 = 0x40;
+
+/// 115200 baud from the 1.8432 MHz reference clock.
+const DIVISOR: u16 = 1;
+
+/// Spins allowed waiting for the transmitter before a byte is dropped.
+const TX_SPIN_LIMIT: u32 = 100_000;
+
+static PRESENT: AtomicBool = AtomicBool::new(false);
+
+/// Program COM1 for 115200 8N1 if it exists; returns whether it does.
+pub fn init() -> bool {
+    if PRESENT.load(Ordering::Relaxed) {
+        return true;
+    }
+
+    // SAFETY: COM1 registers; the scratch probe has no side effects.
+    let present = unsafe {
+        outb(COM1 + SCRATCH, 0x5A);
+        inb(COM1 + SCRATCH) == 0x5A
+    };
+    if !present {
+        return false;
+    }
+
+    unsafe {
+        outb(COM1 + INTERRUPT_ENABLE, 0);
+        outb(COM1 + LINE_CONTROL, LCR_DLAB);
+        outb(COM1 + DATA, DIVISOR as u8);
+        outb(COM1 + INTERRUPT_ENABLE, (DIVISOR >> 8) as u8);
+        outb(COM1 + LINE_CONTROL, LCR_8N1);
+        outb(COM1 + FIFO_CONTROL, FCR_ENABLE_CLEAR);
+        outb(COM1 + MODEM_CONTROL, MCR_READY);
+    }
+
+    PRESENT.store(true, Ordering::Relaxed);
+    true
+}
+
+/// Write `bytes` to COM1, dropping them if the UART is absent or stuck.
+pub fn write_bytes(bytes: &[u8]) {
+    if !PRESENT.load(Ordering::Relaxed) {
+        return;
+    }
+    for &byte in bytes {
+        write_byte(byte);
+    }
+}
+
+/// Wait until every byte written so far has left the UART, giving up after
+/// the same number of spins as a single byte.
+pub fn flush() {
+    if !PRESENT.load(Ordering::Relaxed) {
+        return;
+    }
+    for _ in 0..TX_SPIN_LIMIT {
+        // SAFETY: reading the line status register has no side effects.
+        if unsafe { inb(COM1 + LINE_STATUS) } & LSR_TX_IDLE != 0 {
+            return;
+        }
+        crate::sync::backoff::cpu_relax();
+    }
+}
+
+fn write_byte(byte: u8) {
+    for _ in 0..TX_SPIN_LIMIT {
+        // SAFETY: reading the line status register has no side effects.
+        if unsafe { inb(COM1 + LINE_STATUS) } & LSR_THR_EMPTY != 0 {
+            unsafe { outb(COM1 + DATA, byte) };
+            return;
+        }
+        crate::sync::backoff::cpu_relax();
+    }
+}
@@ -0,0 +1,92 @@
+//! Progress reporting through the loader's status page.
+//!
+//! The kernel records each boot milestone and its final outcome in the
+//! [`StatusPage`] the loader allocated, so the next boot (or a memory dump)
+//! can tell how far this one got without a working console. Every write is
+//! a no-op when the loader provided no page.
+
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use oxide_abi::{BootStage, BootStatus, STATUS_MAGIC, StatusPage};
+
+use crate::volatile::Volatile;
+
+/// Identity-mapped address of the status page, zero when absent.
+static STATUS_PAGE: AtomicU64 = AtomicU64::new(0);
+/// Outcome passed to the last [`finish`], for [`end_session`].
+static OUTCOME: AtomicU32 = AtomicU32::new(BootStatus::Running as u32);
+
+/// Adopt the page at `phys` if the loader initialized it.
+pub fn init(phys: u64) {
+    if phys == 0 {
+        return;
+    }
+    let page = phys as *mut StatusPage;
+    // SAFETY: validated as page-aligned during handoff; the loader places it
+    // below the identity-mapped low gigabyte as `OXIDE_HANDOFF_MEMORY`.
+    if unsafe { Volatile::new(core::ptr::addr_of_mut!((*page).magic)) }.read() == STATUS_MAGIC {
+        STATUS_PAGE.store(phys, Ordering::Release);
+    }
+}
+
+/// Record that boot reached `stage`.
+pub fn stage(stage: BootStage) {
+    with_page(|page| page.stage.write(stage as u32));
+}
+
+/// Record the final outcome of this boot.
+///
+/// Fatal paths call this before their report, so the page says what went
+/// wrong even if the report itself faults.
+pub fn finish(status: BootStatus, detail: u64) {
+    OUTCOME.store(status as u32, Ordering::Relaxed);
+    with_page(|page| {
+        page.detail.write(detail);
+        page.status.write(status as u32);
+    });
+}
+
+/// Record how many fatal paths were entered while another was reporting.
+pub fn nested_faults(count: u32) {
+    with_page(|page| page.nested_faults.write(count));
+}
+
+/// Last step before the CPU halts or resets, once every report is out.
+///
+/// Drains the serial port so the tail of the output is not lost. Under the
+/// `qemu-test` feature this then ends the QEMU session with the outcome
+/// given to [`finish`].
+pub fn end_session() {
+    crate::serial::flush();
+
+    #[cfg(feature = "qemu-test")]
+    crate::qemu::exit(
+        BootStatus::from_raw(OUTCOME.load(Ordering::Relaxed)).unwrap_or(BootStatus::Running),
+    );
+}
+
+/// The fields of the status page the kernel writes.
+struct PageFields {
+    stage: Volatile<u32>,
+    status: Volatile<u32>,
+    nested_faults: Volatile<u32>,
+    detail: Volatile<u64>,
+}
+
+fn with_page(f: impl FnOnce(PageFields)) {
+    let phys = STATUS_PAGE.load(Ordering::Acquire);
+    if phys == 0 {
+        return;
+    }
+    let page = phys as *mut StatusPage;
+    // SAFETY: `init` only adopts a page the loader initialized, which stays
+    // identity-mapped for the life of the kernel.
+    f(unsafe {
+        PageFields {
+            stage: Volatile::new(core::ptr::addr_of_mut!((*page).stage)),
+            status: Volatile::new(core::ptr::addr_of_mut!((*page).status)),
+            nested_faults: Volatile::new(core::ptr::addr_of_mut!((*page).nested_faults)),
+            detail: Volatile::new(core::ptr::addr_of_mut!((*page).detail)),
+        }
+    });
+}
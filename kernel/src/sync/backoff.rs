@@ -0,0 +1,100 @@
+//! Spin-wait helpers.
+//!
+//! Every busy-wait in the kernel should go through [`cpu_relax`] or
+//! [`Backoff`] rather than a bare loop: `pause` lets the sibling hyperthread
+//! run and saves power, and backing off exponentially keeps a contended cache
+//! line from bouncing between cores. [`yield_now`] is where a scheduler will
+//! take over; until one exists it only relaxes the CPU.
+
+/// Hint that the caller is spinning (`pause` on x86).
+#[inline]
+pub fn cpu_relax() {
+    core::hint::spin_loop();
+}
+
+/// Give up the CPU to another runnable thread, if any.
+///
+/// There is no scheduler yet, so this is a single [`cpu_relax`].
+#[inline]
+pub fn yield_now() {
+    cpu_relax();
+}
+
+/// Exponential backoff for spin loops.
+///
+/// Each [`Backoff::spin`] waits twice as long as the previous one, up to
+/// `2^SPIN_LIMIT` pauses. [`Backoff::snooze`] does the same, then switches to
+/// [`yield_now`] once spinning stops paying off.
+#[derive(Debug, Default)]
+pub struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    const SPIN_LIMIT: u32 = 6;
+    const YIELD_LIMIT: u32 = 10;
+
+    pub const fn new() -> Self {
+        Self { step: 0 }
+    }
+
+    /// Start over after progress was made.
+    pub fn reset(&mut self) {
+        self.step = 0;
+    }
+
+    /// Back off in a lock-free retry loop (e.g. a failed compare-exchange).
+    pub fn spin(&mut self) {
+        for _ in 0..1u32 << self.step.min(Self::SPIN_LIMIT) {
+            cpu_relax();
+        }
+        if self.step <= Self::SPIN_LIMIT {
+            self.step += 1;
+        }
+    }
+
+    /// Back off while waiting for another context to make progress.
+    pub fn snooze(&mut self) {
+        if self.step <= Self::SPIN_LIMIT {
+            for _ in 0..1u32 << self.step {
+                cpu_relax();
+            }
+        } else {
+            yield_now();
+        }
+        if self.step <= Self::YIELD_LIMIT {
+            self.step += 1;
+        }
+    }
+
+    /// True once snoozing has escalated to yielding; a caller that can block
+    /// properly should do so instead.
+    pub fn is_completed(&self) -> bool {
+        self.step > Self::YIELD_LIMIT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_escalates_then_completes() {
+        let mut backoff = Backoff::new();
+        for _ in 0..=Backoff::SPIN_LIMIT {
+            backoff.spin();
+        }
+        assert_eq!(backoff.step, Backoff::SPIN_LIMIT + 1);
+        backoff.spin();
+        assert_eq!(backoff.step, Backoff::SPIN_LIMIT + 1);
+        assert!(!backoff.is_completed());
+
+        for _ in 0..=Backoff::YIELD_LIMIT {
+            backoff.snooze();
+        }
+        assert!(backoff.is_completed());
+
+        backoff.reset();
+        assert!(!backoff.is_completed());
+    }
+}
@@ -0,0 +1,221 @@
+//! Blocking primitives for kernel code waiting on conditions.
+//!
+//! There is no scheduler yet, so a "blocked" caller spins with
+//! [`backoff::Backoff`] until it is woken or its deadline passes. The
+//! wait/wake interface is what a scheduler will later implement by parking the
+//! current thread on the [`WaitQueue`] instead of spinning.
+
+#![allow(dead_code)]
+
+use core::{
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    time::Duration,
+};
+
+use crate::time;
+
+pub mod backoff;
+
+use backoff::Backoff;
+
+/// Backoff steps between re-checks of a condition when no wake arrives, so conditions
+/// that change without an explicit wake (polled hardware) are still observed.
+const POLL_SPINS: u32 = 128;
+
+/// A wait ended because its timeout elapsed before the condition was met.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimedOut;
+
+/// A set of waiters blocked until some condition changes.
+pub struct WaitQueue {
+    generation: AtomicU32,
+    waiters: AtomicU32,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            generation: AtomicU32::new(0),
+            waiters: AtomicU32::new(0),
+        }
+    }
+
+    /// Block until `ready` returns true, re-checking after every wake.
+    ///
+    /// With `timeout` set, gives up once it has elapsed; `Duration::ZERO`
+    /// checks the condition exactly once.
+    pub fn wait_until(
+        &self,
+        mut ready: impl FnMut() -> bool,
+        timeout: Option<Duration>,
+    ) -> Result<(), TimedOut> {
+        if ready() {
+            return Ok(());
+        }
+
+        let deadline = timeout.map(|timeout| time::now() + timeout);
+        self.waiters.fetch_add(1, Ordering::Relaxed);
+        let mut backoff = Backoff::new();
+        let result = loop {
+            let seen = self.generation.load(Ordering::Acquire);
+            if ready() {
+                break Ok(());
+            }
+            if deadline.is_some_and(|deadline| time::now() >= deadline) {
+                break Err(TimedOut);
+            }
+            for _ in 0..POLL_SPINS {
+                if self.generation.load(Ordering::Acquire) != seen {
+                    backoff.reset();
+                    break;
+                }
+                backoff.snooze();
+            }
+        };
+        self.waiters.fetch_sub(1, Ordering::Relaxed);
+        result
+    }
+
+    /// Wake every waiter so it re-checks its condition.
+    pub fn wake_all(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Number of callers currently blocked in [`WaitQueue::wait_until`].
+    pub fn waiters(&self) -> u32 {
+        self.waiters.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A manual-reset flag that waiters block on until it is set.
+///
+/// Suitable for signalling from interrupt handlers (e.g. "input available").
+pub struct Event {
+    set: AtomicBool,
+    queue: WaitQueue,
+}
+
+impl Event {
+    pub const fn new() -> Self {
+        Self {
+            set: AtomicBool::new(false),
+            queue: WaitQueue::new(),
+        }
+    }
+
+    /// Set the event and wake all waiters.
+    pub fn set(&self) {
+        self.set.store(true, Ordering::Release);
+        self.queue.wake_all();
+    }
+
+    /// Clear the event so later waits block again.
+    pub fn reset(&self) {
+        self.set.store(false, Ordering::Release);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.set.load(Ordering::Acquire)
+    }
+
+    /// Block until the event is set or `timeout` elapses.
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<(), TimedOut> {
+        self.queue.wait_until(|| self.is_set(), timeout)
+    }
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A counting semaphore.
+pub struct Semaphore {
+    permits: AtomicU32,
+    queue: WaitQueue,
+}
+
+impl Semaphore {
+    pub const fn new(permits: u32) -> Self {
+        Self {
+            permits: AtomicU32::new(permits),
+            queue: WaitQueue::new(),
+        }
+    }
+
+    /// Take a permit without blocking.
+    pub fn try_acquire(&self) -> bool {
+        self.permits
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |permits| {
+                permits.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    /// Block until a permit is available or `timeout` elapses.
+    pub fn acquire(&self, timeout: Option<Duration>) -> Result<(), TimedOut> {
+        self.queue.wait_until(|| self.try_acquire(), timeout)
+    }
+
+    /// Return a permit and wake waiters.
+    pub fn release(&self) {
+        self.permits.fetch_add(1, Ordering::Release);
+        self.queue.wake_all();
+    }
+
+    pub fn available(&self) -> u32 {
+        self.permits.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_wait_times_out_until_set() {
+        let event = Event::new();
+        assert_eq!(event.wait(Some(Duration::ZERO)), Err(TimedOut));
+
+        event.set();
+        assert_eq!(event.wait(Some(Duration::ZERO)), Ok(()));
+        assert_eq!(event.wait(None), Ok(()));
+
+        event.reset();
+        assert!(!event.is_set());
+    }
+
+    #[test]
+    fn semaphore_counts_permits() {
+        let semaphore = Semaphore::new(1);
+        assert_eq!(semaphore.acquire(Some(Duration::ZERO)), Ok(()));
+        assert!(!semaphore.try_acquire());
+        assert_eq!(semaphore.acquire(Some(Duration::ZERO)), Err(TimedOut));
+
+        semaphore.release();
+        assert_eq!(semaphore.available(), 1);
+        assert!(semaphore.try_acquire());
+    }
+
+    #[test]
+    fn wait_queue_rechecks_condition() {
+        let queue = WaitQueue::new();
+        let mut checks = 0;
+        let result = queue.wait_until(
+            || {
+                checks += 1;
+                checks == 3
+            },
+            None,
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(queue.waiters(), 0);
+    }
+}
@@ -0,0 +1,104 @@
+use core::{
+    fmt,
+    ops::{Add, AddAssign, Sub},
+    time::Duration,
+};
+
+/// A point on the kernel's monotonic clock, measured from clock initialisation.
+///
+/// Instants stay comparable across clock source switches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    nanos: u64,
+}
+
+impl Instant {
+    /// The moment the clock was initialised.
+    pub const ZERO: Instant = Instant { nanos: 0 };
+
+    /// Returns the current monotonic time.
+    pub fn now() -> Self {
+        super::now()
+    }
+
+    pub const fn from_nanos(nanos: u64) -> Self {
+        Self { nanos }
+    }
+
+    /// Nanoseconds since clock initialisation.
+    pub const fn as_nanos(self) -> u64 {
+        self.nanos
+    }
+
+    /// Time elapsed between `earlier` and `self`, or zero if `earlier` is later.
+    pub fn duration_since(self, earlier: Instant) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_sub(earlier.nanos))
+    }
+
+    /// Time elapsed since `self`.
+    pub fn elapsed(self) -> Duration {
+        Self::now().duration_since(self)
+    }
+
+    pub fn checked_add(self, duration: Duration) -> Option<Instant> {
+        let nanos = u64::try_from(duration.as_nanos()).ok()?;
+        self.nanos.checked_add(nanos).map(Instant::from_nanos)
+    }
+
+    pub fn saturating_add(self, duration: Duration) -> Instant {
+        self.checked_add(duration)
+            .unwrap_or(Instant::from_nanos(u64::MAX))
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Instant {
+        self.saturating_add(rhs)
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Duration {
+        self.duration_since(rhs)
+    }
+}
+
+impl fmt::Display for Instant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.nanos / 1_000_000_000;
+        let micros = (self.nanos % 1_000_000_000) / 1_000;
+        write!(f, "{}.{:06}", seconds, micros)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instant_arithmetic_saturates() {
+        let start = Instant::from_nanos(1_500);
+        let later = start + Duration::from_micros(2);
+        assert_eq!(later.as_nanos(), 3_500);
+        assert_eq!(later - start, Duration::from_nanos(2_000));
+        assert_eq!(start - later, Duration::ZERO);
+        assert_eq!(
+            Instant::from_nanos(u64::MAX - 1) + Duration::from_secs(1),
+            Instant::from_nanos(u64::MAX)
+        );
+        assert_eq!(
+            Instant::ZERO.checked_add(Duration::from_secs(u64::MAX)),
+            None
+        );
+    }
+}
@@ -1,88 +1,289 @@
+//! Monotonic time built on interchangeable hardware clock sources.
+//!
+//! Every counter that can back the kernel clock implements [`ClockSource`] and
+//! is listed in [`CLOCK_SOURCES`]. At boot the highest-rated usable source is
+//! selected unless `clocksource=<name>` names another one, and [`switch_to`]
+//! can change the source later without making [`Instant::now`] go backwards.
+
 #![allow(dead_code)]
 
-use core::{arch::asm, cell::UnsafeCell};
+use core::cell::UnsafeCell;
+
+use crate::interrupts::without_interrupts;
+
+mod instant;
+mod pit;
+mod tsc;
+
+pub use instant::Instant;
+
+/// A free-running counter that can back the monotonic clock.
+pub trait ClockSource: Sync {
+    /// Short identifier used by `clocksource=<name>`.
+    fn name(&self) -> &'static str;
 
-struct MonotonicCell(UnsafeCell<Option<MonotonicClock>>);
+    /// Preference among usable sources; higher wins. Zero marks the source as
+    /// unusable on this machine.
+    fn rating(&self) -> u32;
+
+    /// Counter frequency in Hz.
+    fn frequency_hz(&self) -> u64;
+
+    /// Prepare the hardware before the source becomes active.
+    fn enable(&self) {}
+
+    /// Read the current counter value.
+    fn read(&self) -> u64;
+}
 
-unsafe impl Sync for MonotonicCell {}
+/// A device that can raise periodic timer interrupts.
+pub trait TickSource: Sync {
+    /// Short identifier for diagnostics.
+    fn name(&self) -> &'static str;
 
-static MONOTONIC_CLOCK: MonotonicCell = MonotonicCell(UnsafeCell::new(None));
+    /// Preference among available tick devices; higher wins.
+    fn rating(&self) -> u32;
 
-/// Configure the global monotonic clock using the processor timestamp counter.
+    /// Program periodic interrupts at roughly `hz`, returning the achieved rate.
+    fn start_periodic(&self, hz: u32) -> Result<u32, TimeError>;
+
+    /// Stop raising tick interrupts.
+    fn stop(&self);
+}
+
+/// Errors reported by the clock source registry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeError {
+    /// No registered source has the requested name.
+    UnknownSource,
+    /// The requested source is not usable on this machine.
+    SourceUnusable,
+    /// A tick rate outside the device's supported range was requested.
+    InvalidRate,
+}
+
+/// Clock sources known to the kernel, in no particular order.
 ///
-/// `frequency_hz` may be zero to indicate unknown calibration, in which case
-/// consumers can still read raw tick counts but nanosecond conversion is
-/// unavailable. Calling this more than once is harmless.
-pub fn init_tsc_monotonic(frequency_hz: u64) {
-    unsafe {
-        let slot = &mut *MONOTONIC_CLOCK.0.get();
-        if slot.is_none() {
-            *slot = Some(MonotonicClock::from_tsc(frequency_hz));
+/// The HPET is not registered yet: locating it requires the ACPI HPET table,
+/// which the kernel does not parse.
+static CLOCK_SOURCES: [&dyn ClockSource; 2] = [&tsc::TSC, &pit::PIT];
+
+/// Periodic tick devices known to the kernel.
+static TICK_SOURCES: [&dyn TickSource; 1] = [&pit::PIT];
+
+struct ClockCell(UnsafeCell<Option<ActiveClock>>);
+
+unsafe impl Sync for ClockCell {}
+
+static CLOCK: ClockCell = ClockCell(UnsafeCell::new(None));
+
+/// The selected clock source and the point at which it took over.
+struct ActiveClock {
+    source: &'static dyn ClockSource,
+    baseline: u64,
+    offset_nanos: u64,
+}
+
+impl ActiveClock {
+    fn start(source: &'static dyn ClockSource, offset_nanos: u64) -> Self {
+        source.enable();
+        Self {
+            source,
+            baseline: source.read(),
+            offset_nanos,
         }
     }
+
+    fn elapsed_ticks(&self) -> u64 {
+        self.source.read().wrapping_sub(self.baseline)
+    }
+
+    fn now_nanos(&self) -> u64 {
+        let since_switch =
+            ticks_to_nanos(self.elapsed_ticks(), self.source.frequency_hz()).unwrap_or(u64::MAX);
+        self.offset_nanos.saturating_add(since_switch)
+    }
 }
 
-/// Returns the number of ticks elapsed since the monotonic clock was initialised.
-/// The units are implementation-defined (TSC ticks for the current implementation).
-pub fn monotonic_ticks() -> Option<u64> {
-    unsafe {
-        let slot = &*MONOTONIC_CLOCK.0.get();
-        slot.as_ref().map(|clock| clock.elapsed_ticks())
+/// Register the boot-time clock sources and select the active one.
+///
+/// `tsc_frequency_hz` is the loader's calibration result and may be zero, in
+/// which case the TSC is not eligible. Calling this more than once is harmless.
+pub fn init(tsc_frequency_hz: u64) {
+    if unsafe { (*CLOCK.0.get()).is_some() } {
+        return;
     }
+
+    tsc::TSC.set_frequency(tsc_frequency_hz);
+
+    let requested = crate::options::value("clocksource");
+    let source = match requested.map(find_source) {
+        Some(Ok(source)) => Some(source),
+        Some(Err(err)) => {
+            crate::println!(
+                "time: clocksource={} rejected ({:?}); using default",
+                requested.unwrap_or(""),
+                err
+            );
+            best_source(&CLOCK_SOURCES)
+        }
+        None => best_source(&CLOCK_SOURCES),
+    };
+
+    let Some(source) = source else {
+        crate::println!("time: no usable clock source; timestamps unavailable");
+        return;
+    };
+
+    without_interrupts(|| unsafe {
+        *CLOCK.0.get() = Some(ActiveClock::start(source, 0));
+    });
+    let _ = crate::power::register("time", stop_ticks);
+    crate::diagln!(
+        "time: clocksource {} (rating {}, {} Hz)",
+        source.name(),
+        source.rating(),
+        source.frequency_hz()
+    );
 }
 
-/// Returns the elapsed time in nanoseconds since the monotonic clock was initialised.
-/// Only available when the time source frequency was provided during initialisation.
-pub fn monotonic_nanos() -> Option<u64> {
+/// Make the named source the active clock.
+///
+/// The current time is carried over so that [`Instant`] values taken before
+/// the switch remain comparable with those taken after it.
+pub fn switch_to(name: &str) -> Result<(), TimeError> {
+    let source = find_source(name)?;
+    // A timer handler reading the clock must not see the old baseline paired
+    // with the new source.
+    without_interrupts(|| unsafe {
+        let slot = &mut *CLOCK.0.get();
+        let offset = slot.as_ref().map_or(0, ActiveClock::now_nanos);
+        *slot = Some(ActiveClock::start(source, offset));
+    });
+    Ok(())
+}
+
+/// Name of the active clock source, if one has been selected.
+pub fn current_source() -> Option<&'static str> {
+    unsafe { (*CLOCK.0.get()).as_ref().map(|clock| clock.source.name()) }
+}
+
+/// All registered clock sources.
+pub fn clock_sources() -> &'static [&'static dyn ClockSource] {
+    &CLOCK_SOURCES
+}
+
+/// The highest-rated periodic tick device.
+pub fn tick_source() -> Option<&'static dyn TickSource> {
+    TICK_SOURCES
+        .iter()
+        .copied()
+        .filter(|source| source.rating() > 0)
+        .max_by_key(|source| source.rating())
+}
+
+/// Silence every tick device so no timer interrupt is pending across a reset.
+fn stop_ticks(_: crate::power::Teardown) {
+    for source in TICK_SOURCES {
+        source.stop();
+    }
+}
+
+/// Returns the current monotonic time.
+///
+/// Before [`init`] selects a source this is [`Instant::ZERO`].
+pub fn now() -> Instant {
     unsafe {
-        let slot = &*MONOTONIC_CLOCK.0.get();
-        slot.as_ref()?.nanoseconds_since_start()
+        (*CLOCK.0.get()).as_ref().map_or(Instant::ZERO, |clock| {
+            Instant::from_nanos(clock.now_nanos())
+        })
     }
 }
 
-struct MonotonicClock {
-    baseline_ticks: u64,
-    frequency_hz: u64,
+fn find_source(name: &str) -> Result<&'static dyn ClockSource, TimeError> {
+    let source = CLOCK_SOURCES
+        .iter()
+        .copied()
+        .find(|source| source.name() == name)
+        .ok_or(TimeError::UnknownSource)?;
+    if source.rating() == 0 {
+        return Err(TimeError::SourceUnusable);
+    }
+    Ok(source)
 }
 
-impl MonotonicClock {
-    fn from_tsc(frequency_hz: u64) -> Self {
-        let baseline_ticks = unsafe { read_tsc() };
-        Self {
-            baseline_ticks,
-            frequency_hz,
-        }
+fn best_source<'a>(sources: &[&'a dyn ClockSource]) -> Option<&'a dyn ClockSource> {
+    sources
+        .iter()
+        .copied()
+        .filter(|source| source.rating() > 0)
+        .max_by_key(|source| source.rating())
+}
+
+/// Convert a tick count at `frequency_hz` into nanoseconds.
+fn ticks_to_nanos(ticks: u64, frequency_hz: u64) -> Option<u64> {
+    if frequency_hz == 0 {
+        return None;
     }
 
-    fn elapsed_ticks(&self) -> u64 {
-        let current = unsafe { read_tsc() };
-        current.wrapping_sub(self.baseline_ticks)
+    let nanos = (ticks as u128).saturating_mul(1_000_000_000u128) / frequency_hz as u128;
+    u64::try_from(nanos).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeSource {
+        name: &'static str,
+        rating: u32,
     }
 
-    fn nanoseconds_since_start(&self) -> Option<u64> {
-        if self.frequency_hz == 0 {
-            return None;
+    impl ClockSource for FakeSource {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn rating(&self) -> u32 {
+            self.rating
         }
 
-        let frequency = self.frequency_hz as u128;
-        let ticks = self.elapsed_ticks() as u128;
-        let nanos = ticks
-            .saturating_mul(1_000_000_000u128)
-            .checked_div(frequency)?;
-        if nanos > u64::MAX as u128 {
-            None
-        } else {
-            Some(nanos as u64)
+        fn frequency_hz(&self) -> u64 {
+            1_000
+        }
+
+        fn read(&self) -> u64 {
+            0
         }
     }
-}
 
-#[inline(always)]
-unsafe fn read_tsc() -> u64 {
-    let high: u32;
-    let low: u32;
-    unsafe {
-        asm!("rdtsc", out("edx") high, out("eax") low, options(nomem, nostack, preserves_flags));
+    #[test]
+    fn best_source_prefers_highest_usable_rating() {
+        let slow = FakeSource {
+            name: "slow",
+            rating: 50,
+        };
+        let fast = FakeSource {
+            name: "fast",
+            rating: 300,
+        };
+        let broken = FakeSource {
+            name: "broken",
+            rating: 0,
+        };
+
+        let sources: [&dyn ClockSource; 3] = [&slow, &fast, &broken];
+        assert_eq!(best_source(&sources).map(|s| s.name()), Some("fast"));
+
+        let sources: [&dyn ClockSource; 1] = [&broken];
+        assert!(best_source(&sources).is_none());
+    }
+
+    #[test]
+    fn ticks_to_nanos_handles_unknown_frequency_and_overflow() {
+        assert_eq!(ticks_to_nanos(1_000, 0), None);
+        assert_eq!(ticks_to_nanos(3_000, 3_000_000_000), Some(1_000));
+        assert_eq!(ticks_to_nanos(1_193_182, 1_193_182), Some(1_000_000_000));
+        assert_eq!(ticks_to_nanos(u64::MAX, 1), None);
     }
-    ((high as u64) << 32) | (low as u64)
 }
@@ -0,0 +1,155 @@
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use super::{ClockSource, TickSource, TimeError};
+use crate::port::{inb, outb};
+
+/// Input clock of the 8254 programmable interval timer.
+const PIT_FREQUENCY_HZ: u64 = 1_193_182;
+
+const CHANNEL0_DATA: u16 = 0x40;
+const COMMAND: u16 = 0x43;
+
+/// Channel 0, lobyte/hibyte access, binary counting.
+const CHANNEL0_ACCESS: u8 = 0b0011_0000;
+const MODE_INTERRUPT_ON_TERMINAL_COUNT: u8 = 0b0000_0000;
+const MODE_RATE_GENERATOR: u8 = 0b0000_0100;
+const LATCH_CHANNEL0: u8 = 0x00;
+
+/// Full 16-bit period; a programmed reload of zero means 65536.
+const MAX_RELOAD: u32 = 0x1_0000;
+
+/// Low-resolution fallback, but present on every PC-compatible machine.
+const CLOCK_RATING: u32 = 50;
+const TICK_RATING: u32 = 100;
+
+/// The legacy PIT, used both as a clock source and as a periodic tick device.
+///
+/// Both roles share channel 0. The hardware counter is only 16 bits wide, so
+/// the clock source extends it in software and must be read at least once per
+/// reload period (about 55 ms at the slowest rate) to avoid losing time.
+pub(super) struct Pit {
+    reload: AtomicU32,
+    last_count: AtomicU32,
+    total: AtomicU64,
+}
+
+pub(super) static PIT: Pit = Pit {
+    reload: AtomicU32::new(0),
+    last_count: AtomicU32::new(0),
+    total: AtomicU64::new(0),
+};
+
+impl Pit {
+    fn program(&self, mode: u8, reload: u32) {
+        let divisor = (reload & 0xFFFF) as u16;
+        unsafe {
+            outb(COMMAND, CHANNEL0_ACCESS | mode);
+            outb(CHANNEL0_DATA, divisor as u8);
+            outb(CHANNEL0_DATA, (divisor >> 8) as u8);
+        }
+        self.reload.store(reload, Ordering::Relaxed);
+        self.last_count.store(reload, Ordering::Relaxed);
+    }
+
+    fn read_count(&self) -> u32 {
+        unsafe {
+            outb(COMMAND, LATCH_CHANNEL0);
+            let low = inb(CHANNEL0_DATA) as u32;
+            let high = inb(CHANNEL0_DATA) as u32;
+            (high << 8) | low
+        }
+    }
+}
+
+impl ClockSource for Pit {
+    fn name(&self) -> &'static str {
+        "pit"
+    }
+
+    fn rating(&self) -> u32 {
+        CLOCK_RATING
+    }
+
+    fn frequency_hz(&self) -> u64 {
+        PIT_FREQUENCY_HZ
+    }
+
+    fn enable(&self) {
+        if self.reload.load(Ordering::Relaxed) == 0 {
+            self.program(MODE_RATE_GENERATOR, MAX_RELOAD);
+        }
+    }
+
+    fn read(&self) -> u64 {
+        let reload = self.reload.load(Ordering::Relaxed);
+        let count = self.read_count();
+        let last = self.last_count.swap(count, Ordering::Relaxed);
+        let delta = elapsed_counts(last, count, reload);
+        self.total.fetch_add(delta, Ordering::Relaxed) + delta
+    }
+}
+
+impl TickSource for Pit {
+    fn name(&self) -> &'static str {
+        "pit"
+    }
+
+    fn rating(&self) -> u32 {
+        TICK_RATING
+    }
+
+    fn start_periodic(&self, hz: u32) -> Result<u32, TimeError> {
+        let divisor = divisor_for(hz).ok_or(TimeError::InvalidRate)?;
+        self.program(MODE_RATE_GENERATOR, divisor);
+        Ok((PIT_FREQUENCY_HZ / divisor as u64) as u32)
+    }
+
+    /// Switch channel 0 to one-shot mode with a full count.
+    ///
+    /// At most one further interrupt fires; the counter keeps running so the
+    /// clock source remains valid.
+    fn stop(&self) {
+        self.program(MODE_INTERRUPT_ON_TERMINAL_COUNT, MAX_RELOAD);
+    }
+}
+
+/// Counts elapsed between two reads of a down-counter that reloads at `reload`.
+fn elapsed_counts(last: u32, current: u32, reload: u32) -> u64 {
+    if current <= last {
+        (last - current) as u64
+    } else {
+        (last + reload.max(current) - current) as u64
+    }
+}
+
+fn divisor_for(hz: u32) -> Option<u32> {
+    if hz == 0 {
+        return None;
+    }
+    let divisor = (PIT_FREQUENCY_HZ / hz as u64) as u32;
+    (1..=MAX_RELOAD).contains(&divisor).then_some(divisor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elapsed_counts_handles_reload_wrap() {
+        assert_eq!(elapsed_counts(1000, 400, MAX_RELOAD), 600);
+        assert_eq!(
+            elapsed_counts(100, 65_000, MAX_RELOAD),
+            100 + 65_536 - 65_000
+        );
+        assert_eq!(elapsed_counts(10, 11_900, 11_931), 10 + 11_931 - 11_900);
+    }
+
+    #[test]
+    fn divisor_for_rejects_unreachable_rates() {
+        assert_eq!(divisor_for(0), None);
+        assert_eq!(divisor_for(10), None);
+        assert_eq!(divisor_for(100), Some(11_931));
+        assert_eq!(divisor_for(1_193_182), Some(1));
+        assert_eq!(divisor_for(2_000_000), None);
+    }
+}
@@ -0,0 +1,72 @@
+use core::{
+    arch::{asm, x86_64::__cpuid},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::ClockSource;
+
+/// Preferred when the TSC ticks at a constant rate across P- and C-states.
+const RATING_INVARIANT: u32 = 300;
+/// Usable but may drift with frequency scaling.
+const RATING_VARIABLE: u32 = 200;
+
+const CPUID_EXTENDED_MAX: u32 = 0x8000_0000;
+const CPUID_ADVANCED_POWER: u32 = 0x8000_0007;
+const INVARIANT_TSC_BIT: u32 = 1 << 8;
+
+/// The processor timestamp counter, calibrated by the loader.
+pub(super) struct TscClock {
+    frequency_hz: AtomicU64,
+}
+
+pub(super) static TSC: TscClock = TscClock {
+    frequency_hz: AtomicU64::new(0),
+};
+
+impl TscClock {
+    pub(super) fn set_frequency(&self, frequency_hz: u64) {
+        self.frequency_hz.store(frequency_hz, Ordering::Relaxed);
+    }
+}
+
+impl ClockSource for TscClock {
+    fn name(&self) -> &'static str {
+        "tsc"
+    }
+
+    fn rating(&self) -> u32 {
+        if self.frequency_hz() == 0 {
+            0
+        } else if is_invariant() {
+            RATING_INVARIANT
+        } else {
+            RATING_VARIABLE
+        }
+    }
+
+    fn frequency_hz(&self) -> u64 {
+        self.frequency_hz.load(Ordering::Relaxed)
+    }
+
+    fn read(&self) -> u64 {
+        unsafe { read_tsc() }
+    }
+}
+
+fn is_invariant() -> bool {
+    let max_extended = __cpuid(CPUID_EXTENDED_MAX).eax;
+    if max_extended < CPUID_ADVANCED_POWER {
+        return false;
+    }
+    __cpuid(CPUID_ADVANCED_POWER).edx & INVARIANT_TSC_BIT != 0
+}
+
+#[inline(always)]
+unsafe fn read_tsc() -> u64 {
+    let high: u32;
+    let low: u32;
+    unsafe {
+        asm!("rdtsc", out("edx") high, out("eax") low, options(nomem, nostack, preserves_flags));
+    }
+    ((high as u64) << 32) | (low as u64)
+}
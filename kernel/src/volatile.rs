@@ -0,0 +1,130 @@
+//! Volatile access to device registers and the framebuffer.
+//!
+//! Memory that a device (or the firmware, or a debugger) also looks at must
+//! be read and written with volatile operations the compiler cannot elide,
+//! merge, or reorder. [`Volatile`] wraps a single location and
+//! [`VolatileSlice`] a run of elements. Building one is the only unsafe step;
+//! the caller vouches for the memory once instead of at every access.
+//! `VolatileSlice` indices are always bounds-checked; an out-of-range index
+//! panics rather than touching memory the caller never vouched for.
+
+use core::ptr;
+
+/// One volatile location of type `T`.
+#[derive(Clone, Copy, Debug)]
+pub struct Volatile<T: Copy> {
+    ptr: *mut T,
+}
+
+impl<T: Copy> Volatile<T> {
+    /// Wrap `ptr`.
+    ///
+    /// # Safety
+    /// `ptr` must be aligned and valid for reads and writes for as long as
+    /// the wrapper (or any copy of it) is used.
+    pub const unsafe fn new(ptr: *mut T) -> Self {
+        Self { ptr }
+    }
+
+    #[inline]
+    pub fn read(&self) -> T {
+        // SAFETY: guaranteed by the caller of `new`.
+        unsafe { self.ptr.read_volatile() }
+    }
+
+    #[inline]
+    pub fn write(&self, value: T) {
+        // SAFETY: guaranteed by the caller of `new`.
+        unsafe { self.ptr.write_volatile(value) }
+    }
+}
+
+/// `len` consecutive volatile elements of type `T`.
+#[derive(Clone, Copy, Debug)]
+pub struct VolatileSlice<T: Copy> {
+    base: *mut T,
+    len: usize,
+}
+
+impl<T: Copy> VolatileSlice<T> {
+    /// Wrap `len` elements starting at `base`.
+    ///
+    /// # Safety
+    /// `base` must be aligned and valid for reads and writes of `len`
+    /// elements for as long as the wrapper (or any copy of it) is used.
+    pub const unsafe fn new(base: *mut T, len: usize) -> Self {
+        Self { base, len }
+    }
+
+    #[inline]
+    pub fn read(&self, index: usize) -> T {
+        assert!(index < self.len, "volatile read {} of {}", index, self.len);
+        // SAFETY: in bounds, and the caller of `new` vouched for the memory.
+        unsafe { self.base.add(index).read_volatile() }
+    }
+
+    #[inline]
+    pub fn write(&self, index: usize, value: T) {
+        assert!(index < self.len, "volatile write {} of {}", index, self.len);
+        // SAFETY: in bounds, and the caller of `new` vouched for the memory.
+        unsafe { self.base.add(index).write_volatile(value) }
+    }
+
+    /// Write `value` to the `count` elements starting at `start`.
+    ///
+    /// Panics if the range runs past the end of the slice.
+    pub fn fill(&self, start: usize, count: usize, value: T) {
+        assert!(start.checked_add(count).is_some_and(|end| end <= self.len));
+        for index in start..start + count {
+            // SAFETY: the whole range was checked above, and the caller of
+            // `new` vouched for the memory.
+            unsafe { self.base.add(index).write_volatile(value) }
+        }
+    }
+
+    /// Copy `count` elements from `src` to `dst`; the ranges may overlap.
+    ///
+    /// This is a plain `memmove`, not element-by-element volatile access, so
+    /// use it for memory like the framebuffer where only the final contents
+    /// matter, never for device registers. Panics if either range runs past
+    /// the end of the slice.
+    pub fn copy_within(&self, src: usize, dst: usize, count: usize) {
+        assert!(src.checked_add(count).is_some_and(|end| end <= self.len));
+        assert!(dst.checked_add(count).is_some_and(|end| end <= self.len));
+        // SAFETY: both ranges are in bounds of memory the caller of `new`
+        // vouched for; `ptr::copy` handles overlap.
+        unsafe { ptr::copy(self.base.add(src), self.base.add(dst), count) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn slice_fill_and_copy_within_stay_in_range() {
+        let mut backing = vec![0u32; 8];
+        let slice = unsafe { VolatileSlice::new(backing.as_mut_ptr(), backing.len()) };
+        slice.fill(1, 3, 7);
+        slice.write(5, 9);
+        slice.copy_within(1, 4, 3);
+        assert_eq!(slice.read(0), 0);
+        assert_eq!(backing, [0, 7, 7, 7, 7, 7, 7, 0]);
+
+        let mut word = 1u64;
+        let cell = unsafe { Volatile::new(&mut word) };
+        cell.write(cell.read() + 1);
+        assert_eq!(word, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "volatile write 4 of 4")]
+    fn slice_write_past_end_panics() {
+        let mut backing = [0u8; 4];
+        let slice = unsafe { VolatileSlice::new(backing.as_mut_ptr(), backing.len()) };
+        slice.write(4, 1);
+    }
+}
@@ -1,5 +1,5 @@
 use core::mem::{MaybeUninit, size_of};
-use oxide_abi::BootAbi;
+use oxide_abi::{BootAbi, OXIDE_HANDOFF_MEMORY, PhysRange};
 use uefi::{
     boot::{AllocateType, MemoryType, allocate_pages},
     mem::memory_map::{MemoryMap, MemoryMapOwned},
@@ -7,7 +7,11 @@ use uefi::{
 
 use crate::{firmware::FirmwareInfo, framebuffer::FramebufferInfo, options::BootOptions};
 
-/// Allocates the BootAbi in LOADER_DATA memory.
+/// Memory type for pages the kernel owns after handoff, so the memory map
+/// itself says which regions the loader left for it.
+pub const HANDOFF_MEMORY: MemoryType = MemoryType::custom(OXIDE_HANDOFF_MEMORY);
+
+/// Allocates the BootAbi in [`HANDOFF_MEMORY`].
 ///
 /// The returned reference is effectively `'static` because the allocation
 /// is intentionally leaked and survives ExitBootServices. The kernel assumes
@@ -19,8 +23,8 @@ pub fn alloc_abi_struct() -> uefi::Result<*mut BootAbi> {
     let pages = abi_size.div_ceil(page_size);
 
     // Allocate physically contiguous pages for the ABI structure
-    // use LOADER_DATA so the kernel can access it after EBS
-    let phys_addr = allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, pages)?;
+    // a loader-owned type survives EBS, and the kernel keeps it reserved
+    let phys_addr = allocate_pages(AllocateType::AnyPages, HANDOFF_MEMORY, pages)?;
 
     // Cast the physical address to a pointer to BootAbi
     let abi_ptr = phys_addr.as_ptr().cast::<MaybeUninit<BootAbi>>();
@@ -40,23 +44,47 @@ pub fn alloc_abi_struct() -> uefi::Result<*mut BootAbi> {
     }
 }
 
-/// Convert UEFI MemoryMapOwned to ABI MemoryMap representation.
-fn convert_memory_map(mem: MemoryMapOwned) -> oxide_abi::MemoryMap {
+/// Describe a UEFI memory map in the ABI representation without taking
+/// ownership; the result is only valid while `mem` is alive.
+///
+/// The pool buffer is usually larger than the map, and some firmware reports
+/// a `map_size` with trailing slack, so the size and entry count are
+/// recomputed from whole descriptors rather than copied.
+pub fn memory_map_view(mem: &MemoryMapOwned) -> oxide_abi::MemoryMap {
     let meta = mem.meta();
     let buf = mem.buffer();
 
-    let abi = oxide_abi::MemoryMap {
+    let entry_size = meta.desc_size.max(1);
+    let filled = meta.map_size.min(buf.len());
+    let entry_count = filled / entry_size;
+
+    oxide_abi::MemoryMap {
         // Physical address of the memory descriptors.
         descriptors_phys: buf.as_ptr() as u64,
-        // use buf.len() instead of meta.map_size to reflect actual buffer size
-        map_size: buf.len() as u64,
+        // Bytes covered by whole descriptors.
+        map_size: (entry_count * entry_size) as u64,
         // The reported memory descriptor size.
         entry_size: meta.desc_size as u32,
         // the version of the descriptor structure
         entry_version: meta.desc_version,
-        // number of keys in the map
-        entry_count: mem.len() as u32,
-    };
+        // number of whole descriptors in the buffer
+        entry_count: entry_count as u32,
+    }
+}
+
+/// Values gathered before `ExitBootServices` that go into the ABI as-is.
+pub struct Handoff {
+    pub tsc_frequency_hz: Option<u64>,
+    pub status_page_phys: u64,
+    /// The microcode blob and the XXH32 of its contents.
+    pub microcode: (PhysRange, u32),
+    pub memory_attributes: PhysRange,
+    pub loader_image: PhysRange,
+}
+
+/// Convert UEFI MemoryMapOwned to ABI MemoryMap representation.
+fn convert_memory_map(mem: MemoryMapOwned) -> oxide_abi::MemoryMap {
+    let abi = memory_map_view(&mem);
 
     core::mem::forget(mem);
 
@@ -69,14 +97,18 @@ fn build_boot_abi(
     fw: FirmwareInfo,
     fb: FramebufferInfo,
     options: BootOptions,
-    tsc_frequency_hz: Option<u64>,
+    handoff: Handoff,
     mem: MemoryMapOwned,
 ) {
     abi.firmware = fw.into();
     abi.framebuffer = fb.into();
     abi.options = options.into();
-    abi.tsc_frequency_hz = tsc_frequency_hz.unwrap_or(0);
+    abi.tsc_frequency_hz = handoff.tsc_frequency_hz.unwrap_or(0);
     abi.memory_map = convert_memory_map(mem);
+    abi.status_page_phys = handoff.status_page_phys;
+    (abi.microcode, abi.microcode_xxh32) = handoff.microcode;
+    abi.memory_attributes = handoff.memory_attributes;
+    abi.loader_image = handoff.loader_image;
 }
 
 /// Unsafe wrapper to build BootAbi from raw pointer.
@@ -87,11 +119,11 @@ pub fn build_boot_abi_from_ptr(
     fw: FirmwareInfo,
     fb: FramebufferInfo,
     options: BootOptions,
-    tsc_frequency_hz: Option<u64>,
+    handoff: Handoff,
     mem: MemoryMapOwned,
 ) {
     unsafe {
         let abi = &mut *abi_ptr;
-        build_boot_abi(abi, fw, fb, options, tsc_frequency_hz, mem);
+        build_boot_abi(abi, fw, fb, options, handoff, mem);
     }
 }
@@ -0,0 +1,96 @@
+//! Reporting batteries and AC adapters from the ACPI tables.
+//!
+//! The loader follows the RSDP from the UEFI configuration table to the
+//! DSDT and every SSDT, and counts the power-supply devices they declare
+//! (see `oxide_util::acpi`). Charge level and whether AC is connected come
+//! from AML methods (`_BST`, `_PSR`) that Oxide cannot evaluate, so the
+//! report only says whether each kind of device exists.
+
+use core::slice;
+
+use oxide_util::acpi::{self, PowerSources, RootTable, SDT_HEADER_LEN};
+use uefi::table::cfg::ConfigTableEntry;
+
+/// Refuse tables claiming more than this; real DSDTs are well under 1 MiB.
+const MAX_TABLE: usize = 4 * 1024 * 1024;
+/// Lengths of the ACPI 1.0 and 2.0 RSDP.
+const RSDP_V1_LEN: usize = 20;
+const RSDP_V2_LEN: usize = 36;
+
+/// Print one line describing the machine's batteries and AC adapters.
+pub fn log_power_sources(debug: bool) {
+    match power_sources() {
+        Some(found) => uefi::println!(
+            "Power: battery {}, AC adapter {} (charge needs AML; not read)",
+            describe(found.batteries),
+            describe(found.ac_adapters)
+        ),
+        None if debug => uefi::println!("Power: unknown (no readable ACPI tables)"),
+        None => {}
+    }
+}
+
+fn describe(count: u32) -> &'static str {
+    match count {
+        0 => "absent",
+        1 => "present",
+        _ => "present (several)",
+    }
+}
+
+/// Scan the DSDT and SSDTs, or `None` if the tables cannot be reached.
+fn power_sources() -> Option<PowerSources> {
+    let rsdp = uefi::system::with_config_table(|entries| {
+        [ConfigTableEntry::ACPI2_GUID, ConfigTableEntry::ACPI_GUID]
+            .iter()
+            .find_map(|guid| entries.iter().find(|entry| entry.guid == *guid))
+            .map(|entry| entry.address.cast::<u8>())
+    })?;
+    // SAFETY: the firmware keeps the RSDP mapped while boot services run.
+    // Only a revision 2 RSDP is read past its first 20 bytes.
+    let revision = unsafe { rsdp.add(15).read() };
+    let len = if revision >= 2 {
+        RSDP_V2_LEN
+    } else {
+        RSDP_V1_LEN
+    };
+    let rsdp = unsafe { slice::from_raw_parts(rsdp, len) };
+    let (root, wide) = match acpi::parse_rsdp(rsdp).ok()? {
+        RootTable::Xsdt(addr) => (addr, true),
+        RootTable::Rsdt(addr) => (addr, false),
+    };
+
+    let root = table(root)?;
+    let mut found = PowerSources::default();
+    for addr in acpi::root_entries(root, wide) {
+        let Some(entry) = table(addr) else { continue };
+        match &entry[..4] {
+            b"FACP" => {
+                if let Some(dsdt) = acpi::fadt_dsdt(entry).and_then(table) {
+                    found.add(PowerSources::scan(&dsdt[SDT_HEADER_LEN..]));
+                }
+            }
+            b"SSDT" => found.add(PowerSources::scan(&entry[SDT_HEADER_LEN..])),
+            _ => {}
+        }
+    }
+    Some(found)
+}
+
+/// The validated table at physical address `addr`.
+fn table(addr: u64) -> Option<&'static [u8]> {
+    if addr == 0 {
+        return None;
+    }
+    // SAFETY: firmware identity-maps ACPI tables while boot services run;
+    // the header is read first so the length is known before the full slice.
+    let header = unsafe { slice::from_raw_parts(addr as *const u8, SDT_HEADER_LEN) };
+    let (_, length) = acpi::sdt_header(header).ok()?;
+    if length > MAX_TABLE {
+        return None;
+    }
+    // SAFETY: as above, for the length the header declares.
+    let table = unsafe { slice::from_raw_parts(addr as *const u8, length) };
+    acpi::validate_table(table).ok()?;
+    Some(table)
+}
@@ -0,0 +1,27 @@
+//! Chainloading of other EFI applications from the loader's boot volume.
+//!
+//! `chainload=<path>` on the loader command line (for example
+//! `chainload=\EFI\BOOT\Shell.efi`) starts that image before Oxide. If the
+//! chained application returns, the loader continues booting the kernel, so a
+//! test machine can keep Oxide as its primary boot entry and still reach the
+//! firmware shell or another OS loader. A boot menu can reuse [`start`] for
+//! its chainload entries.
+
+use uefi::boot::{self, AllocateType, LoadImageSource};
+
+/// Load the EFI application at `path` on the loader's own volume and run it
+/// until it exits. The image may be LZ4-compressed.
+pub fn start(path: &str) -> uefi::Result<()> {
+    let mut image = crate::esp::load(path, AllocateType::AnyPages, usize::MAX)?;
+    let loaded = boot::load_image(
+        boot::image_handle(),
+        LoadImageSource::FromBuffer {
+            buffer: image.as_mut_slice(),
+            file_path: None,
+        },
+    );
+    // The firmware copied the image into its own memory.
+    image.free()?;
+
+    boot::start_image(loaded?)
+}
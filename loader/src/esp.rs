@@ -0,0 +1,169 @@
+//! Reading files from the volume the loader was started from.
+
+use core::{fmt::Write, ptr::NonNull};
+
+use oxide_util::{FixedBufWriter, lz4, xxh32};
+use uefi::{
+    CStr16, Status,
+    boot::{self, AllocateType},
+    proto::media::file::{File, FileAttribute, FileMode, RegularFile},
+};
+
+/// Longest accepted path, in UTF-16 code units including the terminator.
+const PATH_CAP: usize = 256;
+/// Suffix of the checksum file every payload must have next to it.
+const DIGEST_SUFFIX: &str = ".xxh32";
+/// Longest checksum file read; `xxhsum` output is the digest and a file name.
+const DIGEST_FILE_CAP: usize = 128;
+
+/// Open `path` (for example `\EFI\oxide\ucode.bin`) on the loader's volume
+/// and return the file with its size in bytes, positioned at the start.
+pub fn open(path: &str) -> uefi::Result<(RegularFile, usize)> {
+    let mut path_buf = [0u16; PATH_CAP];
+    let path = CStr16::from_str_with_buf(path, &mut path_buf)
+        .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+
+    let mut fs = boot::get_image_file_system(boot::image_handle())?;
+    let mut file = fs
+        .open_volume()?
+        .open(path, FileMode::Read, FileAttribute::empty())?
+        .into_regular_file()
+        .ok_or_else(|| uefi::Error::from(Status::INVALID_PARAMETER))?;
+
+    file.set_position(RegularFile::END_OF_FILE)?;
+    let size = file.get_position()? as usize;
+    file.set_position(0)?;
+    Ok((file, size))
+}
+
+/// Fill `buf` from `file`, failing if the file ends early.
+pub fn read_exact(file: &mut RegularFile, buf: &mut [u8]) -> uefi::Result<()> {
+    let read = file.read(buf)?;
+    if read != buf.len() {
+        return Err(Status::END_OF_FILE.into());
+    }
+    Ok(())
+}
+
+const PAGE_SIZE: usize = 4096;
+
+/// A whole file read into `HANDOFF_MEMORY` pages by [`load`]; what is not
+/// freed before `ExitBootServices` stays reserved for the kernel.
+pub struct Payload {
+    pages: NonNull<u8>,
+    page_count: usize,
+    len: usize,
+    digest: u32,
+}
+
+impl Payload {
+    pub fn phys(&self) -> u64 {
+        self.pages.as_ptr() as u64
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// XXH32 (seed 0) of the contents, as verified against the checksum file.
+    pub fn digest(&self) -> u32 {
+        self.digest
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: the pages hold `len` initialised bytes and are ours until freed.
+        unsafe { core::slice::from_raw_parts_mut(self.pages.as_ptr(), self.len) }
+    }
+
+    /// Return the pages to the firmware.
+    pub fn free(self) -> uefi::Result<()> {
+        // SAFETY: the pages came from `allocate_pages` with this count.
+        unsafe { boot::free_pages(self.pages, self.page_count) }
+    }
+
+    fn allocate(placement: AllocateType, len: usize) -> uefi::Result<Self> {
+        let page_count = len.div_ceil(PAGE_SIZE);
+        let pages = boot::allocate_pages(placement, crate::abi::HANDOFF_MEMORY, page_count)?;
+        Ok(Self {
+            pages,
+            page_count,
+            len,
+            digest: 0,
+        })
+    }
+}
+
+/// Read all of `path` into pages allocated with `placement`, refusing files
+/// larger than `max_len` bytes.
+///
+/// A file that is an LZ4 frame made with `lz4 --content-size` is decompressed
+/// on the way, with `max_len` applied to the decompressed size, so payloads
+/// can be stored compressed on slow media.
+///
+/// `<path>.xxh32` must hold the XXH32 of the contents as loaded (after
+/// decompression), as written by `xxhsum -H32`. A missing or unreadable
+/// checksum file fails the load; a mismatch fails with `CRC_ERROR`.
+pub fn load(path: &str, placement: AllocateType, max_len: usize) -> uefi::Result<Payload> {
+    let expected = read_digest(path)?;
+    let mut payload = load_contents(path, placement, max_len)?;
+    payload.digest = xxh32::xxh32(payload.as_mut_slice(), 0);
+    if payload.digest != expected {
+        let _ = payload.free();
+        return Err(Status::CRC_ERROR.into());
+    }
+    Ok(payload)
+}
+
+/// Read the digest from the checksum file next to `path`.
+fn read_digest(path: &str) -> uefi::Result<u32> {
+    let mut path_buf = [0u8; PATH_CAP];
+    let mut digest_path = FixedBufWriter::new(&mut path_buf);
+    write!(digest_path, "{path}{DIGEST_SUFFIX}")
+        .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+    let digest_path = core::str::from_utf8(digest_path.as_bytes())
+        .map_err(|_| uefi::Error::from(Status::INVALID_PARAMETER))?;
+
+    let (mut file, size) = open(digest_path)?;
+    let mut text = [0u8; DIGEST_FILE_CAP];
+    let text = &mut text[..size.min(DIGEST_FILE_CAP)];
+    read_exact(&mut file, text)?;
+    xxh32::parse_digest(text).ok_or_else(|| uefi::Error::from(Status::COMPROMISED_DATA))
+}
+
+fn load_contents(path: &str, placement: AllocateType, max_len: usize) -> uefi::Result<Payload> {
+    let (mut file, size) = open(path)?;
+    if size == 0 || size > max_len {
+        return Err(Status::BAD_BUFFER_SIZE.into());
+    }
+
+    let mut raw = Payload::allocate(placement, size)?;
+    if let Err(err) = read_exact(&mut file, raw.as_mut_slice()) {
+        let _ = raw.free();
+        return Err(err);
+    }
+    if !lz4::is_frame(raw.as_mut_slice()) {
+        return Ok(raw);
+    }
+
+    let unpacked = decompress(raw.as_mut_slice(), placement, max_len);
+    let _ = raw.free();
+    unpacked
+}
+
+fn decompress(frame: &[u8], placement: AllocateType, max_len: usize) -> uefi::Result<Payload> {
+    let corrupt = |err| match err {
+        lz4::Lz4Error::Checksum => uefi::Error::from(Status::CRC_ERROR),
+        _ => uefi::Error::from(Status::COMPROMISED_DATA),
+    };
+    let len = lz4::content_size(frame).map_err(corrupt)?;
+    if len == 0 || len > max_len as u64 {
+        return Err(Status::BAD_BUFFER_SIZE.into());
+    }
+
+    let mut payload = Payload::allocate(placement, len as usize)?;
+    if let Err(err) = lz4::decompress(frame, payload.as_mut_slice()) {
+        let _ = payload.free();
+        return Err(corrupt(err));
+    }
+    Ok(payload)
+}
@@ -1,5 +1,5 @@
-use crate::writer::FixedBufWriter;
 use oxide_abi::{ABI_VENDOR_CAP, Firmware};
+use oxide_util::FixedBufWriter;
 use uefi::system;
 
 /// Maximum number of UTF-8 bytes we capture from the firmware vendor string.
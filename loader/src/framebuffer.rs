@@ -1,8 +1,11 @@
 use oxide_abi::Framebuffer;
 use uefi::{
-    Status,
-    boot::{self, OpenProtocolAttributes, OpenProtocolParams},
-    proto::console::gop::{GraphicsOutput, PixelFormat},
+    Handle, Status,
+    boot::{self, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol},
+    proto::{
+        console::gop::{GraphicsOutput, PixelFormat},
+        unsafe_protocol,
+    },
 };
 
 /// Framebuffer information required for kernel handoff.
@@ -52,16 +55,7 @@ pub fn get_framebuffer_info() -> uefi::Result<FramebufferInfo> {
     // first we need to get a non-exclusive access to the Graphics Output Protocol
     // if we had exclusive access, we wouldn't be able to use UEFI text console later
     let gop_handle = uefi::boot::get_handle_for_protocol::<GraphicsOutput>()?;
-    let mut gop = unsafe {
-        boot::open_protocol::<GraphicsOutput>(
-            OpenProtocolParams {
-                handle: gop_handle,
-                agent: uefi::boot::image_handle(),
-                controller: None,
-            },
-            OpenProtocolAttributes::GetProtocol,
-        )?
-    };
+    let mut gop = open_shared::<GraphicsOutput>(gop_handle)?;
     let mut fb = gop.frame_buffer();
 
     let base_address = fb.as_mut_ptr();
@@ -87,3 +81,115 @@ fn map_pixel_format(format: PixelFormat) -> uefi::Result<FramebufferPixelFormat>
         _ => Err(Status::UNSUPPORTED.into()),
     }
 }
+
+/// Open a protocol on `handle` without taking exclusive ownership.
+fn open_shared<P: uefi::proto::ProtocolPointer + ?Sized>(
+    handle: Handle,
+) -> uefi::Result<ScopedProtocol<P>> {
+    unsafe {
+        boot::open_protocol::<P>(
+            OpenProtocolParams {
+                handle,
+                agent: uefi::boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+}
+
+/// `EFI_EDID_ACTIVE_PROTOCOL`: the EDID the firmware is currently driving.
+#[repr(C)]
+#[unsafe_protocol("bd8c1056-9f36-44ec-92a8-a6337f817986")]
+struct EdidActive {
+    size: u32,
+    edid: *const u8,
+}
+
+/// `EFI_EDID_DISCOVERED_PROTOCOL`: the EDID read from the display, unfiltered.
+#[repr(C)]
+#[unsafe_protocol("1c0c34f6-d380-41fa-a049-8ad06c1a66aa")]
+struct EdidDiscovered {
+    size: u32,
+    edid: *const u8,
+}
+
+/// Print every GOP mode and the display's EDID identity and native mode.
+///
+/// Diagnostic only: failures are reported and otherwise ignored.
+pub fn log_display_info() {
+    let Ok(gop_handle) = uefi::boot::get_handle_for_protocol::<GraphicsOutput>() else {
+        uefi::println!("GOP: protocol not found");
+        return;
+    };
+    let Ok(gop) = open_shared::<GraphicsOutput>(gop_handle) else {
+        uefi::println!("GOP: open failed");
+        return;
+    };
+
+    let current = gop.current_mode_info();
+    uefi::println!("GOP modes:");
+    for mode in gop.modes() {
+        let info = mode.info();
+        let (width, height) = info.resolution();
+        uefi::println!(
+            "  {}x{} ({} pixels per scanline) {:?}{}",
+            width,
+            height,
+            info.stride(),
+            info.pixel_format(),
+            if *info == current { " (current)" } else { "" }
+        );
+    }
+
+    let edid = match open_shared::<EdidActive>(gop_handle) {
+        Ok(active) => Some((active.edid, active.size)),
+        Err(_) => open_shared::<EdidDiscovered>(gop_handle)
+            .ok()
+            .map(|discovered| (discovered.edid, discovered.size)),
+    };
+    let Some((ptr, size)) = edid.filter(|&(ptr, size)| !ptr.is_null() && size > 0) else {
+        uefi::println!("EDID: not available");
+        return;
+    };
+
+    // SAFETY: the firmware guarantees `size` readable bytes at `ptr` while
+    // the protocol stays installed, which it does until ExitBootServices.
+    let edid = unsafe { core::slice::from_raw_parts(ptr, size as usize) };
+    log_edid(edid);
+}
+
+const EDID_HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+const EDID_BASE_BLOCK: usize = 128;
+const EDID_FIRST_TIMING: usize = 54;
+
+fn log_edid(edid: &[u8]) {
+    if edid.len() < EDID_BASE_BLOCK || edid[..8] != EDID_HEADER {
+        uefi::println!("EDID: {} bytes, unrecognized header", edid.len());
+        return;
+    }
+
+    // Manufacturer ID: three 5-bit letters, big-endian, 1 = 'A'.
+    let id = u16::from_be_bytes([edid[8], edid[9]]);
+    let letter = |shift: u16| (b'A' - 1 + ((id >> shift) & 0x1F) as u8) as char;
+    let product = u16::from_le_bytes([edid[10], edid[11]]);
+    uefi::println!(
+        "EDID: {}{}{} product {:#06x}, version {}.{}",
+        letter(10),
+        letter(5),
+        letter(0),
+        product,
+        edid[18],
+        edid[19]
+    );
+
+    // The first detailed timing descriptor holds the preferred (native) mode.
+    let timing = &edid[EDID_FIRST_TIMING..EDID_FIRST_TIMING + 18];
+    if timing[0] == 0 && timing[1] == 0 {
+        uefi::println!("EDID: no detailed timing for the native mode");
+        return;
+    }
+    let width = timing[2] as u32 | ((timing[4] as u32 & 0xF0) << 4);
+    let height = timing[5] as u32 | ((timing[7] as u32 & 0xF0) << 4);
+    uefi::println!("EDID: native mode {}x{}", width, height);
+}
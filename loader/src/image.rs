@@ -0,0 +1,54 @@
+//! Locating the loader's own image for the kernel.
+//!
+//! The kernel is linked into the loader, so the loader's PE/COFF image is
+//! also the kernel's code and static data. Instead of letting the kernel
+//! guess its extent from whichever memory-map descriptor contains its code,
+//! the loader asks `LoadedImage` where the firmware placed it and reads
+//! `SizeOfImage` from its own PE optional header, then passes the exact
+//! range as `BootAbi::loader_image`.
+
+use oxide_abi::PhysRange;
+use uefi::{Status, boot, proto::loaded_image::LoadedImage};
+
+/// Offset of `e_lfanew` (the PE header's offset) in the DOS header.
+const DOS_LFANEW: usize = 0x3C;
+/// `SizeOfImage` within the optional header, which follows the 4-byte
+/// signature and the 20-byte COFF file header.
+const SIZE_OF_IMAGE: usize = 4 + 20 + 56;
+const PAGE_SIZE: u64 = 4096;
+
+/// Physical range of the loaded image, page-aligned.
+///
+/// Fails with `LOAD_ERROR` when the image headers are not where `LoadedImage`
+/// says, or disagree with the size the firmware reports.
+pub fn own_image() -> uefi::Result<PhysRange> {
+    let loaded = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle())?;
+    let (base, loaded_size) = loaded.info();
+    let base = base.cast::<u8>();
+
+    // SAFETY: the firmware mapped the whole image at `base`, headers
+    // included; every read below stays within `loaded_size`.
+    let read_u32 = |offset: usize| -> uefi::Result<u32> {
+        if offset + 4 > loaded_size as usize {
+            return Err(Status::LOAD_ERROR.into());
+        }
+        Ok(unsafe { base.add(offset).cast::<u32>().read_unaligned() })
+    };
+
+    if read_u32(0)? & 0xFFFF != u32::from(u16::from_le_bytes(*b"MZ")) {
+        return Err(Status::LOAD_ERROR.into());
+    }
+    let pe = read_u32(DOS_LFANEW)? as usize;
+    if read_u32(pe)? != u32::from_le_bytes(*b"PE\0\0") {
+        return Err(Status::LOAD_ERROR.into());
+    }
+    let size = u64::from(read_u32(pe + SIZE_OF_IMAGE)?);
+    if size == 0 || size > loaded_size {
+        return Err(Status::LOAD_ERROR.into());
+    }
+
+    Ok(PhysRange {
+        phys: base as u64,
+        len: size.div_ceil(PAGE_SIZE) * PAGE_SIZE,
+    })
+}
@@ -0,0 +1,125 @@
+//! Keyboard input from the firmware console.
+//!
+//! [`Keyboard`] reads `EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL` on the console-in
+//! handle, which reports modifier state, and falls back to plain
+//! `SimpleTextInput` when the firmware lacks it. Decoding, timeouts, and menu
+//! navigation are in `oxide_util::keys`, behind the [`KeySource`] trait.
+
+use core::{ffi::c_void, time::Duration};
+
+use oxide_util::keys::{self, KeyEvent, KeySource};
+use uefi::{
+    Handle, Status,
+    boot::{self, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol},
+    proto::{console::text::Key, unsafe_protocol},
+};
+
+/// `EFI_KEY_DATA`: the keystroke and the modifier state when it was typed.
+#[repr(C)]
+#[derive(Default)]
+struct KeyData {
+    scan_code: u16,
+    unicode_char: u16,
+    shift_state: u32,
+    toggle_state: u8,
+}
+
+/// `EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL`; only reading keys is used.
+#[repr(C)]
+#[unsafe_protocol("dd9e7534-7762-4698-8c14-f58517a625aa")]
+struct SimpleTextInputEx {
+    reset: unsafe extern "efiapi" fn(*mut Self, u8) -> Status,
+    read_key_stroke_ex: unsafe extern "efiapi" fn(*mut Self, *mut KeyData) -> Status,
+    wait_for_key_ex: *mut c_void,
+    set_state: *const c_void,
+    register_key_notify: *const c_void,
+    unregister_key_notify: *const c_void,
+}
+
+/// The firmware console's keyboard.
+pub struct Keyboard {
+    ex: Option<ScopedProtocol<SimpleTextInputEx>>,
+    present: bool,
+}
+
+impl Keyboard {
+    pub fn open() -> Self {
+        let Some(handle) = console_in_handle() else {
+            return Self {
+                ex: None,
+                present: false,
+            };
+        };
+        let ex = unsafe {
+            boot::open_protocol::<SimpleTextInputEx>(
+                OpenProtocolParams {
+                    handle,
+                    agent: boot::image_handle(),
+                    controller: None,
+                },
+                OpenProtocolAttributes::GetProtocol,
+            )
+        };
+        Self {
+            ex: ex.ok(),
+            present: true,
+        }
+    }
+
+    /// The next raw keystroke as (scan code, character, shift state).
+    fn read_raw(&mut self) -> Option<(u16, u16, u32)> {
+        if let Some(ex) = self.ex.as_mut() {
+            let protocol: *mut SimpleTextInputEx = &mut **ex;
+            let mut data = KeyData::default();
+            // SAFETY: the protocol stays open for the life of `self`.
+            let status = unsafe { ((*protocol).read_key_stroke_ex)(protocol, &mut data) };
+            return (status == Status::SUCCESS).then_some((
+                data.scan_code,
+                data.unicode_char,
+                data.shift_state,
+            ));
+        }
+
+        match uefi::system::with_stdin(|stdin| stdin.read_key()).ok()?? {
+            Key::Printable(c) => Some((0, u16::from(c), 0)),
+            Key::Special(scan) => Some((scan.0, 0, 0)),
+        }
+    }
+}
+
+impl KeySource for Keyboard {
+    fn poll(&mut self) -> Option<KeyEvent> {
+        if !self.present {
+            return None;
+        }
+        // Skip keystrokes that decode to nothing, such as a lone Shift.
+        loop {
+            let (scan_code, unicode, shift_state) = self.read_raw()?;
+            if let Some(event) = KeyEvent::decode(scan_code, unicode, shift_state) {
+                return Some(event);
+            }
+        }
+    }
+
+    fn idle(&mut self, interval: Duration) {
+        boot::stall(interval);
+    }
+}
+
+/// Wait up to `timeout` for a fresh key press, ignoring keys typed earlier.
+pub fn wait_for_key(timeout: Duration) -> Option<KeyEvent> {
+    let mut keyboard = Keyboard::open();
+    keys::drain(&mut keyboard);
+    keys::read_key(&mut keyboard, Some(timeout))
+}
+
+fn console_in_handle() -> Option<Handle> {
+    let table = uefi::table::system_table_raw()?;
+    // SAFETY: the system table stays valid while boot services are active.
+    let table = unsafe { table.as_ref() };
+    if table.stdin.is_null() {
+        return None;
+    }
+    // SAFETY: the firmware's console-in handle, valid alongside `stdin`.
+    unsafe { Handle::from_ptr(table.stdin_handle) }
+}
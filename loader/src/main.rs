@@ -2,14 +2,26 @@
 #![no_main]
 
 use oxide_kernel::kernel_main;
+use progress::Step;
 use uefi::prelude::*;
 
 mod abi;
+mod acpi;
+mod chainload;
+mod esp;
 mod firmware;
 mod framebuffer;
+mod image;
+mod input;
+mod memattr;
+mod memcheck;
+mod microcode;
 mod options;
+mod panic;
+mod progress;
+mod status;
 mod time;
-mod writer;
+mod vars;
 
 /// UEFI application entry point
 #[entry]
@@ -34,33 +46,127 @@ fn run() -> uefi::Result<()> {
 
     uefi::println!("Oxide UEFI loader starting...");
 
+    let boot_options = options::get_boot_options();
+    progress::ok(Step::BootOptions);
+
     // pre-allocate memory for the ABI structures we need to build, before exit boot services
-    let boot_abi = abi::alloc_abi_struct()?;
-    uefi::println!("Allocated BootAbi at {:p}", boot_abi);
+    let boot_abi = progress::run(Step::AllocateAbi, abi::alloc_abi_struct)?;
+    if boot_options.debug {
+        uefi::println!("  BootAbi at {:p}", boot_abi);
+    }
 
     let fw_info = firmware::get_info();
+    progress::ok(Step::Firmware);
+    vars::log_firmware_state(boot_options.debug);
+    acpi::log_power_sources(boot_options.debug);
 
-    let fb_info = framebuffer::get_framebuffer_info()?;
-    uefi::println!(
-        "Framebuffer: \n  addr={:#?}\n  size={} bytes\n  {}x{}, {} bpp",
-        fb_info.base_address,
-        fb_info.buffer_size,
-        fb_info.width,
-        fb_info.height,
-        fb_info.pixels_per_scanline * 8 / fb_info.width
-    );
+    let fb_info = progress::run(Step::Framebuffer, framebuffer::get_framebuffer_info)?;
+    if boot_options.debug {
+        uefi::println!(
+            "  addr={:#?} size={} bytes {}x{}, {} pixels per scanline",
+            fb_info.base_address,
+            fb_info.buffer_size,
+            fb_info.width,
+            fb_info.height,
+            fb_info.pixels_per_scanline
+        );
+        framebuffer::log_display_info();
+    }
 
-    let boot_options = options::get_boot_options();
+    if let Some(path) = boot_options.value("chainload") {
+        uefi::println!("Chainloading {}...", path);
+        match chainload::start(path) {
+            Ok(()) => progress::ok(Step::Chainload),
+            Err(err) => {
+                uefi::println!("  {} failed: {:?}", path, err.status());
+                progress::warn(Step::Chainload, "continuing to Oxide");
+            }
+        }
+    }
+
+    let microcode = match boot_options.value("microcode") {
+        Some(path) => match microcode::load(path) {
+            Ok(blob) => {
+                progress::ok(Step::Microcode);
+                blob
+            }
+            Err(err) => {
+                uefi::println!("  {}: {:?}", path, err.status());
+                progress::warn(Step::Microcode, "not loaded; booting without it");
+                (oxide_abi::PhysRange::default(), 0)
+            }
+        },
+        None => (oxide_abi::PhysRange::default(), 0),
+    };
+
+    let status_page = status::prepare();
+    match status_page {
+        0 => progress::warn(Step::StatusPage, "no page available"),
+        _ => progress::ok(Step::StatusPage),
+    }
 
-    let tsc_frequency = time::measure_tsc_frequency();
-    if let Some(freq) = tsc_frequency {
-        uefi::println!("Measured TSC frequency: {} Hz", freq);
+    let remeasure = boot_options.value("tsc") == Some("measure");
+    let tsc_frequency = match time::tsc_frequency(remeasure) {
+        Some((freq, source)) => {
+            progress::ok(Step::Tsc);
+            if boot_options.debug {
+                uefi::println!("  {} Hz ({:?})", freq, source);
+            }
+            Some(freq)
+        }
+        None => {
+            progress::warn(Step::Tsc, "unable to measure TSC frequency");
+            None
+        }
+    };
+
+    let memory_attributes = match memattr::copy_table() {
+        Ok(range) => {
+            progress::ok(Step::MemoryAttributes);
+            range
+        }
+        Err(err) if err.status() == Status::NOT_FOUND => {
+            progress::warn(Step::MemoryAttributes, "firmware provides none");
+            oxide_abi::PhysRange::default()
+        }
+        Err(err) => {
+            progress::warn(Step::MemoryAttributes, "unreadable; booting without it");
+            if boot_options.debug {
+                uefi::println!("  {:?}", err.status());
+            }
+            oxide_abi::PhysRange::default()
+        }
+    };
+
+    let loader_image = match image::own_image() {
+        Ok(range) => {
+            progress::ok(Step::LoaderImage);
+            if boot_options.debug {
+                uefi::println!("  {:#x}..{:#x}", range.phys, range.phys + range.len);
+            }
+            range
+        }
+        Err(err) => {
+            progress::warn(Step::LoaderImage, "unknown; kernel will infer it");
+            if boot_options.debug {
+                uefi::println!("  {:?}", err.status());
+            }
+            oxide_abi::PhysRange::default()
+        }
+    };
+
+    if memcheck::precheck() {
+        progress::ok(Step::MemoryMap);
     } else {
-        uefi::println!("Warning: Unable to measure TSC frequency");
+        progress::warn(Step::MemoryMap, "see warnings above");
     }
 
+    // Nothing can be printed once boot services are gone.
+    progress::ok(Step::StartKernel);
+
     // Here we exit boot services, so we lose all UEFI services after this point
-    let mem_map = unsafe { uefi::boot::exit_boot_services(None) };
+    panic::leaving_boot_services();
+    let mem_map = unsafe { uefi::boot::exit_boot_services(Some(abi::HANDOFF_MEMORY)) };
 
     // - build BootAbi
     abi::build_boot_abi_from_ptr(
@@ -68,7 +174,13 @@ fn run() -> uefi::Result<()> {
         fw_info,
         fb_info,
         boot_options,
-        tsc_frequency,
+        abi::Handoff {
+            tsc_frequency_hz: tsc_frequency,
+            status_page_phys: status_page,
+            microcode,
+            memory_attributes,
+            loader_image,
+        },
         mem_map,
     );
 
@@ -0,0 +1,61 @@
+//! Passing the firmware's memory attributes table to the kernel.
+//!
+//! `EFI_MEMORY_ATTRIBUTES_TABLE` splits the runtime-services regions into
+//! code and data and marks each part read-only (`EFI_MEMORY_RO`) or
+//! non-executable (`EFI_MEMORY_XP`). The loader copies it into `HANDOFF_MEMORY`
+//! pages below 1 GiB, like the microcode blob, and passes the copy as
+//! `BootAbi::memory_attributes`; the kernel maps those regions accordingly
+//! instead of read-write-execute.
+
+use oxide_abi::PhysRange;
+use uefi::{
+    Status,
+    boot::{AllocateType, allocate_pages},
+    table::cfg::ConfigTableEntry,
+};
+
+/// Header: version, entry count, descriptor size, flags.
+const HEADER_SIZE: usize = 16;
+/// Far more than any firmware's handful of runtime images needs.
+const MAX_TABLE: usize = 64 * 1024;
+const PAGE_SIZE: usize = 4096;
+/// Highest address the copy may occupy (the kernel's low identity map).
+const COPY_LIMIT: u64 = 0x3FFF_FFFF;
+
+/// Copy the table into reserved memory and return its physical range.
+///
+/// Fails with `NOT_FOUND` when the firmware publishes no table.
+pub fn copy_table() -> uefi::Result<PhysRange> {
+    let table = uefi::system::with_config_table(|entries| {
+        entries
+            .iter()
+            .find(|entry| entry.guid == ConfigTableEntry::MEMORY_ATTRIBUTES_GUID)
+            .map(|entry| entry.address.cast::<u8>())
+    })
+    .ok_or(Status::NOT_FOUND)?;
+
+    // SAFETY: the firmware publishes the table in memory that stays valid
+    // while boot services run; the header is four `u32`s.
+    let header = unsafe { table.cast::<[u32; 4]>().read_unaligned() };
+    let [_version, count, descriptor_size, _flags] = header;
+    let size = (count as usize)
+        .checked_mul(descriptor_size as usize)
+        .and_then(|bytes| bytes.checked_add(HEADER_SIZE))
+        .filter(|&size| size <= MAX_TABLE)
+        .ok_or(Status::BAD_BUFFER_SIZE)?;
+
+    let pages = size.div_ceil(PAGE_SIZE);
+    let copy = allocate_pages(
+        AllocateType::MaxAddress(COPY_LIMIT),
+        crate::abi::HANDOFF_MEMORY,
+        pages,
+    )?;
+    // SAFETY: `size` bytes of the table are readable (its header says so) and
+    // the destination allocation is at least that large and exclusively ours.
+    unsafe { core::ptr::copy_nonoverlapping(table, copy.as_ptr(), size) };
+
+    Ok(PhysRange {
+        phys: copy.as_ptr() as u64,
+        len: size as u64,
+    })
+}
@@ -0,0 +1,156 @@
+//! Sanity checks on the memory map the loader is about to hand over.
+//!
+//! The map handed to the kernel only exists after ExitBootServices, when the
+//! loader can no longer print. These checks therefore run on a snapshot taken
+//! just before exiting, converted exactly like the final map, so a warning
+//! here points at the loader's conversion or the firmware rather than at the
+//! kernel's own validation.
+
+use core::mem::size_of;
+
+use oxide_abi::{EfiMemoryType, MemoryDescriptor, MemoryMap};
+use oxide_util::cpuid;
+use uefi::boot::MemoryType;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// Problems found in a converted memory map.
+#[derive(Clone, Copy, Debug, Default)]
+struct MapReport {
+    /// `entry_count`, `entry_size`, and `map_size` disagree.
+    geometry: Option<&'static str>,
+    /// Descriptors whose start is not page-aligned or whose end overflows.
+    malformed: usize,
+    /// Descriptors ending above the CPU's physical address width.
+    beyond_width: usize,
+    /// Pairs of conventional regions that overlap.
+    conventional_overlaps: usize,
+}
+
+impl MapReport {
+    fn is_clean(&self) -> bool {
+        self.geometry.is_none()
+            && self.malformed == 0
+            && self.beyond_width == 0
+            && self.conventional_overlaps == 0
+    }
+
+    /// Print one warning per problem class; silent when the map looks sane.
+    fn log(&self) {
+        if self.is_clean() {
+            return;
+        }
+        uefi::println!("Warning: memory map failed loader sanity checks:");
+        if let Some(reason) = self.geometry {
+            uefi::println!("  {}", reason);
+        }
+        if self.malformed > 0 {
+            uefi::println!("  {} malformed descriptor(s)", self.malformed);
+        }
+        if self.beyond_width > 0 {
+            uefi::println!(
+                "  {} descriptor(s) beyond the physical address width",
+                self.beyond_width
+            );
+        }
+        if self.conventional_overlaps > 0 {
+            uefi::println!(
+                "  {} overlapping conventional region pair(s)",
+                self.conventional_overlaps
+            );
+        }
+    }
+}
+
+/// Snapshot the current memory map, convert it as for handoff, and print
+/// any sanity warnings. Returns true when the map looks sane.
+pub fn precheck() -> bool {
+    let snapshot = match uefi::boot::memory_map(MemoryType::LOADER_DATA) {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            uefi::println!("Memory map precheck skipped: {:?}", err.status());
+            return false;
+        }
+    };
+    let view = crate::abi::memory_map_view(&snapshot);
+    // SAFETY: `view` describes `snapshot`'s buffer, which outlives the check.
+    let report = unsafe { check(&view) };
+    report.log();
+    report.is_clean()
+}
+
+/// Check `map` the way the kernel will read it.
+///
+/// # Safety
+/// `map.descriptors_phys` must point at `map.map_size` readable bytes.
+unsafe fn check(map: &MemoryMap) -> MapReport {
+    let mut report = MapReport {
+        geometry: geometry_problem(map),
+        ..MapReport::default()
+    };
+    if report.geometry.is_some() {
+        return report;
+    }
+
+    let limit = cpuid::phys_addr_limit(cpuid::phys_addr_bits());
+    let descriptor = |index: u32| unsafe {
+        ((map.descriptors_phys + index as u64 * map.entry_size as u64) as *const MemoryDescriptor)
+            .read_unaligned()
+    };
+
+    for i in 0..map.entry_count {
+        let d = descriptor(i);
+        let Some(end) = region_end(&d) else {
+            report.malformed += 1;
+            continue;
+        };
+        if end > limit {
+            report.beyond_width += 1;
+        }
+        if d.typ != EfiMemoryType::ConventionalMemory as u32 {
+            continue;
+        }
+        for j in (i + 1)..map.entry_count {
+            let other = descriptor(j);
+            if other.typ != EfiMemoryType::ConventionalMemory as u32 {
+                continue;
+            }
+            if let Some(other_end) = region_end(&other)
+                && d.physical_start < other_end
+                && other.physical_start < end
+            {
+                report.conventional_overlaps += 1;
+            }
+        }
+    }
+
+    report
+}
+
+fn geometry_problem(map: &MemoryMap) -> Option<&'static str> {
+    let entry_size = map.entry_size as u64;
+    if entry_size < size_of::<MemoryDescriptor>() as u64 {
+        return Some("entry size smaller than a memory descriptor");
+    }
+    if map.entry_count == 0 {
+        return Some("no memory descriptors");
+    }
+    if !map.map_size.is_multiple_of(entry_size) {
+        return Some("map size not divisible by entry size");
+    }
+    if map.entry_count as u64 > map.map_size / entry_size {
+        return Some("entry count exceeds buffer capacity");
+    }
+    None
+}
+
+/// Exclusive end of the descriptor's range, or `None` if it is misaligned or
+/// overflows.
+fn region_end(d: &MemoryDescriptor) -> Option<u64> {
+    if !d.physical_start.is_multiple_of(PAGE_SIZE) {
+        return None;
+    }
+    d.number_of_pages
+        .checked_mul(PAGE_SIZE)
+        .and_then(|len| d.physical_start.checked_add(len))
+}
@@ -0,0 +1,27 @@
+//! Loading a CPU microcode update blob for the kernel.
+//!
+//! `microcode=<path>` names an update file on the loader's volume (for
+//! example an `intel-ucode` file for the test machine's CPU). The loader
+//! copies it into `HANDOFF_MEMORY` pages below 1 GiB, where the kernel's identity
+//! map covers it and its allocator leaves it alone, and passes the range as
+//! `BootAbi::microcode` with its digest in `BootAbi::microcode_xxh32`. The
+//! kernel rechecks the digest, validates the update, and applies it.
+
+use oxide_abi::PhysRange;
+use uefi::boot::AllocateType;
+
+/// Largest blob accepted; Intel updates are well under this.
+const MAX_BLOB: usize = 4 * 1024 * 1024;
+/// Highest address the blob may occupy (the kernel's low identity map).
+const BLOB_LIMIT: u64 = 0x3FFF_FFFF;
+
+/// Read `path` into reserved memory and return its physical range with the
+/// verified XXH32 of its contents. The file may be LZ4-compressed.
+pub fn load(path: &str) -> uefi::Result<(PhysRange, u32)> {
+    let blob = crate::esp::load(path, AllocateType::MaxAddress(BLOB_LIMIT), MAX_BLOB)?;
+    let range = PhysRange {
+        phys: blob.phys(),
+        len: blob.len() as u64,
+    };
+    Ok((range, blob.digest()))
+}
@@ -1,5 +1,5 @@
-use crate::writer::FixedBufWriter;
-use oxide_abi::Options;
+use oxide_abi::{ABI_CMDLINE_CAP, Options};
+use oxide_util::{FixedBufWriter, cmdline::has_flag};
 use uefi::{
     boot::{OpenProtocolAttributes, OpenProtocolParams, image_handle, open_protocol},
     proto::loaded_image::LoadedImage,
@@ -7,10 +7,13 @@ use uefi::{
 
 #[allow(dead_code)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-/// Boolean boot options parsed from the loader command line. Kept minimal for handoff.
+/// Boolean boot options parsed from the loader command line, plus the raw
+/// command line so the kernel can interpret its own `key=value` options.
 pub struct BootOptions {
     pub debug: bool,
     pub quiet: bool,
+    cmdline: [u8; ABI_CMDLINE_CAP],
+    cmdline_len: usize,
 }
 
 impl Default for BootOptions {
@@ -18,25 +21,52 @@ impl Default for BootOptions {
         Self {
             debug: cfg!(feature = "debug-default"),
             quiet: false,
+            cmdline: [0; ABI_CMDLINE_CAP],
+            cmdline_len: 0,
         }
     }
 }
 
+impl BootOptions {
+    /// Value of the last `key=value` token on the command line, if any.
+    pub fn value(&self, key: &str) -> Option<&str> {
+        let cmdline = core::str::from_utf8(&self.cmdline[..self.cmdline_len]).ok()?;
+        oxide_util::cmdline::find_value(cmdline, key)
+    }
+}
+
 /// Convert to ABI Options representation.
 impl From<BootOptions> for Options {
     fn from(opts: BootOptions) -> Self {
         Options {
             debug: if opts.debug { 1 } else { 0 },
             quiet: if opts.quiet { 1 } else { 0 },
+            cmdline_len: opts.cmdline_len as u16,
+            cmdline: opts.cmdline,
         }
     }
 }
 
-/// Inspect the UEFI load options and extract simple boolean boot options.
+/// Determine the boot command line and extract simple boolean boot options.
 ///
-/// Returns `BootOptions::default()` if options are absent or malformed so the
+/// The command line comes from, in order: the one-shot `OxideBootNext`
+/// variable, the UEFI load options, then the `OxideDefault` variable.
+/// Returns `BootOptions::default()` if none is present or usable so the
 /// loader stays resilient to firmware quirks.
 pub fn get_boot_options() -> BootOptions {
+    let mut buf = [0u8; ABI_CMDLINE_CAP];
+    let len = crate::vars::take_boot_next(&mut buf)
+        .or_else(|| load_options(&mut buf))
+        .or_else(|| crate::vars::read_default(&mut buf));
+
+    match len {
+        Some(len) => parse(&buf[..len]),
+        None => BootOptions::default(),
+    }
+}
+
+/// Copy the image's load options into `buf` as UTF-8, returning the length.
+fn load_options(buf: &mut [u8; ABI_CMDLINE_CAP]) -> Option<usize> {
     let image_handle = image_handle();
     let loaded_image = unsafe {
         open_protocol::<LoadedImage>(
@@ -49,36 +79,25 @@ pub fn get_boot_options() -> BootOptions {
         )
         .unwrap()
     };
-    let opts16 = match loaded_image.load_options_as_cstr16() {
-        Ok(opts) => opts,
-        Err(_) => {
-            // no load options provided
-            return BootOptions::default();
-        }
-    };
+    // no load options provided
+    let opts16 = loaded_image.load_options_as_cstr16().ok()?;
 
-    let mut buf = [0u8; 256];
-    let mut writer = FixedBufWriter::new(&mut buf);
-
-    if opts16.as_str_in_buf(&mut writer).is_err() {
-        // truncated or failed conversion; ignore to avoid parsing partial tokens
-        return BootOptions::default();
-    }
-    let len = writer.len();
+    let mut writer = FixedBufWriter::new(buf);
+    // truncated or failed conversion; ignore to avoid parsing partial tokens
+    opts16.as_str_in_buf(&mut writer).ok()?;
+    Some(writer.len())
+}
 
-    let cmdline = core::str::from_utf8(&buf[..len]).unwrap_or("");
+fn parse(cmdline_bytes: &[u8]) -> BootOptions {
+    let len = cmdline_bytes.len();
+    let cmdline = core::str::from_utf8(cmdline_bytes).unwrap_or("");
 
     let mut options = BootOptions::default();
+    options.cmdline[..len].copy_from_slice(cmdline_bytes);
+    options.cmdline_len = len;
 
-    for token in cmdline.split_whitespace() {
-        match token {
-            "debug" => options.debug = true,
-            "quiet" => options.quiet = true,
-            _ => {
-                // ignore unknown flags
-            }
-        }
-    }
+    options.debug |= has_flag(cmdline, "debug");
+    options.quiet |= has_flag(cmdline, "quiet");
 
     options
 }
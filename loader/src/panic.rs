@@ -0,0 +1,46 @@
+//! The loader's panic handler, shared with the kernel it links.
+//!
+//! Once `kernel_main` has started, a panic anywhere in the image is a kernel
+//! panic: [`oxide_kernel::report_panic`] reports it, runs the teardown, and
+//! does what `onpanic=` asks. Before that the message goes to the firmware
+//! console while boot services are up, and the machine shuts down after a
+//! pause to read it. Between `ExitBootServices` and the kernel's entry there
+//! is no output path at all, so the CPU just halts.
+
+use core::{
+    arch::asm,
+    panic::PanicInfo,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use uefi::{Status, boot, runtime};
+
+/// How long the message stays up before the loader shuts down.
+const READ_DELAY: Duration = Duration::from_secs(10);
+
+static BOOT_SERVICES_EXITED: AtomicBool = AtomicBool::new(false);
+
+/// Record that boot services are about to go away; call right before
+/// `ExitBootServices`.
+pub fn leaving_boot_services() {
+    BOOT_SERVICES_EXITED.store(true, Ordering::Relaxed);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if oxide_kernel::started() {
+        oxide_kernel::report_panic(info);
+    }
+
+    if !BOOT_SERVICES_EXITED.load(Ordering::Relaxed) {
+        uefi::println!("[PANIC]: {}", info);
+        boot::stall(READ_DELAY);
+        runtime::reset(runtime::ResetType::SHUTDOWN, Status::ABORTED, None);
+    }
+
+    loop {
+        // SAFETY: halting with interrupts masked touches no memory.
+        unsafe { asm!("cli", "hlt", options(nomem, nostack)) };
+    }
+}
@@ -0,0 +1,114 @@
+//! Numbered boot steps with an OK/WARN/FAIL status line each.
+//!
+//! When the loader stops on real hardware, the last status line on screen
+//! names the step and, for a failure, an error code `E<step>-<status>`
+//! combining the step number and the UEFI status, so a photo of the screen
+//! is enough to identify what broke.
+
+use core::time::Duration;
+
+use uefi::{Status, proto::console::text::Color};
+
+/// How long a failure stays on screen before the firmware takes over again,
+/// unless a key is pressed first.
+const FAIL_HOLD: Duration = Duration::from_secs(30);
+
+/// Loader steps, in execution order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Step {
+    BootOptions = 1,
+    AllocateAbi,
+    Firmware,
+    Framebuffer,
+    Chainload,
+    Microcode,
+    StatusPage,
+    Tsc,
+    MemoryAttributes,
+    LoaderImage,
+    MemoryMap,
+    StartKernel,
+}
+
+impl Step {
+    const COUNT: u8 = Step::StartKernel as u8;
+
+    const fn label(self) -> &'static str {
+        match self {
+            Step::BootOptions => "Read boot options",
+            Step::AllocateAbi => "Allocate BootAbi",
+            Step::Firmware => "Read firmware info",
+            Step::Framebuffer => "Query framebuffer",
+            Step::Chainload => "Chainload",
+            Step::Microcode => "Load microcode",
+            Step::StatusPage => "Prepare status page",
+            Step::Tsc => "Calibrate TSC",
+            Step::MemoryAttributes => "Copy memory attributes table",
+            Step::LoaderImage => "Locate loader image",
+            Step::MemoryMap => "Check memory map",
+            Step::StartKernel => "Exit boot services and start kernel",
+        }
+    }
+}
+
+/// Run a fallible step, print its outcome, and hold a failure on screen.
+pub fn run<T>(step: Step, f: impl FnOnce() -> uefi::Result<T>) -> uefi::Result<T> {
+    let result = f();
+    match &result {
+        Ok(_) => ok(step),
+        Err(err) => fail(step, err.status()),
+    }
+    result
+}
+
+/// Report a step that completed.
+pub fn ok(step: Step) {
+    line(Color::LightGreen, " OK ", step);
+    uefi::println!();
+}
+
+/// Report a step that completed in a degraded way.
+pub fn warn(step: Step, reason: &str) {
+    line(Color::Yellow, "WARN", step);
+    uefi::println!(": {}", reason);
+}
+
+fn fail(step: Step, status: Status) {
+    line(Color::LightRed, "FAIL", step);
+    uefi::println!(": error {} ({:?})", ErrorCode { step, status }, status);
+    uefi::println!("  Press any key to continue.");
+    crate::input::wait_for_key(FAIL_HOLD);
+}
+
+fn line(color: Color, tag: &str, step: Step) {
+    set_color(color);
+    uefi::print!("[{}]", tag);
+    set_color(Color::LightGray);
+    uefi::print!(" {}/{} {}", step as u8, Step::COUNT, step.label());
+}
+
+fn set_color(color: Color) {
+    uefi::system::with_stdout(|stdout| {
+        let _ = stdout.set_color(color, Color::Black);
+    });
+}
+
+/// `E<step>-<status>`, with the status's error bit stripped so the code stays
+/// short (e.g. `E04-3` for UNSUPPORTED while querying the framebuffer).
+struct ErrorCode {
+    step: Step,
+    status: Status,
+}
+
+impl core::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        const ERROR_BIT: usize = 1 << (usize::BITS - 1);
+        write!(
+            f,
+            "E{:02}-{:X}",
+            self.step as u8,
+            self.status.0 & !ERROR_BIT
+        )
+    }
+}
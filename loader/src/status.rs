@@ -0,0 +1,113 @@
+//! Loader side of the boot status page.
+//!
+//! One `HANDOFF_MEMORY` page below 1 GiB is handed to the kernel through
+//! `BootAbi::status_page_phys`. Its address is kept in the `OxideStatusPage`
+//! variable; the next boot tries to allocate the same page again and, if the
+//! previous contents survived the reset, reports how far that boot got before
+//! reinitializing it. Firmware that scrubs memory on reset simply yields no
+//! report.
+
+use core::ptr::NonNull;
+
+use oxide_abi::{BootStage, BootStatus, STATUS_MAGIC, StatusPage};
+use uefi::{
+    CStr16,
+    boot::{AllocateType, allocate_pages},
+    cstr16,
+    runtime::{self, VariableAttributes},
+};
+
+use crate::vars::OXIDE_VENDOR;
+
+const STATUS_PAGE_VAR: &CStr16 = cstr16!("OxideStatusPage");
+
+/// Highest address the page may occupy; the kernel only identity-maps the
+/// low gigabyte unconditionally.
+const STATUS_PAGE_LIMIT: u64 = 0x3FFF_FFFF;
+
+/// Allocate and initialize the status page, returning its physical address
+/// or 0 if no page could be allocated.
+pub fn prepare() -> u64 {
+    let page = previous_page()
+        .inspect(|&page| report_previous(page))
+        .or_else(|| {
+            allocate_pages(
+                AllocateType::MaxAddress(STATUS_PAGE_LIMIT),
+                crate::abi::HANDOFF_MEMORY,
+                1,
+            )
+            .ok()
+        });
+
+    let Some(page) = page else {
+        return 0;
+    };
+
+    let phys = page.as_ptr() as u64;
+    // SAFETY: the page was just allocated and is at least `StatusPage` sized.
+    unsafe {
+        page.cast::<StatusPage>().write_volatile(StatusPage {
+            magic: STATUS_MAGIC,
+            stage: BootStage::Loader as u32,
+            status: BootStatus::Running as u32,
+            nested_faults: 0,
+            detail: 0,
+        });
+    }
+
+    if let Err(err) = runtime::set_variable(
+        STATUS_PAGE_VAR,
+        &OXIDE_VENDOR,
+        VariableAttributes::NON_VOLATILE
+            | VariableAttributes::BOOTSERVICE_ACCESS
+            | VariableAttributes::RUNTIME_ACCESS,
+        &phys.to_le_bytes(),
+    ) {
+        uefi::println!("Failed to record status page: {:?}", err.status());
+    }
+
+    phys
+}
+
+/// Re-allocate the page recorded by the previous boot, if it is free.
+fn previous_page() -> Option<NonNull<u8>> {
+    let mut buf = [0u8; 8];
+    let (value, _) = runtime::get_variable(STATUS_PAGE_VAR, &OXIDE_VENDOR, &mut buf).ok()?;
+    let phys = u64::from_le_bytes(value.try_into().ok()?);
+    if phys == 0 || phys > STATUS_PAGE_LIMIT || !phys.is_multiple_of(4096) {
+        return None;
+    }
+
+    allocate_pages(AllocateType::Address(phys), crate::abi::HANDOFF_MEMORY, 1).ok()
+}
+
+fn report_previous(page: NonNull<u8>) {
+    // SAFETY: the page is allocated to us; any bit pattern is a valid `StatusPage`.
+    let previous = unsafe { page.cast::<StatusPage>().read_volatile() };
+    if previous.magic != STATUS_MAGIC {
+        return;
+    }
+
+    match (
+        BootStage::from_raw(previous.stage),
+        BootStatus::from_raw(previous.status),
+    ) {
+        (Some(stage), Some(status)) => uefi::println!(
+            "Previous boot reached {:?}, status {:?} (detail {:#x})",
+            stage,
+            status,
+            previous.detail
+        ),
+        _ => uefi::println!(
+            "Previous boot left an unreadable status page (stage {}, status {})",
+            previous.stage,
+            previous.status
+        ),
+    }
+    if previous.nested_faults != 0 {
+        uefi::println!(
+            "  {} nested fault(s) while reporting",
+            previous.nested_faults
+        );
+    }
+}
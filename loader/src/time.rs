@@ -1,10 +1,98 @@
-use core::{arch::asm, time};
+use core::{
+    arch::{asm, x86_64::__cpuid},
+    time,
+};
 
-use uefi::boot::stall;
+use oxide_util::xxh32::xxh32;
+use uefi::{
+    CStr16,
+    boot::stall,
+    cstr16,
+    runtime::{self, VariableAttributes},
+};
+
+use crate::vars::OXIDE_VENDOR;
 
 const MEASUREMENT_DELAY_US: u64 = 50_000; // 50 ms for stable measurement
 
-pub fn measure_tsc_frequency() -> Option<u64> {
+/// Last calibration: the CPU it was measured on and the frequency, so later
+/// boots on the same CPU can skip the measurement stall.
+const TSC_CACHE: &CStr16 = cstr16!("OxideTscCache");
+
+/// Frequencies outside this range are treated as a bad measurement: never
+/// cached, and ignored if found in the cache.
+const PLAUSIBLE_HZ: core::ops::RangeInclusive<u64> = 100_000_000..=10_000_000_000;
+
+/// Where the TSC frequency came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TscSource {
+    Cached,
+    Measured,
+}
+
+/// The TSC frequency from `OxideTscCache` when it was recorded on this CPU,
+/// otherwise measured and cached for the next boot. `remeasure` skips the
+/// cache.
+pub fn tsc_frequency(remeasure: bool) -> Option<(u64, TscSource)> {
+    let key = cpu_key();
+    if !remeasure && let Some(hz) = read_cache(key) {
+        return Some((hz, TscSource::Cached));
+    }
+
+    let hz = measure_tsc_frequency()?;
+    if !PLAUSIBLE_HZ.contains(&hz) {
+        uefi::println!("Not caching implausible TSC frequency: {} Hz", hz);
+    } else if let Err(err) = write_cache(key, hz) {
+        uefi::println!("Failed to cache TSC frequency: {:?}", err.status());
+    }
+    Some((hz, TscSource::Measured))
+}
+
+/// Identifies the CPU model: the CPUID signature, which names family, model,
+/// and stepping, and a hash of the brand string, which tells apart SKUs of
+/// one stepping that run at different frequencies.
+fn cpu_key() -> [u32; 2] {
+    let mut brand = [0u8; 48];
+    if __cpuid(0x8000_0000).eax >= 0x8000_0004 {
+        for (leaf, chunk) in (0x8000_0002u32..).zip(brand.chunks_exact_mut(16)) {
+            let regs = __cpuid(leaf);
+            for (bytes, reg) in chunk
+                .chunks_exact_mut(4)
+                .zip([regs.eax, regs.ebx, regs.ecx, regs.edx])
+            {
+                bytes.copy_from_slice(&reg.to_le_bytes());
+            }
+        }
+    }
+    [__cpuid(1).eax, xxh32(&brand, 0)]
+}
+
+fn read_cache(key: [u32; 2]) -> Option<u64> {
+    let mut buf = [0u8; 16];
+    let (value, _) = runtime::get_variable(TSC_CACHE, &OXIDE_VENDOR, &mut buf).ok()?;
+    let value: &[u8; 16] = (&*value).try_into().ok()?;
+    let signature = u32::from_le_bytes(value[0..4].try_into().ok()?);
+    let brand = u32::from_le_bytes(value[4..8].try_into().ok()?);
+    let hz = u64::from_le_bytes(value[8..16].try_into().ok()?);
+    ([signature, brand] == key && PLAUSIBLE_HZ.contains(&hz)).then_some(hz)
+}
+
+/// Boot-services access only: the cache is read before `ExitBootServices`
+/// and the kernel has no use for it (ADR 0004).
+fn write_cache(key: [u32; 2], hz: u64) -> uefi::Result {
+    let mut value = [0u8; 16];
+    value[0..4].copy_from_slice(&key[0].to_le_bytes());
+    value[4..8].copy_from_slice(&key[1].to_le_bytes());
+    value[8..16].copy_from_slice(&hz.to_le_bytes());
+    runtime::set_variable(
+        TSC_CACHE,
+        &OXIDE_VENDOR,
+        VariableAttributes::NON_VOLATILE | VariableAttributes::BOOTSERVICE_ACCESS,
+        &value,
+    )
+}
+
+fn measure_tsc_frequency() -> Option<u64> {
     let start = unsafe { read_tsc() };
 
     stall(time::Duration::from_micros(MEASUREMENT_DELAY_US));
@@ -0,0 +1,159 @@
+//! Persistent boot selection stored in UEFI variables.
+//!
+//! Two non-volatile variables under Oxide's vendor GUID hold UTF-8 command
+//! lines:
+//! - `OxideBootNext` is used for a single boot and deleted as soon as it is
+//!   read, so a bad selection cannot trap the machine.
+//! - `OxideDefault` is used whenever the firmware boot entry carries no load
+//!   options.
+//!
+//! Both are set from the firmware shell (`setvar`) or Linux (`efivar`); the
+//! loader only reads them, since it has no boot menu to write them from yet.
+//! The kernel cannot reach them: runtime services stay on the loader side of
+//! the boundary (ADR 0004).
+
+use oxide_abi::ABI_CMDLINE_CAP;
+use uefi::{
+    CStr16, Status,
+    boot::{self, MemoryType},
+    cstr16, guid,
+    runtime::{self, VariableVendor},
+};
+
+/// Most `BootOrder` entries copied out; longer lists are truncated.
+pub const MAX_BOOT_ORDER: usize = 32;
+
+/// `OsIndicationsSupported` bit: the firmware can boot to its setup UI.
+const OS_INDICATION_BOOT_TO_FW_UI: u64 = 1;
+
+/// Vendor GUID owning Oxide's loader variables.
+pub const OXIDE_VENDOR: VariableVendor =
+    VariableVendor(guid!("4f78a4e2-5d1b-4c7e-9b0a-6f1d2c3e8a51"));
+
+const BOOT_NEXT: &CStr16 = cstr16!("OxideBootNext");
+const DEFAULT: &CStr16 = cstr16!("OxideDefault");
+
+/// Read and delete the one-shot command line, returning its length in `buf`.
+pub fn take_boot_next(buf: &mut [u8; ABI_CMDLINE_CAP]) -> Option<usize> {
+    let len = read(BOOT_NEXT, buf);
+    if let Err(err) = runtime::delete_variable(BOOT_NEXT, &OXIDE_VENDOR)
+        && err.status() != Status::NOT_FOUND
+    {
+        uefi::println!("Failed to clear OxideBootNext: {:?}", err.status());
+    }
+    len
+}
+
+/// Read the persistent default command line, returning its length in `buf`.
+pub fn read_default(buf: &mut [u8; ABI_CMDLINE_CAP]) -> Option<usize> {
+    read(DEFAULT, buf)
+}
+
+/// Copy a variable's value into `buf`, ignoring it if missing, oversized, or
+/// not UTF-8.
+fn read(name: &CStr16, buf: &mut [u8; ABI_CMDLINE_CAP]) -> Option<usize> {
+    let (value, _) = runtime::get_variable(name, &OXIDE_VENDOR, buf).ok()?;
+    let len = value.len();
+    core::str::from_utf8(&buf[..len]).ok()?;
+    Some(len)
+}
+
+/// Whether Secure Boot is currently enforced (`SecureBoot` global variable).
+pub fn secure_boot() -> Option<bool> {
+    global_flag(cstr16!("SecureBoot"))
+}
+
+/// Whether the platform is in setup mode, i.e. no platform key is enrolled.
+pub fn setup_mode() -> Option<bool> {
+    global_flag(cstr16!("SetupMode"))
+}
+
+/// Copy the first [`MAX_BOOT_ORDER`] entries of the firmware `BootOrder` into
+/// `order`, returning the full entry count, which may be larger.
+///
+/// A list too long for the stack buffer is read again into a pool
+/// allocation of the size the firmware reports.
+pub fn boot_order(order: &mut [u16; MAX_BOOT_ORDER]) -> Option<usize> {
+    let name = cstr16!("BootOrder");
+    let vendor = &VariableVendor::GLOBAL_VARIABLE;
+    let mut buf = [0u8; MAX_BOOT_ORDER * 2];
+    match runtime::get_variable(name, vendor, &mut buf) {
+        Ok((value, _)) => Some(copy_boot_order(value, order)),
+        Err(err) if err.status() == Status::BUFFER_TOO_SMALL => {
+            let size = (*err.data())?;
+            let pool = boot::allocate_pool(MemoryType::LOADER_DATA, size).ok()?;
+            // SAFETY: the pool allocation is `size` bytes and ours until freed.
+            let whole = unsafe { core::slice::from_raw_parts_mut(pool.as_ptr(), size) };
+            let count = runtime::get_variable(name, vendor, whole)
+                .ok()
+                .map(|(value, _)| copy_boot_order(value, order));
+            // SAFETY: allocated above and no longer borrowed.
+            let _ = unsafe { boot::free_pool(pool) };
+            count
+        }
+        Err(_) => None,
+    }
+}
+
+/// Decode the leading entries of a raw `BootOrder` value into `order`,
+/// returning how many the value holds.
+fn copy_boot_order(value: &[u8], order: &mut [u16; MAX_BOOT_ORDER]) -> usize {
+    for (slot, bytes) in order.iter_mut().zip(value.chunks_exact(2)) {
+        *slot = u16::from_le_bytes([bytes[0], bytes[1]]);
+    }
+    value.len() / 2
+}
+
+/// Whether the firmware supports rebooting into its setup UI via `OsIndications`.
+pub fn supports_boot_to_firmware_ui() -> Option<bool> {
+    let mut buf = [0u8; 8];
+    let (value, _) = runtime::get_variable(
+        cstr16!("OsIndicationsSupported"),
+        &VariableVendor::GLOBAL_VARIABLE,
+        &mut buf,
+    )
+    .ok()?;
+    let bits = u64::from_le_bytes(value.try_into().ok()?);
+    Some(bits & OS_INDICATION_BOOT_TO_FW_UI != 0)
+}
+
+/// Print the security-relevant firmware state; the boot order only with `debug`.
+pub fn log_firmware_state(debug: bool) {
+    let describe = |flag: Option<bool>| match flag {
+        Some(true) => "on",
+        Some(false) => "off",
+        None => "unknown",
+    };
+    uefi::println!(
+        "Secure Boot: {}, setup mode: {}",
+        describe(secure_boot()),
+        describe(setup_mode())
+    );
+
+    if !debug {
+        return;
+    }
+    let mut order = [0u16; MAX_BOOT_ORDER];
+    if let Some(count) = boot_order(&mut order) {
+        uefi::print!("BootOrder:");
+        for entry in &order[..count.min(MAX_BOOT_ORDER)] {
+            uefi::print!(" Boot{:04X}", entry);
+        }
+        if count > MAX_BOOT_ORDER {
+            uefi::print!(" (+{} more)", count - MAX_BOOT_ORDER);
+        }
+        uefi::println!();
+    }
+    uefi::println!(
+        "Boot to firmware UI supported: {}",
+        describe(supports_boot_to_firmware_ui())
+    );
+}
+
+/// Read a one-byte boolean from the EFI global variable namespace.
+fn global_flag(name: &CStr16) -> Option<bool> {
+    let mut buf = [0u8; 1];
+    let (value, _) =
+        runtime::get_variable(name, &VariableVendor::GLOBAL_VARIABLE, &mut buf).ok()?;
+    value.first().map(|&byte| byte == 1)
+}
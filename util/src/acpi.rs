@@ -0,0 +1,242 @@
+//! Reading ACPI tables without an AML interpreter.
+//!
+//! Enough to follow RSDP → XSDT/RSDT → FADT → DSDT and to spot devices by
+//! their `_HID` in the AML byte stream. Nothing here evaluates a method, so
+//! values that firmware only exposes through control methods (a battery's
+//! charge in `_BST`) stay out of reach; what can be learned is whether a
+//! battery or AC adapter is declared at all.
+
+/// Size of the common header in front of every system description table.
+pub const SDT_HEADER_LEN: usize = 36;
+
+/// ACPI 1.0 RSDP length; the 2.0 structure is [`RSDP_V2_LEN`] bytes.
+const RSDP_V1_LEN: usize = 20;
+const RSDP_V2_LEN: usize = 36;
+
+// AML encoding of `Name(_HID, ...)`.
+const AML_DWORD_PREFIX: u8 = 0x0C;
+const AML_STRING_PREFIX: u8 = 0x0D;
+
+/// `_HID` of a control-method battery.
+const BATTERY_HID: &str = "PNP0C0A";
+/// `_HID` of an AC adapter.
+const AC_ADAPTER_HID: &str = "ACPI0003";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AcpiError {
+    /// No `"RSD PTR "` signature.
+    BadSignature,
+    /// The bytes do not sum to zero.
+    BadChecksum,
+    /// Shorter than its header or its own length field.
+    Truncated,
+}
+
+/// The root table the RSDP points at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RootTable {
+    /// 64-bit entries (ACPI 2.0 and later).
+    Xsdt(u64),
+    /// 32-bit entries (ACPI 1.0).
+    Rsdt(u64),
+}
+
+/// Whether `bytes` sums to zero, as every ACPI checksum requires.
+pub fn checksum_ok(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) == 0
+}
+
+/// Validate an RSDP and return the root table it names, preferring the XSDT.
+pub fn parse_rsdp(rsdp: &[u8]) -> Result<RootTable, AcpiError> {
+    if rsdp.len() < RSDP_V1_LEN {
+        return Err(AcpiError::Truncated);
+    }
+    if &rsdp[..8] != b"RSD PTR " {
+        return Err(AcpiError::BadSignature);
+    }
+    if !checksum_ok(&rsdp[..RSDP_V1_LEN]) {
+        return Err(AcpiError::BadChecksum);
+    }
+
+    let revision = rsdp[15];
+    if revision >= 2 && rsdp.len() >= RSDP_V2_LEN {
+        if !checksum_ok(&rsdp[..RSDP_V2_LEN]) {
+            return Err(AcpiError::BadChecksum);
+        }
+        let xsdt = read_u64(rsdp, 24);
+        if xsdt != 0 {
+            return Ok(RootTable::Xsdt(xsdt));
+        }
+    }
+    Ok(RootTable::Rsdt(u64::from(read_u32(rsdp, 16))))
+}
+
+/// Signature and total length from a table header.
+pub fn sdt_header(header: &[u8]) -> Result<([u8; 4], usize), AcpiError> {
+    if header.len() < SDT_HEADER_LEN {
+        return Err(AcpiError::Truncated);
+    }
+    let signature = [header[0], header[1], header[2], header[3]];
+    let length = read_u32(header, 4) as usize;
+    if length < SDT_HEADER_LEN {
+        return Err(AcpiError::Truncated);
+    }
+    Ok((signature, length))
+}
+
+/// Check a whole table: header, length, and checksum.
+pub fn validate_table(table: &[u8]) -> Result<[u8; 4], AcpiError> {
+    let (signature, length) = sdt_header(table)?;
+    if table.len() < length {
+        return Err(AcpiError::Truncated);
+    }
+    if !checksum_ok(&table[..length]) {
+        return Err(AcpiError::BadChecksum);
+    }
+    Ok(signature)
+}
+
+/// Table addresses listed by an XSDT (`wide`) or RSDT.
+pub fn root_entries(table: &[u8], wide: bool) -> impl Iterator<Item = u64> + '_ {
+    let width = if wide { 8 } else { 4 };
+    table
+        .get(SDT_HEADER_LEN..)
+        .unwrap_or_default()
+        .chunks_exact(width)
+        .map(move |entry| match wide {
+            true => read_u64(entry, 0),
+            false => u64::from(read_u32(entry, 0)),
+        })
+}
+
+/// The DSDT address from a FADT, preferring the 64-bit `X_DSDT` field.
+pub fn fadt_dsdt(fadt: &[u8]) -> Option<u64> {
+    let x_dsdt = (fadt.len() >= 148).then(|| read_u64(fadt, 140));
+    match x_dsdt {
+        Some(addr) if addr != 0 => Some(addr),
+        _ => (fadt.len() >= 44)
+            .then(|| u64::from(read_u32(fadt, 40)))
+            .filter(|&addr| addr != 0),
+    }
+}
+
+/// Power-supply devices declared in AML.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PowerSources {
+    pub batteries: u32,
+    pub ac_adapters: u32,
+}
+
+impl PowerSources {
+    /// Count the batteries and AC adapters whose `_HID` appears in `aml`
+    /// (the body of a DSDT or SSDT), in either the EISA-ID or string form.
+    pub fn scan(aml: &[u8]) -> Self {
+        let mut found = Self::default();
+        let mut rest = aml;
+        while let Some(at) = find(rest, b"_HID") {
+            let value = &rest[at + 4..];
+            if hid_matches(value, BATTERY_HID) {
+                found.batteries += 1;
+            } else if hid_matches(value, AC_ADAPTER_HID) {
+                found.ac_adapters += 1;
+            }
+            rest = value;
+        }
+        found
+    }
+
+    pub fn add(&mut self, other: Self) {
+        self.batteries += other.batteries;
+        self.ac_adapters += other.ac_adapters;
+    }
+}
+
+/// Whether the AML data object at the start of `value` is the ID `hid`.
+fn hid_matches(value: &[u8], hid: &str) -> bool {
+    match value.split_first() {
+        Some((&AML_DWORD_PREFIX, id)) => {
+            id.len() >= 4 && eisa_id(hid).is_some_and(|eisa| read_u32(id, 0) == eisa)
+        }
+        Some((&AML_STRING_PREFIX, text)) => {
+            text.starts_with(hid.as_bytes()) && text.get(hid.len()) == Some(&0)
+        }
+        _ => false,
+    }
+}
+
+/// Compress a seven-character PNP ID ("PNP0C0A") the way ASL's `EISAID()`
+/// does: three 5-bit letters and four hex digits, stored big-endian.
+fn eisa_id(id: &str) -> Option<u32> {
+    let bytes = id.as_bytes();
+    if bytes.len() != 7 || !bytes[..3].iter().all(u8::is_ascii_uppercase) {
+        return None;
+    }
+    let letters = bytes[..3]
+        .iter()
+        .fold(0u32, |acc, &letter| (acc << 5) | u32::from(letter - b'@'));
+    let digits = u32::from_str_radix(core::str::from_utf8(&bytes[3..]).ok()?, 16).ok()?;
+    Some(((letters << 16) | digits).swap_bytes())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_checksum(bytes: &mut [u8], range: core::ops::Range<usize>, at: usize) {
+        bytes[at] = 0;
+        let sum = bytes[range]
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        bytes[at] = sum.wrapping_neg();
+    }
+
+    #[test]
+    fn rsdp_prefers_the_xsdt_and_checks_both_checksums() {
+        let mut rsdp = [0u8; RSDP_V2_LEN];
+        rsdp[..8].copy_from_slice(b"RSD PTR ");
+        rsdp[15] = 2;
+        rsdp[16..20].copy_from_slice(&0x1000u32.to_le_bytes());
+        rsdp[24..32].copy_from_slice(&0x2_0000_0000u64.to_le_bytes());
+        with_checksum(&mut rsdp, 0..RSDP_V1_LEN, 8);
+        with_checksum(&mut rsdp, 0..RSDP_V2_LEN, 32);
+        assert_eq!(parse_rsdp(&rsdp), Ok(RootTable::Xsdt(0x2_0000_0000)));
+
+        rsdp[33] ^= 1;
+        assert_eq!(parse_rsdp(&rsdp), Err(AcpiError::BadChecksum));
+        assert_eq!(parse_rsdp(b"RSD PTX too short"), Err(AcpiError::Truncated));
+    }
+
+    #[test]
+    fn finds_batteries_and_adapters_by_hid() {
+        assert_eq!(eisa_id(BATTERY_HID), Some(0x0A0C_D041));
+
+        // Name(_HID, EISAID("PNP0C0A")), Name(_HID, "ACPI0003"),
+        // Name(_HID, "ACPI0003X"), Name(_HID, EISAID("PNP0C0B"))
+        let mut aml = [0u8; 64].to_vec();
+        aml.extend_from_slice(b"\x08_HID\x0C\x41\xD0\x0C\x0A\x14");
+        aml.extend_from_slice(b"\x08_HID\x0DACPI0003\x00");
+        aml.extend_from_slice(b"\x08_HID\x0DACPI0003X\x00");
+        aml.extend_from_slice(b"\x08_HID\x0C\x41\xD0\x0C\x0B");
+        assert_eq!(
+            PowerSources::scan(&aml),
+            PowerSources {
+                batteries: 1,
+                ac_adapters: 1
+            }
+        );
+    }
+}
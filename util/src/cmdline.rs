@@ -0,0 +1,109 @@
+//! Helpers for interpreting the boot command line.
+//!
+//! Shared by the loader and the kernel so both sides agree on how flags,
+//! `key=value` tokens, and numeric values are read.
+
+/// Whether the bare token `flag` (such as `debug`) appears in `cmdline`.
+pub fn has_flag(cmdline: &str, flag: &str) -> bool {
+    cmdline.split_ascii_whitespace().any(|token| token == flag)
+}
+
+/// Iterate over the values of every `key=value` token in `cmdline`, in order.
+pub fn find_values<'a, 'k>(
+    cmdline: &'a str,
+    key: &'k str,
+) -> impl DoubleEndedIterator<Item = &'a str> + 'k
+where
+    'a: 'k,
+{
+    cmdline
+        .split_ascii_whitespace()
+        .filter_map(|token| token.split_once('='))
+        .filter(move |(name, _)| *name == key)
+        .map(|(_, value)| value)
+}
+
+/// Value of the last `key=value` token in `cmdline`; later tokens override
+/// earlier ones.
+pub fn find_value<'a>(cmdline: &'a str, key: &str) -> Option<&'a str> {
+    find_values(cmdline, key).next_back()
+}
+
+/// Parse a decimal or `0x`-prefixed hexadecimal number.
+pub fn parse_u64(value: &str) -> Option<u64> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Parse a byte count or address such as `512M`, `0x100000`, or `4096`.
+///
+/// Accepts [`parse_u64`] numbers with an optional `K`, `M`, or `G` suffix
+/// (binary multiples, either case). Returns `None` on malformed input or
+/// overflow.
+pub fn parse_size(value: &str) -> Option<u64> {
+    let (digits, shift) = match value.as_bytes().last()? {
+        b'K' | b'k' => (&value[..value.len() - 1], 10),
+        b'M' | b'm' => (&value[..value.len() - 1], 20),
+        b'G' | b'g' => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+
+    parse_u64(digits)?.checked_mul(1u64 << shift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn has_flag_matches_whole_tokens_only() {
+        let cmdline = "debug quiet=summary  mem=512M";
+        assert!(has_flag(cmdline, "debug"));
+        assert!(!has_flag(cmdline, "quiet"));
+        assert!(!has_flag(cmdline, "deb"));
+        assert!(!has_flag("", "debug"));
+    }
+
+    #[test]
+    fn find_value_returns_last_matching_token() {
+        let cmdline = "debug clocksource=pit quiet clocksource=tsc";
+        assert_eq!(find_value(cmdline, "clocksource"), Some("tsc"));
+        assert_eq!(find_value(cmdline, "debug"), None);
+        assert_eq!(find_value(cmdline, "missing"), None);
+        assert_eq!(find_value("mem=", "mem"), Some(""));
+
+        let mut all = find_values(cmdline, "clocksource");
+        assert_eq!(all.next(), Some("pit"));
+        assert_eq!(all.next(), Some("tsc"));
+        assert_eq!(all.next(), None);
+    }
+
+    #[test]
+    fn parse_u64_accepts_decimal_and_hex() {
+        assert_eq!(parse_u64("1024"), Some(1024));
+        assert_eq!(parse_u64("0xFF"), Some(255));
+        assert_eq!(parse_u64("0Xff"), Some(255));
+        assert_eq!(parse_u64("0x"), None);
+        assert_eq!(parse_u64("-1"), None);
+        assert_eq!(parse_u64("18446744073709551616"), None);
+    }
+
+    #[test]
+    fn parse_size_accepts_suffixes_and_hex() {
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("64K"), Some(64 * 1024));
+        assert_eq!(parse_size("512M"), Some(512 * 1024 * 1024));
+        assert_eq!(parse_size("2g"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("0x100000"), Some(0x10_0000));
+        assert_eq!(parse_size("0x10M"), Some(16 * 1024 * 1024));
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("12Q"), None);
+        assert_eq!(parse_size("17179869184G"), None);
+    }
+}
@@ -0,0 +1,46 @@
+//! CPUID leaves the loader and the kernel both decode.
+//!
+//! `CPUID` is unprivileged, but the decoding is kept apart from the
+//! instruction so the fallbacks can be tested on the host with canned
+//! register values.
+
+/// MAXPHYADDR assumed when the CPU lacks leaf `0x8000_0008`.
+pub const DEFAULT_PHYS_ADDR_BITS: u8 = 36;
+
+/// Physical address width (MAXPHYADDR) of the running CPU.
+#[cfg(target_arch = "x86_64")]
+pub fn phys_addr_bits() -> u8 {
+    use core::arch::x86_64::__cpuid;
+
+    let leaf = (__cpuid(0x8000_0000).eax >= 0x8000_0008).then(|| __cpuid(0x8000_0008).eax);
+    decode_phys_addr_bits(leaf)
+}
+
+/// MAXPHYADDR from bits 7:0 of EAX of leaf `0x8000_0008`, or
+/// [`DEFAULT_PHYS_ADDR_BITS`] when the leaf is not available.
+pub fn decode_phys_addr_bits(eax: Option<u32>) -> u8 {
+    match eax {
+        Some(eax) => (eax & 0xFF) as u8,
+        None => DEFAULT_PHYS_ADDR_BITS,
+    }
+}
+
+/// One past the highest physical address a CPU with `bits` of MAXPHYADDR
+/// can generate, saturating at `u64::MAX`.
+pub fn phys_addr_limit(bits: u8) -> u64 {
+    1u64.checked_shl(u32::from(bits)).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phys_addr_bits_reads_low_byte_or_falls_back() {
+        // Linear width in bits 15:8 must not leak into the result.
+        assert_eq!(decode_phys_addr_bits(Some(0x3027)), 39);
+        assert_eq!(decode_phys_addr_bits(None), 36);
+        assert_eq!(phys_addr_limit(39), 1 << 39);
+        assert_eq!(phys_addr_limit(64), u64::MAX);
+    }
+}
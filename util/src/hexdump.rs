@@ -0,0 +1,82 @@
+//! Canonical hex-and-ASCII dumps of byte slices.
+
+use core::fmt;
+
+/// Bytes shown per output line.
+pub const BYTES_PER_LINE: usize = 16;
+
+/// Formats `bytes` as lines of `address  hex bytes  |ascii|`, with addresses
+/// starting at `base`.
+///
+/// ```text
+/// 0000000000001000  4f 58 49 44 45 00 01 02  03 04 05 06 07 08 09 0a  |OXIDE...........|
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct HexDump<'a> {
+    bytes: &'a [u8],
+    base: u64,
+}
+
+pub fn hexdump(bytes: &[u8], base: u64) -> HexDump<'_> {
+    HexDump { bytes, base }
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, line) in self.bytes.chunks(BYTES_PER_LINE).enumerate() {
+            let address = self.base.wrapping_add((index * BYTES_PER_LINE) as u64);
+            write!(f, "{:016x} ", address)?;
+
+            for column in 0..BYTES_PER_LINE {
+                if column % 8 == 0 {
+                    f.write_str(" ")?;
+                }
+                match line.get(column) {
+                    Some(byte) => write!(f, "{:02x} ", byte)?,
+                    None => f.write_str("   ")?,
+                }
+            }
+
+            f.write_str(" |")?;
+            for &byte in line {
+                let shown = if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                };
+                write!(f, "{}", shown)?;
+            }
+            f.write_str("|\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedBufWriter;
+    use core::fmt::Write;
+
+    #[test]
+    fn hexdump_pads_short_lines_and_masks_control_bytes() {
+        let mut buf = [0u8; 256];
+        let mut writer = FixedBufWriter::new(&mut buf);
+        let bytes = *b"OXIDE\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0aok";
+        write!(writer, "{}", hexdump(&bytes, 0x1000)).unwrap();
+
+        let text = core::str::from_utf8(writer.as_bytes()).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(
+            lines.next(),
+            Some(
+                "0000000000001000  4f 58 49 44 45 00 01 02  03 04 05 06 07 08 09 0a  |OXIDE...........|"
+            )
+        );
+        assert_eq!(
+            lines.next(),
+            Some("0000000000001010  6f 6b                                             |ok|")
+        );
+        assert_eq!(lines.next(), None);
+    }
+}
@@ -0,0 +1,254 @@
+//! Keystrokes as the loader's menus see them.
+//!
+//! The loader reads the firmware's `EFI_KEY_DATA` (scan code, UTF-16
+//! character, shift state) through a [`KeySource`]; everything after that,
+//! decoding, timeouts, and menu navigation, lives here so it can be tested
+//! on the host with a scripted source.
+
+use core::time::Duration;
+
+/// Interval between polls while waiting for a key.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// EFI_KEY_STATE shift-state bits.
+const SHIFT_STATE_VALID: u32 = 0x8000_0000;
+const RIGHT_SHIFT: u32 = 0x01;
+const LEFT_SHIFT: u32 = 0x02;
+const RIGHT_CONTROL: u32 = 0x04;
+const LEFT_CONTROL: u32 = 0x08;
+const RIGHT_ALT: u32 = 0x10;
+const LEFT_ALT: u32 = 0x20;
+
+// EFI scan codes.
+const SCAN_NULL: u16 = 0x00;
+const SCAN_UP: u16 = 0x01;
+const SCAN_DOWN: u16 = 0x02;
+const SCAN_RIGHT: u16 = 0x03;
+const SCAN_LEFT: u16 = 0x04;
+const SCAN_HOME: u16 = 0x05;
+const SCAN_END: u16 = 0x06;
+const SCAN_DELETE: u16 = 0x08;
+const SCAN_PAGE_UP: u16 = 0x09;
+const SCAN_PAGE_DOWN: u16 = 0x0A;
+const SCAN_F1: u16 = 0x0B;
+const SCAN_F10: u16 = 0x14;
+const SCAN_F11: u16 = 0x15;
+const SCAN_F12: u16 = 0x16;
+const SCAN_ESCAPE: u16 = 0x17;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    /// Decode an `EFI_KEY_STATE.KeyShiftState`; zero without the valid bit,
+    /// as plain `SimpleTextInput` reports.
+    pub const fn from_shift_state(state: u32) -> Self {
+        if state & SHIFT_STATE_VALID == 0 {
+            return Self {
+                shift: false,
+                ctrl: false,
+                alt: false,
+            };
+        }
+        Self {
+            shift: state & (LEFT_SHIFT | RIGHT_SHIFT) != 0,
+            ctrl: state & (LEFT_CONTROL | RIGHT_CONTROL) != 0,
+            alt: state & (LEFT_ALT | RIGHT_ALT) != 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    /// A printable character. Control-letter combinations arrive as the
+    /// letter with [`Modifiers::ctrl`] set.
+    Char(char),
+    Enter,
+    Escape,
+    Backspace,
+    Tab,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Delete,
+    PageUp,
+    PageDown,
+    /// `F1` to `F12`.
+    Function(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl KeyEvent {
+    /// Decode firmware key data; `None` for keys the loader has no use for
+    /// (OEM scan codes, lone modifier presses, unpaired surrogates).
+    pub fn decode(scan_code: u16, unicode: u16, shift_state: u32) -> Option<Self> {
+        let mut modifiers = Modifiers::from_shift_state(shift_state);
+        let key = match scan_code {
+            SCAN_NULL => match unicode {
+                0 => return None,
+                0x08 => Key::Backspace,
+                0x09 => Key::Tab,
+                0x0A | 0x0D => Key::Enter,
+                0x1B => Key::Escape,
+                // Ctrl-A..Ctrl-Z as control characters, from firmware that
+                // folds the modifier in.
+                0x01..=0x1A => {
+                    modifiers.ctrl = true;
+                    Key::Char(char::from(b'a' + (unicode as u8 - 1)))
+                }
+                _ => Key::Char(char::from_u32(u32::from(unicode))?),
+            },
+            SCAN_UP => Key::Up,
+            SCAN_DOWN => Key::Down,
+            SCAN_RIGHT => Key::Right,
+            SCAN_LEFT => Key::Left,
+            SCAN_HOME => Key::Home,
+            SCAN_END => Key::End,
+            SCAN_DELETE => Key::Delete,
+            SCAN_PAGE_UP => Key::PageUp,
+            SCAN_PAGE_DOWN => Key::PageDown,
+            SCAN_F1..=SCAN_F10 => Key::Function((scan_code - SCAN_F1 + 1) as u8),
+            SCAN_F11 => Key::Function(11),
+            SCAN_F12 => Key::Function(12),
+            SCAN_ESCAPE => Key::Escape,
+            _ => return None,
+        };
+        Some(Self { key, modifiers })
+    }
+
+    /// The menu action this key stands for, if any.
+    pub fn nav(&self) -> Option<Nav> {
+        match (self.key, self.modifiers.ctrl) {
+            (Key::Up, _) | (Key::Char('p'), true) => Some(Nav::Up),
+            (Key::Down, _) | (Key::Char('n'), true) => Some(Nav::Down),
+            (Key::Home | Key::PageUp, _) => Some(Nav::First),
+            (Key::End | Key::PageDown, _) => Some(Nav::Last),
+            (Key::Enter | Key::Right, _) => Some(Nav::Select),
+            (Key::Escape | Key::Left, _) => Some(Nav::Back),
+            _ => None,
+        }
+    }
+}
+
+/// Menu navigation, independent of which key produced it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nav {
+    Up,
+    Down,
+    First,
+    Last,
+    Select,
+    Back,
+}
+
+impl Nav {
+    /// Move a highlight at `index` in a list of `len` entries; up and down
+    /// wrap around. Select and back leave it where it is.
+    pub fn apply(self, index: usize, len: usize) -> usize {
+        if len == 0 {
+            return 0;
+        }
+        match self {
+            Nav::Up => index.checked_sub(1).unwrap_or(len - 1).min(len - 1),
+            Nav::Down => (index + 1) % len,
+            Nav::First => 0,
+            Nav::Last => len - 1,
+            Nav::Select | Nav::Back => index.min(len - 1),
+        }
+    }
+}
+
+/// A keyboard, as seen by the code waiting on it.
+pub trait KeySource {
+    /// The next pending key, without waiting.
+    fn poll(&mut self) -> Option<KeyEvent>;
+
+    /// Wait about `interval` for a key to arrive.
+    fn idle(&mut self, interval: Duration);
+}
+
+/// Wait up to `timeout` for a key; `None` waits indefinitely.
+pub fn read_key(source: &mut impl KeySource, timeout: Option<Duration>) -> Option<KeyEvent> {
+    let mut waited = Duration::ZERO;
+    loop {
+        if let Some(event) = source.poll() {
+            return Some(event);
+        }
+        if timeout.is_some_and(|timeout| waited >= timeout) {
+            return None;
+        }
+        source.idle(POLL_INTERVAL);
+        waited += POLL_INTERVAL;
+    }
+}
+
+/// Discard keys typed before a prompt appeared.
+pub fn drain(source: &mut impl KeySource) {
+    while source.poll().is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keys arriving after a given number of idle intervals.
+    struct Scripted<'a> {
+        keys: &'a [(u32, KeyEvent)],
+        ticks: u32,
+    }
+
+    impl KeySource for Scripted<'_> {
+        fn poll(&mut self) -> Option<KeyEvent> {
+            let (&(due, event), rest) = self.keys.split_first()?;
+            if due > self.ticks {
+                return None;
+            }
+            self.keys = rest;
+            Some(event)
+        }
+
+        fn idle(&mut self, _: Duration) {
+            self.ticks += 1;
+        }
+    }
+
+    #[test]
+    fn decodes_keys_and_times_out_while_waiting() {
+        let ctrl_n = KeyEvent::decode(0, 0x0E, 0).unwrap();
+        assert_eq!(ctrl_n.key, Key::Char('n'));
+        assert!(ctrl_n.modifiers.ctrl);
+        assert_eq!(ctrl_n.nav(), Some(Nav::Down));
+
+        let shifted = KeyEvent::decode(SCAN_F1 + 4, 0, SHIFT_STATE_VALID | LEFT_SHIFT).unwrap();
+        assert_eq!(shifted.key, Key::Function(5));
+        assert!(shifted.modifiers.shift && !shifted.modifiers.alt);
+        assert_eq!(KeyEvent::decode(0, 0, 0), None);
+        assert_eq!(KeyEvent::decode(0x8001, 0, 0), None);
+
+        let escape = KeyEvent::decode(SCAN_ESCAPE, 0, 0).unwrap();
+        assert_eq!(escape.nav(), Some(Nav::Back));
+        assert_eq!(Nav::Up.apply(0, 3), 2);
+        assert_eq!(Nav::Down.apply(2, 3), 0);
+
+        let keys = [(5, escape)];
+        let mut source = Scripted {
+            keys: &keys,
+            ticks: 0,
+        };
+        assert_eq!(read_key(&mut source, Some(POLL_INTERVAL * 2)), None);
+        assert_eq!(read_key(&mut source, None), Some(escape));
+        assert_eq!(source.ticks, 5);
+    }
+}
@@ -0,0 +1,14 @@
+//! `no_std` helpers shared by the loader and the kernel.
+
+#![no_std]
+
+pub mod acpi;
+pub mod cmdline;
+pub mod cpuid;
+pub mod hexdump;
+pub mod keys;
+pub mod lz4;
+pub mod writer;
+pub mod xxh32;
+
+pub use writer::FixedBufWriter;
@@ -0,0 +1,282 @@
+//! LZ4 frame decompression for payloads read from slow boot media.
+//!
+//! Only what `lz4 --content-size` produces is accepted: the frame must record
+//! its decompressed size so the caller can allocate the output up front. The
+//! header checksum, and the block and content checksums when the frame has
+//! them, are verified with [`xxh32`], so a payload damaged on its way from
+//! the boot medium is rejected rather than used. Every length and match
+//! offset is checked against the input and output slices, so a corrupt or
+//! hostile file yields an error rather than a panic.
+
+use crate::xxh32::xxh32;
+
+/// First four bytes of an LZ4 frame.
+const MAGIC: u32 = 0x184D_2204;
+
+const FLG_VERSION_MASK: u8 = 0xC0;
+const FLG_VERSION_1: u8 = 0x40;
+const FLG_BLOCK_CHECKSUM: u8 = 0x10;
+const FLG_CONTENT_SIZE: u8 = 0x08;
+const FLG_CONTENT_CHECKSUM: u8 = 0x04;
+const FLG_DICT_ID: u8 = 0x01;
+
+/// Set in a block size when the block is stored uncompressed.
+const BLOCK_UNCOMPRESSED: u32 = 0x8000_0000;
+const MIN_MATCH: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lz4Error {
+    /// Not an LZ4 frame.
+    BadMagic,
+    /// A frame version or flag this decoder does not handle.
+    Unsupported,
+    /// The frame does not record its decompressed size.
+    NoContentSize,
+    /// The input ends inside a header, block, or sequence.
+    Truncated,
+    /// A match reaches back before the start of the output.
+    BadOffset,
+    /// The data decompresses to more than the output holds.
+    OutputOverflow,
+    /// The data decompresses to less than the recorded size.
+    SizeMismatch,
+    /// A header, block, or content checksum does not match.
+    Checksum,
+}
+
+/// Whether `data` starts with the LZ4 frame magic.
+pub fn is_frame(data: &[u8]) -> bool {
+    read_u32(data, 0) == Some(MAGIC)
+}
+
+/// Decompressed size recorded in the frame header.
+pub fn content_size(frame: &[u8]) -> Result<u64, Lz4Error> {
+    Ok(Header::parse(frame)?.content_size)
+}
+
+/// Decompress `frame` into `out`, which must be exactly
+/// [`content_size`] bytes long.
+pub fn decompress(frame: &[u8], out: &mut [u8]) -> Result<(), Lz4Error> {
+    let header = Header::parse(frame)?;
+    if header.content_size != out.len() as u64 {
+        return Err(Lz4Error::SizeMismatch);
+    }
+
+    let mut input = header.len;
+    let mut written: usize = 0;
+    loop {
+        let size = read_u32(frame, input).ok_or(Lz4Error::Truncated)?;
+        input += 4;
+        if size == 0 {
+            break;
+        }
+
+        let len = (size & !BLOCK_UNCOMPRESSED) as usize;
+        let block = frame
+            .get(input..input.checked_add(len).ok_or(Lz4Error::Truncated)?)
+            .ok_or(Lz4Error::Truncated)?;
+        written = if size & BLOCK_UNCOMPRESSED != 0 {
+            let end = written.checked_add(len).ok_or(Lz4Error::OutputOverflow)?;
+            out.get_mut(written..end)
+                .ok_or(Lz4Error::OutputOverflow)?
+                .copy_from_slice(block);
+            end
+        } else {
+            decompress_block(block, out, written)?
+        };
+        input += len;
+        if header.block_checksums {
+            let stored = read_u32(frame, input).ok_or(Lz4Error::Truncated)?;
+            if stored != xxh32(block, 0) {
+                return Err(Lz4Error::Checksum);
+            }
+            input += 4;
+        }
+    }
+
+    if written != out.len() {
+        return Err(Lz4Error::SizeMismatch);
+    }
+    if header.content_checksum {
+        let stored = read_u32(frame, input).ok_or(Lz4Error::Truncated)?;
+        if stored != xxh32(out, 0) {
+            return Err(Lz4Error::Checksum);
+        }
+    }
+    Ok(())
+}
+
+/// Decode one compressed block into `out` starting at `written`; matches may
+/// reach back into earlier blocks. Returns the new end of the output.
+fn decompress_block(block: &[u8], out: &mut [u8], mut written: usize) -> Result<usize, Lz4Error> {
+    let mut input = 0;
+    while input < block.len() {
+        let token = block[input];
+        input += 1;
+
+        let literals = extended_length(block, &mut input, usize::from(token >> 4))?;
+        let source = block
+            .get(input..input.checked_add(literals).ok_or(Lz4Error::Truncated)?)
+            .ok_or(Lz4Error::Truncated)?;
+        let end = written
+            .checked_add(literals)
+            .ok_or(Lz4Error::OutputOverflow)?;
+        out.get_mut(written..end)
+            .ok_or(Lz4Error::OutputOverflow)?
+            .copy_from_slice(source);
+        input += literals;
+        written = end;
+
+        // The last sequence of a block is literals only.
+        if input == block.len() {
+            break;
+        }
+
+        let offset = usize::from(read_u16(block, input).ok_or(Lz4Error::Truncated)?);
+        input += 2;
+        if offset == 0 || offset > written {
+            return Err(Lz4Error::BadOffset);
+        }
+        let len = extended_length(block, &mut input, usize::from(token & 0x0F))? + MIN_MATCH;
+        let end = written.checked_add(len).ok_or(Lz4Error::OutputOverflow)?;
+        if end > out.len() {
+            return Err(Lz4Error::OutputOverflow);
+        }
+        // Byte by byte: a match may overlap the bytes it is producing.
+        for index in written..end {
+            out[index] = out[index - offset];
+        }
+        written = end;
+    }
+    Ok(written)
+}
+
+/// A 4-bit length from a token, extended by 255-valued bytes when it is 15.
+fn extended_length(block: &[u8], input: &mut usize, nibble: usize) -> Result<usize, Lz4Error> {
+    let mut len = nibble;
+    if nibble == 15 {
+        loop {
+            let byte = *block.get(*input).ok_or(Lz4Error::Truncated)?;
+            *input += 1;
+            len = len
+                .checked_add(usize::from(byte))
+                .ok_or(Lz4Error::OutputOverflow)?;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+struct Header {
+    /// Bytes up to the first block.
+    len: usize,
+    content_size: u64,
+    block_checksums: bool,
+    content_checksum: bool,
+}
+
+impl Header {
+    fn parse(frame: &[u8]) -> Result<Self, Lz4Error> {
+        if !is_frame(frame) {
+            return Err(Lz4Error::BadMagic);
+        }
+        let flags = *frame.get(4).ok_or(Lz4Error::Truncated)?;
+        if flags & FLG_VERSION_MASK != FLG_VERSION_1 || flags & FLG_DICT_ID != 0 {
+            return Err(Lz4Error::Unsupported);
+        }
+        if flags & FLG_CONTENT_SIZE == 0 {
+            return Err(Lz4Error::NoContentSize);
+        }
+        let content_size = read_u64(frame, 6).ok_or(Lz4Error::Truncated)?;
+        // Magic, FLG, BD, content size, header checksum.
+        let len = 4 + 2 + 8 + 1;
+        let checksum = *frame.get(len - 1).ok_or(Lz4Error::Truncated)?;
+        if checksum != (xxh32(&frame[4..len - 1], 0) >> 8) as u8 {
+            return Err(Lz4Error::Checksum);
+        }
+        Ok(Self {
+            len,
+            content_size,
+            block_checksums: flags & FLG_BLOCK_CHECKSUM != 0,
+            content_checksum: flags & FLG_CONTENT_CHECKSUM != 0,
+        })
+    }
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `lz4 --content-size` of "oxide boots. " twelve times, then "done\n".
+    const FRAME: [u8; 50] = [
+        0x04, 0x22, 0x4d, 0x18, 0x6c, 0x40, 0xa1, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0,
+        0x17, 0x00, 0x00, 0x00, 0xdf, 0x6f, 0x78, 0x69, 0x64, 0x65, 0x20, 0x62, 0x6f, 0x6f, 0x74,
+        0x73, 0x2e, 0x20, 0x0d, 0x00, 0x7c, 0x50, 0x64, 0x6f, 0x6e, 0x65, 0x0a, 0x00, 0x00, 0x00,
+        0x00, 0x90, 0xb5, 0xfb, 0x53,
+    ];
+
+    #[test]
+    fn decompresses_frames_from_the_reference_tool() {
+        assert!(is_frame(&FRAME));
+        assert_eq!(content_size(&FRAME), Ok(161));
+
+        let mut out = [0u8; 161];
+        decompress(&FRAME, &mut out).unwrap();
+        assert!(out[..156].chunks(13).all(|chunk| chunk == b"oxide boots. "));
+        assert_eq!(&out[156..], b"done\n");
+
+        assert_eq!(
+            decompress(&FRAME, &mut out[..160]),
+            Err(Lz4Error::SizeMismatch)
+        );
+        assert_eq!(decompress(&FRAME[..30], &mut out), Err(Lz4Error::Truncated));
+        assert_eq!(content_size(&FRAME[1..]), Err(Lz4Error::BadMagic));
+
+        // One flipped bit in a literal survives decoding but not the
+        // content checksum.
+        let mut rotten = FRAME;
+        rotten[22] ^= 0x04;
+        assert_eq!(decompress(&rotten, &mut out), Err(Lz4Error::Checksum));
+        rotten = FRAME;
+        rotten[6] ^= 0x01;
+        assert_eq!(content_size(&rotten), Err(Lz4Error::Checksum));
+    }
+
+    #[test]
+    fn corrupt_frames_fail_without_panicking() {
+        // xorshift32: deterministic, so a failure reproduces.
+        let mut seed = 0x2545_f491u32;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed
+        };
+
+        let mut out = [0u8; 161];
+        for _ in 0..20_000 {
+            let mut frame = FRAME;
+            for _ in 0..1 + next() % 4 {
+                // Keep the header so the block decoder is what gets exercised.
+                let at = 15 + next() as usize % (frame.len() - 15);
+                frame[at] = next() as u8;
+            }
+            let len = frame.len() - next() as usize % 8;
+            let _ = decompress(&frame[..len], &mut out);
+        }
+    }
+}
@@ -14,6 +14,15 @@ impl<'a> FixedBufWriter<'a> {
     pub fn len(&self) -> usize {
         self.len
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The bytes written so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
 }
 
 impl<'a> core::fmt::Write for FixedBufWriter<'a> {
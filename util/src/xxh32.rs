@@ -0,0 +1,104 @@
+//! XXH32, the checksum LZ4 frames carry.
+//!
+//! Fast enough to run over every payload the loader reads, and strong enough
+//! to catch the bit rot and truncated copies that USB sticks produce. Not a
+//! cryptographic hash.
+
+const PRIME1: u32 = 0x9E37_79B1;
+const PRIME2: u32 = 0x85EB_CA77;
+const PRIME3: u32 = 0xC2B2_AE3D;
+const PRIME4: u32 = 0x27D4_EB2F;
+const PRIME5: u32 = 0x1656_67B1;
+
+/// XXH32 of `data` with `seed`.
+pub fn xxh32(data: &[u8], seed: u32) -> u32 {
+    let mut stripes = data.chunks_exact(16);
+    let mut hash = if data.len() >= 16 {
+        let mut lanes = [
+            seed.wrapping_add(PRIME1).wrapping_add(PRIME2),
+            seed.wrapping_add(PRIME2),
+            seed,
+            seed.wrapping_sub(PRIME1),
+        ];
+        for stripe in &mut stripes {
+            for (lane, word) in lanes.iter_mut().zip(stripe.chunks_exact(4)) {
+                *lane = round(*lane, read_u32(word));
+            }
+        }
+        lanes[0]
+            .rotate_left(1)
+            .wrapping_add(lanes[1].rotate_left(7))
+            .wrapping_add(lanes[2].rotate_left(12))
+            .wrapping_add(lanes[3].rotate_left(18))
+    } else {
+        seed.wrapping_add(PRIME5)
+    };
+    hash = hash.wrapping_add(data.len() as u32);
+
+    let tail = stripes.remainder();
+    let mut words = tail.chunks_exact(4);
+    for word in &mut words {
+        hash = hash
+            .wrapping_add(read_u32(word).wrapping_mul(PRIME3))
+            .rotate_left(17)
+            .wrapping_mul(PRIME4);
+    }
+    for &byte in words.remainder() {
+        hash = hash
+            .wrapping_add(u32::from(byte).wrapping_mul(PRIME5))
+            .rotate_left(11)
+            .wrapping_mul(PRIME1);
+    }
+
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(PRIME2);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(PRIME3);
+    hash ^ (hash >> 16)
+}
+
+/// Read the digest at the start of a checksum file: eight hex digits, as
+/// written by `xxhsum -H32` (anything after the first whitespace, such as the
+/// file name, is ignored).
+pub fn parse_digest(text: &[u8]) -> Option<u32> {
+    let text = text.trim_ascii_start();
+    let end = text
+        .iter()
+        .position(u8::is_ascii_whitespace)
+        .unwrap_or(text.len());
+    let digits = &text[..end];
+    if digits.len() != 8 || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    u32::from_str_radix(core::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+fn round(lane: u32, word: u32) -> u32 {
+    lane.wrapping_add(word.wrapping_mul(PRIME2))
+        .rotate_left(13)
+        .wrapping_mul(PRIME1)
+}
+
+fn read_u32(word: &[u8]) -> u32 {
+    u32::from_le_bytes([word[0], word[1], word[2], word[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_reference_values() {
+        assert_eq!(xxh32(b"", 0), 0x02CC_5D05);
+        assert_eq!(xxh32(b"abc", 0), 0x32D1_53FF);
+    }
+
+    #[test]
+    fn parses_xxhsum_output() {
+        assert_eq!(parse_digest(b"32d153ff  ucode.bin\n"), Some(0x32D1_53FF));
+        assert_eq!(parse_digest(b"02CC5D05\n"), Some(0x02CC_5D05));
+        assert_eq!(parse_digest(b"32d153f  ucode.bin"), None);
+        assert_eq!(parse_digest(b"xxh32 ucode.bin"), None);
+        assert_eq!(parse_digest(b""), None);
+    }
+}